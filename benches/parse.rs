@@ -0,0 +1,50 @@
+#[macro_use]
+extern crate criterion;
+extern crate osuparse;
+
+use std::fs;
+
+use criterion::{black_box, Criterion, Throughput};
+
+use osuparse::deserialize::ToOsuString;
+
+const MAPS: &[&str] = &["map.osu", "omtest.osu", "test.osu"];
+
+fn read_maps() -> Vec<(&'static str, String)> {
+    MAPS.iter()
+        .map(|name| (*name, fs::read_to_string(name).unwrap()))
+        .collect()
+}
+
+fn parse(c: &mut Criterion) {
+    let maps = read_maps();
+
+    let mut group = c.benchmark_group("parse_beatmap");
+    for (name, contents) in &maps {
+        group.throughput(Throughput::Bytes(contents.len() as u64));
+        group.bench_function(*name, |b| b.iter(|| osuparse::parse_beatmap(black_box(contents)).unwrap()));
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("parse_raw");
+    for (name, contents) in &maps {
+        group.throughput(Throughput::Bytes(contents.len() as u64));
+        group.bench_function(*name, |b| b.iter(|| osuparse::parse_raw(black_box(contents)).unwrap()));
+    }
+    group.finish();
+}
+
+fn write(c: &mut Criterion) {
+    let maps = read_maps();
+
+    let mut group = c.benchmark_group("to_osu_string");
+    for (name, contents) in &maps {
+        let map = osuparse::parse_beatmap(contents).unwrap();
+        group.throughput(Throughput::Bytes(contents.len() as u64));
+        group.bench_function(*name, |b| b.iter(|| black_box(&map).to_osu_string()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, write);
+criterion_main!(benches);
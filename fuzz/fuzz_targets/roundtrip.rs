@@ -0,0 +1,22 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate osuparse;
+
+use osuparse::deserialize::ToOsuString;
+
+fuzz_target!(|data: &[u8]| {
+    let input = match std::str::from_utf8(data) {
+        Ok(input) => input,
+        Err(_) => return,
+    };
+
+    if let Ok(map) = osuparse::parse_beatmap(input) {
+        let parsed = osuparse::parse_beatmap(&map.to_osu_string())
+            .expect("serialized beatmap failed to parse");
+        let reparsed = osuparse::parse_beatmap(&parsed.to_osu_string())
+            .expect("serialized beatmap failed to parse");
+
+        assert_eq!(parsed, reparsed);
+    }
+});
@@ -57,6 +57,31 @@ fn build_game_mode(py: Python, mode: GameMode) -> PyResult<PyString> {
     })
 }
 
+fn build_beatmap_id(_py: Python, id: BeatmapId) -> PyResult<i32> {
+    Ok(id.0)
+}
+
+fn build_beatmap_set_id(_py: Python, id: BeatmapSetId) -> PyResult<i32> {
+    Ok(id.0)
+}
+
+fn build_countdown(py: Python, countdown: CountdownSpeed) -> PyResult<PyString> {
+    Ok(match countdown {
+        CountdownSpeed::None => "none".to_py_object(py),
+        CountdownSpeed::Normal => "normal".to_py_object(py),
+        CountdownSpeed::Half => "half".to_py_object(py),
+        CountdownSpeed::Double => "double".to_py_object(py),
+    })
+}
+
+fn build_overlay_position(py: Python, position: OverlayPosition) -> PyResult<PyString> {
+    Ok(match position {
+        OverlayPosition::NoChange => "no_change".to_py_object(py),
+        OverlayPosition::Below => "below".to_py_object(py),
+        OverlayPosition::Above => "above".to_py_object(py),
+    })
+}
+
 fn build_slider_type(py: Python, slider_type: SliderType) -> PyResult<PyString> {
     Ok(match slider_type {
         SliderType::Linear => "linear".to_py_object(py),
@@ -66,6 +91,30 @@ fn build_slider_type(py: Python, slider_type: SliderType) -> PyResult<PyString>
     })
 }
 
+fn build_time(_py: Python, time: Time) -> PyResult<f64> {
+    Ok(time.0)
+}
+
+fn build_times(_py: Python, times: Vec<Time>) -> PyResult<Vec<f64>> {
+    Ok(times.into_iter().map(f64::from).collect())
+}
+
+fn build_optional_time(_py: Python, time: Option<Time>) -> PyResult<Option<f64>> {
+    Ok(time.map(f64::from))
+}
+
+fn build_key_set(py: Python, keys: KeySet) -> PyResult<PyList> {
+    Ok(keys.iter().collect::<Vec<&str>>().to_py_object(py))
+}
+
+fn build_effects(py: Python, effects: Effects) -> PyResult<PyDict> {
+    let dict = PyDict::new(py);
+    dict.set_item(py, "kiai", effects.kiai())?;
+    dict.set_item(py, "omit_first_barline", effects.omit_first_barline())?;
+    dict.set_item(py, "bits", effects.bits())?;
+    Ok(dict)
+}
+
 fn build_hit_object(py: Python, obj: HitObject) -> PyResult<PyDict> {
     match obj {
         HitObject::HitCircle(c) => Ok(("hit_circle", build_hitcircle(py, c))),
@@ -82,20 +131,32 @@ fn build_hit_object(py: Python, obj: HitObject) -> PyResult<PyDict> {
 }
 
 section_builder![build_editor_section -> EditorSection {
-   bookmarks, distance_spacing, beat_divisor, grid_size, timeline_zoom
+   distance_spacing, beat_divisor, grid_size, timeline_zoom, unknown
+} {
+   bookmarks: build_times,
+   current_time: build_optional_time
 }];
 
 section_builder![build_metadata_section -> MetadataSection {
     title, title_unicode, artist, artist_unicode, creator, version, source,
-    tags, beatmap_id, beatmap_set_id
+    tags
+} {
+    beatmap_id: build_beatmap_id,
+    beatmap_set_id: build_beatmap_set_id
 }];
 
 section_builder![build_general_section -> GeneralSection {
-    audio_filename, audio_lead_in, preview_time, countdown, sample_set,
+    audio_filename, sample_set,
     stack_leniency, letterbox_in_breaks, widescreen_storyboard,
-    story_fire_in_front, special_style, epilepsy_warning, use_skin_sprites
+    story_fire_in_front, special_style, epilepsy_warning, use_skin_sprites,
+    countdown_offset, skin_preference, always_show_playfield,
+    samples_match_playback_rate
 } {
-   game_mode: build_game_mode 
+   audio_lead_in: build_time,
+   preview_time: build_time,
+   game_mode: build_game_mode,
+   countdown: build_countdown,
+   overlay_position: build_overlay_position
 }];
 
 section_builder![build_difficulty_section -> DifficultySection {
@@ -104,8 +165,10 @@ section_builder![build_difficulty_section -> DifficultySection {
 }];
 
 section_builder![build_timing_point -> TimingPoint {
-    offset, ms_per_beat, meter, sample_set, sample_index,
-    volume, inherited, kiai_mode
+    ms_per_beat, meter, sample_set, sample_index, volume, inherited
+} {
+    offset: build_time,
+    effects: build_effects
 }];
 
 section_builder![build_extras -> HitObjectExtras {
@@ -113,33 +176,86 @@ section_builder![build_extras -> HitObjectExtras {
 }];
 
 section_builder![build_hitcircle -> HitCircle {
-    x, y, new_combo, color_skip, time, hitsound
+    x, y, new_combo, color_skip, raw_type, hitsound
 } {
+    time: build_time,
     extras: build_extras
 }];
 
 section_builder![build_hold_note -> HoldNote {
-    x, y, new_combo, color_skip, time, hitsound, end_time
+    x, y, new_combo, color_skip, raw_type, hitsound
 } {
+    time: build_time,
+    end_time: build_time,
     extras: build_extras
 }];
 
 section_builder![build_spinner -> Spinner {
-    x, y, new_combo, color_skip, time, hitsound, end_time
+    x, y, new_combo, color_skip, raw_type, hitsound
 } {
+    time: build_time,
+    end_time: build_time,
     extras: build_extras
 }];
 
+section_builder![build_edge_sample_set -> EdgeSampleSet {
+    sample_set, addition_set
+}];
+
+list_builder![build_edge_sample_sets, EdgeSampleSet, build_edge_sample_set];
+
 section_builder![build_slider -> Slider {
-    x, y, new_combo, color_skip, time, hitsound,
-    curve_points, repeat, pixel_length, edge_hitsounds,
-    edge_additions
+    x, y, new_combo, color_skip, raw_type, hitsound,
+    curve_points, repeat, pixel_length, edge_hitsounds
 } {
+    time: build_time,
     extras: build_extras,
-    slider_type: build_slider_type
+    slider_type: build_slider_type,
+    edge_additions: build_edge_sample_sets
+}];
+
+section_builder![build_present_keys -> PresentKeys {} {
+    general: build_key_set,
+    editor: build_key_set,
+    metadata: build_key_set,
+    difficulty: build_key_set
 }];
 
 list_builder![build_timing_points, TimingPoint, build_timing_point];
+
+section_builder![build_background -> events::Background {
+    filename, x_offset, y_offset
+}];
+
+fn build_optional_background(
+    py: Python,
+    background: Option<events::Background>,
+) -> PyResult<PyObject> {
+    match background {
+        Some(b) => build_background(py, b).map(|d| d.into_object()),
+        None => Ok(py.None()),
+    }
+}
+
+section_builder![build_video -> events::Video {
+    filename, x_offset, y_offset
+} {
+    start_time: build_time
+}];
+
+section_builder![build_break -> events::Break {} {
+    start_time: build_time,
+    end_time: build_time
+}];
+
+list_builder![build_videos, events::Video, build_video];
+list_builder![build_breaks, events::Break, build_break];
+
+section_builder![build_events -> events::Events {} {
+    background: build_optional_background,
+    videos: build_videos,
+    breaks: build_breaks
+}];
 list_builder![build_hit_objects, HitObject, build_hit_object];
 
 section_builder![build_beatmap -> Beatmap {
@@ -149,8 +265,10 @@ section_builder![build_beatmap -> Beatmap {
     editor: build_editor_section,
     metadata: build_metadata_section,
     difficulty: build_difficulty_section,
+    events: build_events,
     timing_points: build_timing_points,
-    hit_objects: build_hit_objects
+    hit_objects: build_hit_objects,
+    present_keys: build_present_keys
 }];
 
 // add bindings to the generated python module
@@ -0,0 +1,271 @@
+//! Models of the beatmaps returned by the osu! web API (v2), and conversions
+//! between them and the sections of a parsed beatmap, for servers that merge
+//! online information with local files.
+//!
+//! Requires the `api` feature. The structs deserialize from the API's JSON
+//! with any serde format crate, and only hold the fields that have a
+//! counterpart in a beatmap file, plus the star rating. Other fields are
+//! ignored, and missing ones are left at their defaults.
+//!
+//! # Examples
+//!
+//! ```
+//! use osuparse::{BeatmapId, BeatmapSetId, DifficultySection, MetadataSection};
+//! use osuparse::api::{ApiBeatmap, ApiBeatmapset};
+//!
+//! let set = ApiBeatmapset {
+//!     id: BeatmapSetId(39804),
+//!     artist: "xi".to_string(),
+//!     title: "FREEDOM DiVE".to_string(),
+//!     creator: "Nakagawa-Kanon".to_string(),
+//!     tags: "parousia kanon".to_string(),
+//!     ..Default::default()
+//! };
+//! let beatmap = ApiBeatmap {
+//!     id: BeatmapId(129891),
+//!     version: "FOUR DIMENSIONS".to_string(),
+//!     cs: 4.0,
+//!     ar: 9.0,
+//!     ..Default::default()
+//! };
+//!
+//! let metadata = set.metadata_for(&beatmap);
+//! assert_eq!(metadata.filename(), "xi - FREEDOM DiVE (Nakagawa-Kanon) [FOUR DIMENSIONS].osu");
+//! assert_eq!(metadata.tags, vec!["parousia", "kanon"]);
+//!
+//! let difficulty = DifficultySection::from(&beatmap);
+//! assert_eq!(difficulty.approach_rate, 9.0);
+//! ```
+
+use super::*;
+
+/// A difficulty as returned by the API.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiBeatmap {
+    pub id: BeatmapId,
+    pub beatmapset_id: BeatmapSetId,
+    pub version: String,
+    #[serde(with = "api_mode")]
+    pub mode: GameMode,
+    /// Star rating without mods.
+    pub difficulty_rating: f64,
+    pub cs: f32,
+    pub ar: f32,
+    /// Overall difficulty.
+    pub accuracy: f32,
+    /// HP drain rate.
+    pub drain: f32,
+    pub count_circles: i32,
+    pub count_sliders: i32,
+    pub count_spinners: i32,
+}
+
+impl Default for ApiBeatmap {
+    fn default() -> Self {
+        ApiBeatmap {
+            id: BeatmapId(0),
+            beatmapset_id: BeatmapSetId(0),
+            version: String::new(),
+            mode: GameMode::Osu,
+            difficulty_rating: 0.0,
+            cs: 0.0,
+            ar: 0.0,
+            accuracy: 0.0,
+            drain: 0.0,
+            count_circles: 0,
+            count_sliders: 0,
+            count_spinners: 0,
+        }
+    }
+}
+
+/// A beatmap set as returned by the API, with its difficulties if the
+/// response included them.
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiBeatmapset {
+    pub id: BeatmapSetId,
+    pub artist: String,
+    pub artist_unicode: String,
+    pub title: String,
+    pub title_unicode: String,
+    pub creator: String,
+    pub source: String,
+    /// Space-separated, as in the `Tags` key of a beatmap.
+    pub tags: String,
+    pub beatmaps: Vec<ApiBeatmap>,
+}
+
+impl ApiBeatmapset {
+    /// The metadata of one of the set's difficulties, as it appears in the
+    /// difficulty's file.
+    pub fn metadata_for(&self, beatmap: &ApiBeatmap) -> MetadataSection {
+        MetadataSection {
+            version: beatmap.version.clone(),
+            beatmap_id: beatmap.id,
+            ..MetadataSection::from(self)
+        }
+    }
+}
+
+/// Fills in the metadata shared by the set. The version and beatmap id are
+/// left empty, see [`ApiBeatmapset::metadata_for`](struct.ApiBeatmapset.html#method.metadata_for).
+impl<'a> From<&'a ApiBeatmapset> for MetadataSection {
+    fn from(set: &'a ApiBeatmapset) -> Self {
+        MetadataSection {
+            title: set.title.clone(),
+            title_unicode: set.title_unicode.clone(),
+            artist: set.artist.clone(),
+            artist_unicode: set.artist_unicode.clone(),
+            creator: set.creator.clone(),
+            source: set.source.clone(),
+            tags: set.tags.split_whitespace().map(String::from).collect(),
+            beatmap_set_id: set.id,
+            ..Default::default()
+        }
+    }
+}
+
+/// Leaves out the set's difficulties, which a single file doesn't know of.
+impl<'a> From<&'a MetadataSection> for ApiBeatmapset {
+    fn from(metadata: &'a MetadataSection) -> Self {
+        ApiBeatmapset {
+            id: metadata.beatmap_set_id,
+            artist: metadata.artist.clone(),
+            artist_unicode: metadata.artist_unicode.clone(),
+            title: metadata.title.clone(),
+            title_unicode: metadata.title_unicode.clone(),
+            creator: metadata.creator.clone(),
+            source: metadata.source.clone(),
+            tags: metadata.tags.join(" "),
+            beatmaps: Vec::new(),
+        }
+    }
+}
+
+/// The API doesn't report the slider multiplier and tick rate, which keep
+/// their defaults.
+impl<'a> From<&'a ApiBeatmap> for DifficultySection {
+    fn from(beatmap: &'a ApiBeatmap) -> Self {
+        DifficultySection {
+            hp_drain_rate: beatmap.drain,
+            circle_size: beatmap.cs,
+            overall_difficulty: beatmap.accuracy,
+            approach_rate: beatmap.ar,
+            ..Default::default()
+        }
+    }
+}
+
+/// Describes a local beatmap the way the API would. The star rating isn't
+/// computed, and is left at 0.
+impl<'a> From<&'a Beatmap> for ApiBeatmap {
+    fn from(map: &'a Beatmap) -> Self {
+        let count = |f: fn(&HitObject) -> bool| map.hit_objects.iter().filter(|o| f(o)).count() as i32;
+
+        ApiBeatmap {
+            id: map.metadata.beatmap_id,
+            beatmapset_id: map.metadata.beatmap_set_id,
+            version: map.metadata.version.clone(),
+            mode: map.general.game_mode,
+            difficulty_rating: 0.0,
+            cs: map.difficulty.circle_size,
+            ar: map.difficulty.approach_rate,
+            accuracy: map.difficulty.overall_difficulty,
+            drain: map.difficulty.hp_drain_rate,
+            count_circles: count(|o| matches!(o, HitObject::HitCircle(_))),
+            count_sliders: count(|o| matches!(o, HitObject::Slider(_))),
+            count_spinners: count(|o| matches!(o, HitObject::Spinner(_))),
+        }
+    }
+}
+
+/// The API names modes differently from this crate's serde representation,
+/// calling osu!catch `fruits`.
+mod api_mode {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use GameMode;
+
+    pub fn serialize<S: Serializer>(mode: &GameMode, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match mode {
+            GameMode::Osu => "osu",
+            GameMode::Taiko => "taiko",
+            GameMode::CTB => "fruits",
+            GameMode::Mania => "mania",
+        })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<GameMode, D::Error> {
+        match String::deserialize(deserializer)?.as_str() {
+            "osu" => Ok(GameMode::Osu),
+            "taiko" => Ok(GameMode::Taiko),
+            "fruits" => Ok(GameMode::CTB),
+            "mania" => Ok(GameMode::Mania),
+            other => Err(D::Error::unknown_variant(other, &["osu", "taiko", "fruits", "mania"])),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+
+    use serde_json;
+
+    #[test]
+    fn test_api_json() {
+        let json = r#"{
+            "id": 39804,
+            "artist": "xi",
+            "artist_unicode": "xi",
+            "title": "FREEDOM DiVE",
+            "title_unicode": "FREEDOM DiVE",
+            "creator": "Nakagawa-Kanon",
+            "source": "BMS",
+            "tags": "parousia kanon",
+            "status": "ranked",
+            "beatmaps": [{
+                "id": 129891,
+                "beatmapset_id": 39804,
+                "version": "FOUR DIMENSIONS",
+                "mode": "fruits",
+                "difficulty_rating": 7.08,
+                "cs": 4,
+                "ar": 9,
+                "accuracy": 8,
+                "drain": 5,
+                "count_circles": 1320,
+                "count_sliders": 642,
+                "count_spinners": 0,
+                "checksum": "da8aae79c8f3306b5d65ec951874a7fb"
+            }]
+        }"#;
+
+        let set: ApiBeatmapset = serde_json::from_str(json).unwrap();
+        let beatmap = &set.beatmaps[0];
+        assert_eq!(beatmap.mode, GameMode::CTB);
+
+        let metadata = set.metadata_for(beatmap);
+        assert_eq!((metadata.beatmap_set_id, metadata.beatmap_id), (BeatmapSetId(39804), BeatmapId(129891)));
+        assert_eq!(ApiBeatmapset::from(&metadata), ApiBeatmapset { beatmaps: Vec::new(), ..set.clone() });
+
+        let difficulty = DifficultySection::from(beatmap);
+        assert_eq!(difficulty.overall_difficulty, 8.0);
+        assert_eq!(difficulty.slider_multiplier, DifficultySection::default().slider_multiplier);
+
+        let mut map = Beatmap {
+            metadata,
+            difficulty,
+            ..Default::default()
+        };
+        map.general.game_mode = GameMode::CTB;
+        let local = ApiBeatmap::from(&map);
+        assert_eq!(local, ApiBeatmap { difficulty_rating: 0.0, count_circles: 0, count_sliders: 0, ..beatmap.clone() });
+
+        let written = serde_json::to_value(&local).unwrap();
+        assert_eq!(written["mode"], "fruits");
+    }
+}
@@ -0,0 +1,115 @@
+//! Reading beatmaps from disk without blocking an async runtime.
+//!
+//! Requires the `tokio` feature. Files are read through `tokio::fs`, while
+//! parsing itself happens on the task awaiting the result, as it takes well
+//! under a millisecond for typical beatmaps.
+
+use std::path::{Path, PathBuf};
+
+use futures::{stream, Future, FutureExt, Stream, StreamExt};
+use tokio::fs;
+
+use super::*;
+
+/// Reads and parses the beatmap at the given path.
+///
+/// # Examples
+///
+/// ```edition2018
+/// # fn main() {
+/// # let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// # runtime.block_on(async {
+/// let map = osuparse::parse_beatmap_from_path_async("map.osu").await.unwrap();
+/// assert_eq!(map.version, 14);
+/// # });
+/// # }
+/// ```
+pub fn parse_beatmap_from_path_async<P>(path: P) -> impl Future<Output = Result<Beatmap>>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    fs::read_to_string(path).map(|contents| parse_beatmap(&contents.map_err(Error::Io)?))
+}
+
+/// Reads and parses many beatmaps, with at most `concurrency` files being
+/// read at once. Each result is yielded alongside the path it came from, in
+/// the order they finish rather than the order they were given.
+pub fn parse_beatmaps_async<I>(
+    paths: I,
+    concurrency: usize,
+) -> impl Stream<Item = (PathBuf, Result<Beatmap>)>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    parse_beatmaps_async_with_progress(paths, concurrency, |_| {})
+}
+
+/// How far along a [`parse_beatmaps_async_with_progress`](fn.parse_beatmaps_async_with_progress.html)
+/// is.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct BatchProgress<'a> {
+    /// The file that was just parsed, or failed to.
+    pub path: &'a Path,
+    /// Number of files finished so far, including this one.
+    pub files: usize,
+    /// Total number of files given.
+    pub total_files: usize,
+}
+
+/// Same as [`parse_beatmaps_async`](fn.parse_beatmaps_async.html), but
+/// calls `progress` as each result is yielded, for driving progress bars.
+pub fn parse_beatmaps_async_with_progress<I, F>(
+    paths: I,
+    concurrency: usize,
+    mut progress: F,
+) -> impl Stream<Item = (PathBuf, Result<Beatmap>)>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+    F: FnMut(BatchProgress),
+{
+    let paths: Vec<PathBuf> = paths.into_iter().map(|p| p.as_ref().to_owned()).collect();
+    let total_files = paths.len();
+    let mut files = 0;
+
+    stream::iter(paths)
+        .map(|path| parse_beatmap_from_path_async(path.clone()).map(move |res| (path, res)))
+        .buffer_unordered(concurrency.max(1))
+        .map(move |(path, res)| {
+            files += 1;
+            progress(BatchProgress {
+                path: &path,
+                files,
+                total_files,
+            });
+            (path, res)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_beatmaps_async() {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+        let mut updates = Vec::new();
+        let paths = vec!["map.osu", "omtest.osu", "missing.osu"];
+        let mut results: Vec<_> = runtime.block_on(
+            parse_beatmaps_async_with_progress(paths, 2, |p| updates.push((p.files, p.total_files))).collect(),
+        );
+        assert_eq!(updates, vec![(1, 3), (2, 3), (3, 3)]);
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].0, PathBuf::from("map.osu"));
+        assert!(results[0].1.is_ok());
+        match results[1].1 {
+            Err(Error::Io(_)) => {}
+            ref other => panic!("expected an IO error, got {:?}", other),
+        }
+        assert_eq!(results[2].1.as_ref().unwrap().general.game_mode, GameMode::Mania);
+    }
+}
@@ -0,0 +1,139 @@
+extern crate osuparse;
+
+use std::env;
+use std::fs::File;
+use std::io::prelude::*;
+use std::process;
+
+use osuparse::deserialize::ToOsuString;
+use osuparse::*;
+
+const USAGE: &str = "Usage: osuparse <command> <file>...
+
+Commands:
+    inspect     Print a summary of each beatmap
+    json        Dump each beatmap as JSON
+    validate    Check that each beatmap parses, listing any warnings
+    roundtrip   Check that each beatmap survives a serialize/parse cycle";
+
+fn read_file(filename: &str) -> std::result::Result<String, String> {
+    let mut contents = String::new();
+    File::open(filename)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|e| format!("{}: {}", filename, e))?;
+
+    Ok(contents)
+}
+
+fn read_beatmap(filename: &str) -> std::result::Result<Beatmap, String> {
+    let contents = read_file(filename)?;
+    parse_beatmap(&contents).map_err(|e| format!("{}: {}", filename, e))
+}
+
+fn inspect(filename: &str) -> std::result::Result<(), String> {
+    let map = read_beatmap(filename)?;
+
+    let (mut circles, mut sliders, mut spinners, mut hold_notes) = (0, 0, 0, 0);
+    for obj in &map.hit_objects {
+        match obj {
+            HitObject::HitCircle(_) => circles += 1,
+            HitObject::Slider(_) => sliders += 1,
+            HitObject::Spinner(_) => spinners += 1,
+            HitObject::HoldNote(_) => hold_notes += 1,
+        }
+    }
+
+    println!("{}", filename);
+    println!(
+        "  {} - {} ({}) [{}]",
+        map.metadata.artist, map.metadata.title, map.metadata.creator, map.metadata.version
+    );
+    println!("  Format version: v{}", map.version);
+    println!("  Mode:           {:?}", map.general.game_mode);
+    println!("  Audio:          {}", map.general.audio_filename);
+    println!(
+        "  Difficulty:     HP {} CS {} OD {} AR {}",
+        map.difficulty.hp_drain_rate,
+        map.difficulty.circle_size,
+        map.difficulty.overall_difficulty,
+        map.difficulty.approach_rate
+    );
+    println!("  Timing points:  {}", map.timing_points.len());
+    println!(
+        "  Hit objects:    {} ({} circles, {} sliders, {} spinners, {} hold notes)",
+        map.hit_objects.len(),
+        circles,
+        sliders,
+        spinners,
+        hold_notes
+    );
+
+    Ok(())
+}
+
+fn json(filename: &str) -> std::result::Result<(), String> {
+    let map = read_beatmap(filename)?;
+    let json = map.to_json_pretty().map_err(|e| format!("{}: {}", filename, e))?;
+    println!("{}", json);
+
+    Ok(())
+}
+
+fn validate(filename: &str) -> std::result::Result<(), String> {
+    let contents = read_file(filename)?;
+    let (_, warnings) = parse_beatmap_with_options(&contents, &ParserOptions::default())
+        .map_err(|e| format!("{}: {}", filename, e))?;
+
+    for warning in warnings {
+        println!("{}: {}", filename, warning);
+    }
+    println!("{}: OK", filename);
+
+    Ok(())
+}
+
+fn roundtrip(filename: &str) -> std::result::Result<(), String> {
+    let map = read_beatmap(filename)?;
+    let reparsed = parse_beatmap(&map.to_osu_string())
+        .map_err(|e| format!("{}: serialized output failed to parse: {}", filename, e))?;
+
+    if map != reparsed {
+        return Err(format!("{}: beatmap changed after roundtrip", filename));
+    }
+
+    println!("{}: OK", filename);
+
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if args.len() < 2 {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+
+    let command: fn(&str) -> std::result::Result<(), String> = match args[0].as_str() {
+        "inspect" => inspect,
+        "json" => json,
+        "validate" => validate,
+        "roundtrip" => roundtrip,
+        _ => {
+            eprintln!("Unknown command: {}\n\n{}", args[0], USAGE);
+            process::exit(2);
+        }
+    };
+
+    let mut failed = false;
+    for filename in &args[1..] {
+        if let Err(e) = command(filename) {
+            eprintln!("{}", e);
+            failed = true;
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
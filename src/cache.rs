@@ -0,0 +1,324 @@
+//! A persistent cache of beatmap information for scanning large Songs
+//! directories.
+//!
+//! Requires the `cache` feature. [`BeatmapCache::scan`](struct.BeatmapCache.html#method.scan)
+//! only reads files whose size or modification time changed since the last
+//! scan, and only parses those whose contents actually differ.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use osuparse::cache::BeatmapCache;
+//!
+//! let mut cache = BeatmapCache::load("songs.cache").unwrap_or_default();
+//! let summary = cache.scan("Songs").unwrap();
+//! println!("{} new or changed, {} unchanged", summary.parsed, summary.unchanged);
+//!
+//! cache.save("songs.cache").unwrap();
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use bincode;
+
+use super::*;
+use hash::fnv1a;
+
+/// Written at the start of every cache file, before the format version.
+const MAGIC: &[u8; 8] = b"osucache";
+/// Version of the layout of cache files. Bincode doesn't describe the layout
+/// it writes, so this has to change whenever `CachedBeatmap` or anything in
+/// it does, for old caches to be dropped instead of misread.
+const FORMAT_VERSION: u32 = 1;
+
+/// The magic bytes and format version that cache files start with.
+fn header() -> [u8; 12] {
+    let mut header = [0; 12];
+    header[..8].copy_from_slice(MAGIC);
+    header[8..].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+    header
+}
+
+/// The information kept about each beatmap file.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct CachedBeatmap {
+    /// Modification time of the file when it was last read, if the platform
+    /// provides one.
+    pub modified: Option<SystemTime>,
+    /// Size of the file in bytes.
+    pub len: u64,
+    /// FNV-1a hash of the file contents.
+    pub hash: u64,
+    pub version: i32,
+    pub game_mode: GameMode,
+    pub metadata: MetadataSection,
+    pub difficulty: DifficultySection,
+}
+
+/// The outcome of a [`BeatmapCache::scan`](struct.BeatmapCache.html#method.scan).
+#[derive(Debug, Default)]
+pub struct ScanSummary {
+    /// Files that were new or had changed, and were parsed again.
+    pub parsed: usize,
+    /// Files that were skipped, either because their size and modification
+    /// time were unchanged, or because their contents hashed the same.
+    pub unchanged: usize,
+    /// Entries dropped because their file no longer exists.
+    pub removed: usize,
+    /// Files that couldn't be read or parsed. They are left out of the cache.
+    pub failed: Vec<(PathBuf, Error)>,
+}
+
+/// How far along a [`BeatmapCache::scan_with_progress`](struct.BeatmapCache.html#method.scan_with_progress)
+/// is.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct ScanProgress<'a> {
+    /// The file that was just checked.
+    pub path: &'a Path,
+    /// Number of files checked so far, including this one.
+    pub files: usize,
+    /// Total number of `.osu` files found in the directory.
+    pub total_files: usize,
+}
+
+/// Cached information about every `.osu` file found by previous scans, keyed
+/// by path.
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+pub struct BeatmapCache {
+    entries: BTreeMap<PathBuf, CachedBeatmap>,
+}
+
+impl BeatmapCache {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Reads a cache previously written by [`save`](#method.save). A cache
+    /// written by another version of this crate with a different layout, or
+    /// a file that isn't a cache at all, gives an empty cache, so that the
+    /// next scan reads every file again.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(path).map_err(Error::Io)?);
+
+        let mut file_header = [0; 12];
+        match reader.read_exact(&mut file_header) {
+            Ok(()) if file_header == header() => {}
+            Ok(()) => return Ok(BeatmapCache::new()),
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(BeatmapCache::new()),
+            Err(e) => return Err(Error::Io(e)),
+        }
+
+        bincode::deserialize_from(reader).map_err(Error::Cache)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path).map_err(Error::Io)?);
+        writer.write_all(&header()).map_err(Error::Io)?;
+        bincode::serialize_into(&mut writer, self).map_err(Error::Cache)?;
+        writer.flush().map_err(Error::Io)
+    }
+
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<&CachedBeatmap> {
+        self.entries.get(path.as_ref())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &CachedBeatmap)> {
+        self.entries.iter().map(|(p, e)| (p.as_path(), e))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Recursively scans a directory for `.osu` files, bringing the cache up
+    /// to date with them. Entries under the directory whose file is gone are
+    /// removed.
+    ///
+    /// Errors reading or parsing individual files are collected into the
+    /// summary; only failing to list a directory aborts the scan.
+    pub fn scan<P: AsRef<Path>>(&mut self, dir: P) -> Result<ScanSummary> {
+        self.scan_with_progress(dir, |_| {})
+    }
+
+    /// Same as [`scan`](#method.scan), but calls `progress` after each file
+    /// is checked, for driving progress bars.
+    pub fn scan_with_progress<P, F>(&mut self, dir: P, mut progress: F) -> Result<ScanSummary>
+    where
+        P: AsRef<Path>,
+        F: FnMut(ScanProgress),
+    {
+        let dir = dir.as_ref();
+        let mut summary = ScanSummary::default();
+
+        let mut files = Vec::new();
+        find_beatmap_files(dir, &mut files)?;
+        files.sort();
+
+        let stale: Vec<PathBuf> = self.entries.keys()
+            .filter(|p| p.starts_with(dir) && files.binary_search(p).is_err())
+            .cloned()
+            .collect();
+        for path in stale {
+            self.entries.remove(&path);
+            summary.removed += 1;
+        }
+
+        let total_files = files.len();
+        for (i, path) in files.into_iter().enumerate() {
+            match self.update(&path) {
+                Ok(true) => summary.parsed += 1,
+                Ok(false) => summary.unchanged += 1,
+                Err(e) => {
+                    self.entries.remove(&path);
+                    summary.failed.push((path.clone(), e));
+                }
+            }
+
+            progress(ScanProgress {
+                path: &path,
+                files: i + 1,
+                total_files,
+            });
+        }
+
+        Ok(summary)
+    }
+
+    /// Brings the entry for a single file up to date, returning whether it
+    /// had to be parsed.
+    fn update(&mut self, path: &Path) -> Result<bool> {
+        let file_metadata = fs::metadata(path).map_err(Error::Io)?;
+        let modified = file_metadata.modified().ok();
+        let len = file_metadata.len();
+
+        if let Some(entry) = self.entries.get(path) {
+            if modified.is_some() && entry.modified == modified && entry.len == len {
+                return Ok(false);
+            }
+        }
+
+        let contents = fs::read(path).map_err(Error::Io)?;
+        let hash = fnv1a(&contents);
+
+        if let Some(entry) = self.entries.get_mut(path) {
+            if entry.hash == hash {
+                entry.modified = modified;
+                entry.len = len;
+                return Ok(false);
+            }
+        }
+
+        let contents = String::from_utf8_lossy(&contents);
+        let map = parse_beatmap(&contents)?;
+
+        self.entries.insert(path.to_owned(), CachedBeatmap {
+            modified,
+            len,
+            hash,
+            version: map.version,
+            game_mode: map.general.game_mode,
+            metadata: map.metadata,
+            difficulty: map.difficulty,
+        });
+
+        Ok(true)
+    }
+}
+
+/// Collects the paths of all `.osu` files under a directory. Symbolic links
+/// to directories aren't followed, so that a link back up the tree can't
+/// make the scan go on forever.
+fn find_beatmap_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).map_err(Error::Io)? {
+        let entry = entry.map_err(Error::Io)?;
+        let path = entry.path();
+
+        if entry.file_type().map_err(Error::Io)?.is_dir() {
+            find_beatmap_files(&path, files)?;
+        } else if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("osu")) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::io::Write;
+
+    #[test]
+    fn test_scan() {
+        let dir = env::temp_dir().join(format!("osuparse-cache-{}", std::process::id()));
+        let set_dir = dir.join("1 Artist - Title");
+        fs::create_dir_all(&set_dir).unwrap();
+        fs::copy("map.osu", set_dir.join("easy.osu")).unwrap();
+        fs::copy("omtest.osu", set_dir.join("mania.osu")).unwrap();
+        fs::write(set_dir.join("notes.txt"), "not a beatmap").unwrap();
+
+        let mut cache = BeatmapCache::new();
+        let summary = cache.scan(&dir).unwrap();
+        assert_eq!((summary.parsed, summary.unchanged, summary.removed), (2, 0, 0));
+        assert_eq!(cache.get(set_dir.join("mania.osu")).unwrap().game_mode, GameMode::Mania);
+
+        let mut updates = Vec::new();
+        let summary = cache.scan_with_progress(&dir, |p| updates.push((p.files, p.total_files))).unwrap();
+        assert_eq!((summary.parsed, summary.unchanged, summary.removed), (0, 2, 0));
+        assert_eq!(updates, vec![(1, 2), (2, 2)]);
+
+        // Same contents under a new modification time are hashed, not parsed
+        let contents = fs::read(set_dir.join("easy.osu")).unwrap();
+        fs::write(set_dir.join("easy.osu"), &contents).unwrap();
+        cache.entries.get_mut(&set_dir.join("easy.osu")).unwrap().modified = None;
+        let summary = cache.scan(&dir).unwrap();
+        assert_eq!((summary.parsed, summary.unchanged), (0, 2));
+
+        let mut file = fs::OpenOptions::new().append(true).open(set_dir.join("easy.osu")).unwrap();
+        writeln!(file, "256,192,999999,1,0,0:0:0:0:").unwrap();
+        fs::remove_file(set_dir.join("mania.osu")).unwrap();
+        let summary = cache.scan(&dir).unwrap();
+        assert_eq!((summary.parsed, summary.unchanged, summary.removed), (1, 0, 1));
+        assert_eq!(cache.len(), 1);
+
+        let cache_file = dir.join("songs.cache");
+        cache.save(&cache_file).unwrap();
+        assert_eq!(BeatmapCache::load(&cache_file).unwrap(), cache);
+
+        // Caches of another format version are dropped rather than misread
+        let mut contents = fs::read(&cache_file).unwrap();
+        contents[8] ^= 0xff;
+        fs::write(&cache_file, &contents).unwrap();
+        assert!(BeatmapCache::load(&cache_file).unwrap().is_empty());
+        fs::write(&cache_file, "osu").unwrap();
+        assert!(BeatmapCache::load(&cache_file).unwrap().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_symlink_loop() {
+        let dir = env::temp_dir().join(format!("osuparse-cache-loop-{}", std::process::id()));
+        let set_dir = dir.join("1 Artist - Title");
+        fs::create_dir_all(&set_dir).unwrap();
+        fs::copy("map.osu", set_dir.join("easy.osu")).unwrap();
+        std::os::unix::fs::symlink(&dir, set_dir.join("loop")).unwrap();
+
+        let mut cache = BeatmapCache::new();
+        let summary = cache.scan(&dir).unwrap();
+        assert_eq!(summary.parsed, 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
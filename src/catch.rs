@@ -0,0 +1,444 @@
+//! The objects of an osu!catch beatmap as the game creates them.
+//!
+//! Sliders become juice streams, made of fruits at their head, repeats and
+//! tail, droplets on their ticks and tiny droplets in between. Spinners
+//! become banana showers. Tiny droplets and bananas are moved sideways by
+//! the game's random number generator, which is seeded the same way every
+//! time, so the positions here match the game's. Fruits and droplets that
+//! can only be caught by hyperdashing to the next one are marked.
+//!
+//! Sliders are followed for at most 9000 spans, as in the game, and no
+//! juice stream or banana shower is given more than 100,000 objects.
+//!
+//! Mods, such as the offsets Hard Rock adds to fruits, aren't applied.
+
+use super::*;
+use modes::{CatchBeatmap, CatchObject};
+
+/// Width of the playfield in osu!pixels.
+const PLAYFIELD_WIDTH: f32 = 512.0;
+/// Width of the catcher at a circle size of 5.
+const CATCHER_BASE_SIZE: f32 = 106.75;
+/// Distance the catcher moves per millisecond while dashing.
+const BASE_DASH_SPEED: f32 = 1.0;
+/// Seed of the random number generator moving tiny droplets and bananas.
+const RNG_SEED: i32 = 1337;
+/// Longest slider path the game follows.
+const MAX_PATH_LENGTH: f32 = 100_000.0;
+/// How long before the end of a slider its last tick is counted.
+const LAST_TICK_OFFSET: Time = Time(36.0);
+/// Most spans a slider can have. The game refuses to load sliders with
+/// more repeats than this.
+const MAX_SPANS: i32 = 9000;
+/// Most objects created for one juice stream or banana shower. Real
+/// beatmaps stay far below it, but a slider or spinner lasting for weeks
+/// would otherwise use up all memory.
+const MAX_GENERATED_OBJECTS: usize = 100_000;
+
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub enum CatchObjectKind {
+    Fruit,
+    Droplet,
+    TinyDroplet,
+    Banana,
+}
+
+/// A single thing to catch.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct CatchHitObject {
+    pub kind: CatchObjectKind,
+    pub time: Time,
+    /// Horizontal position in osu!pixels, from `0` to `512`.
+    pub x: f32,
+    /// Whether the catcher has to hyperdash to reach the next fruit or
+    /// droplet. Always `false` for tiny droplets and bananas.
+    pub hyper_dash: bool,
+    /// How much further than needed the catcher could dash before the next
+    /// fruit or droplet, in osu!pixels. `0` when hyperdashing.
+    pub distance_to_hyper_dash: f32,
+}
+
+impl CatchHitObject {
+    fn new(kind: CatchObjectKind, time: Time, x: f32) -> Self {
+        CatchHitObject { kind, time, x, hyper_dash: false, distance_to_hyper_dash: 0.0 }
+    }
+
+    /// Whether the object counts towards combo and hyperdashes: fruits and
+    /// droplets.
+    pub fn is_palpable(&self) -> bool {
+        self.kind == CatchObjectKind::Fruit || self.kind == CatchObjectKind::Droplet
+    }
+}
+
+/// The xorshift generator used by osu!stable.
+struct LegacyRandom {
+    x: u32,
+    y: u32,
+    z: u32,
+    w: u32,
+}
+
+impl LegacyRandom {
+    fn new(seed: i32) -> Self {
+        LegacyRandom { x: seed as u32, y: 842_502_087, z: 3_579_807_591, w: 273_326_509 }
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        let t = self.x ^ (self.x << 11);
+        self.x = self.y;
+        self.y = self.z;
+        self.z = self.w;
+        self.w = self.w ^ (self.w >> 19) ^ t ^ (t >> 8);
+        self.w
+    }
+
+    fn next(&mut self) -> i32 {
+        (self.next_u32() & 0x7fff_ffff) as i32
+    }
+
+    fn next_in(&mut self, low: i32, high: i32) -> i32 {
+        let fraction = f64::from(self.next()) / (f64::from(i32::MAX) + 1.0);
+        (f64::from(low) + fraction * f64::from(high - low)) as i32
+    }
+}
+
+/// A point on a slider that creates an object, as a time and a fraction of
+/// the way along its path.
+#[derive(Debug, PartialEq, Copy, Clone)]
+struct SliderEvent {
+    kind: Option<CatchObjectKind>,
+    time: Time,
+    progress: f32,
+}
+
+impl<'a> CatchBeatmap<'a> {
+    /// Every object to catch, in the order the game creates them: each
+    /// fruit, and the objects making up each juice stream and banana shower.
+    ///
+    /// ```
+    /// use osuparse::catch::CatchObjectKind;
+    /// use osuparse::Time;
+    ///
+    /// let map = osuparse::parse_beatmap("osu file format v14
+    ///
+    /// [General]
+    /// Mode: 2
+    ///
+    /// [Difficulty]
+    /// SliderMultiplier:1
+    ///
+    /// [TimingPoints]
+    /// 0,500,4,1,0,100,1,0
+    ///
+    /// [HitObjects]
+    /// 0,192,1000,1,0
+    /// 512,192,1100,1,0
+    /// 256,192,2000,2,0,L|356:192,1,100
+    /// ").unwrap();
+    ///
+    /// let objects = map.as_catch().unwrap().generate_objects();
+    /// let kinds: Vec<CatchObjectKind> = objects.iter().map(|o| o.kind).collect();
+    ///
+    /// assert!(objects[0].hyper_dash);
+    /// assert_eq!(kinds.iter().filter(|&&k| k == CatchObjectKind::TinyDroplet).count(), 7);
+    /// assert_eq!((objects[10].kind, objects[10].time, objects[10].x), (CatchObjectKind::Fruit, Time(2500.0), 356.0));
+    /// ```
+    pub fn generate_objects(&self) -> Vec<CatchHitObject> {
+        let map = self.beatmap;
+        let index = map.timing_index();
+
+        let mut objects = Vec::new();
+        let mut rng = LegacyRandom::new(RNG_SEED);
+        for obj in &self.objects {
+            match *obj {
+                CatchObject::Fruit(c) => {
+                    objects.push(CatchHitObject::new(CatchObjectKind::Fruit, c.time, clamp_x(c.x as f32)));
+                }
+                CatchObject::JuiceStream(s) => juice_stream(map, &index, s, &mut rng, &mut objects),
+                CatchObject::BananaShower(s) => banana_shower(s, &mut rng, &mut objects),
+            }
+        }
+
+        set_hyper_dashes(&mut objects, map.difficulty.circle_size);
+        objects
+    }
+}
+
+fn clamp_x(x: f32) -> f32 {
+    x.clamp(0.0, PLAYFIELD_WIDTH)
+}
+
+/// Adds the objects of a juice stream, following the game's slider events:
+/// the head, the ticks and repeats of every span, the legacy last tick and
+/// the tail.
+fn juice_stream(
+    map: &Beatmap,
+    index: &TimingIndex,
+    slider: &Slider,
+    rng: &mut LegacyRandom,
+    objects: &mut Vec<CatchHitObject>,
+) {
+    let limit = objects.len() + MAX_GENERATED_OBJECTS;
+    let time = slider.time;
+    let spans = slider.repeat.clamp(1, MAX_SPANS);
+    let span_duration = index.slider_duration(slider) / f64::from(slider.repeat.max(1));
+    let path = slider.path();
+    let length = path.length().min(MAX_PATH_LENGTH);
+
+    let sv = index.slider_velocity_at(slider.time);
+    let scoring_distance = 100.0 * map.difficulty.slider_multiplier * sv;
+    let velocity = index.beat_length_at(slider.time).map_or(0.0, |b| scoring_distance / b as f32);
+    let mut tick_distance = scoring_distance / map.difficulty.slider_tick_rate;
+    if !map.format_version().has_approach_rate() {
+        // Before v8, ticks didn't follow the slider velocity
+        tick_distance /= sv;
+    }
+    let tick_distance = if tick_distance.is_finite() { tick_distance.clamp(0.0, length) } else { 0.0 };
+    let min_distance_from_end = velocity * 10.0;
+
+    let mut events = vec![SliderEvent { kind: Some(CatchObjectKind::Fruit), time, progress: 0.0 }];
+    if tick_distance > 0.0 {
+        for span in 0..spans {
+            let span_start = time + span_duration * f64::from(span);
+            let reversed = span % 2 == 1;
+
+            let mut ticks = Vec::new();
+            let mut distance = tick_distance;
+            while distance <= length && distance < length - min_distance_from_end && events.len() + ticks.len() < MAX_GENERATED_OBJECTS {
+                let progress = distance / length;
+                let time_progress = if reversed { 1.0 - progress } else { progress };
+                ticks.push(SliderEvent {
+                    kind: Some(CatchObjectKind::Droplet),
+                    time: span_start + span_duration * f64::from(time_progress),
+                    progress,
+                });
+                distance += tick_distance;
+            }
+            if reversed {
+                ticks.reverse();
+            }
+            events.extend(ticks);
+
+            if span < spans - 1 {
+                events.push(SliderEvent {
+                    kind: Some(CatchObjectKind::Fruit),
+                    time: span_start + span_duration,
+                    progress: ((span + 1) % 2) as f32,
+                });
+            }
+        }
+    }
+
+    let total_duration = span_duration * f64::from(spans);
+    let final_span_start = time + span_duration * f64::from(spans - 1);
+    let last_tick = (time + total_duration / 2.0).max(final_span_start + span_duration - LAST_TICK_OFFSET);
+    let mut last_tick_progress = ((last_tick - final_span_start).0 / span_duration.0) as f32;
+    if spans % 2 == 0 {
+        last_tick_progress = 1.0 - last_tick_progress;
+    }
+    // The last tick doesn't create an object, but tiny droplets lead up to it
+    events.push(SliderEvent { kind: None, time: last_tick, progress: last_tick_progress });
+    events.push(SliderEvent {
+        kind: Some(CatchObjectKind::Fruit),
+        time: time + total_duration,
+        progress: (spans % 2) as f32,
+    });
+
+    let mut last: Option<SliderEvent> = None;
+    for event in events {
+        if let Some(last) = last {
+            // The game measures the gap in whole milliseconds
+            let since_last = event.time.0.trunc() - last.time.0.trunc();
+            if since_last > 80.0 {
+                let mut between = since_last;
+                while between > 100.0 {
+                    between /= 2.0;
+                }
+
+                let mut t = between;
+                while t < since_last && objects.len() < limit {
+                    let progress = last.progress + (t / since_last) as f32 * (event.progress - last.progress);
+                    let x = path.position_at(progress).0;
+                    let offset = (rng.next_in(-20, 20) as f32).clamp(-x, PLAYFIELD_WIDTH - x);
+                    objects.push(CatchHitObject::new(CatchObjectKind::TinyDroplet, last.time + Time(t), clamp_x(x + offset)));
+                    t += between;
+                }
+            }
+        }
+        last = Some(event);
+
+        if let Some(kind) = event.kind {
+            if kind == CatchObjectKind::Droplet {
+                // The game picked a rotation for every droplet
+                rng.next();
+            }
+            objects.push(CatchHitObject::new(kind, event.time, clamp_x(path.position_at(event.progress).0)));
+        }
+    }
+}
+
+/// Adds the bananas of a banana shower, one every 100ms or less from the
+/// start of the spinner to its end.
+fn banana_shower(spinner: &Spinner, rng: &mut LegacyRandom, objects: &mut Vec<CatchHitObject>) {
+    let (start, end) = (spinner.time, spinner.end_time);
+    let mut spacing = end - start;
+    while spacing > Time(100.0) {
+        spacing = spacing / 2.0;
+    }
+    if spacing <= Time::ZERO {
+        return;
+    }
+
+    let mut time = start;
+    for _ in 0..MAX_GENERATED_OBJECTS {
+        if time > end {
+            break;
+        }
+
+        let x = (rng.next() % PLAYFIELD_WIDTH as i32) as f32;
+        // The game also picked a type, rotation and colour for every banana
+        rng.next();
+        rng.next();
+        rng.next();
+
+        objects.push(CatchHitObject::new(CatchObjectKind::Banana, time, x));
+        time += spacing;
+    }
+}
+
+/// Marks the fruits and droplets that need a hyperdash to reach the next
+/// one, as osu!stable does.
+fn set_hyper_dashes(objects: &mut [CatchHitObject], circle_size: f32) {
+    let scale = 1.0 - 0.7 * (circle_size - 5.0) / 5.0;
+    let half_catcher_width = CATCHER_BASE_SIZE * scale / 2.0;
+
+    let palpable: Vec<usize> = (0..objects.len()).filter(|&i| objects[i].is_palpable()).collect();
+    let mut last_direction = 0;
+    let mut last_excess = half_catcher_width;
+
+    for pair in palpable.windows(2) {
+        let (current, next) = (objects[pair[0]], objects[pair[1]]);
+        let direction = if next.x > current.x { 1 } else { -1 };
+
+        // A quarter of a frame of leniency
+        let time_to_next = (next.time.0.trunc() - current.time.0.trunc()) as f32 - 1000.0 / 60.0 / 4.0;
+        let excess = if direction == last_direction { last_excess } else { half_catcher_width };
+        let distance_to_next = (next.x - current.x).abs() - excess;
+        let distance_to_hyper = time_to_next * BASE_DASH_SPEED - distance_to_next;
+
+        let object = &mut objects[pair[0]];
+        if distance_to_hyper < 0.0 {
+            object.hyper_dash = true;
+            object.distance_to_hyper_dash = 0.0;
+            last_excess = half_catcher_width;
+        } else {
+            object.hyper_dash = false;
+            object.distance_to_hyper_dash = distance_to_hyper;
+            last_excess = distance_to_hyper.clamp(0.0, half_catcher_width);
+        }
+        last_direction = direction;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_random() {
+        let mut rng = LegacyRandom::new(RNG_SEED);
+        let values: Vec<i32> = (0..3).map(|_| rng.next()).collect();
+        assert!(values.iter().all(|&v| v >= 0));
+        assert_ne!(values[0], values[1]);
+
+        let mut rng = LegacyRandom::new(RNG_SEED);
+        assert!((0..100).map(|_| rng.next_in(-20, 20)).all(|v| (-20..20).contains(&v)));
+    }
+
+    #[test]
+    fn test_generate_objects() {
+        let map = parse_beatmap("osu file format v14
+
+[General]
+Mode: 2
+
+[Difficulty]
+SliderMultiplier:1
+SliderTickRate:2
+
+[TimingPoints]
+0,500,4,1,0,100,1,0
+
+[HitObjects]
+256,192,1000,2,0,L|456:192,2,200
+256,192,4000,8,0,4300
+").unwrap();
+
+        let objects = map.as_catch().unwrap().generate_objects();
+        let palpable: Vec<(CatchObjectKind, f64, f32)> = objects
+            .iter()
+            .filter(|o| o.is_palpable())
+            .map(|o| (o.kind, o.time.0, o.x))
+            .collect();
+        assert_eq!(palpable, vec![
+            (CatchObjectKind::Fruit, 1000.0, 256.0),
+            (CatchObjectKind::Droplet, 1250.0, 306.0),
+            (CatchObjectKind::Droplet, 1500.0, 356.0),
+            (CatchObjectKind::Droplet, 1750.0, 406.0),
+            (CatchObjectKind::Fruit, 2000.0, 456.0),
+            (CatchObjectKind::Droplet, 2250.0, 406.0),
+            (CatchObjectKind::Droplet, 2500.0, 356.0),
+            (CatchObjectKind::Droplet, 2750.0, 306.0),
+            (CatchObjectKind::Fruit, 3000.0, 256.0),
+        ]);
+
+        let tiny: Vec<f64> = objects
+            .iter()
+            .filter(|o| o.kind == CatchObjectKind::TinyDroplet)
+            .map(|o| o.time.0)
+            .collect();
+        // Three between each pair of ticks, and between the last one and the
+        // legacy last tick
+        assert_eq!(tiny.len(), 24);
+        assert_eq!(&tiny[..3], &[1062.5, 1125.0, 1187.5]);
+        assert_eq!(&tiny[21..], &[2803.5, 2857.0, 2910.5]);
+
+        let bananas: Vec<f64> = objects
+            .iter()
+            .filter(|o| o.kind == CatchObjectKind::Banana)
+            .map(|o| o.time.0)
+            .collect();
+        assert_eq!(bananas, vec![4000.0, 4075.0, 4150.0, 4225.0, 4300.0]);
+        assert!(objects.iter().all(|o| !o.hyper_dash));
+    }
+
+    #[test]
+    fn test_far_off_objects() {
+        let map = parse_beatmap("osu file format v14
+
+[General]
+Mode: 2
+
+[TimingPoints]
+0,500,4,1,0,100,1,0
+
+[HitObjects]
+256,192,1073800000,12,0,1074280000
+256,192,2000000000,2,0,L|356:192,2147483647,100
+").unwrap();
+
+        let objects = map.as_catch().unwrap().generate_objects();
+        let bananas: Vec<f64> = objects
+            .iter()
+            .filter(|o| o.kind == CatchObjectKind::Banana)
+            .map(|o| o.time.0)
+            .collect();
+        assert_eq!(bananas.len(), 8193);
+        assert_eq!(bananas[1] - bananas[0], 480_000.0 / 8192.0);
+        assert_eq!(bananas.last(), Some(&1_074_280_000.0));
+
+        let fruits = objects.iter().filter(|o| o.kind == CatchObjectKind::Fruit).count();
+        assert_eq!(fruits, MAX_SPANS as usize + 1);
+        assert!(objects.len() < 2 * MAX_GENERATED_OBJECTS);
+    }
+}
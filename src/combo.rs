@@ -0,0 +1,115 @@
+use super::*;
+
+/// Where a hit object falls within the beatmap's combos, as the game
+/// displays them.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct Combo {
+    /// Whether the object starts a new combo. Unlike
+    /// [`HitObject::new_combo`](enum.HitObject.html#method.new_combo) this
+    /// takes into account the objects that always start one.
+    pub new_combo: bool,
+    /// The number shown on the object, starting at 1 for the first object of
+    /// each combo.
+    pub number: usize,
+    /// Index of the object's combo colour, before wrapping around the number
+    /// of colours available. Every new combo moves one colour further, plus
+    /// however many its [`color_skip`](enum.HitObject.html#method.color_skip)
+    /// asks for.
+    pub colour_index: usize,
+}
+
+impl Beatmap {
+    /// Works out the combo of every hit object, in the same order as
+    /// [`hit_objects`](struct.Beatmap.html#structfield.hit_objects), which
+    /// should be sorted by time.
+    ///
+    /// Besides objects flagged with a new combo, the first object always
+    /// starts one, as do spinners and the object that follows a spinner.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let input = "osu file format v14
+    ///
+    /// [HitObjects]
+    /// 256,192,1000,1,0
+    /// 256,192,1500,1,0
+    /// 256,192,2000,8,0,3000
+    /// 256,192,3500,1,0
+    /// ";
+    ///
+    /// let map = osuparse::parse_beatmap(input).unwrap();
+    /// let starts: Vec<bool> = map.combos().iter().map(|c| c.new_combo).collect();
+    ///
+    /// assert_eq!(starts, vec![true, false, true, true]);
+    /// ```
+    pub fn combos(&self) -> Vec<Combo> {
+        let mut combos: Vec<Combo> = Vec::with_capacity(self.hit_objects.len());
+        let mut after_spinner = false;
+
+        for obj in &self.hit_objects {
+            let is_spinner = matches!(obj, HitObject::Spinner(_));
+
+            let combo = match combos.last() {
+                None => Combo {
+                    new_combo: true,
+                    number: 1,
+                    colour_index: 0,
+                },
+                Some(prev) if obj.new_combo() || is_spinner || after_spinner => Combo {
+                    new_combo: true,
+                    number: 1,
+                    colour_index: prev.colour_index + 1 + (obj.color_skip() & 0b111) as usize,
+                },
+                Some(prev) => Combo {
+                    new_combo: false,
+                    number: prev.number + 1,
+                    colour_index: prev.colour_index,
+                },
+            };
+
+            combos.push(combo);
+            after_spinner = is_spinner;
+        }
+
+        combos
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combos() {
+        let input = "osu file format v14
+
+[HitObjects]
+256,192,1000,5,0
+256,192,1500,1,0
+256,192,2000,1,0
+256,192,2500,37,0
+256,192,3000,1,0
+256,192,3500,12,0,4000
+256,192,4500,1,0
+256,192,5000,1,0
+";
+
+        let map = parse_beatmap(input).unwrap();
+        let combos: Vec<(bool, usize, usize)> = map.combos()
+            .iter()
+            .map(|c| (c.new_combo, c.number, c.colour_index))
+            .collect();
+
+        assert_eq!(combos, vec![
+            (true, 1, 0),
+            (false, 2, 0),
+            (false, 3, 0),
+            (true, 1, 3),
+            (false, 2, 3),
+            (true, 1, 4),
+            (true, 1, 5),
+            (false, 2, 5),
+        ]);
+    }
+}
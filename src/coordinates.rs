@@ -0,0 +1,168 @@
+//! Conversions between osu!pixels and screen coordinates.
+//!
+//! Hit objects are placed on a 512×384 playfield, and storyboards on a
+//! 640×480 canvas, 854×480 for widescreen storyboards. The game scales both
+//! to the height of the screen and centres them, and this module does the
+//! same so that anything drawing a beatmap lines up with the game.
+
+type Point = (f32, f32);
+
+/// Size of the playfield, in osu!pixels.
+pub const PLAYFIELD_WIDTH: f32 = 512.0;
+pub const PLAYFIELD_HEIGHT: f32 = 384.0;
+
+/// Size of the storyboard canvas, in storyboard pixels.
+pub const STORYBOARD_WIDTH: f32 = 640.0;
+pub const STORYBOARD_HEIGHT: f32 = 480.0;
+/// Width of the canvas of widescreen storyboards.
+pub const WIDESCREEN_STORYBOARD_WIDTH: f32 = 854.0;
+
+/// How far the playfield is moved down from the centre of the screen, in
+/// storyboard pixels.
+const PLAYFIELD_SHIFT: f32 = 8.0;
+
+/// Maps a point on the playfield to `0..1` on both axes.
+pub fn to_normalized(point: Point) -> Point {
+    (point.0 / PLAYFIELD_WIDTH, point.1 / PLAYFIELD_HEIGHT)
+}
+
+/// Maps a point in `0..1` on both axes back to the playfield.
+pub fn from_normalized(point: Point) -> Point {
+    (point.0 * PLAYFIELD_WIDTH, point.1 * PLAYFIELD_HEIGHT)
+}
+
+/// The screen a beatmap is drawn on.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Screen {
+    pub width: f32,
+    pub height: f32,
+    /// Whether the game area keeps its aspect ratio, with black bars filling
+    /// the rest of the screen, instead of taking up the whole screen.
+    pub letterbox: bool,
+    /// Whether the game area is 16:9 rather than 4:3. This should match the
+    /// beatmap's
+    /// [`widescreen_storyboard`](../struct.GeneralSection.html#structfield.widescreen_storyboard).
+    pub widescreen: bool,
+}
+
+impl Screen {
+    pub fn new(width: f32, height: f32) -> Self {
+        Screen {
+            width,
+            height,
+            letterbox: false,
+            widescreen: false,
+        }
+    }
+
+    /// The part of the screen the game draws in, as its top left corner and
+    /// its size.
+    pub fn game_area(&self) -> (Point, Point) {
+        if !self.letterbox {
+            return ((0.0, 0.0), (self.width, self.height));
+        }
+
+        let canvas_width = if self.widescreen { WIDESCREEN_STORYBOARD_WIDTH } else { STORYBOARD_WIDTH };
+        let aspect = canvas_width / STORYBOARD_HEIGHT;
+        let (width, height) = if self.width / self.height > aspect {
+            (self.height * aspect, self.height)
+        } else {
+            (self.width, self.width / aspect)
+        };
+
+        (((self.width - width) / 2.0, (self.height - height) / 2.0), (width, height))
+    }
+}
+
+/// A scale followed by a translation, mapping osu!pixels or storyboard
+/// pixels to screen pixels.
+///
+/// # Examples
+///
+/// ```
+/// use osuparse::coordinates::{Screen, Transform};
+///
+/// let transform = Transform::playfield(&Screen::new(640.0, 480.0));
+/// assert_eq!(transform.apply((0.0, 0.0)), (64.0, 56.0));
+/// assert_eq!(transform.invert((320.0, 248.0)), (256.0, 192.0));
+/// ```
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Transform {
+    pub scale: f32,
+    pub offset: Point,
+}
+
+impl Transform {
+    /// Places the playfield as the game does: at the scale of the storyboard
+    /// canvas, which makes it 80% of the height of the game area, centred and
+    /// moved down slightly.
+    pub fn playfield(screen: &Screen) -> Self {
+        let ((x, y), (width, height)) = screen.game_area();
+        let scale = height / STORYBOARD_HEIGHT;
+
+        Transform {
+            scale,
+            offset: (
+                x + (width - PLAYFIELD_WIDTH * scale) / 2.0,
+                y + (height - PLAYFIELD_HEIGHT * scale) / 2.0 + PLAYFIELD_SHIFT * scale,
+            ),
+        }
+    }
+
+    /// Places the storyboard canvas as the game does: filling the height of
+    /// the game area, centred. Widescreen storyboards use the same transform,
+    /// their canvas reaching further to each side.
+    pub fn storyboard(screen: &Screen) -> Self {
+        let ((x, y), (width, height)) = screen.game_area();
+        let scale = height / STORYBOARD_HEIGHT;
+
+        Transform {
+            scale,
+            offset: (x + (width - STORYBOARD_WIDTH * scale) / 2.0, y),
+        }
+    }
+
+    pub fn apply(&self, point: Point) -> Point {
+        (point.0 * self.scale + self.offset.0, point.1 * self.scale + self.offset.1)
+    }
+
+    /// Maps a screen point back, e.g. to find where on the playfield the
+    /// cursor is.
+    pub fn invert(&self, point: Point) -> Point {
+        ((point.0 - self.offset.0) / self.scale, (point.1 - self.offset.1) / self.scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Point, b: Point) -> bool {
+        (a.0 - b.0).abs() < 0.01 && (a.1 - b.1).abs() < 0.01
+    }
+
+    #[test]
+    fn test_transforms() {
+        assert_eq!(to_normalized((256.0, 96.0)), (0.5, 0.25));
+        assert_eq!(from_normalized((0.5, 0.25)), (256.0, 96.0));
+
+        let screen = Screen::new(1920.0, 1080.0);
+        let playfield = Transform::playfield(&screen);
+        assert!(close(playfield.apply((0.0, 0.0)), (384.0, 126.0)));
+        assert!(close(playfield.apply((256.0, 192.0)), (960.0, 558.0)));
+        assert!(close(playfield.invert(playfield.apply((100.0, 300.0))), (100.0, 300.0)));
+
+        let storyboard = Transform::storyboard(&screen);
+        assert_eq!(storyboard.apply((320.0, 240.0)), (960.0, 540.0));
+        assert_eq!(storyboard.apply((0.0, 0.0)), (240.0, 0.0));
+
+        // A 4:3 area in the middle of a portrait screen
+        let portrait = Screen { letterbox: true, ..Screen::new(600.0, 1000.0) };
+        assert_eq!(portrait.game_area(), ((0.0, 275.0), (600.0, 450.0)));
+        assert_eq!(Transform::storyboard(&portrait).apply((0.0, 0.0)), (0.0, 275.0));
+
+        let widescreen = Screen { letterbox: true, widescreen: true, ..Screen::new(1000.0, 480.0) };
+        let (corner, size) = widescreen.game_area();
+        assert!(close(corner, (73.0, 0.0)) && close(size, (854.0, 480.0)));
+    }
+}
@@ -0,0 +1,318 @@
+//! Evaluating the paths of sliders.
+
+use std::f32::consts::PI;
+
+use super::*;
+
+type Point = (f32, f32);
+
+/// Number of points sampled on each Catmull-Rom segment.
+const CATMULL_DETAIL: usize = 50;
+/// Roughly how far apart, in osu!pixels, points are sampled on curves.
+const CURVE_SPACING: f32 = 4.0;
+
+/// The path followed by a slider's ball, as a polyline trimmed or extended
+/// to the slider's length.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SliderPath {
+    points: Vec<Point>,
+    /// Distance along the path to each point.
+    distances: Vec<f32>,
+}
+
+impl SliderPath {
+    /// The points making up the path, starting at the slider's head.
+    pub fn points(&self) -> &[Point] {
+        &self.points
+    }
+
+    /// Length of the path in osu!pixels.
+    pub fn length(&self) -> f32 {
+        self.distances.last().cloned().unwrap_or(0.0)
+    }
+
+    /// The position a fraction `progress` of the way along the path, from 0
+    /// at the head to 1 at the end of the first span.
+    pub fn position_at(&self, progress: f32) -> Point {
+        let distance = progress.clamp(0.0, 1.0) * self.length();
+        let i = self.distances.partition_point(|&d| d < distance);
+
+        match i {
+            0 => self.points[0],
+            i if i >= self.points.len() => self.points[self.points.len() - 1],
+            i => {
+                let (a, b) = (self.points[i - 1], self.points[i]);
+                let (da, db) = (self.distances[i - 1], self.distances[i]);
+                let t = if db > da { (distance - da) / (db - da) } else { 0.0 };
+                (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+            }
+        }
+    }
+}
+
+fn distance(a: Point, b: Point) -> f32 {
+    ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt()
+}
+
+fn lerp(a: Point, b: Point, t: f32) -> Point {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+/// Samples a single Bézier curve with de Casteljau's algorithm.
+fn bezier(control: &[Point], output: &mut Vec<Point>) {
+    let polygon_length: f32 = control.windows(2).map(|w| distance(w[0], w[1])).sum();
+    let steps = ((polygon_length / CURVE_SPACING).ceil() as usize).clamp(1, 1000);
+
+    let mut scratch = control.to_vec();
+    for step in 0..=steps {
+        let t = step as f32 / steps as f32;
+        scratch.copy_from_slice(control);
+        for n in (1..scratch.len()).rev() {
+            for i in 0..n {
+                scratch[i] = lerp(scratch[i], scratch[i + 1], t);
+            }
+        }
+        output.push(scratch[0]);
+    }
+}
+
+/// A Bézier slider is made of several curves, which are separated by
+/// repeating a control point.
+fn bezier_segments(control: &[Point], output: &mut Vec<Point>) {
+    let mut start = 0;
+    for i in 1..=control.len() {
+        if i == control.len() || control[i] == control[i - 1] {
+            if i - start > 1 {
+                bezier(&control[start..i], output);
+            }
+            start = i;
+        }
+    }
+}
+
+/// Samples the circular arc through three points, or returns `false` if
+/// they lie on a line.
+fn circular_arc(a: Point, b: Point, c: Point, output: &mut Vec<Point>) -> bool {
+    let d = 2.0 * (a.0 * (b.1 - c.1) + b.0 * (c.1 - a.1) + c.0 * (a.1 - b.1));
+    if d.abs() < 1e-3 {
+        return false;
+    }
+
+    let a_sq = a.0 * a.0 + a.1 * a.1;
+    let b_sq = b.0 * b.0 + b.1 * b.1;
+    let c_sq = c.0 * c.0 + c.1 * c.1;
+    let centre = (
+        (a_sq * (b.1 - c.1) + b_sq * (c.1 - a.1) + c_sq * (a.1 - b.1)) / d,
+        (a_sq * (c.0 - b.0) + b_sq * (a.0 - c.0) + c_sq * (b.0 - a.0)) / d,
+    );
+    let radius = distance(a, centre);
+
+    let theta_start = (a.1 - centre.1).atan2(a.0 - centre.0);
+    let mut theta_end = (c.1 - centre.1).atan2(c.0 - centre.0);
+    while theta_end < theta_start {
+        theta_end += 2.0 * PI;
+    }
+
+    let mut direction = 1.0;
+    let mut theta_range = theta_end - theta_start;
+    // Go the other way around if b isn't on the arc found so far
+    let ortho = (c.1 - a.1, -(c.0 - a.0));
+    if ortho.0 * (b.0 - a.0) + ortho.1 * (b.1 - a.1) < 0.0 {
+        direction = -1.0;
+        theta_range = 2.0 * PI - theta_range;
+    }
+
+    let steps = ((theta_range * radius / CURVE_SPACING).ceil() as usize).clamp(2, 1000);
+    for step in 0..=steps {
+        let theta = theta_start + direction * theta_range * step as f32 / steps as f32;
+        output.push((centre.0 + radius * theta.cos(), centre.1 + radius * theta.sin()));
+    }
+
+    true
+}
+
+fn catmull(control: &[Point], output: &mut Vec<Point>) {
+    for i in 0..control.len().saturating_sub(1) {
+        let v2 = control[i];
+        let v3 = control[i + 1];
+        let v1 = if i > 0 { control[i - 1] } else { v2 };
+        let v4 = control.get(i + 2).cloned().unwrap_or((2.0 * v3.0 - v2.0, 2.0 * v3.1 - v2.1));
+
+        for step in 0..=CATMULL_DETAIL {
+            let t = step as f32 / CATMULL_DETAIL as f32;
+            let (t2, t3) = (t * t, t * t * t);
+            let axis = |p1: f32, p2: f32, p3: f32, p4: f32| {
+                0.5 * (2.0 * p2
+                    + (-p1 + p3) * t
+                    + (2.0 * p1 - 5.0 * p2 + 4.0 * p3 - p4) * t2
+                    + (-p1 + 3.0 * p2 - 3.0 * p3 + p4) * t3)
+            };
+            output.push((axis(v1.0, v2.0, v3.0, v4.0), axis(v1.1, v2.1, v3.1, v4.1)));
+        }
+    }
+}
+
+impl Slider {
+    /// Evaluates the slider's curve into a path of
+    /// [`pixel_length`](../struct.Slider.html#structfield.pixel_length)
+    /// osu!pixels, cutting the curve short or extending its last segment in
+    /// a straight line as the game does.
+    ///
+    /// ```
+    /// let input = "osu file format v14
+    ///
+    /// [HitObjects]
+    /// 0,0,1000,2,0,L|100:0,1,50
+    /// ";
+    ///
+    /// let map = osuparse::parse_beatmap(input).unwrap();
+    /// if let osuparse::HitObject::Slider(ref slider) = map.hit_objects[0] {
+    ///     let path = slider.path();
+    ///     assert_eq!(path.position_at(1.0), (50.0, 0.0));
+    /// }
+    /// ```
+    pub fn path(&self) -> SliderPath {
+        let mut control = Vec::with_capacity(self.curve_points.len() + 1);
+        control.push((self.x as f32, self.y as f32));
+        control.extend(self.curve_points.iter().map(|&(x, y)| (x as f32, y as f32)));
+
+        let mut points = Vec::new();
+        match self.slider_type {
+            SliderType::Linear => points.extend_from_slice(&control),
+            SliderType::Perfect if control.len() == 3 => {
+                if !circular_arc(control[0], control[1], control[2], &mut points) {
+                    bezier_segments(&control, &mut points);
+                }
+            }
+            SliderType::Catmull => catmull(&control, &mut points),
+            _ => bezier_segments(&control, &mut points),
+        }
+        points.dedup();
+        if points.is_empty() {
+            points.push(control[0]);
+        }
+
+        let mut distances = Vec::with_capacity(points.len());
+        let mut total = 0.0;
+        distances.push(0.0);
+        for w in points.windows(2) {
+            total += distance(w[0], w[1]);
+            distances.push(total);
+        }
+
+        let length = if self.pixel_length > 0.0 { self.pixel_length as f32 } else { total };
+        if total > length {
+            let end = distances.partition_point(|&d| d < length);
+            let path = SliderPath { points, distances };
+            let last = path.position_at(length / total);
+            let mut points = path.points[..end].to_vec();
+            let mut distances = path.distances[..end].to_vec();
+            points.push(last);
+            distances.push(length);
+            return SliderPath { points, distances };
+        }
+
+        if total < length && points.len() > 1 {
+            let (a, b) = (points[points.len() - 2], points[points.len() - 1]);
+            let segment = distance(a, b);
+            let extended = lerp(a, b, (segment + length - total) / segment);
+            *points.last_mut().unwrap() = extended;
+            *distances.last_mut().unwrap() = length;
+        }
+
+        SliderPath { points, distances }
+    }
+
+    /// Where the slider ends once its repeats are done: at the end of its
+    /// path after an odd number of spans, and back at its head after an even
+    /// number. Distances to the next object are measured from here.
+    ///
+    /// ```
+    /// let input = "osu file format v14
+    ///
+    /// [HitObjects]
+    /// 0,0,1000,2,0,L|100:0,1,50
+    /// 0,0,2000,2,0,L|100:0,2,50
+    /// ";
+    ///
+    /// let map = osuparse::parse_beatmap(input).unwrap();
+    /// if let osuparse::HitObject::Slider(ref slider) = map.hit_objects[0] {
+    ///     assert_eq!(slider.end_position(), (50.0, 0.0));
+    /// }
+    /// if let osuparse::HitObject::Slider(ref slider) = map.hit_objects[1] {
+    ///     assert_eq!(slider.end_position(), (0.0, 0.0));
+    /// }
+    /// ```
+    pub fn end_position(&self) -> Point {
+        if self.repeat.max(1) % 2 == 0 {
+            (self.x as f32, self.y as f32)
+        } else {
+            self.path().position_at(1.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slider(slider_type: SliderType, curve_points: Vec<(i32, i32)>, pixel_length: f32) -> Slider {
+        Slider {
+            x: 0,
+            y: 0,
+            new_combo: false,
+            color_skip: 0,
+            raw_type: 0,
+            time: Time(0.0),
+            slider_type,
+            curve_points,
+            repeat: 1,
+            pixel_length: f64::from(pixel_length),
+            edge_hitsounds: Vec::new(),
+            edge_additions: Vec::new(),
+            hitsound: 0,
+            extras: Default::default(),
+        }
+    }
+
+    fn close(a: (f32, f32), b: (f32, f32)) -> bool {
+        distance(a, b) < 0.5
+    }
+
+    #[test]
+    fn test_slider_paths() {
+        let linear = slider(SliderType::Linear, vec![(100, 0), (100, 100)], 150.0).path();
+        assert_eq!(linear.length(), 150.0);
+        assert!(close(linear.position_at(1.0), (100.0, 50.0)));
+        assert!(close(linear.position_at(0.5), (75.0, 0.0)));
+
+        let extended = slider(SliderType::Linear, vec![(100, 0)], 200.0).path();
+        assert!(close(extended.position_at(1.0), (200.0, 0.0)));
+
+        // Half a circle of radius 50
+        let arc = slider(SliderType::Perfect, vec![(50, 50), (100, 0)], 50.0 * PI).path();
+        assert!(close(arc.position_at(0.5), (50.0, 50.0)));
+        assert!(close(arc.position_at(1.0), (100.0, 0.0)));
+
+        let collinear = slider(SliderType::Perfect, vec![(50, 0), (100, 0)], 100.0).path();
+        assert!(close(collinear.position_at(1.0), (100.0, 0.0)));
+
+        let bezier = slider(SliderType::Bezier, vec![(100, 0), (100, 0), (100, 100)], 200.0).path();
+        assert!(close(bezier.position_at(0.5), (100.0, 0.0)));
+
+        let catmull = slider(SliderType::Catmull, vec![(100, 0)], 100.0).path();
+        assert!(close(catmull.position_at(1.0), (100.0, 0.0)));
+    }
+
+    #[test]
+    fn test_end_position() {
+        let mut s = slider(SliderType::Linear, vec![(100, 0), (100, 100)], 150.0);
+        assert!(close(s.end_position(), (100.0, 50.0)));
+
+        s.repeat = 2;
+        assert_eq!(s.end_position(), (0.0, 0.0));
+
+        s.repeat = 3;
+        assert!(close(s.end_position(), (100.0, 50.0)));
+    }
+}
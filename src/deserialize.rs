@@ -1,218 +1,652 @@
 use super::*;
+use events::{
+    Animation, Background, Break, ColourTransform, Command, Events, Layer, LoopType, Origin,
+    Parameter, Sprite, Storyboard, StoryboardObject, StoryboardSample, Tween, Video,
+};
 
-/// If a struct has this trait, it means that it represents something that can
-/// be parsed from a .osu file.
-pub trait Parsable {
+/// Writes something as it appears in a .osu file. Besides whole beatmaps,
+/// this is implemented by their parts, such as hit objects and timing
+/// points, each written as the line or section that parses back to it.
+///
+/// # Examples
+///
+/// ```
+/// use osuparse::{Time, ToOsuString, TimingPoint};
+///
+/// let point = TimingPoint { offset: Time(1000.0), ms_per_beat: 500.0, ..Default::default() };
+/// assert_eq!(point.to_osu_string(), "1000,500,4,0,0,100,0,0");
+/// ```
+pub trait ToOsuString {
     /// Returns a string equal to the struct's representation in a .osu file,
     /// or in other words a string that can be parsed to get this struct.
+    fn to_osu_string(&self) -> String {
+        self.to_osu_string_with(&WriterOptions::default())
+    }
+
+    /// Same as [`to_osu_string`](#method.to_osu_string), but formatted
+    /// according to the given [`WriterOptions`](struct.WriterOptions.html).
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String;
+}
+
+/// The former name of [`ToOsuString`](trait.ToOsuString.html), implemented
+/// for everything that implements it.
+#[deprecated(since = "3.0.0", note = "use ToOsuString instead")]
+pub trait Parsable {
     fn as_parsed(&self) -> String;
+    fn as_parsed_with(&self, options: &WriterOptions) -> String;
 }
 
-impl Parsable for Beatmap {
+#[allow(deprecated)]
+impl<T: ToOsuString + ?Sized> Parsable for T {
     fn as_parsed(&self) -> String {
-        let hitobjects_string = self
-            .hit_objects
-            .iter()
-            .map(|hitobject| hitobject.as_parsed())
-            .collect::<Vec<String>>()
-            .join("\n");
+        self.to_osu_string()
+    }
+
+    fn as_parsed_with(&self, options: &WriterOptions) -> String {
+        self.to_osu_string_with(options)
+    }
+}
+
+/// How key-value pairs are spaced in the `[General]`, `[Editor]`,
+/// `[Metadata]` and `[Difficulty]` sections.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum KeySpacing {
+    /// `Key: value` in every section.
+    Spaced,
+    /// `Key:value` in every section.
+    Compact,
+    /// `Key: value` in `[General]` and `[Editor]`, `Key:value` in
+    /// `[Metadata]` and `[Difficulty]`, which is what the osu! editor writes.
+    Editor,
+}
+
+/// Options controlling how beatmaps are written out by
+/// [`ToOsuString::to_osu_string_with`](trait.ToOsuString.html#tymethod.to_osu_string_with).
+///
+/// The default options write `Key: value` pairs, `\n` line endings, floats in
+/// their shortest form and keep the beatmap's own format version.
+/// [`WriterOptions::editor`](#method.editor) instead follows the
+/// conventions of the osu! editor.
+#[derive(Debug, PartialEq, Clone)]
+pub struct WriterOptions {
+    /// Maximum number of decimal places written for floating point values.
+    /// Trailing zeros are always trimmed. `None` writes the shortest
+    /// representation that parses back to the same value.
+    pub float_precision: Option<usize>,
+    /// Whether floats are written the way the game writes them, rounded to
+    /// 7 significant digits and switching to exponent notation, like `1E-05`,
+    /// for very small or large values. Beat lengths, slider lengths and
+    /// times, which the game keeps as doubles, are rounded to 15 digits
+    /// instead. This keeps the
+    /// values osu! wrote unchanged. Ignored when
+    /// [`float_precision`](#structfield.float_precision) is set.
+    pub stable_floats: bool,
+    /// Whether lines are terminated by `\r\n` instead of `\n`.
+    pub crlf: bool,
+    /// Format version written in the header line. `None` keeps
+    /// [`Beatmap::version`](../struct.Beatmap.html#structfield.version).
+    /// Keys and hit object extras that the version doesn't have are left
+    /// out, so that the file can be read by tools expecting that version.
+    /// Only those are version dependent: timing points are always written
+    /// with all eight of their fields. See
+    /// [`FormatVersion`](../struct.FormatVersion.html).
+    pub target_version: Option<i32>,
+    pub key_spacing: KeySpacing,
+}
+
+impl Default for WriterOptions {
+    fn default() -> Self {
+        WriterOptions {
+            float_precision: None,
+            stable_floats: false,
+            crlf: false,
+            target_version: None,
+            key_spacing: KeySpacing::Spaced,
+        }
+    }
+}
+
+impl WriterOptions {
+    /// Options matching the output of the osu! editor: CRLF line endings,
+    /// per-section key spacing and the game's float formatting.
+    pub fn editor() -> Self {
+        WriterOptions {
+            stable_floats: true,
+            crlf: true,
+            key_spacing: KeySpacing::Editor,
+            ..Default::default()
+        }
+    }
+
+    /// Options for writing files of an older format `version`, as the
+    /// editor of the time would.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use osuparse::{parse_beatmap, FormatVersion, ToOsuString, WriterOptions};
+    ///
+    /// let map = parse_beatmap("osu file format v14
+    ///
+    /// [Difficulty]
+    /// OverallDifficulty:6
+    /// ApproachRate:8
+    ///
+    /// [HitObjects]
+    /// 256,192,1000,1,0,0:0:0:0:
+    /// ").unwrap();
+    ///
+    /// let output = map.to_osu_string_with(&WriterOptions::for_version(FormatVersion(7)));
+    /// assert!(output.starts_with("osu file format v7\r\n"));
+    /// assert!(!output.contains("ApproachRate"));
+    /// assert!(output.ends_with("\r\n256,192,1000,1,0\r\n"));
+    /// ```
+    pub fn for_version(version: FormatVersion) -> Self {
+        WriterOptions {
+            target_version: Some(version.0),
+            ..WriterOptions::editor()
+        }
+    }
+
+    /// Whether `key` is written, according to the target version.
+    fn writes_key(&self, key: &str) -> bool {
+        self.target_version.map_or(true, |v| FormatVersion(v).has_key(key))
+    }
+
+    /// The extras of a hit object as its last field, including the comma
+    /// before them, or nothing if the target version doesn't have them.
+    fn extras_field(&self, extras: &HitObjectExtras) -> String {
+        if self.writes_extras() {
+            format!(",{}", extras.to_osu_string_with(self))
+        } else {
+            String::new()
+        }
+    }
+
+    fn writes_extras(&self) -> bool {
+        self.target_version.map_or(true, |v| FormatVersion(v).supports_extras())
+    }
+
+    fn line_ending(&self) -> &'static str {
+        if self.crlf {
+            "\r\n"
+        } else {
+            "\n"
+        }
+    }
+
+    fn key_separator(&self, compact_in_editor: bool) -> &'static str {
+        match self.key_spacing {
+            KeySpacing::Spaced => ": ",
+            KeySpacing::Compact => ":",
+            KeySpacing::Editor if compact_in_editor => ":",
+            KeySpacing::Editor => ": ",
+        }
+    }
+
+    fn float(&self, value: f32) -> String {
+        match self.float_precision {
+            Some(precision) => fixed_float(value, precision),
+            None if self.stable_floats => stable_float(f64::from(value), FLOAT_DIGITS),
+            None => value.to_string(),
+        }
+    }
+
+    fn double(&self, value: f64) -> String {
+        match self.float_precision {
+            Some(precision) => fixed_float(value, precision),
+            None if self.stable_floats => stable_float(value, DOUBLE_DIGITS),
+            None => value.to_string(),
+        }
+    }
+
+    /// Formats a time, which is written in whole milliseconds unless it has
+    /// a fraction.
+    fn time(&self, time: Time) -> String {
+        self.double(time.0)
+    }
+}
+
+/// Formats `value` with at most `precision` decimal places, trimming
+/// trailing zeros.
+fn fixed_float<T: std::fmt::Display>(value: T, precision: usize) -> String {
+    let s = format!("{:.*}", precision, value);
+    if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        s
+    }
+}
 
-        let timing_points_string = self
-            .timing_points
+/// Significant digits .NET writes for a `float` and for a `double`.
+const FLOAT_DIGITS: usize = 7;
+const DOUBLE_DIGITS: usize = 15;
+
+/// Formats `value` as .NET writes a `float` or a `double`, which is how the
+/// game writes them: rounded to `precision` significant digits with trailing
+/// zeros removed, in exponent notation if the exponent is below -4 or not
+/// below `precision`.
+fn stable_float(value: f64, precision: usize) -> String {
+    if value.is_nan() {
+        return "NaN".to_string();
+    } else if value.is_infinite() {
+        return if value > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    } else if value == 0.0 {
+        // Negative zero is written without its sign
+        return "0".to_string();
+    }
+
+    // Rounded to the significant digits, as in `-5.100003e0`
+    let scientific = format!("{:.*e}", precision - 1, value.abs());
+    let (mantissa, exponent) = scientific.split_at(scientific.find('e').unwrap());
+    let exponent: i32 = exponent[1..].parse().unwrap();
+    let digits = mantissa.replace('.', "");
+    let digits = digits.trim_end_matches('0');
+    let sign = if value < 0.0 { "-" } else { "" };
+
+    if !(-4..precision as i32).contains(&exponent) {
+        let (first, rest) = digits.split_at(1);
+        let point = if rest.is_empty() { "" } else { "." };
+        let exponent_sign = if exponent < 0 { '-' } else { '+' };
+        return format!("{}{}{}{}E{}{:02}", sign, first, point, rest, exponent_sign, exponent.abs());
+    }
+
+    // Number of digits before the decimal point
+    let whole = exponent + 1;
+    if whole <= 0 {
+        format!("{}0.{}{}", sign, "0".repeat(-whole as usize), digits)
+    } else if whole as usize >= digits.len() {
+        format!("{}{}{}", sign, digits, "0".repeat(whole as usize - digits.len()))
+    } else {
+        let (whole, fraction) = digits.split_at(whole as usize);
+        format!("{}{}.{}", sign, whole, fraction)
+    }
+}
+
+/// Writes a key-value section, `compact_in_editor` selecting the `Key:value`
+/// form under [`KeySpacing::Editor`](enum.KeySpacing.html#variant.Editor).
+fn write_kv_section(
+    name: &str,
+    pairs: &[(&str, String)],
+    compact_in_editor: bool,
+    options: &WriterOptions,
+) -> String {
+    let separator = options.key_separator(compact_in_editor);
+
+    let mut lines = Vec::with_capacity(pairs.len() + 1);
+    lines.push(format!("[{}]", name));
+    lines.extend(
+        pairs
             .iter()
-            .map(|timing_point| timing_point.as_parsed())
-            .collect::<Vec<String>>()
-            .join("\n");
+            .map(|(key, value)| format!("{}{}{}", key, separator, value)),
+    );
 
-        format!(
-            r#"osu file format v{}
+    lines.join(options.line_ending())
+}
 
-{}
+/// Orders `pairs`, given in the usual order, so that keys in `keys` come in
+/// the order they were recorded. Each of the other keys is placed after the
+/// key that precedes it in the usual order.
+fn keep_key_order<'a>(pairs: Vec<(&'a str, String)>, keys: &KeySet) -> Vec<(&'a str, String)> {
+    let position = |key: &str| keys.iter().position(|k| unicase::eq(k, key));
+
+    let (mut ordered, added): (Vec<_>, Vec<_>) = pairs
+        .into_iter()
+        .enumerate()
+        .partition(|(_, (key, _))| keys.contains(key));
+    ordered.sort_by_key(|(_, (key, _))| position(key));
+
+    for (index, pair) in added {
+        let after = ordered
+            .iter()
+            .enumerate()
+            .filter(|&(_, &(i, _))| i < index)
+            .max_by_key(|&(_, &(i, _))| i)
+            .map_or(0, |(at, _)| at + 1);
+        ordered.insert(after, (index, pair));
+    }
 
-{}
+    ordered.into_iter().map(|(_, pair)| pair).collect()
+}
 
-{}
+/// Sections made of key-value pairs, which are written out based on the keys
+/// recorded in [`PresentKeys`](../struct.PresentKeys.html).
+trait KeyValueSection: Default {
+    const NAME: &'static str;
+    /// Whether the section uses `Key:value` under
+    /// [`KeySpacing::Editor`](enum.KeySpacing.html#variant.Editor).
+    const COMPACT_IN_EDITOR: bool;
 
-{}
+    fn pairs(&self, options: &WriterOptions) -> Vec<(&str, String)>;
 
-[TimingPoints]
-{}
+    /// Writes the section, leaving out keys that weren't present in the
+    /// original file and still hold their default value. Keys from the file
+    /// are written in the order they appeared, and any others after the key
+    /// that comes before them in the usual order. An empty `keys` writes
+    /// every key in the usual order.
+    fn write(&self, keys: &KeySet, options: &WriterOptions) -> String {
+        let mut pairs = self.pairs(options);
+        pairs.retain(|(key, _)| options.writes_key(key));
 
-{}
+        if !keys.is_empty() {
+            let default = Self::default();
+            let defaults = default.pairs(options);
+            pairs.retain(|(key, value)| {
+                keys.contains(key)
+                    || defaults
+                        .iter()
+                        .find(|(default_key, _)| default_key == key)
+                        .map_or(true, |(_, default)| default != value)
+            });
+            pairs = keep_key_order(pairs, keys);
+        }
 
-[HitObjects]
-{}
-"#,
-            self.version,
-            self.general.as_parsed(),
-            self.editor.as_parsed(),
-            self.metadata.as_parsed(),
-            self.difficulty.as_parsed(),
-            timing_points_string,
-            self.colours.as_parsed(),
-            hitobjects_string
-        )
+        write_kv_section(Self::NAME, &pairs, Self::COMPACT_IN_EDITOR, options)
     }
 }
 
-impl Parsable for GeneralSection {
-    fn as_parsed(&self) -> String {
-        format!(
-            r#"[General]
-AudioFilename: {}
-AudioLeadIn: {}
-Previewtime: {}
-Countdown: {}
-SampleSet: {}
-StackLeniency: {}
-Mode: {}
-LetterboxInBreaks: {}
-WidescreenStoryboard: {}
-StoryFireInFront: {}
-SpecialStyle: {}
-EpilepsyWarning: {}
-UseSkinSprites: {}"#,
-            self.audio_filename,
-            self.audio_lead_in,
-            self.preview_time,
-            self.countdown as u8,
-            self.sample_set,
-            self.stack_leniency,
-            self.game_mode as u8,
-            self.letterbox_in_breaks as u8,
-            self.widescreen_storyboard as u8,
-            self.story_fire_in_front as u8,
-            self.special_style as u8,
-            self.epilepsy_warning as u8,
-            self.use_skin_sprites as u8
-        )
+impl ToOsuString for Beatmap {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
+        let eol = options.line_ending();
+
+        let mut sections = vec![format!(
+            "osu file format v{}",
+            options.target_version.unwrap_or(self.version)
+        )];
+
+        for &name in SECTION_NAMES.iter() {
+            // [Events] is written even when empty, since the editor puts it
+            // back in its place when the file is saved otherwise
+            let empty = name == "Variables" && self.events.variables.is_empty();
+            if !empty {
+                sections.extend(self.section_with(name, options));
+            }
+        }
+
+        let mut output = sections.join(&format!("{}{}", eol, eol));
+        output.push_str(eol);
+        output
     }
 }
 
-impl Parsable for EditorSection {
-    fn as_parsed(&self) -> String {
-        let bookmark_string = if !self.bookmarks.is_empty() {
-            format!(
-                "Bookmarks: {}",
-                self.bookmarks
+impl Beatmap {
+    /// Writes a single section, including its header. `None` if there's no
+    /// section with the given name.
+    fn section_with(&self, name: &str, options: &WriterOptions) -> Option<String> {
+        let eol = options.line_ending();
+        let join = |lines: Vec<String>| lines.join(eol);
+
+        let section = match name {
+            "General" => self.general.write(&self.present_keys.general, options),
+            "Editor" => self.editor.write(&self.present_keys.editor, options),
+            "Metadata" => self.metadata.write(&self.present_keys.metadata, options),
+            "Difficulty" => self.difficulty.write(&self.present_keys.difficulty, options),
+            "Variables" => {
+                let variables = self.events.variables
                     .iter()
-                    .map(|i| i.to_string())
-                    .collect::<Vec<String>>()
-                    .join(" ")
-            )
-        } else {
-            "".to_string()
+                    .map(|v| format!("{}={}", v.name, v.value))
+                    .collect();
+                format!("[Variables]{}{}", eol, join(variables))
+            }
+            "Events" if self.events.raw_lines_match() => {
+                format!("[Events]{}{}", eol, self.events.raw_lines.join(eol))
+            }
+            "Events" => self.events.to_osu_string_with(options),
+            "TimingPoints" => {
+                let timing_points = self.timing_points
+                    .iter()
+                    .map(|timing_point| timing_point.to_osu_string_with(options))
+                    .collect();
+                format!("[TimingPoints]{}{}", eol, join(timing_points))
+            }
+            "Colours" => self.colours.to_osu_string_with(options),
+            "HitObjects" => {
+                let hit_objects = self.hit_objects
+                    .iter()
+                    .map(|hitobject| hitobject.to_osu_string_with(options))
+                    .collect();
+                format!("[HitObjects]{}{}", eol, join(hit_objects))
+            }
+            _ => return None,
         };
 
-        format!(
-            r#"[Editor]
-{}
-DistanceSpacing: {}
-BeatDivisor: {}
-GridSize: {}
-TimelineZoom: {}"#,
-            bookmark_string,
-            self.distance_spacing,
-            self.beat_divisor,
-            self.grid_size,
-            self.timeline_zoom
-        )
+        Some(section)
+    }
+
+    /// Writes the section `name` into `original`, the contents of the file
+    /// the beatmap was parsed from, leaving the rest of the file untouched.
+    /// This keeps the changes to a file down to what was actually edited.
+    ///
+    /// The section is written with the line endings `original` uses. Fails
+    /// if the section isn't in `original`, or isn't one that beatmaps are
+    /// written with.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let original = "osu file format v14\n\n[Metadata]\nTitle:Old\n\n[HitObjects]\n256,192,1000,1,0\n";
+    ///
+    /// let mut map = osuparse::parse_beatmap(original).unwrap();
+    /// map.metadata.title = "New".to_string();
+    ///
+    /// assert_eq!(
+    ///     map.patch_section(original, "Metadata").unwrap(),
+    ///     "osu file format v14\n\n[Metadata]\nTitle: New\n\n[HitObjects]\n256,192,1000,1,0\n"
+    /// );
+    /// ```
+    pub fn patch_section(&self, original: &str, name: &str) -> Result<String> {
+        let options = WriterOptions {
+            crlf: original.contains("\r\n"),
+            ..Default::default()
+        };
+        self.patch_section_with(original, name, &options)
+    }
+
+    /// Same as [`patch_section`](#method.patch_section), but the section is
+    /// written according to the given options.
+    pub fn patch_section_with(&self, original: &str, name: &str, options: &WriterOptions) -> Result<String> {
+        let name = canonical_section_name(name);
+        let section = self.section_with(name, options)
+            .ok_or(Error::Message("Unknown section"))?;
+
+        // The section runs from its header to its last non-empty line, so
+        // that the blank lines separating it from the next one are kept
+        let mut start = None;
+        let mut end = 0;
+        let mut offset = 0;
+        for line in original.split_inclusive('\n') {
+            let content_end = offset + line.trim_end().len();
+            match (start, match_header_line(line)) {
+                (None, Some(header)) if canonical_section_name(header) == name => {
+                    start = Some(offset);
+                    end = content_end;
+                }
+                (Some(_), Some(_)) => break,
+                (Some(_), None) if !line.trim().is_empty() => end = content_end,
+                _ => {}
+            }
+            offset += line.len();
+        }
+
+        let start = start.ok_or(Error::Message("Section not found"))?;
+        Ok(format!("{}{}{}", &original[..start], section.trim_end(), &original[end..]))
     }
 }
 
-impl Parsable for MetadataSection {
-    fn as_parsed(&self) -> String {
-        let tags_string = self.tags.join(" ");
+impl KeyValueSection for GeneralSection {
+    const NAME: &'static str = "General";
+    const COMPACT_IN_EDITOR: bool = false;
 
-        format!(
-            r#"[Metadata]
-Title: {}
-TitleUnicode: {}
-Artist: {}
-ArtistUnicode: {}
-Creator: {}
-Version: {}
-Source: {}
-Tags: {}
-BeatmapID: {}
-BeatmapSetID: {}"#,
-            self.title,
-            self.title_unicode,
-            self.artist,
-            self.artist_unicode,
-            self.creator,
-            self.version,
-            self.source,
-            tags_string,
-            self.beatmap_id,
-            self.beatmap_set_id,
-        )
+    fn pairs(&self, options: &WriterOptions) -> Vec<(&str, String)> {
+        vec![
+            ("AudioFilename", self.audio_filename.clone()),
+            ("AudioLeadIn", options.time(self.audio_lead_in)),
+            ("PreviewTime", options.time(self.preview_time)),
+            ("Countdown", (self.countdown as u8).to_string()),
+            ("CountdownOffset", self.countdown_offset.to_string()),
+            ("SampleSet", self.sample_set.clone()),
+            ("StackLeniency", options.float(self.stack_leniency)),
+            ("Mode", self.game_mode.to_raw().to_string()),
+            ("LetterboxInBreaks", (self.letterbox_in_breaks as u8).to_string()),
+            ("WidescreenStoryboard", (self.widescreen_storyboard as u8).to_string()),
+            ("StoryFireInFront", (self.story_fire_in_front as u8).to_string()),
+            ("SpecialStyle", (self.special_style as u8).to_string()),
+            ("EpilepsyWarning", (self.epilepsy_warning as u8).to_string()),
+            ("UseSkinSprites", (self.use_skin_sprites as u8).to_string()),
+            ("SkinPreference", self.skin_preference.clone()),
+            ("AlwaysShowPlayfield", (self.always_show_playfield as u8).to_string()),
+            ("OverlayPosition", self.overlay_position.to_osu_string_with(options)),
+            (
+                "SamplesMatchPlaybackRate",
+                (self.samples_match_playback_rate as u8).to_string(),
+            ),
+        ]
     }
 }
 
-impl Parsable for DifficultySection {
-    fn as_parsed(&self) -> String {
-        format!(
-            r#"[Difficulty]
-HPDrainRate: {}
-CircleSize: {}
-OverallDifficulty: {}
-ApproachRate: {}
-SliderMultiplier: {}
-SliderTickRate: {}"#,
-            self.hp_drain_rate,
-            self.circle_size,
-            self.overall_difficulty,
-            self.approach_rate,
-            self.slider_multiplier,
-            self.slider_tick_rate,
-        )
+impl ToOsuString for GeneralSection {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
+        self.write(&KeySet::default(), options)
     }
 }
 
-impl Parsable for TimingPoint {
-    fn as_parsed(&self) -> String {
+impl ToOsuString for OverlayPosition {
+    fn to_osu_string_with(&self, _options: &WriterOptions) -> String {
+        match self {
+            OverlayPosition::NoChange => "NoChange".to_string(),
+            OverlayPosition::Below => "Below".to_string(),
+            OverlayPosition::Above => "Above".to_string(),
+        }
+    }
+}
+
+impl KeyValueSection for EditorSection {
+    const NAME: &'static str = "Editor";
+    const COMPACT_IN_EDITOR: bool = false;
+
+    fn pairs(&self, options: &WriterOptions) -> Vec<(&str, String)> {
+        let mut pairs = Vec::with_capacity(5);
+
+        if !self.bookmarks.is_empty() {
+            let bookmarks = self
+                .bookmarks
+                .iter()
+                .map(|&time| options.time(time))
+                .collect::<Vec<String>>()
+                .join(",");
+            pairs.push(("Bookmarks", bookmarks));
+        }
+
+        pairs.push(("DistanceSpacing", options.float(self.distance_spacing)));
+        pairs.push(("BeatDivisor", self.beat_divisor.to_string()));
+        pairs.push(("GridSize", self.grid_size.to_string()));
+        if let Some(current_time) = self.current_time {
+            pairs.push(("CurrentTime", options.time(current_time)));
+        }
+        pairs.push(("TimelineZoom", options.float(self.timeline_zoom)));
+        pairs.extend(self.unknown.iter().map(|(key, value)| (key.as_str(), value.clone())));
+
+        pairs
+    }
+}
+
+impl ToOsuString for EditorSection {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
+        self.write(&KeySet::default(), options)
+    }
+}
+
+impl KeyValueSection for MetadataSection {
+    const NAME: &'static str = "Metadata";
+    const COMPACT_IN_EDITOR: bool = true;
+
+    fn pairs(&self, _options: &WriterOptions) -> Vec<(&str, String)> {
+        vec![
+            ("Title", self.title.clone()),
+            ("TitleUnicode", self.title_unicode.clone()),
+            ("Artist", self.artist.clone()),
+            ("ArtistUnicode", self.artist_unicode.clone()),
+            ("Creator", self.creator.clone()),
+            ("Version", self.version.clone()),
+            ("Source", self.source.clone()),
+            ("Tags", self.tags.join(" ")),
+            ("BeatmapID", self.beatmap_id.to_string()),
+            ("BeatmapSetID", self.beatmap_set_id.to_string()),
+        ]
+    }
+}
+
+impl ToOsuString for MetadataSection {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
+        self.write(&KeySet::default(), options)
+    }
+}
+
+impl KeyValueSection for DifficultySection {
+    const NAME: &'static str = "Difficulty";
+    const COMPACT_IN_EDITOR: bool = true;
+
+    fn pairs(&self, options: &WriterOptions) -> Vec<(&str, String)> {
+        vec![
+            ("HPDrainRate", options.float(self.hp_drain_rate)),
+            ("CircleSize", options.float(self.circle_size)),
+            ("OverallDifficulty", options.float(self.overall_difficulty)),
+            ("ApproachRate", options.float(self.approach_rate)),
+            ("SliderMultiplier", options.float(self.slider_multiplier)),
+            ("SliderTickRate", options.float(self.slider_tick_rate)),
+        ]
+    }
+}
+
+impl ToOsuString for DifficultySection {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
+        self.write(&KeySet::default(), options)
+    }
+}
+
+impl ToOsuString for TimingPoint {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
         format!(
             "{},{},{},{},{},{},{},{}",
-            self.offset,
-            self.ms_per_beat,
+            options.time(self.offset),
+            options.double(self.ms_per_beat),
             self.meter,
             self.sample_set,
             self.sample_index,
             self.volume,
             self.inherited as u8,
-            self.kiai_mode as u8,
+            self.effects.bits(),
         )
     }
 }
 
-impl Parsable for HitObject {
-    fn as_parsed(&self) -> String {
+impl ToOsuString for HitObject {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
         match self {
-            HitObject::HitCircle(circle) => circle.as_parsed(),
-            HitObject::Slider(slider) => slider.as_parsed(),
-            HitObject::Spinner(spinner) => spinner.as_parsed(),
-            HitObject::HoldNote(hold_note) => hold_note.as_parsed(),
+            HitObject::HitCircle(circle) => circle.to_osu_string_with(options),
+            HitObject::Slider(slider) => slider.to_osu_string_with(options),
+            HitObject::Spinner(spinner) => spinner.to_osu_string_with(options),
+            HitObject::HoldNote(hold_note) => hold_note.to_osu_string_with(options),
         }
     }
 }
 
-impl Parsable for HitCircle {
-    fn as_parsed(&self) -> String {
+impl ToOsuString for HitCircle {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
         format!(
-            "{},{},{},{},{},{}",
+            "{},{},{},{},{}{}",
             self.x,
             self.y,
-            self.time,
-            get_type(1, self.new_combo, self.color_skip),
+            options.time(self.time),
+            get_type(1, self.new_combo, self.color_skip, self.raw_type),
             self.hitsound,
-            self.extras.as_parsed(),
+            options.extras_field(&self.extras),
         )
     }
 }
 
-impl Parsable for SliderType {
-    fn as_parsed(&self) -> String {
+impl ToOsuString for SliderType {
+    fn to_osu_string_with(&self, _options: &WriterOptions) -> String {
         match self {
             SliderType::Linear => "L".to_string(),
             SliderType::Bezier => "B".to_string(),
@@ -222,8 +656,8 @@ impl Parsable for SliderType {
     }
 }
 
-impl Parsable for Slider {
-    fn as_parsed(&self) -> String {
+impl ToOsuString for Slider {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
         let curve_points = self
             .curve_points
             .iter()
@@ -241,60 +675,80 @@ impl Parsable for Slider {
         let edge_additions = self
             .edge_additions
             .iter()
-            .map(|(sample, addition)| format!("{}:{}", sample, addition))
+            .map(|edge| edge.to_osu_string_with(options))
             .collect::<Vec<String>>()
             .join("|");
 
+        // Without extras, the edge fields may be left out too
+        let extras = options.extras_field(&self.extras);
+        let edges = if extras.is_empty() && edge_hitsounds.is_empty() && edge_additions.is_empty() {
+            String::new()
+        } else {
+            format!(",{},{}", edge_hitsounds, edge_additions)
+        };
+
         format!(
-            "{},{},{},{},{},{}|{},{},{},{},{},{}",
+            "{},{},{},{},{},{}|{},{},{}{}{}",
             self.x,
             self.y,
-            self.time,
-            get_type(2, self.new_combo, self.color_skip),
+            options.time(self.time),
+            get_type(2, self.new_combo, self.color_skip, self.raw_type),
             self.hitsound,
-            self.slider_type.as_parsed(),
+            self.slider_type.to_osu_string_with(options),
             curve_points,
             self.repeat,
-            self.pixel_length,
-            edge_hitsounds,
-            edge_additions,
-            self.extras.as_parsed(),
+            options.double(self.pixel_length),
+            edges,
+            extras,
         )
     }
 }
 
-impl Parsable for Spinner {
-    fn as_parsed(&self) -> String {
+impl ToOsuString for EdgeSampleSet {
+    fn to_osu_string_with(&self, _options: &WriterOptions) -> String {
+        format!("{}:{}", self.sample_set, self.addition_set)
+    }
+}
+
+impl ToOsuString for Spinner {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
         format!(
-            "{},{},{},{},{},{},{}",
+            "{},{},{},{},{},{}{}",
             self.x,
             self.y,
-            self.time,
-            get_type(8, self.new_combo, self.color_skip),
+            options.time(self.time),
+            get_type(8, self.new_combo, self.color_skip, self.raw_type),
             self.hitsound,
-            self.end_time,
-            self.extras.as_parsed(),
+            options.time(self.end_time),
+            options.extras_field(&self.extras),
         )
     }
 }
 
-impl Parsable for HoldNote {
-    fn as_parsed(&self) -> String {
+impl ToOsuString for HoldNote {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
+        // The extras share a field with the end time
+        let extras = if options.writes_extras() {
+            format!(":{}", self.extras.to_osu_string_with(options))
+        } else {
+            String::new()
+        };
+
         format!(
-            "{},{},{},{},{},{}:{}",
+            "{},{},{},{},{},{}{}",
             self.x,
             self.y,
-            self.time,
-            get_type(128, self.new_combo, self.color_skip),
+            options.time(self.time),
+            get_type(128, self.new_combo, self.color_skip, self.raw_type),
             self.hitsound,
-            self.end_time,
-            self.extras.as_parsed(),
+            options.time(self.end_time),
+            extras,
         )
     }
 }
 
-impl Parsable for HitObjectExtras {
-    fn as_parsed(&self) -> String {
+impl ToOsuString for HitObjectExtras {
+    fn to_osu_string_with(&self, _options: &WriterOptions) -> String {
         format!(
             "{}:{}:{}:{}:{}",
             self.sample_set,
@@ -306,34 +760,473 @@ impl Parsable for HitObjectExtras {
     }
 }
 
-impl Parsable for Colour {
-    fn as_parsed(&self) -> String {
+impl ToOsuString for Colour {
+    fn to_osu_string_with(&self, _options: &WriterOptions) -> String {
         format!("{},{},{}", self.0, self.1, self.2)
     }
 }
 
-impl Parsable for ColoursSection {
-    fn as_parsed(&self) -> String {
-        let colours = self
-            .colours
-            .iter()
-            .enumerate()
-            .map(|(i, colour)| format!("Combo{} : {}", i + 1, colour.as_parsed()))
-            .collect::<Vec<String>>()
-            .join("\n");
+impl ToOsuString for ColoursSection {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
+        let mut lines = Vec::with_capacity(self.colours.len() + 4);
+        lines.push("[Colours]".to_string());
+        lines.extend(
+            self.colours
+                .iter()
+                .enumerate()
+                .map(|(i, colour)| format!("Combo{} : {}", i + 1, colour.to_osu_string_with(options))),
+        );
+
+        let overrides = [
+            ("SliderTrackOverride", self.slider_track_override),
+            ("SliderBody", self.slider_body),
+            ("SliderBorder", self.slider_border),
+        ];
+        lines.extend(overrides.iter().filter_map(|&(key, colour)| {
+            colour.map(|c| format!("{} : {}", key, c.to_osu_string_with(options)))
+        }));
+
+        lines.join(options.line_ending())
+    }
+}
+
+/// Writes the `[Events]` section from the parsed events, ignoring
+/// [`raw_lines`](../events/struct.Events.html#structfield.raw_lines).
+/// An `.osb` file is the same section, holding only a storyboard.
+impl ToOsuString for Events {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
+        let mut lines = vec!["[Events]".to_string()];
+
+        lines.extend(self.background.iter().map(|b| b.to_osu_string_with(options)));
+        lines.extend(self.videos.iter().map(|v| v.to_osu_string_with(options)));
+        lines.extend(self.breaks.iter().map(|b| b.to_osu_string_with(options)));
+        lines.extend(self.colour_transforms.iter().map(|c| c.to_osu_string_with(options)));
+        if !self.storyboard.objects.is_empty() || !self.storyboard.samples.is_empty() {
+            lines.push(self.storyboard.to_osu_string_with(options));
+        }
+        lines.extend(self.unknown_lines.iter().cloned());
+
+        lines.join(options.line_ending())
+    }
+}
+
+impl ToOsuString for Background {
+    fn to_osu_string_with(&self, _options: &WriterOptions) -> String {
+        format!("0,0,\"{}\",{},{}", self.filename, self.x_offset, self.y_offset)
+    }
+}
+
+impl ToOsuString for Video {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
+        format!(
+            "Video,{},\"{}\",{},{}",
+            options.time(self.start_time), self.filename, self.x_offset, self.y_offset
+        )
+    }
+}
+
+impl ToOsuString for Break {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
+        format!("2,{},{}", options.time(self.start_time), options.time(self.end_time))
+    }
+}
+
+impl ToOsuString for ColourTransform {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
+        let Colour(r, g, b) = self.colour;
+        format!("3,{},{},{},{}", options.time(self.time), r, g, b)
+    }
+}
+
+/// Writes every storyboard object with its commands, followed by the sound
+/// samples, as event lines without a section header.
+impl ToOsuString for Storyboard {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
+        let mut lines = Vec::new();
+
+        for object in &self.objects {
+            lines.push(object.to_osu_string_with(options));
+            write_commands(&object.sprite().commands, 1, options, &mut lines);
+        }
+        lines.extend(self.samples.iter().map(|s| s.to_osu_string_with(options)));
+
+        lines.join(options.line_ending())
+    }
+}
 
-        format!("[Colours]\n{}", colours)
+/// Writes the line declaring the object, without its commands.
+impl ToOsuString for StoryboardObject {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
+        match self {
+            StoryboardObject::Sprite(sprite) => format!("Sprite,{}", sprite_fields(sprite, options)),
+            StoryboardObject::Animation(animation) => animation.to_osu_string_with(options),
+        }
+    }
+}
+
+impl ToOsuString for Animation {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
+        format!(
+            "Animation,{},{},{},{}",
+            sprite_fields(&self.sprite, options),
+            self.frame_count,
+            options.float(self.frame_delay),
+            self.loop_type.to_osu_string_with(options),
+        )
+    }
+}
+
+impl ToOsuString for StoryboardSample {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
+        format!(
+            "Sample,{},{},\"{}\",{}",
+            options.time(self.time), self.layer as u8, self.filepath, self.volume
+        )
+    }
+}
+
+impl ToOsuString for Layer {
+    fn to_osu_string_with(&self, _options: &WriterOptions) -> String {
+        format!("{:?}", self)
+    }
+}
+
+impl ToOsuString for Origin {
+    fn to_osu_string_with(&self, _options: &WriterOptions) -> String {
+        format!("{:?}", self)
+    }
+}
+
+impl ToOsuString for LoopType {
+    fn to_osu_string_with(&self, _options: &WriterOptions) -> String {
+        format!("{:?}", self)
+    }
+}
+
+impl ToOsuString for Parameter {
+    fn to_osu_string_with(&self, _options: &WriterOptions) -> String {
+        match self {
+            Parameter::FlipHorizontal => "H".to_string(),
+            Parameter::FlipVertical => "V".to_string(),
+            Parameter::AdditiveBlending => "A".to_string(),
+        }
+    }
+}
+
+/// Writes a single command. Loops and triggers are written without the
+/// commands they contain, which go on the following, further indented,
+/// lines.
+impl ToOsuString for Command {
+    fn to_osu_string_with(&self, options: &WriterOptions) -> String {
+        let float = |v: &f32| options.float(*v);
+        let pair = |&(x, y): &(f32, f32)| format!("{},{}", options.float(x), options.float(y));
+
+        match self {
+            Command::Fade(t) => write_tween("F", t, options, float),
+            Command::Move(t) => write_tween("M", t, options, pair),
+            Command::MoveX(t) => write_tween("MX", t, options, float),
+            Command::MoveY(t) => write_tween("MY", t, options, float),
+            Command::Scale(t) => write_tween("S", t, options, float),
+            Command::VectorScale(t) => write_tween("V", t, options, pair),
+            Command::Rotate(t) => write_tween("R", t, options, float),
+            Command::Colour(t) => write_tween("C", t, options, |c| c.to_osu_string_with(options)),
+            Command::Parameter(t) => write_tween("P", t, options, |p| p.to_osu_string_with(options)),
+            Command::Loop(l) => format!("L,{},{}", options.time(l.start_time), l.loop_count),
+            Command::Trigger(t) => {
+                let (start, end) = (options.time(t.start_time), options.time(t.end_time));
+                match t.group_number {
+                    Some(group) => format!("T,{},{},{},{}", t.trigger, start, end, group),
+                    None => format!("T,{},{},{}", t.trigger, start, end),
+                }
+            }
+        }
+    }
+}
+
+/// The fields shared by sprites and animations, from the layer to the
+/// position.
+fn sprite_fields(sprite: &Sprite, options: &WriterOptions) -> String {
+    format!(
+        "{},{},\"{}\",{},{}",
+        sprite.layer.to_osu_string_with(options),
+        sprite.origin.to_osu_string_with(options),
+        sprite.filepath,
+        options.float(sprite.x),
+        options.float(sprite.y),
+    )
+}
+
+fn write_tween<T, F>(name: &str, tween: &Tween<T>, options: &WriterOptions, f: F) -> String
+where
+    F: Fn(&T) -> String,
+{
+    let end_time = tween.end_time.map(|t| options.time(t)).unwrap_or_default();
+    let values = tween.values.iter().map(f).collect::<Vec<String>>().join(",");
+
+    format!("{},{},{},{},{}", name, tween.easing as u8, options.time(tween.start_time), end_time, values)
+}
+
+/// Writes commands indented by `depth` spaces, followed by the commands
+/// nested in each loop or trigger one level deeper.
+fn write_commands(commands: &[Command], depth: usize, options: &WriterOptions, lines: &mut Vec<String>) {
+    for command in commands {
+        lines.push(format!("{}{}", " ".repeat(depth), command.to_osu_string_with(options)));
+
+        match command {
+            Command::Loop(l) => write_commands(&l.commands, depth + 1, options, lines),
+            Command::Trigger(t) => write_commands(&t.commands, depth + 1, options, lines),
+            _ => {}
+        }
     }
 }
 
 /// Helper function htat, given a base number of either 1, 2, 4, or 128,
-/// returns the `type` bitmap for hitobjects.
-fn get_type(base: u8, new_combo: bool, color_skip: i32) -> u8 {
+/// returns the `type` bitmap for hitobjects. Only the low three bits of
+/// `color_skip` fit in the bitmap, so the rest are dropped. Bits of
+/// `raw_type` above the first byte are kept as they are.
+fn get_type(base: i32, new_combo: bool, color_skip: i32, raw_type: i32) -> i32 {
     debug_assert_eq!(base & 0b0111_0100, 0);
-    let mut ret = base;
+    let mut ret = base | (raw_type & !0xff);
     if new_combo {
         ret |= 0b0000_0100;
     }
-    ret += (color_skip as u8) << 4;
-    return ret;
+    ret | ((color_skip & 0b111) << 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writer_options() {
+        let mut map = Beatmap {
+            version: 14,
+            ..Default::default()
+        };
+        map.metadata.title = "Title".to_string();
+        map.difficulty.slider_multiplier = 1.333_333_3;
+        map.editor.bookmarks = vec![Time(100.0), Time(200.0)];
+
+        let output = map.to_osu_string_with(&WriterOptions {
+            float_precision: Some(2),
+            target_version: Some(12),
+            ..WriterOptions::editor()
+        });
+
+        assert!(output.starts_with("osu file format v12\r\n\r\n[General]\r\n"));
+        assert!(output.contains("\r\nAudioLeadIn: 0\r\n"));
+        assert!(output.contains("\r\nBookmarks: 100,200\r\n"));
+        assert!(output.contains("\r\nTitle:Title\r\n"));
+        assert!(output.contains("\r\nSliderMultiplier:1.33\r\n"));
+        assert!(output.contains("\r\n\r\n[Events]\r\n\r\n[TimingPoints]\r\n"));
+        assert!(!output.replace("\r\n", "").contains('\n'));
+
+        let reparsed = parse_beatmap(&output).unwrap();
+        assert_eq!(reparsed.editor.bookmarks, map.editor.bookmarks);
+        assert_eq!(reparsed.version, 12);
+    }
+
+    #[test]
+    fn test_older_target_version() {
+        let input = "osu file format v14\n\n[General]\nWidescreenStoryboard: 1\n\n\
+                     [Metadata]\nBeatmapID:100\n\n[HitObjects]\n\
+                     0,0,1000,2,0,L|100:0,1,100\n0,0,2000,2,0,L|100:0,1,100,2|0,0:0|0:0,0:0:0:0:\n\
+                     256,192,3000,12,0,4000,0:0:0:0:\n64,192,5000,128,0,6000:0:0:0:0:\n";
+        let map = parse_beatmap(input).unwrap();
+
+        let output = map.to_osu_string_with(&WriterOptions::for_version(FormatVersion(9)));
+        assert!(!output.contains("WidescreenStoryboard") && !output.contains("BeatmapID"));
+        assert!(output.ends_with("[HitObjects]\r\n0,0,1000,2,0,L|100:0,1,100\r\n\
+                                  0,0,2000,2,0,L|100:0,1,100,2|0,0:0|0:0\r\n256,192,3000,12,0,4000\r\n\
+                                  64,192,5000,128,0,6000\r\n"));
+
+        let reparsed = parse_beatmap(&output).unwrap();
+        assert_eq!(reparsed.hit_objects, map.hit_objects);
+
+        let output = map.to_osu_string_with(&WriterOptions::for_version(FormatVersion(12)));
+        assert!(output.contains("WidescreenStoryboard: 1") && output.contains("BeatmapID:100"));
+        assert!(output.ends_with("64,192,5000,128,0,6000:0:0:0:0:\r\n"));
+    }
+
+    #[test]
+    fn test_key_order() {
+        let input = "osu file format v14
+
+[General]
+Mode: 1
+AudioFilename: audio.mp3
+PreviewTime: 500
+
+[Difficulty]
+SliderMultiplier:1.4
+ApproachRate:9
+";
+
+        let mut map = parse_beatmap(input).unwrap();
+        map.general.countdown = CountdownSpeed::None;
+        map.difficulty.overall_difficulty = 8.0;
+
+        let output = map.to_osu_string();
+        assert!(output.contains("[General]\nMode: 1\nAudioFilename: audio.mp3\nPreviewTime: 500\nCountdown: 0\n"));
+        assert!(output.contains("[Difficulty]\nOverallDifficulty: 8\nSliderMultiplier: 1.4\nApproachRate: 9\n"));
+    }
+
+    #[test]
+    fn test_stable_float() {
+        let cases: [(f32, &str); 10] = [
+            (5.100003, "5.100003"),
+            (5.100_003_2, "5.100003"),
+            (-1.5, "-1.5"),
+            (100.0, "100"),
+            (1_234_567.0, "1234567"),
+            (12_345_678.0, "1.234568E+07"),
+            (0.0001, "0.0001"),
+            (0.000_012_5, "1.25E-05"),
+            (-0.0, "0"),
+            (9.999_999_9, "10"),
+        ];
+        for &(value, expected) in cases.iter() {
+            assert_eq!(stable_float(f64::from(value), FLOAT_DIGITS), expected);
+        }
+
+        let cases = [
+            (4000.0 / 11.0, "363.636363636364"),
+            (-400.0 / 3.0, "-133.333333333333"),
+            (1e15, "1E+15"),
+            (0.1 + 0.2, "0.3"),
+        ];
+        for &(value, expected) in cases.iter() {
+            assert_eq!(stable_float(value, DOUBLE_DIGITS), expected);
+        }
+
+        let input = "osu file format v14\r\n\r\n[TimingPoints]\r\n764,363.636363636364,4,2,1,50,1,0\r\n\
+                     2764,-133.333333333333,4,2,1,50,0,0\r\n";
+        let output = parse_beatmap(input).unwrap().to_osu_string_with(&WriterOptions::editor());
+        assert!(output.contains("764,363.636363636364,4,2,1,50,1,0\r\n"));
+        assert!(output.contains("2764,-133.333333333333,4,2,1,50,0,0\r\n"));
+
+        let input = "osu file format v14\r\n\r\n[HitObjects]\r\n\
+                     47,196,764,6,0,L|38:127,2,63.7500024318696,2|0|0,0:0|0:0|0:0,0:0:0:0:\r\n\
+                     230,192,2764,2,0,L|207:41,1,113.999996520996,2|0,0:0|0:0,0:0:0:0:\r\n";
+        let output = parse_beatmap(input).unwrap().to_osu_string_with(&WriterOptions::editor());
+        assert!(output.contains("47,196,764,6,0,L|38:127,2,63.7500024318696,2|0|0,0:0|0:0|0:0,0:0:0:0:\r\n"));
+        assert!(output.contains("230,192,2764,2,0,L|207:41,1,113.999996520996,2|0,0:0|0:0,0:0:0:0:\r\n"));
+    }
+
+    #[test]
+    fn test_patch_section() {
+        let original = "osu file format v14\r\n\r\n[General]\r\nAudioFilename: a.mp3\r\n// kept\r\n\r\n\
+                        [Metadata]\r\nTitle:Old\r\nVersion:Hard\r\n\r\n\r\n\
+                        [HitObjects]\r\n256,192,1000,1,0\r\n";
+
+        let mut map = parse_beatmap(original).unwrap();
+        map.metadata.title = "New".to_string();
+        map.hit_objects.clear();
+
+        let patched = map.patch_section_with(original, "Metadata", &WriterOptions::editor()).unwrap();
+        assert_eq!(patched, original.replace("Title:Old", "Title:New"));
+
+        let patched = map.patch_section(&patched, "HitObjects").unwrap();
+        assert!(patched.ends_with("\r\n\r\n\r\n[HitObjects]\r\n"));
+        assert!(patched.contains("// kept"));
+
+        assert!(map.patch_section(original, "Colours").is_err());
+        assert!(map.patch_section(original, "Unknown").is_err());
+    }
+
+    #[test]
+    fn test_write_colours() {
+        let input = "osu file format v14
+
+[Colours]
+Combo1 : 255,0,0
+SliderBorder : 0,0,0
+SliderTrackOverride : 10,20,30
+";
+
+        let map = parse_beatmap(input).unwrap();
+        assert_eq!(map.colours.slider_body, None);
+        assert_eq!(map.colours.slider_border, Some(Colour(0, 0, 0)));
+
+        assert_eq!(map.colours.to_osu_string(), "[Colours]
+Combo1 : 255,0,0
+SliderTrackOverride : 10,20,30
+SliderBorder : 0,0,0");
+        assert_eq!(parse_beatmap(&map.to_osu_string()).unwrap().colours, map.colours);
+    }
+
+    #[test]
+    fn test_write_events() {
+        let input = "osu file format v14
+
+[Variables]
+$fade=F,0
+
+[Events]
+0,0,\"bg.jpg\",0,0
+Video,500,\"video.mp4\",0,0
+2,1000,2000
+Sprite,Foreground,Centre,\"sb/a.png\",320,240
+ $fade,0,1000,0,1
+ M,1,0,,320.5,240
+ L,1000,4
+  R,0,0,500,0,3.1415
+Animation,Fail,TopLeft,\"sb/anim.png\",0,0,4,50,LoopOnce
+ T,HitSoundClap,0,10000,2
+  C,0,0,100,255,0,0,255,255,255
+ P,0,0,,A
+Sample,1500,0,\"sfx/clap.wav\",60
+";
+
+        let map = parse_beatmap(input).unwrap();
+
+        let output = map.events.to_osu_string();
+        assert_eq!(output, "[Events]
+0,0,\"bg.jpg\",0,0
+Video,500,\"video.mp4\",0,0
+2,1000,2000
+Sprite,Foreground,Centre,\"sb/a.png\",320,240
+ F,0,0,1000,0,1
+ M,1,0,,320.5,240
+ L,1000,4
+  R,0,0,500,0,3.1415
+Animation,Fail,TopLeft,\"sb/anim.png\",0,0,4,50,LoopOnce
+ T,HitSoundClap,0,10000,2
+  C,0,0,100,255,0,0,255,255,255
+ P,0,0,,A
+Sample,1500,0,\"sfx/clap.wav\",60");
+
+        let reparsed = parse_beatmap(&map.to_osu_string()).unwrap();
+        assert_eq!(reparsed.events.storyboard, map.events.storyboard);
+        assert_eq!(reparsed.events.breaks, map.events.breaks);
+    }
+
+    #[test]
+    fn test_edited_events() {
+        let input = "osu file format v14\n\n[Variables]\n$bg=\"bg.jpg\"\n\n[Events]\n\
+                     Background,0,$bg\nBreak,1000,2000\n";
+        let mut map = parse_beatmap(input).unwrap();
+
+        // Unchanged events are written as they were
+        assert!(map.to_osu_string().contains("[Events]\nBackground,0,$bg\nBreak,1000,2000\n"));
+
+        map.events.breaks[0].end_time = Time(3000.0);
+        map.events.videos.push(Video { start_time: Time(0.0), filename: "video.mp4".to_string(), x_offset: 0, y_offset: 0 });
+        assert!(!map.events.raw_lines_match());
+        assert!(map.to_osu_string().contains("[Events]\n0,0,\"bg.jpg\",0,0\nVideo,0,\"video.mp4\",0,0\n2,1000,3000\n"));
+
+        let reparsed = parse_beatmap(&map.to_osu_string()).unwrap();
+        assert_eq!(reparsed.events.breaks, map.events.breaks);
+        assert_eq!(reparsed.events.videos, map.events.videos);
+    }
+
+    #[test]
+    fn test_get_type() {
+        assert_eq!(get_type(1, false, 0, 0), 1);
+        assert_eq!(get_type(2, true, 3, 0), 2 | 4 | 48);
+        assert_eq!(get_type(128, true, 7, 0), 128 | 4 | 112);
+        assert_eq!(get_type(8, false, 8, 0), 8);
+        assert_eq!(get_type(8, false, -1, 0), 8 | 112);
+        assert_eq!(get_type(1, false, 0, 256 | 2 | 4), 256 | 1);
+    }
 }
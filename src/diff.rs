@@ -0,0 +1,292 @@
+//! Structured comparison of two beatmaps.
+//!
+//! ```
+//! use osuparse::{beatmap_diff, Beatmap};
+//!
+//! let old = Beatmap::default();
+//! let mut new = old.clone();
+//! new.metadata.version = "Insane".to_string();
+//!
+//! let diff = beatmap_diff(&old, &new);
+//! assert_eq!(diff.fields[0].field, "version");
+//! ```
+
+use std::collections::BTreeMap;
+use std::mem;
+
+use super::*;
+
+/// A single changed field outside of the timing point and hit object lists.
+/// Values are rendered using their `Debug` representation.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FieldChange {
+    /// The section the field belongs to, e.g. `Metadata`.
+    pub section: &'static str,
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+/// A change to the hit objects of a beatmap. Objects are matched by time and
+/// type.
+#[derive(Debug, PartialEq, Clone)]
+pub enum HitObjectChange {
+    Added(HitObject),
+    Removed(HitObject),
+    /// The object's position changed, and nothing else did.
+    Moved {
+        time: Time,
+        from: (i32, i32),
+        to: (i32, i32),
+    },
+    /// Any other property of the object changed.
+    Modified { old: HitObject, new: HitObject },
+}
+
+/// A change to the timing points of a beatmap. Points are matched by offset
+/// and whether they are inherited.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TimingPointChange {
+    Added(TimingPoint),
+    Removed(TimingPoint),
+    Modified { old: TimingPoint, new: TimingPoint },
+}
+
+/// The result of [`beatmap_diff`](fn.beatmap_diff.html).
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct BeatmapDiff {
+    pub fields: Vec<FieldChange>,
+    pub timing_points: Vec<TimingPointChange>,
+    pub hit_objects: Vec<HitObjectChange>,
+}
+
+impl BeatmapDiff {
+    /// Whether the two beatmaps were found to be equal.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.timing_points.is_empty() && self.hit_objects.is_empty()
+    }
+}
+
+macro_rules! diff_fields {
+    ($changes:ident, $section:expr, $a:expr, $b:expr; $($field:ident),*) => {
+        $(
+            if $a.$field != $b.$field {
+                $changes.push(FieldChange {
+                    section: $section,
+                    field: stringify!($field),
+                    old: format!("{:?}", $a.$field),
+                    new: format!("{:?}", $b.$field),
+                });
+            }
+        )*
+    };
+}
+
+/// Compares two beatmaps, reporting what it would take to turn `a` into `b`.
+///
+/// Hit objects and timing points are reported in time order. Objects at the
+/// same time and of the same type are paired up in the order they appear.
+pub fn beatmap_diff(a: &Beatmap, b: &Beatmap) -> BeatmapDiff {
+    let mut fields = Vec::new();
+
+    if a.version != b.version {
+        fields.push(FieldChange {
+            section: "",
+            field: "version",
+            old: a.version.to_string(),
+            new: b.version.to_string(),
+        });
+    }
+
+    diff_fields!(fields, "General", a.general, b.general;
+        audio_filename, audio_lead_in, preview_time, countdown, sample_set,
+        stack_leniency, countdown_offset, skin_preference, game_mode,
+        letterbox_in_breaks, widescreen_storyboard, story_fire_in_front,
+        special_style, epilepsy_warning, use_skin_sprites, always_show_playfield,
+        overlay_position, samples_match_playback_rate);
+
+    diff_fields!(fields, "Editor", a.editor, b.editor;
+        bookmarks, distance_spacing, beat_divisor, grid_size, timeline_zoom,
+        current_time, unknown);
+
+    diff_fields!(fields, "Metadata", a.metadata, b.metadata;
+        title, title_unicode, artist, artist_unicode, creator, version,
+        source, tags, beatmap_id, beatmap_set_id);
+
+    diff_fields!(fields, "Difficulty", a.difficulty, b.difficulty;
+        hp_drain_rate, circle_size, overall_difficulty, approach_rate,
+        slider_multiplier, slider_tick_rate);
+
+    diff_fields!(fields, "Colours", a.colours, b.colours;
+        colours, slider_body, slider_track_override, slider_border);
+
+    BeatmapDiff {
+        fields,
+        timing_points: diff_timing_points(&a.timing_points, &b.timing_points),
+        hit_objects: diff_hit_objects(&a.hit_objects, &b.hit_objects),
+    }
+}
+
+fn diff_timing_points(a: &[TimingPoint], b: &[TimingPoint]) -> Vec<TimingPointChange> {
+    let mut unmatched: Vec<&TimingPoint> = b.iter().collect();
+    let mut changes = Vec::new();
+
+    for old in a {
+        let matching = unmatched
+            .iter()
+            .position(|p| p.offset == old.offset && p.inherited == old.inherited);
+
+        match matching {
+            Some(i) => {
+                let new = unmatched.remove(i);
+                if new != old {
+                    changes.push(TimingPointChange::Modified {
+                        old: old.clone(),
+                        new: new.clone(),
+                    });
+                }
+            }
+            None => changes.push(TimingPointChange::Removed(old.clone())),
+        }
+    }
+
+    changes.extend(unmatched.into_iter().map(|p| TimingPointChange::Added(p.clone())));
+    changes.sort_by_key(timing_change_offset);
+
+    changes
+}
+
+fn timing_change_offset(change: &TimingPointChange) -> Time {
+    match change {
+        TimingPointChange::Added(p) | TimingPointChange::Removed(p) => p.offset,
+        TimingPointChange::Modified { old, .. } => old.offset,
+    }
+}
+
+fn group_by_time(objects: &[HitObject]) -> BTreeMap<Time, Vec<&HitObject>> {
+    let mut groups: BTreeMap<Time, Vec<&HitObject>> = BTreeMap::new();
+    for obj in objects {
+        groups.entry(obj.time()).or_default().push(obj);
+    }
+
+    groups
+}
+
+fn with_position(obj: &HitObject, (x, y): (i32, i32)) -> HitObject {
+    let mut obj = obj.clone();
+    match obj {
+        HitObject::HitCircle(ref mut o) => {
+            o.x = x;
+            o.y = y;
+        }
+        HitObject::Slider(ref mut o) => {
+            o.x = x;
+            o.y = y;
+        }
+        HitObject::Spinner(ref mut o) => {
+            o.x = x;
+            o.y = y;
+        }
+        HitObject::HoldNote(ref mut o) => {
+            o.x = x;
+            o.y = y;
+        }
+    }
+
+    obj
+}
+
+fn diff_hit_objects(a: &[HitObject], b: &[HitObject]) -> Vec<HitObjectChange> {
+    let mut old_groups = group_by_time(a);
+    let mut new_groups = group_by_time(b);
+
+    let mut times: Vec<Time> = old_groups.keys().chain(new_groups.keys()).cloned().collect();
+    times.sort_unstable();
+    times.dedup();
+
+    let mut changes = Vec::new();
+    for time in times {
+        let old_objects = old_groups.remove(&time).unwrap_or_default();
+        let mut new_objects = new_groups.remove(&time).unwrap_or_default();
+
+        for old in old_objects {
+            let matching = new_objects
+                .iter()
+                .position(|new| mem::discriminant(*new) == mem::discriminant(old));
+
+            let new = match matching {
+                Some(i) => new_objects.remove(i),
+                None => {
+                    changes.push(HitObjectChange::Removed(old.clone()));
+                    continue;
+                }
+            };
+
+            if new == old {
+                continue;
+            }
+
+            if with_position(old, new.position()) == *new {
+                changes.push(HitObjectChange::Moved {
+                    time,
+                    from: old.position(),
+                    to: new.position(),
+                });
+            } else {
+                changes.push(HitObjectChange::Modified {
+                    old: old.clone(),
+                    new: new.clone(),
+                });
+            }
+        }
+
+        changes.extend(new_objects.into_iter().map(|o| HitObjectChange::Added(o.clone())));
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn circle(x: i32, time: i32) -> HitObject {
+        HitObject::from(HitCircle::new(x, 192, Time::from(time)))
+    }
+
+    #[test]
+    fn test_beatmap_diff() {
+        let a = Beatmap {
+            hit_objects: vec![circle(100, 100), circle(100, 200), circle(100, 300)],
+            timing_points: vec![TimingPoint::default()],
+            ..Default::default()
+        };
+
+        let mut b = a.clone();
+        b.metadata.creator = "mapper".to_string();
+        b.hit_objects = vec![circle(100, 100), circle(150, 200), circle(100, 400)];
+        b.timing_points[0].volume = 50;
+
+        let diff = beatmap_diff(&a, &b);
+
+        assert_eq!(diff.fields.len(), 1);
+        assert_eq!(diff.fields[0].section, "Metadata");
+        assert_eq!(diff.fields[0].new, "\"mapper\"");
+
+        assert_eq!(
+            diff.hit_objects,
+            vec![
+                HitObjectChange::Moved {
+                    time: Time(200.0),
+                    from: (100, 192),
+                    to: (150, 192),
+                },
+                HitObjectChange::Removed(circle(100, 300)),
+                HitObjectChange::Added(circle(100, 400)),
+            ]
+        );
+
+        assert_eq!(diff.timing_points.len(), 1);
+        assert!(beatmap_diff(&a, &a).is_empty());
+    }
+}
@@ -0,0 +1,99 @@
+use super::*;
+
+impl EditorSection {
+    /// Adds a bookmark, keeping bookmarks sorted. Returns `false` if there
+    /// already was one at that time.
+    pub fn add_bookmark(&mut self, time: Time) -> bool {
+        self.sort_bookmarks();
+        match self.bookmarks.binary_search(&time) {
+            Ok(_) => false,
+            Err(i) => {
+                self.bookmarks.insert(i, time);
+                true
+            }
+        }
+    }
+
+    /// Removes the bookmark at the given time, returning whether there was
+    /// one.
+    pub fn remove_bookmark(&mut self, time: Time) -> bool {
+        let len = self.bookmarks.len();
+        self.bookmarks.retain(|&b| b != time);
+        self.bookmarks.len() != len
+    }
+
+    /// Sorts bookmarks by time and removes duplicates.
+    pub fn sort_bookmarks(&mut self) {
+        self.bookmarks.sort_unstable();
+        self.bookmarks.dedup();
+    }
+}
+
+impl GeneralSection {
+    /// Sets the song select preview point. `-1` clears it. When the length
+    /// of the audio file is known, the preview point must fall within it.
+    pub fn set_preview_time(&mut self, time: Time, audio_length: Option<Time>) -> Result<()> {
+        if time < Time(-1.0) {
+            return Err(Error::Message("Preview time can't be negative"));
+        }
+        if let Some(length) = audio_length {
+            if time > length {
+                return Err(Error::Message("Preview time is past the end of the audio"));
+            }
+        }
+
+        self.preview_time = time;
+        Ok(())
+    }
+}
+
+impl Beatmap {
+    /// Moves every bookmark to the nearest beat, using the beatmap's timing
+    /// points, then sorts them and removes duplicates. Bookmarks are left in
+    /// place if the beatmap has no uninherited timing points.
+    pub fn snap_bookmarks(&mut self) {
+        let snapped: Vec<Time> = self.editor.bookmarks
+            .iter()
+            .map(|&b| self.nearest_beat(b).map_or(b, |t| Time::from(t.round())))
+            .collect();
+
+        self.editor.bookmarks = snapped;
+        self.editor.sort_bookmarks();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bookmarks() {
+        let mut map = Beatmap {
+            timing_points: vec![TimingPoint { offset: Time(100.0), ms_per_beat: 300.0, ..Default::default() }],
+            ..Default::default()
+        };
+        map.editor.bookmarks = vec![Time(1000.0), Time(390.0)];
+
+        assert!(map.editor.add_bookmark(Time(500.0)));
+        assert!(!map.editor.add_bookmark(Time(1000.0)));
+        assert_eq!(map.editor.bookmarks, vec![Time(390.0), Time(500.0), Time(1000.0)]);
+        assert!(map.editor.remove_bookmark(Time(500.0)));
+        assert!(!map.editor.remove_bookmark(Time(500.0)));
+
+        map.editor.add_bookmark(Time(420.0));
+        map.snap_bookmarks();
+        assert_eq!(map.editor.bookmarks, vec![Time(400.0), Time(1000.0)]);
+    }
+
+    #[test]
+    fn test_set_preview_time() {
+        let mut general = GeneralSection::default();
+
+        assert!(general.set_preview_time(Time(5000.0), Some(Time(60_000.0))).is_ok());
+        assert_eq!(general.preview_time, Time(5000.0));
+        assert!(general.set_preview_time(Time(70_000.0), Some(Time(60_000.0))).is_err());
+        assert!(general.set_preview_time(Time(-5.0), None).is_err());
+        assert!(general.set_preview_time(Time(-1.0), None).is_ok());
+        assert_eq!(general.preview_time, Time(-1.0));
+    }
+}
@@ -6,22 +6,120 @@ pub type Result<T> = std::result::Result<T, Error>;
 #[derive(Debug)]
 pub enum Error {
     Parse,
-    Syntax(Option<(usize, String)>, String),
+    Syntax(Option<Location>, String),
     Message(&'static str),
+    /// An error in the field starting `column` bytes into a line. Parsers of
+    /// single lines return these, and they are turned into `Syntax` errors
+    /// once the line is known.
+    Field(usize, Box<Error>),
+    Io(std::io::Error),
+    #[cfg(feature = "json")]
+    Json(::serde_json::Error),
+    #[cfg(feature = "cache")]
+    Cache(::bincode::Error),
+    #[cfg(feature = "osz")]
+    Zip(::zip::result::ZipError),
 }
 
 impl Display for Error {
     fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
         match *self {
-            Error::Message(ref msg) => formatter.write_str(msg),
-            Error::Syntax(ref line, ref reason) => {
-                if let Some((line_n, line)) = line {
-                    write!(formatter, "Syntax error on line {}: {}\n {}", line_n + 1, reason, line)
-                } else {
-                    write!(formatter, "Syntax error: {}", reason)
+            Error::Message(msg) => formatter.write_str(msg),
+            Error::Syntax(Some(ref location), ref reason) => {
+                write!(formatter, "Syntax error on line {}", location.line + 1)?;
+                if let Some(ref section) = location.section {
+                    write!(formatter, " in [{}]", section)?;
                 }
+                write!(formatter, ": {}\n{}", reason, location.snippet())
             },
+            Error::Syntax(None, ref reason) => write!(formatter, "Syntax error: {}", reason),
+            Error::Field(column, ref err) => write!(formatter, "{} at column {}", err, column + 1),
             Error::Parse => formatter.write_str("Parsing error"),
+            Error::Io(ref err) => write!(formatter, "IO error: {}", err),
+            #[cfg(feature = "json")]
+            Error::Json(ref err) => write!(formatter, "JSON error: {}", err),
+            #[cfg(feature = "cache")]
+            Error::Cache(ref err) => write!(formatter, "Cache error: {}", err),
+            #[cfg(feature = "osz")]
+            Error::Zip(ref err) => write!(formatter, "Archive error: {}", err),
+        }
+    }
+}
+
+impl Error {
+    /// Mark the error as coming from the field `column` bytes into the text
+    /// given to a parser. Columns of errors from parsers of parts of that
+    /// field are added on, so that they count from the start of the text.
+    pub fn in_field(self, column: usize) -> Error {
+        match self {
+            Error::Field(inner, err) => Error::Field(column + inner, err),
+            err => Error::Field(column, Box::new(err)),
+        }
+    }
+}
+
+/// Where a syntax error was found.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Location {
+    /// Index of the line, starting at 0.
+    pub line: usize,
+    /// The text of the line, without any trailing comment.
+    pub text: String,
+    /// Where the field that couldn't be read starts within `text`, in bytes,
+    /// if the error was caused by a single field.
+    pub column: Option<usize>,
+    /// Name of the section the line is in, as written in its header.
+    pub section: Option<String>,
+}
+
+impl Location {
+    /// The line, with a caret under the field that couldn't be read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use osuparse::{parse_beatmap, Error};
+    ///
+    /// match parse_beatmap("osu file format v14\n\n[HitObjects]\n256,abc,1000,1,0\n") {
+    ///     Err(Error::Syntax(Some(location), _)) => {
+    ///         assert_eq!(location.section.as_deref(), Some("HitObjects"));
+    ///         assert_eq!(location.snippet(), "  4 | 256,abc,1000,1,0\n    |     ^^^");
+    ///     }
+    ///     other => panic!("unexpected result {:?}", other),
+    /// }
+    /// ```
+    pub fn snippet(&self) -> String {
+        let number = (self.line + 1).to_string();
+        let gutter = " ".repeat(number.len() + 3);
+        let mut snippet = format!("  {} | {}", number, self.text);
+
+        if let Some(column) = self.column.filter(|&c| self.text.is_char_boundary(c)) {
+            let field = &self.text[column..];
+            let width = field.find([',', ':', '|']).unwrap_or(field.len());
+            let indent = self.text[..column].chars().count();
+            let carets = field[..width].chars().count().max(1);
+            snippet.push_str(&format!("\n{}| {}{}", gutter, " ".repeat(indent), "^".repeat(carets)));
+        }
+
+        snippet
+    }
+}
+
+/// A problem found while parsing that didn't prevent the beatmap from being
+/// read, such as a value outside of its expected range.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Warning {
+    /// The line the problem was found on, starting at 1.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Warning {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(line) = self.line {
+            write!(formatter, "Warning on line {}: {}", line, self.message)
+        } else {
+            write!(formatter, "Warning: {}", self.message)
         }
     }
 }
@@ -0,0 +1,1433 @@
+//! The `[Events]` section: backgrounds, videos, breaks and storyboards.
+//!
+//! Event lines may refer to variables declared in a `[Variables]` section,
+//! as written by storyboard generators. Variables are substituted before the
+//! events are parsed, while the lines as they appeared in the file are kept
+//! in [`Events::raw_lines`](struct.Events.html#structfield.raw_lines) so
+//! that they can be written back unchanged, as long as the events aren't
+//! edited.
+//!
+//! Lines that can't be parsed are skipped with a warning rather than
+//! failing the whole beatmap, as the game does.
+
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+use std::f32::consts::PI;
+
+use super::*;
+use error::{Error, Result, Warning};
+
+/// Everything found in the `[Events]` section of a beatmap.
+#[derive(Debug, Default, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Events {
+    pub background: Option<Background>,
+    pub videos: Vec<Video>,
+    pub breaks: Vec<Break>,
+    /// Background colour changes, found in maps made before storyboards.
+    pub colour_transforms: Vec<ColourTransform>,
+    pub storyboard: Storyboard,
+    /// Variables declared in the `[Variables]` section.
+    pub variables: Vec<Variable>,
+    /// Lines of an event type this crate doesn't know, as they appeared in
+    /// the file. They are written back out after the other events.
+    pub unknown_lines: Vec<String>,
+    /// The lines of the `[Events]` section as they appeared in the file,
+    /// before variable substitution and without comments. These are written
+    /// back out in place of the other fields for as long as they still
+    /// parse to them, see [`raw_lines_match`](#method.raw_lines_match).
+    pub raw_lines: Vec<String>,
+}
+
+/// A storyboard variable, written as `$name=value`. Every occurrence of the
+/// name in an event line is replaced by the value.
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Variable {
+    /// The variable's name, including the leading `$`.
+    pub name: String,
+    pub value: String,
+}
+
+/// The background image shown during gameplay.
+#[derive(Debug, Default, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Background {
+    /// Path of the image, relative to the beatmap's folder.
+    pub filename: String,
+    /// Offset in osu!pixels from the centre of the screen.
+    pub x_offset: i32,
+    pub y_offset: i32,
+}
+
+/// A background video.
+#[derive(Debug, Default, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Video {
+    /// When the video starts, in milliseconds.
+    pub start_time: Time,
+    /// Path of the video, relative to the beatmap's folder.
+    pub filename: String,
+    pub x_offset: i32,
+    pub y_offset: i32,
+}
+
+/// The kind of a top level event line, given by its first field. Each type
+/// can be written either as its number or its name, e.g. `2` or `Break`.
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum EventType {
+    Background = 0,
+    Video = 1,
+    Break = 2,
+    Colour = 3,
+    Sprite = 4,
+    Sample = 5,
+    Animation = 6,
+}
+
+impl EventType {
+    /// Reads the type from either its number or its name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use osuparse::events::EventType;
+    ///
+    /// assert_eq!(EventType::from_code("3"), Some(EventType::Colour));
+    /// assert_eq!(EventType::from_code("Sprite"), Some(EventType::Sprite));
+    /// assert_eq!(EventType::from_code("7"), None);
+    /// ```
+    pub fn from_code(s: &str) -> Option<EventType> {
+        match s {
+            "0" | "Background" => Some(EventType::Background),
+            "1" | "Video" => Some(EventType::Video),
+            "2" | "Break" => Some(EventType::Break),
+            "3" | "Colour" => Some(EventType::Colour),
+            "4" | "Sprite" => Some(EventType::Sprite),
+            "5" | "Sample" => Some(EventType::Sample),
+            "6" | "Animation" => Some(EventType::Animation),
+            _ => None,
+        }
+    }
+
+    /// The form the osu! editor writes the type in.
+    pub fn code(self) -> &'static str {
+        match self {
+            EventType::Background => "0",
+            EventType::Video => "Video",
+            EventType::Break => "2",
+            EventType::Colour => "3",
+            EventType::Sprite => "Sprite",
+            EventType::Sample => "Sample",
+            EventType::Animation => "Animation",
+        }
+    }
+}
+
+/// A break period, during which health doesn't drain.
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Break {
+    pub start_time: Time,
+    pub end_time: Time,
+}
+
+/// Changes the colour of the background behind the playfield, written as
+/// `3,time,r,g,b`. Only used by maps from before storyboards were added.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ColourTransform {
+    pub time: Time,
+    pub colour: Colour,
+}
+
+/// The storyboard objects and sound samples defined by a beatmap, in the
+/// order they appear.
+#[derive(Debug, Default, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Storyboard {
+    pub objects: Vec<StoryboardObject>,
+    pub samples: Vec<StoryboardSample>,
+}
+
+impl Events {
+    /// Whether [`raw_lines`](#structfield.raw_lines) parse to the events as
+    /// they are now, meaning that none were changed since they were read
+    /// and the lines can be written back as they were. Once any event is
+    /// edited, the section is written from the events instead.
+    pub fn raw_lines_match(&self) -> bool {
+        if self.raw_lines.is_empty() {
+            return false;
+        }
+
+        let lines: Vec<(usize, &str)> = self.raw_lines.iter().map(String::as_str).enumerate().collect();
+        let (parsed, _) = parse_events(&lines, self.variables.clone());
+        parsed == *self
+    }
+
+    /// Moves the videos, breaks and storyboard `offset` milliseconds later,
+    /// or earlier if negative.
+    /// [`raw_lines`](#structfield.raw_lines) is cleared, so that the events
+    /// are written from their moved times.
+    pub fn shift_time(&mut self, offset: Time) {
+        for video in &mut self.videos {
+            video.start_time += offset;
+        }
+        for b in &mut self.breaks {
+            b.start_time += offset;
+            b.end_time += offset;
+        }
+        for transform in &mut self.colour_transforms {
+            transform.time += offset;
+        }
+        self.storyboard.shift_time(offset);
+        self.raw_lines.clear();
+    }
+}
+
+/// Commands above which a storyboard counts as heavy.
+const HEAVY_STORYBOARD_COMMANDS: usize = 10_000;
+/// Objects above which a storyboard counts as heavy.
+const HEAVY_STORYBOARD_OBJECTS: usize = 1_000;
+
+impl Storyboard {
+    /// Whether the storyboard has any objects or samples.
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty() && self.samples.is_empty()
+    }
+
+    /// Number of commands on every object, counting loops and triggers and
+    /// each command inside them once, as they are written.
+    pub fn command_count(&self) -> usize {
+        fn count(commands: &[Command]) -> usize {
+            commands
+                .iter()
+                .map(|c| match c {
+                    Command::Loop(l) => 1 + count(&l.commands),
+                    Command::Trigger(t) => 1 + count(&t.commands),
+                    _ => 1,
+                })
+                .sum()
+        }
+
+        self.objects.iter().map(|o| count(&o.sprite().commands)).sum()
+    }
+
+    /// Moves every object and sample `offset` milliseconds later, or earlier
+    /// if negative.
+    pub fn shift_time(&mut self, offset: Time) {
+        for object in &mut self.objects {
+            for command in &mut object.sprite_mut().commands {
+                command.shift_time(offset);
+            }
+        }
+        for sample in &mut self.samples {
+            sample.time += offset;
+        }
+    }
+
+    /// The objects on a single layer, in the order they are drawn.
+    pub fn layer<'a>(&'a self, layer: Layer) -> impl Iterator<Item = &'a StoryboardObject> + 'a {
+        self.objects.iter().filter(move |o| o.sprite().layer == layer)
+    }
+
+    /// Every object in the order it is drawn: by layer, from
+    /// [`Background`](enum.Layer.html#variant.Background) to
+    /// [`Overlay`](enum.Layer.html#variant.Overlay), and within a layer in
+    /// the order they appear in the file, so that later objects are drawn on
+    /// top.
+    pub fn draw_order(&self) -> Vec<&StoryboardObject> {
+        let mut objects: Vec<&StoryboardObject> = self.objects.iter().collect();
+        // Sorting is stable, so file order is kept within each layer
+        objects.sort_by_key(|o| o.sprite().layer);
+        objects
+    }
+
+    /// Every file used by the storyboard: sprite images, each frame of every
+    /// animation and sound samples. Paths are relative to the beatmap's
+    /// folder, as written in the file, sorted and without duplicates.
+    pub fn referenced_files(&self) -> Vec<String> {
+        let mut files = BTreeSet::new();
+
+        for object in &self.objects {
+            match object {
+                StoryboardObject::Sprite(s) => {
+                    files.insert(s.filepath.clone());
+                }
+                StoryboardObject::Animation(a) => files.extend(a.frame_paths()),
+            }
+        }
+        files.extend(self.samples.iter().map(|s| s.filepath.clone()));
+
+        files.into_iter().collect()
+    }
+
+    /// The objects that are visible at the given time, in draw order. An
+    /// object is active from the start of its first command to the end of
+    /// its last; objects without commands are never active.
+    pub fn active_at(&self, time: Time) -> Vec<&StoryboardObject> {
+        self.draw_order()
+            .into_iter()
+            .filter(|o| {
+                let sprite = o.sprite();
+                match (sprite.start_time(), sprite.end_time()) {
+                    (Some(start), Some(end)) => start <= time && time <= end,
+                    _ => false,
+                }
+            })
+            .collect()
+    }
+}
+
+/// A sound played at a given time, written as
+/// `Sample,time,layer,"filepath",volume`. Hitsound-replacement maps use
+/// these in place of hit object hitsounds.
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StoryboardSample {
+    pub time: Time,
+    /// Samples on the fail or pass layer are only played while the player is
+    /// failing or passing. Samples can't be on the overlay layer.
+    pub layer: Layer,
+    /// Path of the sound file, relative to the beatmap's folder.
+    pub filepath: String,
+    /// Volume from 0 to 100. Defaults to 100 if left out.
+    pub volume: i32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum StoryboardObject {
+    Sprite(Sprite),
+    Animation(Animation),
+}
+
+impl StoryboardObject {
+    pub fn sprite(&self) -> &Sprite {
+        match self {
+            StoryboardObject::Sprite(s) => s,
+            StoryboardObject::Animation(a) => &a.sprite,
+        }
+    }
+
+    pub fn sprite_mut(&mut self) -> &mut Sprite {
+        match self {
+            StoryboardObject::Sprite(s) => s,
+            StoryboardObject::Animation(a) => &mut a.sprite,
+        }
+    }
+}
+
+/// The layer a storyboard object is drawn on. Layers are drawn in the order
+/// listed here, so later layers appear on top.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Layer {
+    Background = 0,
+    /// Only shown while the player is failing.
+    Fail = 1,
+    /// Only shown while the player is passing.
+    Pass = 2,
+    Foreground = 3,
+    /// Drawn above hit objects.
+    Overlay = 4,
+}
+
+/// The point of an image that its position refers to.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Origin {
+    TopLeft = 0,
+    Centre = 1,
+    CentreLeft = 2,
+    TopRight = 3,
+    BottomCentre = 4,
+    TopCentre = 5,
+    /// Behaves like `TopLeft`.
+    Custom = 6,
+    CentreRight = 7,
+    BottomLeft = 8,
+    BottomRight = 9,
+}
+
+/// A still image.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Sprite {
+    pub layer: Layer,
+    pub origin: Origin,
+    /// Path of the image, relative to the beatmap's folder.
+    pub filepath: String,
+    /// Position in storyboard pixels, where the visible area is 640x480.
+    pub x: f32,
+    pub y: f32,
+    pub commands: Vec<Command>,
+}
+
+impl Sprite {
+    /// When the sprite's first command starts, if it has any.
+    pub fn start_time(&self) -> Option<Time> {
+        self.commands.iter().map(Command::start_time).min()
+    }
+
+    /// When the sprite's last command ends, if it has any.
+    pub fn end_time(&self) -> Option<Time> {
+        self.commands.iter().map(Command::end_time).max()
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum LoopType {
+    LoopForever,
+    /// Stops on the last frame after playing once.
+    LoopOnce,
+}
+
+/// An image that cycles through numbered frames: `filepath` `sample.png`
+/// shows `sample0.png`, `sample1.png` and so on.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Animation {
+    pub sprite: Sprite,
+    pub frame_count: i32,
+    /// Milliseconds between frames.
+    pub frame_delay: f32,
+    pub loop_type: LoopType,
+}
+
+impl Animation {
+    /// The path of each frame, made by adding the frame number before the
+    /// extension of the animation's path.
+    pub fn frame_paths(&self) -> Vec<String> {
+        let path = &self.sprite.filepath;
+        let name_start = path.rfind(['/', '\\']).map_or(0, |i| i + 1);
+        let (stem, extension) = match path[name_start..].rfind('.') {
+            Some(i) => path.split_at(name_start + i),
+            None => (path.as_str(), ""),
+        };
+
+        (0..self.frame_count.max(0))
+            .map(|i| format!("{}{}{}", stem, i, extension))
+            .collect()
+    }
+}
+
+/// A change of one of a storyboard object's properties over time, such as
+/// `F,0,1000,2000,0,1`.
+///
+/// `values` holds every value written, so a command such as
+/// `F,0,1000,2000,0` fades to `0` and stays there, while
+/// `F,0,1000,2000,0,1,0` is shorthand for fading from `0` to `1` and then,
+/// over another 1000ms, back to `0`.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Tween<T> {
+    pub easing: Easing,
+    pub start_time: Time,
+    /// `None` if the end time was left empty, which means the same as the
+    /// start time.
+    pub end_time: Option<Time>,
+    pub values: Vec<T>,
+}
+
+impl<T> Tween<T> {
+    fn shift_time(&mut self, offset: Time) {
+        self.start_time += offset;
+        if let Some(end_time) = self.end_time.as_mut() {
+            *end_time += offset;
+        }
+    }
+
+    /// When the last value is reached. Each value after the second adds
+    /// another `end_time - start_time` to the command's length.
+    pub fn end_time(&self) -> Time {
+        let end = self.end_time.unwrap_or(self.start_time);
+        let segments = self.values.len().saturating_sub(1).max(1) as f64;
+        self.start_time + (end - self.start_time) * segments
+    }
+}
+
+impl<T: Interpolate> Tween<T> {
+    /// The value at the given time, or `None` before the command starts.
+    /// After it ends, the last value is kept.
+    pub fn value_at(&self, time: Time) -> Option<T> {
+        let (&first, &last) = match (self.values.first(), self.values.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return None,
+        };
+        if time < self.start_time {
+            return None;
+        }
+
+        let duration = self.end_time.unwrap_or(self.start_time) - self.start_time;
+        if self.values.len() == 1 || duration <= Time::ZERO || time >= self.end_time() {
+            return Some(if self.values.len() == 1 { first } else { last });
+        }
+
+        let elapsed = time - self.start_time;
+        let segment = (elapsed.0 / duration.0) as usize;
+        let t = ((elapsed.0 % duration.0) / duration.0) as f32;
+
+        Some(T::interpolate(
+            self.values[segment],
+            self.values[segment + 1],
+            self.easing.evaluate(t),
+        ))
+    }
+}
+
+/// Values that storyboard commands can move between.
+pub trait Interpolate: Copy {
+    /// The value a fraction `t` of the way from `from` to `to`. `t` is
+    /// usually between 0 and 1, but some easings overshoot.
+    fn interpolate(from: Self, to: Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(from: Self, to: Self, t: f32) -> Self {
+        from + (to - from) * t
+    }
+}
+
+impl Interpolate for (f32, f32) {
+    fn interpolate(from: Self, to: Self, t: f32) -> Self {
+        (f32::interpolate(from.0, to.0, t), f32::interpolate(from.1, to.1, t))
+    }
+}
+
+impl Interpolate for Colour {
+    fn interpolate(from: Self, to: Self, t: f32) -> Self {
+        let channel = |a: i32, b: i32| f32::interpolate(a as f32, b as f32, t).round() as i32;
+        Colour(channel(from.0, to.0), channel(from.1, to.1), channel(from.2, to.2))
+    }
+}
+
+/// Parameters don't change gradually: the first value holds until the
+/// command ends.
+impl Interpolate for Parameter {
+    fn interpolate(from: Self, _to: Self, _t: f32) -> Self {
+        from
+    }
+}
+
+/// How a command's values change over its duration. The names and numbers
+/// are those used in `.osu` and `.osb` files.
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Easing {
+    Linear = 0,
+    /// Same as `QuadOut`.
+    Out = 1,
+    /// Same as `QuadIn`.
+    In = 2,
+    QuadIn = 3,
+    QuadOut = 4,
+    QuadInOut = 5,
+    CubicIn = 6,
+    CubicOut = 7,
+    CubicInOut = 8,
+    QuartIn = 9,
+    QuartOut = 10,
+    QuartInOut = 11,
+    QuintIn = 12,
+    QuintOut = 13,
+    QuintInOut = 14,
+    SineIn = 15,
+    SineOut = 16,
+    SineInOut = 17,
+    ExpoIn = 18,
+    ExpoOut = 19,
+    ExpoInOut = 20,
+    CircIn = 21,
+    CircOut = 22,
+    CircInOut = 23,
+    ElasticIn = 24,
+    ElasticOut = 25,
+    ElasticHalfOut = 26,
+    ElasticQuarterOut = 27,
+    ElasticInOut = 28,
+    BackIn = 29,
+    BackOut = 30,
+    BackInOut = 31,
+    BounceIn = 32,
+    BounceOut = 33,
+    BounceInOut = 34,
+}
+
+impl Easing {
+    const ALL: [Easing; 35] = [
+        Easing::Linear, Easing::Out, Easing::In,
+        Easing::QuadIn, Easing::QuadOut, Easing::QuadInOut,
+        Easing::CubicIn, Easing::CubicOut, Easing::CubicInOut,
+        Easing::QuartIn, Easing::QuartOut, Easing::QuartInOut,
+        Easing::QuintIn, Easing::QuintOut, Easing::QuintInOut,
+        Easing::SineIn, Easing::SineOut, Easing::SineInOut,
+        Easing::ExpoIn, Easing::ExpoOut, Easing::ExpoInOut,
+        Easing::CircIn, Easing::CircOut, Easing::CircInOut,
+        Easing::ElasticIn, Easing::ElasticOut, Easing::ElasticHalfOut,
+        Easing::ElasticQuarterOut, Easing::ElasticInOut,
+        Easing::BackIn, Easing::BackOut, Easing::BackInOut,
+        Easing::BounceIn, Easing::BounceOut, Easing::BounceInOut,
+    ];
+
+    /// The easing with the given number, as written in command lines.
+    pub fn from_id(id: i32) -> Option<Easing> {
+        if id < 0 {
+            return None;
+        }
+        Easing::ALL.get(id as usize).cloned()
+    }
+
+    /// Map the fraction `t` of a command's duration that has passed, from 0
+    /// to 1, to the fraction of the way between its values.
+    pub fn evaluate(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+
+        // Every easing is derived from the `In` form of its curve.
+        let out = |f: fn(f32) -> f32| 1.0 - f(1.0 - t);
+        let in_out = |f: fn(f32) -> f32| {
+            if t < 0.5 {
+                f(2.0 * t) / 2.0
+            } else {
+                1.0 - f(2.0 - 2.0 * t) / 2.0
+            }
+        };
+
+        match self {
+            Easing::Linear => t,
+            Easing::In | Easing::QuadIn => quad(t),
+            Easing::Out | Easing::QuadOut => out(quad),
+            Easing::QuadInOut => in_out(quad),
+            Easing::CubicIn => cubic(t),
+            Easing::CubicOut => out(cubic),
+            Easing::CubicInOut => in_out(cubic),
+            Easing::QuartIn => quart(t),
+            Easing::QuartOut => out(quart),
+            Easing::QuartInOut => in_out(quart),
+            Easing::QuintIn => quint(t),
+            Easing::QuintOut => out(quint),
+            Easing::QuintInOut => in_out(quint),
+            Easing::SineIn => sine(t),
+            Easing::SineOut => out(sine),
+            Easing::SineInOut => in_out(sine),
+            Easing::ExpoIn => expo(t),
+            Easing::ExpoOut => out(expo),
+            Easing::ExpoInOut => in_out(expo),
+            Easing::CircIn => circ(t),
+            Easing::CircOut => out(circ),
+            Easing::CircInOut => in_out(circ),
+            Easing::ElasticIn => 1.0 - elastic_out(1.0 - t, 1.0),
+            Easing::ElasticOut => elastic_out(t, 1.0),
+            Easing::ElasticHalfOut => elastic_out(t, 0.5),
+            Easing::ElasticQuarterOut => elastic_out(t, 0.25),
+            Easing::ElasticInOut => in_out(|t| 1.0 - elastic_out(1.0 - t, 1.0)),
+            Easing::BackIn => back(t),
+            Easing::BackOut => out(back),
+            Easing::BackInOut => in_out(|t| {
+                let s = BACK_OVERSHOOT * 1.525;
+                t * t * ((s + 1.0) * t - s)
+            }),
+            Easing::BounceIn => 1.0 - bounce_out(1.0 - t),
+            Easing::BounceOut => bounce_out(t),
+            Easing::BounceInOut => in_out(|t| 1.0 - bounce_out(1.0 - t)),
+        }
+    }
+}
+
+const BACK_OVERSHOOT: f32 = 1.701_58;
+
+fn quad(t: f32) -> f32 {
+    t * t
+}
+
+fn cubic(t: f32) -> f32 {
+    t * t * t
+}
+
+fn quart(t: f32) -> f32 {
+    t * t * t * t
+}
+
+fn quint(t: f32) -> f32 {
+    t * t * t * t * t
+}
+
+fn sine(t: f32) -> f32 {
+    1.0 - (t * PI / 2.0).cos()
+}
+
+fn expo(t: f32) -> f32 {
+    if t <= 0.0 {
+        0.0
+    } else {
+        2f32.powf(10.0 * (t - 1.0))
+    }
+}
+
+fn circ(t: f32) -> f32 {
+    1.0 - (1.0 - t * t).sqrt()
+}
+
+fn back(t: f32) -> f32 {
+    t * t * ((BACK_OVERSHOOT + 1.0) * t - BACK_OVERSHOOT)
+}
+
+/// The elastic curves differ only in how many times they oscillate before
+/// settling, given by `frequency`.
+fn elastic_out(t: f32, frequency: f32) -> f32 {
+    if t >= 1.0 {
+        return 1.0;
+    }
+    2f32.powf(-10.0 * t) * ((frequency * t - 0.075) * (2.0 * PI) / 0.3).sin() + 1.0
+}
+
+fn bounce_out(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+
+    if t < 1.0 / D {
+        N * t * t
+    } else if t < 2.0 / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984_375
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Parameter {
+    FlipHorizontal,
+    FlipVertical,
+    AdditiveBlending,
+}
+
+/// Repeats its commands `loop_count` times, with their times relative to
+/// `start_time`.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Loop {
+    pub start_time: Time,
+    pub loop_count: i32,
+    pub commands: Vec<Command>,
+}
+
+/// Runs its commands when a game event, such as a hitsound playing, happens
+/// between `start_time` and `end_time`.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Trigger {
+    /// The trigger condition, e.g. `HitSoundClap` or `Passing`.
+    pub trigger: String,
+    pub start_time: Time,
+    pub end_time: Time,
+    pub group_number: Option<i32>,
+    pub commands: Vec<Command>,
+}
+
+/// The value of a command at some point in time, as returned by
+/// [`Command::value_at`](enum.Command.html#method.value_at).
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum CommandValue {
+    /// Opacity, a single coordinate, scale or rotation.
+    Float(f32),
+    /// A position or vector scale.
+    Vector(f32, f32),
+    Colour(Colour),
+    Parameter(Parameter),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum Command {
+    Fade(Tween<f32>),
+    Move(Tween<(f32, f32)>),
+    MoveX(Tween<f32>),
+    MoveY(Tween<f32>),
+    Scale(Tween<f32>),
+    VectorScale(Tween<(f32, f32)>),
+    /// Rotation in radians.
+    Rotate(Tween<f32>),
+    Colour(Tween<Colour>),
+    Parameter(Tween<Parameter>),
+    Loop(Loop),
+    Trigger(Trigger),
+}
+
+impl Command {
+    /// Moves the command `offset` milliseconds. The times of the commands
+    /// inside loops and triggers are relative to the loop or trigger, so
+    /// they stay as they are.
+    fn shift_time(&mut self, offset: Time) {
+        match self {
+            Command::Fade(t) | Command::MoveX(t) | Command::MoveY(t) | Command::Scale(t) |
+            Command::Rotate(t) => t.shift_time(offset),
+            Command::Move(t) | Command::VectorScale(t) => t.shift_time(offset),
+            Command::Colour(t) => t.shift_time(offset),
+            Command::Parameter(t) => t.shift_time(offset),
+            Command::Loop(l) => l.start_time += offset,
+            Command::Trigger(t) => {
+                t.start_time += offset;
+                t.end_time += offset;
+            }
+        }
+    }
+
+    /// When the command starts. Times inside loops are relative to the
+    /// loop's start time, so this is only meaningful for commands at the top
+    /// level of a sprite.
+    pub fn start_time(&self) -> Time {
+        match self {
+            Command::Fade(t) | Command::MoveX(t) | Command::MoveY(t) | Command::Scale(t) |
+            Command::Rotate(t) => t.start_time,
+            Command::Move(t) | Command::VectorScale(t) => t.start_time,
+            Command::Colour(t) => t.start_time,
+            Command::Parameter(t) => t.start_time,
+            Command::Loop(l) => l.start_time + l.commands.iter().map(Command::start_time).min().unwrap_or(Time::ZERO),
+            Command::Trigger(t) => t.start_time,
+        }
+    }
+
+    /// When the command ends. A loop ends once all of its iterations have
+    /// played. A trigger's commands may run at any point until its end time,
+    /// so that is taken as the end.
+    pub fn end_time(&self) -> Time {
+        match self {
+            Command::Fade(t) | Command::MoveX(t) | Command::MoveY(t) | Command::Scale(t) |
+            Command::Rotate(t) => t.end_time(),
+            Command::Move(t) | Command::VectorScale(t) => t.end_time(),
+            Command::Colour(t) => t.end_time(),
+            Command::Parameter(t) => t.end_time(),
+            Command::Loop(l) => {
+                let first = l.commands.iter().map(Command::start_time).min().unwrap_or(Time::ZERO);
+                let last = l.commands.iter().map(Command::end_time).max().unwrap_or(Time::ZERO);
+                l.start_time + first + (last - first) * f64::from(l.loop_count.max(1))
+            }
+            Command::Trigger(t) => t.end_time,
+        }
+    }
+
+    /// The value the command gives its property at the given time, or
+    /// `None` before it starts. Loops and triggers don't have a value of
+    /// their own.
+    pub fn value_at(&self, time: Time) -> Option<CommandValue> {
+        match self {
+            Command::Fade(t) | Command::MoveX(t) | Command::MoveY(t) | Command::Scale(t) |
+            Command::Rotate(t) => t.value_at(time).map(CommandValue::Float),
+            Command::Move(t) | Command::VectorScale(t) => {
+                t.value_at(time).map(|(x, y)| CommandValue::Vector(x, y))
+            }
+            Command::Colour(t) => t.value_at(time).map(CommandValue::Colour),
+            Command::Parameter(t) => t.value_at(time).map(CommandValue::Parameter),
+            Command::Loop(_) | Command::Trigger(_) => None,
+        }
+    }
+}
+
+fn parse_layer(s: &str) -> Result<Layer> {
+    match s {
+        "0" | "Background" => Ok(Layer::Background),
+        "1" | "Fail" => Ok(Layer::Fail),
+        "2" | "Pass" => Ok(Layer::Pass),
+        "3" | "Foreground" => Ok(Layer::Foreground),
+        "4" | "Overlay" => Ok(Layer::Overlay),
+        _ => Err(Error::Message("Unknown storyboard layer")),
+    }
+}
+
+fn parse_origin(s: &str) -> Result<Origin> {
+    match s {
+        "0" | "TopLeft" => Ok(Origin::TopLeft),
+        "1" | "Centre" => Ok(Origin::Centre),
+        "2" | "CentreLeft" => Ok(Origin::CentreLeft),
+        "3" | "TopRight" => Ok(Origin::TopRight),
+        "4" | "BottomCentre" => Ok(Origin::BottomCentre),
+        "5" | "TopCentre" => Ok(Origin::TopCentre),
+        "6" | "Custom" => Ok(Origin::Custom),
+        "7" | "CentreRight" => Ok(Origin::CentreRight),
+        "8" | "BottomLeft" => Ok(Origin::BottomLeft),
+        "9" | "BottomRight" => Ok(Origin::BottomRight),
+        _ => Err(Error::Message("Unknown storyboard origin")),
+    }
+}
+
+fn parse_loop_type(s: &str) -> Result<LoopType> {
+    match s {
+        "0" | "LoopForever" => Ok(LoopType::LoopForever),
+        "1" | "LoopOnce" => Ok(LoopType::LoopOnce),
+        _ => Err(Error::Message("Unknown animation loop type")),
+    }
+}
+
+fn parse_easing(s: &str) -> Result<Easing> {
+    parse_num(s).and_then(|n| Easing::from_id(n).ok_or(Error::Message("Unknown easing")))
+}
+
+fn parse_parameter(s: &str) -> Result<Parameter> {
+    match s {
+        "H" => Ok(Parameter::FlipHorizontal),
+        "V" => Ok(Parameter::FlipVertical),
+        "A" => Ok(Parameter::AdditiveBlending),
+        _ => Err(Error::Message("Unknown storyboard parameter")),
+    }
+}
+
+/// Paths in events are usually quoted, but don't have to be.
+fn parse_path(s: &str) -> Result<String> {
+    Ok(s.trim_matches('"').to_string())
+}
+
+/// Read the field at `i`, which may be missing from the end of the line.
+fn optional_field<T, F>(fields: &[&str], i: usize, f: F, default: T) -> Result<T>
+where
+    F: Fn(&str) -> Result<T>,
+{
+    match fields.get(i) {
+        Some(s) if !s.is_empty() => f(s),
+        _ => Ok(default),
+    }
+}
+
+fn field<T, F>(fields: &[&str], i: usize, f: F) -> Result<T>
+where
+    F: Fn(&str) -> Result<T>,
+{
+    fields.get(i)
+        .ok_or(Error::Message("Missing field in event"))
+        .and_then(|s| f(s))
+}
+
+/// A top level event line.
+enum EventLine {
+    Background(Background),
+    Video(Video),
+    Break(Break),
+    Colour(ColourTransform),
+    Object(StoryboardObject),
+    Sample(StoryboardSample),
+}
+
+fn parse_sprite(fields: &[&str]) -> Result<Sprite> {
+    Ok(Sprite {
+        layer: field(fields, 1, parse_layer)?,
+        origin: field(fields, 2, parse_origin)?,
+        filepath: field(fields, 3, parse_path)?,
+        x: field(fields, 4, parse_num)?,
+        y: field(fields, 5, parse_num)?,
+        commands: Vec::new(),
+    })
+}
+
+fn parse_event(event_type: EventType, fields: &[&str]) -> Result<EventLine> {
+    match event_type {
+        EventType::Background => Ok(EventLine::Background(Background {
+            filename: field(fields, 2, parse_path)?,
+            x_offset: optional_field(fields, 3, parse_num, 0)?,
+            y_offset: optional_field(fields, 4, parse_num, 0)?,
+        })),
+        EventType::Video => Ok(EventLine::Video(Video {
+            start_time: field(fields, 1, parse_time)?,
+            filename: field(fields, 2, parse_path)?,
+            x_offset: optional_field(fields, 3, parse_num, 0)?,
+            y_offset: optional_field(fields, 4, parse_num, 0)?,
+        })),
+        EventType::Break => Ok(EventLine::Break(Break {
+            start_time: field(fields, 1, parse_time)?,
+            end_time: field(fields, 2, parse_time)?,
+        })),
+        EventType::Colour => Ok(EventLine::Colour(ColourTransform {
+            time: field(fields, 1, parse_time)?,
+            colour: fields.get(2..5)
+                .ok_or(Error::Message("Missing field in event"))
+                .and_then(parse_colour_value)?,
+        })),
+        EventType::Sprite => Ok(EventLine::Object(StoryboardObject::Sprite(parse_sprite(fields)?))),
+        EventType::Animation => Ok(EventLine::Object(StoryboardObject::Animation(Animation {
+            sprite: parse_sprite(fields)?,
+            frame_count: field(fields, 6, parse_num)?,
+            frame_delay: field(fields, 7, parse_num)?,
+            loop_type: optional_field(fields, 8, parse_loop_type, LoopType::LoopForever)?,
+        }))),
+        EventType::Sample => {
+            let layer = field(fields, 2, parse_layer)?;
+            if layer == Layer::Overlay {
+                return Err(Error::Message("Samples can't be on the overlay layer"));
+            }
+
+            Ok(EventLine::Sample(StoryboardSample {
+                time: field(fields, 1, parse_time)?,
+                layer,
+                filepath: field(fields, 3, parse_path)?,
+                volume: optional_field(fields, 4, parse_num, 100)?,
+            }))
+        }
+    }
+}
+
+fn parse_pair(fields: &[&str]) -> Result<(f32, f32)> {
+    Ok((parse_num(fields[0])?, parse_num(fields[1])?))
+}
+
+fn parse_colour_value(fields: &[&str]) -> Result<Colour> {
+    Ok(Colour(parse_num(fields[0])?, parse_num(fields[1])?, parse_num(fields[2])?))
+}
+
+/// Parse the fields of a command after its name, where each value takes up
+/// `arity` fields.
+fn parse_tween<T, F>(fields: &[&str], arity: usize, f: F) -> Result<Tween<T>>
+where
+    F: Fn(&[&str]) -> Result<T>,
+{
+    let values = fields.get(4..).unwrap_or(&[]);
+    if values.is_empty() || values.len() % arity != 0 {
+        return Err(Error::Message("Wrong number of values in command"));
+    }
+
+    Ok(Tween {
+        easing: field(fields, 1, parse_easing)?,
+        start_time: field(fields, 2, parse_time)?,
+        end_time: optional_field(fields, 3, |s| parse_time(s).map(Some), None)?,
+        values: values.chunks(arity).map(f).collect::<Result<_>>()?,
+    })
+}
+
+fn parse_command(fields: &[&str]) -> Result<Command> {
+    match fields[0] {
+        "F" => parse_tween(fields, 1, |v| parse_num(v[0])).map(Command::Fade),
+        "M" => parse_tween(fields, 2, parse_pair).map(Command::Move),
+        "MX" => parse_tween(fields, 1, |v| parse_num(v[0])).map(Command::MoveX),
+        "MY" => parse_tween(fields, 1, |v| parse_num(v[0])).map(Command::MoveY),
+        "S" => parse_tween(fields, 1, |v| parse_num(v[0])).map(Command::Scale),
+        "V" => parse_tween(fields, 2, parse_pair).map(Command::VectorScale),
+        "R" => parse_tween(fields, 1, |v| parse_num(v[0])).map(Command::Rotate),
+        "C" => parse_tween(fields, 3, parse_colour_value).map(Command::Colour),
+        "P" => parse_tween(fields, 1, |v| parse_parameter(v[0])).map(Command::Parameter),
+        "L" => Ok(Command::Loop(Loop {
+            start_time: field(fields, 1, parse_time)?,
+            loop_count: field(fields, 2, parse_num)?,
+            commands: Vec::new(),
+        })),
+        "T" => Ok(Command::Trigger(Trigger {
+            trigger: field(fields, 1, parse_string)?,
+            start_time: field(fields, 2, parse_time)?,
+            end_time: field(fields, 3, parse_time)?,
+            group_number: optional_field(fields, 4, |s| parse_num(s).map(Some), None)?,
+            commands: Vec::new(),
+        })),
+        _ => Err(Error::Message("Unknown storyboard command")),
+    }
+}
+
+/// Add a command to the last storyboard object, or to its last loop or
+/// trigger if the command is nested.
+fn add_command(storyboard: &mut Storyboard, depth: usize, command: Command) -> Result<()> {
+    let sprite = storyboard.objects
+        .last_mut()
+        .ok_or(Error::Message("Command outside of a storyboard object"))?
+        .sprite_mut();
+
+    match depth {
+        1 => sprite.commands.push(command),
+        2 => match sprite.commands.last_mut() {
+            Some(Command::Loop(l)) => l.commands.push(command),
+            Some(Command::Trigger(t)) => t.commands.push(command),
+            _ => return Err(Error::Message("Nested command outside of a loop or trigger")),
+        },
+        _ => return Err(Error::Message("Command nested too deeply")),
+    }
+
+    Ok(())
+}
+
+/// Replace every variable in the line by its value, longest names first so
+/// that `$ab` isn't mistaken for `$a` followed by `b`.
+fn substitute<'a>(line: &'a str, variables: &[Variable]) -> Cow<'a, str> {
+    if variables.is_empty() || !line.contains('$') {
+        return Cow::Borrowed(line);
+    }
+
+    let mut sorted: Vec<&Variable> = variables.iter().collect();
+    sorted.sort_by_key(|v| Reverse(v.name.len()));
+
+    let mut line = line.to_string();
+    for variable in sorted {
+        line = line.replace(&variable.name, &variable.value);
+    }
+
+    Cow::Owned(line)
+}
+
+impl Beatmap {
+    /// Every file the beatmap uses: its audio, background, videos, custom
+    /// hitsound files set on hit objects and everything referenced by the
+    /// storyboard. Sorted and without duplicates, so that cleanup tools can
+    /// compare it against the contents of the beatmap's folder.
+    pub fn referenced_files(&self) -> Vec<String> {
+        let mut files: BTreeSet<String> = self.events.storyboard
+            .referenced_files()
+            .into_iter()
+            .collect();
+
+        if !self.general.audio_filename.is_empty() {
+            files.insert(self.general.audio_filename.clone());
+        }
+        if let Some(ref background) = self.events.background {
+            files.insert(background.filename.clone());
+        }
+        files.extend(self.events.videos.iter().map(|v| v.filename.clone()));
+
+        for obj in &self.hit_objects {
+            let extras = match obj {
+                HitObject::HitCircle(o) => &o.extras,
+                HitObject::Slider(o) => &o.extras,
+                HitObject::Spinner(o) => &o.extras,
+                HitObject::HoldNote(o) => &o.extras,
+            };
+            if !extras.filename.is_empty() {
+                files.insert(extras.filename.clone());
+            }
+        }
+
+        files.into_iter().collect()
+    }
+
+    /// Whether the beatmap has a storyboard of its own. Storyboards shared
+    /// by a whole set live in a separate `.osb` file, which isn't read.
+    pub fn has_storyboard(&self) -> bool {
+        !self.events.storyboard.is_empty()
+    }
+
+    pub fn has_video(&self) -> bool {
+        !self.events.videos.is_empty()
+    }
+
+    /// Whether the storyboard is big enough that clients may want to load
+    /// it ahead of time or skip it on slow machines: more than 10,000
+    /// commands or 1,000 objects.
+    pub fn is_storyboard_heavy(&self) -> bool {
+        let storyboard = &self.events.storyboard;
+        storyboard.command_count() > HEAVY_STORYBOARD_COMMANDS
+            || storyboard.objects.len() > HEAVY_STORYBOARD_OBJECTS
+    }
+}
+
+/// Parse a `$name=value` line of the `[Variables]` section.
+pub fn parse_variable(line: &str) -> Result<Variable> {
+    let mut iter = line.splitn(2, '=');
+    let name = iter.next().unwrap_or("").trim();
+    let value = iter.next().ok_or(Error::Message("Variable is missing a value"))?;
+
+    if !name.starts_with('$') || name.len() < 2 {
+        return Err(Error::Message("Variable names must start with $"));
+    }
+
+    Ok(Variable {
+        name: name.to_string(),
+        value: value.trim().to_string(),
+    })
+}
+
+/// Parse the lines of the `[Events]` section, given with their line index.
+/// Lines that can't be parsed are skipped and reported as warnings.
+pub fn parse_events(lines: &[(usize, &str)], variables: Vec<Variable>) -> (Events, Vec<Warning>) {
+    let mut events = Events::default();
+    let mut warnings = Vec::new();
+
+    for &(i, raw) in lines {
+        let line = substitute(raw, &variables);
+        let depth = line.chars().take_while(|&c| c == ' ' || c == '_').count();
+        let fields: Vec<&str> = line[depth..].split(',').map(|s| s.trim()).collect();
+
+        let result = if depth == 0 {
+            let event_type = match EventType::from_code(fields[0]) {
+                Some(event_type) => event_type,
+                None => {
+                    events.unknown_lines.push(raw.to_string());
+                    warnings.push(Warning {
+                        line: Some(i + 1),
+                        message: format!("Kept event of unknown type {}", fields[0]),
+                    });
+                    continue;
+                }
+            };
+
+            parse_event(event_type, &fields).map(|event| match event {
+                EventLine::Background(b) => events.background = Some(b),
+                EventLine::Video(v) => events.videos.push(v),
+                EventLine::Break(b) => events.breaks.push(b),
+                EventLine::Colour(c) => events.colour_transforms.push(c),
+                EventLine::Object(o) => events.storyboard.objects.push(o),
+                EventLine::Sample(sample) => events.storyboard.samples.push(sample),
+            })
+        } else {
+            parse_command(&fields).and_then(|c| add_command(&mut events.storyboard, depth, c))
+        };
+
+        if let Err(err) = result {
+            warnings.push(Warning {
+                line: Some(i + 1),
+                message: format!("Skipped event: {}", err),
+            });
+        }
+    }
+
+    events.variables = variables;
+    events.raw_lines = lines.iter().map(|&(_, l)| l.to_string()).collect();
+
+    (events, warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_events() {
+        let input = "osu file format v14
+
+[Variables]
+$bg=\"sb/bg.png\"
+$fade=F,0
+
+[Events]
+0,0,\"bg.jpg\",0,0
+Video,500,\"video.mp4\"
+2,1000,2000
+Sprite,Foreground,Centre,$bg,320,240
+ $fade,0,1000,0,1
+ M,1,0,,320,240
+ L,1000,4
+  R,0,0,500,0,3.1415
+Animation,Fail,TopLeft,\"sb/anim.png\",0,0,4,50,LoopOnce
+ T,HitSoundClap,0,10000
+  C,0,0,100,255,0,0,255,255,255
+ P,0,0,,A
+Sprite,Nowhere,Centre,\"x.png\",0,0
+Sample,1500,0,\"sfx/clap.wav\",60
+5,2500,3,\"sfx/kick.wav\"
+Sample,3000,Overlay,\"sfx/none.wav\",100
+";
+
+        let (map, warnings) = parse_beatmap_with_options(input, &Default::default()).unwrap();
+        let events = &map.events;
+
+        assert_eq!(events.background.as_ref().unwrap().filename, "bg.jpg");
+        assert_eq!(events.videos[0].start_time, Time(500.0));
+        assert_eq!(events.breaks, vec![Break { start_time: Time(1000.0), end_time: Time(2000.0) }]);
+        assert_eq!(events.variables.len(), 2);
+        assert_eq!(events.raw_lines[3], "Sprite,Foreground,Centre,$bg,320,240");
+        assert_eq!(events.storyboard.objects.len(), 2);
+
+        let sprite = events.storyboard.objects[0].sprite();
+        assert_eq!(sprite.layer, Layer::Foreground);
+        assert_eq!(sprite.filepath, "sb/bg.png");
+        assert_eq!(sprite.commands.len(), 3);
+        assert_eq!(sprite.commands[0], Command::Fade(Tween {
+            easing: Easing::Linear,
+            start_time: Time(0.0),
+            end_time: Some(Time(1000.0)),
+            values: vec![0.0, 1.0],
+        }));
+        match sprite.commands[1] {
+            Command::Move(ref t) => assert_eq!((t.end_time, t.values.len()), (None, 1)),
+            ref other => panic!("expected a move command, got {:?}", other),
+        }
+        match sprite.commands[2] {
+            Command::Loop(ref l) => assert_eq!((l.loop_count, l.commands.len()), (4, 1)),
+            ref other => panic!("expected a loop, got {:?}", other),
+        }
+
+        match events.storyboard.objects[1] {
+            StoryboardObject::Animation(ref a) => {
+                assert_eq!((a.frame_count, a.loop_type), (4, LoopType::LoopOnce));
+                assert_eq!(a.sprite.commands.len(), 2);
+            }
+            ref other => panic!("expected an animation, got {:?}", other),
+        }
+
+        assert_eq!(events.storyboard.samples, vec![
+            StoryboardSample {
+                time: Time(1500.0),
+                layer: Layer::Background,
+                filepath: "sfx/clap.wav".to_string(),
+                volume: 60,
+            },
+            StoryboardSample {
+                time: Time(2500.0),
+                layer: Layer::Foreground,
+                filepath: "sfx/kick.wav".to_string(),
+                volume: 100,
+            },
+        ]);
+
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].line, Some(20));
+        assert_eq!(warnings[1].line, Some(23));
+    }
+
+    #[test]
+    fn test_legacy_events() {
+        let input = "osu file format v5\n\n[Events]\n3,100,163,162,255\nColour,2000,0,0,0\n\
+                     9,500,\"unknown\"\n3,3000,1,2\n";
+        let (map, warnings) = parse_beatmap_with_options(input, &Default::default()).unwrap();
+        let events = &map.events;
+
+        assert_eq!(events.colour_transforms, vec![
+            ColourTransform { time: Time(100.0), colour: Colour(163, 162, 255) },
+            ColourTransform { time: Time(2000.0), colour: Colour(0, 0, 0) },
+        ]);
+        assert_eq!(events.unknown_lines, vec!["9,500,\"unknown\""]);
+        assert_eq!(warnings.iter().map(|w| w.line).collect::<Vec<_>>(), vec![Some(6), Some(7)]);
+
+        assert_eq!(
+            events.to_osu_string(),
+            "[Events]\n3,100,163,162,255\n3,2000,0,0,0\n9,500,\"unknown\""
+        );
+    }
+
+    #[test]
+    fn test_easing() {
+        for &easing in Easing::ALL.iter() {
+            assert_eq!(Easing::from_id(easing as i32), Some(easing));
+            assert!(easing.evaluate(0.0).abs() < 1e-3, "{:?}", easing);
+            assert!((easing.evaluate(1.0) - 1.0).abs() < 1e-3, "{:?}", easing);
+        }
+        assert_eq!(Easing::from_id(35), None);
+
+        assert_eq!(Easing::QuadIn.evaluate(0.5), 0.25);
+        assert_eq!(Easing::Out.evaluate(0.5), 0.75);
+        assert!(Easing::BackIn.evaluate(0.2) < 0.0);
+        assert!(Easing::ElasticOut.evaluate(0.2) > 1.0);
+    }
+
+    #[test]
+    fn test_value_at() {
+        let fade = Tween {
+            easing: Easing::Linear,
+            start_time: Time(1000.0),
+            end_time: Some(Time(2000.0)),
+            values: vec![0.0, 1.0, 0.5],
+        };
+
+        assert_eq!(fade.value_at(Time(500.0)), None);
+        assert_eq!(fade.value_at(Time(1000.0)), Some(0.0));
+        assert_eq!(fade.value_at(Time(1500.0)), Some(0.5));
+        assert_eq!(fade.value_at(Time(2500.0)), Some(0.75));
+        assert_eq!(fade.value_at(Time(5000.0)), Some(0.5));
+
+        let colour = Command::Colour(Tween {
+            easing: Easing::QuadIn,
+            start_time: Time(0.0),
+            end_time: Some(Time(100.0)),
+            values: vec![Colour(0, 0, 0), Colour(200, 100, 0)],
+        });
+        assert_eq!(colour.value_at(Time(50.0)), Some(CommandValue::Colour(Colour(50, 25, 0))));
+
+        let instant = Command::MoveX(Tween {
+            easing: Easing::Linear,
+            start_time: Time(100.0),
+            end_time: None,
+            values: vec![10.0, 20.0],
+        });
+        assert_eq!(instant.value_at(Time(100.0)), Some(CommandValue::Float(20.0)));
+    }
+
+    #[test]
+    fn test_referenced_files() {
+        let input = "osu file format v14
+
+[General]
+AudioFilename: audio.mp3
+
+[Events]
+0,0,\"bg.jpg\",0,0
+Video,0,\"video.mp4\"
+Sprite,Background,Centre,\"sb\\star.png\",0,0
+Animation,Foreground,Centre,\"sb/dots.v2.png\",0,0,3,100
+Sprite,Foreground,Centre,\"sb\\star.png\",0,0
+Sample,0,0,\"sfx/hit.ogg\",100
+
+[HitObjects]
+256,192,1000,1,0,0:0:0:0:custom.wav
+256,192,2000,1,0,0:0:0:0:
+";
+
+        let map = parse_beatmap(input).unwrap();
+
+        assert!(map.has_video());
+        assert_eq!(map.events.storyboard.referenced_files(), vec![
+            "sb/dots.v20.png",
+            "sb/dots.v21.png",
+            "sb/dots.v22.png",
+            "sb\\star.png",
+            "sfx/hit.ogg",
+        ]);
+        assert_eq!(map.referenced_files(), vec![
+            "audio.mp3",
+            "bg.jpg",
+            "custom.wav",
+            "sb/dots.v20.png",
+            "sb/dots.v21.png",
+            "sb/dots.v22.png",
+            "sb\\star.png",
+            "sfx/hit.ogg",
+            "video.mp4",
+        ]);
+    }
+
+    #[test]
+    fn test_storyboard_order() {
+        let input = "osu file format v14
+
+[Events]
+Sprite,Foreground,Centre,\"a.png\",0,0
+ F,0,1000,2000,0,1
+Sprite,Background,Centre,\"b.png\",0,0
+ F,0,0,500,1,0,1
+Sprite,Foreground,Centre,\"c.png\",0,0
+ L,3000,2
+  S,0,0,1000,1
+  S,0,1000,2000,2
+Sprite,Overlay,Centre,\"d.png\",0,0
+";
+
+        let map = parse_beatmap(input).unwrap();
+        let storyboard = &map.events.storyboard;
+        let names = |objects: Vec<&StoryboardObject>| -> Vec<String> {
+            objects.iter().map(|o| o.sprite().filepath.clone()).collect()
+        };
+
+        assert_eq!(names(storyboard.draw_order()), vec!["b.png", "a.png", "c.png", "d.png"]);
+        assert_eq!(names(storyboard.layer(Layer::Foreground).collect()), vec!["a.png", "c.png"]);
+
+        assert_eq!(storyboard.objects[1].sprite().end_time(), Some(Time(1000.0)));
+        assert_eq!(storyboard.objects[2].sprite().start_time(), Some(Time(3000.0)));
+        assert_eq!(storyboard.objects[2].sprite().end_time(), Some(Time(7000.0)));
+
+        assert_eq!(names(storyboard.active_at(Time(750.0))), vec!["b.png"]);
+        assert_eq!(names(storyboard.active_at(Time(1500.0))), vec!["a.png"]);
+        assert_eq!(names(storyboard.active_at(Time(6000.0))), vec!["c.png"]);
+        assert!(storyboard.active_at(Time(8000.0)).is_empty());
+
+        assert_eq!(storyboard.command_count(), 5);
+        assert!(map.has_storyboard());
+        assert!(!map.is_storyboard_heavy());
+        assert!(!map.has_video());
+    }
+}
@@ -0,0 +1,86 @@
+use super::*;
+
+/// 64-bit FNV-1a, which unlike the standard library's hasher is stable
+/// across Rust versions, and so safe to persist.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+impl Beatmap {
+    /// A hash of the parsed beatmap rather than the text it was read from,
+    /// for finding copies of a beatmap among files that only differ in
+    /// whitespace, comments, line endings, key order or the way numbers are
+    /// written. Which keys were present in the file is ignored, as is the
+    /// text of the `[Events]` section, leaving only its parsed events.
+    ///
+    /// The hash is stable across runs and Rust versions, so it can be
+    /// stored, but may change when new versions of this crate parse more of
+    /// the file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use osuparse::parse_beatmap;
+    ///
+    /// let a = parse_beatmap("osu file format v14\n\n[Difficulty]\nApproachRate:9\nCircleSize:4\n").unwrap();
+    /// let b = parse_beatmap("osu file format v14\r\n\r\n[Difficulty]\r\nCircleSize : 4.0\r\nApproachRate : 9 // AR\r\n").unwrap();
+    ///
+    /// assert_eq!(a.semantic_hash(), b.semantic_hash());
+    /// ```
+    pub fn semantic_hash(&self) -> u64 {
+        let mut canonical = self.clone();
+        canonical.present_keys = PresentKeys::default();
+        canonical.events.raw_lines.clear();
+
+        fnv1a(canonical.to_osu_string().as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_semantic_hash() {
+        let a = parse_beatmap("osu file format v14
+
+[General]
+AudioFilename: audio.mp3
+Mode: 0
+
+[Events]
+0,0,\"bg.jpg\",0,0
+
+[TimingPoints]
+0,500,4,2,0,100,1,0
+
+[HitObjects]
+256,192,1000,1,0
+").unwrap();
+
+        let b = parse_beatmap("osu file format v14\r
+\r
+[General]\r
+Mode:0\r
+AudioFilename:audio.mp3\r
+\r
+[Events]\r
+//Background and Video events\r
+0, 0, \"bg.jpg\", 0, 0\r
+\r
+[TimingPoints]\r
+0,500.00,4,2,0,100,1,0\r
+\r
+[HitObjects]\r
+256,192,1000,1,0\r
+").unwrap();
+
+        assert_eq!(a.semantic_hash(), b.semantic_hash());
+
+        let mut c = a.clone();
+        c.timing_points[0].ms_per_beat = 400.0;
+        assert_ne!(a.semantic_hash(), c.semantic_hash());
+    }
+}
@@ -0,0 +1,634 @@
+//! Working out which sound samples a hit object plays.
+//!
+//! A hit object's sounds depend on its own hitsound flags and
+//! [`HitObjectExtras`](../struct.HitObjectExtras.html), on the timing point
+//! in effect when it is hit and on the beatmap's default sample set.
+//! [`HitsoundResolver`](struct.HitsoundResolver.html) combines these the way
+//! the game does.
+
+use super::*;
+
+/// A set of hitsound samples, chosen per timing point or per hit object.
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub enum SampleBank {
+    Normal,
+    Soft,
+    Drum,
+}
+
+impl SampleBank {
+    /// The bank with the given number, as used in timing points and hit
+    /// object extras. `0` means the bank is inherited, so has no bank of its
+    /// own.
+    pub fn from_id(id: i32) -> Option<SampleBank> {
+        match id {
+            1 => Some(SampleBank::Normal),
+            2 => Some(SampleBank::Soft),
+            3 => Some(SampleBank::Drum),
+            _ => None,
+        }
+    }
+
+    /// The bank named in [`GeneralSection::sample_set`](../struct.GeneralSection.html#structfield.sample_set).
+    pub fn from_name(name: &str) -> Option<SampleBank> {
+        if unicase::eq(name, "Normal") {
+            Some(SampleBank::Normal)
+        } else if unicase::eq(name, "Soft") {
+            Some(SampleBank::Soft)
+        } else if unicase::eq(name, "Drum") {
+            Some(SampleBank::Drum)
+        } else {
+            None
+        }
+    }
+
+    /// The prefix of the bank's sample files, e.g. `soft`.
+    pub fn name(self) -> &'static str {
+        match self {
+            SampleBank::Normal => "normal",
+            SampleBank::Soft => "soft",
+            SampleBank::Drum => "drum",
+        }
+    }
+}
+
+/// One of the four sounds a hit object can play.
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub enum HitSound {
+    Normal,
+    Whistle,
+    Finish,
+    Clap,
+}
+
+impl HitSound {
+    /// The sounds flagged in a `hitsound` value. The normal sound always
+    /// plays, whether its bit is set or not.
+    pub fn from_bits(bits: i32) -> Vec<HitSound> {
+        let mut sounds = vec![HitSound::Normal];
+        for &(bit, sound) in &[(2, HitSound::Whistle), (4, HitSound::Finish), (8, HitSound::Clap)] {
+            if bits & bit != 0 {
+                sounds.push(sound);
+            }
+        }
+        sounds
+    }
+
+    /// The name of the sound in sample files, e.g. `hitclap`.
+    pub fn name(self) -> &'static str {
+        match self {
+            HitSound::Normal => "hitnormal",
+            HitSound::Whistle => "hitwhistle",
+            HitSound::Finish => "hitfinish",
+            HitSound::Clap => "hitclap",
+        }
+    }
+}
+
+/// A sample played by a hit object.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct HitSample {
+    /// The file to play, such as `soft-hitnormal2.wav`, or the hit object's
+    /// custom [`filename`](../struct.HitObjectExtras.html#structfield.filename).
+    pub filename: String,
+    /// Volume from 0 to 100.
+    pub volume: i32,
+    /// The custom sample index. With index `0` the skin's sample is always
+    /// used; otherwise a file of that name in the beatmap's folder takes
+    /// precedence over the skin's.
+    pub custom_index: i32,
+}
+
+/// Resolves the samples played by hit objects of a beatmap, following the
+/// game's fallback rules:
+///
+/// * An object's sample set, when `0`, falls back to the timing point's,
+///   and then to the beatmap's default set.
+/// * The addition set, used by the whistle, finish and clap, falls back to
+///   the object's sample set.
+/// * The custom index and volume fall back to the timing point's.
+/// * A custom filename replaces every other sample.
+///
+/// ```
+/// use osuparse::hitsounds::HitsoundResolver;
+///
+/// let input = "osu file format v14
+///
+/// [General]
+/// SampleSet: Soft
+///
+/// [TimingPoints]
+/// 0,500,4,0,2,70,1,0
+///
+/// [HitObjects]
+/// 256,192,1000,1,8,0:3:0:0:
+/// ";
+///
+/// let map = osuparse::parse_beatmap(input).unwrap();
+/// let resolver = HitsoundResolver::new(&map);
+/// let files: Vec<String> = resolver
+///     .samples(&map.hit_objects[0])
+///     .into_iter()
+///     .map(|s| s.filename)
+///     .collect();
+///
+/// assert_eq!(files, vec!["soft-hitnormal2.wav", "drum-hitclap2.wav"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct HitsoundResolver<'a> {
+    map: &'a Beatmap,
+    default_bank: SampleBank,
+}
+
+impl<'a> HitsoundResolver<'a> {
+    pub fn new(map: &'a Beatmap) -> Self {
+        HitsoundResolver {
+            map,
+            default_bank: SampleBank::from_name(&map.general.sample_set).unwrap_or(SampleBank::Normal),
+        }
+    }
+
+    /// The samples played when the object is hit. For sliders these are the
+    /// samples of the slider's head, and for spinners those played when the
+    /// spinner ends.
+    pub fn samples(&self, obj: &HitObject) -> Vec<HitSample> {
+        match obj {
+            HitObject::HitCircle(o) => {
+                self.resolve(o.time, o.hitsound, o.extras.sample_set, o.extras.addition_set, &o.extras)
+            }
+            HitObject::Slider(o) => self.edge_samples(o, 0, o.time),
+            HitObject::Spinner(o) => {
+                self.resolve(o.end_time, o.hitsound, o.extras.sample_set, o.extras.addition_set, &o.extras)
+            }
+            HitObject::HoldNote(o) => {
+                self.resolve(o.time, o.hitsound, o.extras.sample_set, o.extras.addition_set, &o.extras)
+            }
+        }
+    }
+
+    /// The samples played on one of a slider's edges, counting from `0` for
+    /// its head. The edge is reached at `time`, which decides the timing
+    /// point used. Edges without their own hitsounds use the slider's.
+    pub fn edge_samples(&self, slider: &Slider, edge: usize, time: Time) -> Vec<HitSample> {
+        let hitsound = slider.edge_hitsounds.get(edge).cloned().unwrap_or(slider.hitsound);
+        let sets = slider.edge_additions.get(edge).cloned().unwrap_or(EdgeSampleSet {
+            sample_set: slider.extras.sample_set,
+            addition_set: slider.extras.addition_set,
+        });
+
+        self.resolve(time, hitsound, sets.sample_set, sets.addition_set, &slider.extras)
+    }
+
+    fn resolve(
+        &self,
+        time: Time,
+        hitsound: i32,
+        sample_set: i32,
+        addition_set: i32,
+        extras: &HitObjectExtras,
+    ) -> Vec<HitSample> {
+        let point = self.map.timing_point_at(time);
+
+        let custom_index = match (extras.custom_index, point) {
+            (0, Some(p)) => p.sample_index,
+            (index, _) => index,
+        };
+        let volume = effective_volume(extras, point);
+
+        if !extras.filename.is_empty() {
+            return vec![HitSample { filename: extras.filename.clone(), volume, custom_index }];
+        }
+
+        let normal_bank = SampleBank::from_id(sample_set)
+            .or_else(|| point.and_then(|p| SampleBank::from_id(p.sample_set)))
+            .unwrap_or(self.default_bank);
+        let addition_bank = SampleBank::from_id(addition_set).unwrap_or(normal_bank);
+
+        HitSound::from_bits(hitsound)
+            .into_iter()
+            .map(|sound| {
+                let bank = if sound == HitSound::Normal { normal_bank } else { addition_bank };
+                // Index 1 is the beatmap's unnumbered sample
+                let suffix = if custom_index > 1 { custom_index.to_string() } else { String::new() };
+
+                HitSample {
+                    filename: format!("{}-{}{}.wav", bank.name(), sound.name(), suffix),
+                    volume,
+                    custom_index,
+                }
+            })
+            .collect()
+    }
+}
+
+/// The volume of a sample: the object's own, unless it is `0`, in which
+/// case the timing point's.
+fn effective_volume(extras: &HitObjectExtras, point: Option<&TimingPoint>) -> i32 {
+    match (extras.sample_volume, point) {
+        (0, Some(p)) => p.volume,
+        (0, None) => 100,
+        (volume, _) => volume,
+    }
+}
+
+/// A change in hitsound volume, which holds until the next one.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct VolumeStep {
+    pub time: Time,
+    /// Volume from 0 to 100.
+    pub volume: i32,
+}
+
+impl Beatmap {
+    /// The volume hitsounds are played at over the course of the beatmap, as
+    /// a step function. Timing points set the volume, and hit objects with
+    /// their own [`sample_volume`](../struct.HitObjectExtras.html#structfield.sample_volume)
+    /// override it from the moment they are hit until the next object or
+    /// timing point. Consecutive steps with the same volume are merged.
+    ///
+    /// Objects are taken at the time their samples play: spinners when they
+    /// end, everything else when they start.
+    pub fn volume_envelope(&self) -> Vec<VolumeStep> {
+        let mut changes: Vec<VolumeStep> = self.timing_points
+            .iter()
+            .map(|p| VolumeStep { time: p.offset, volume: p.volume })
+            .collect();
+
+        changes.extend(self.hit_objects.iter().map(|obj| {
+            let (time, extras) = match obj {
+                HitObject::HitCircle(o) => (o.time, &o.extras),
+                HitObject::Slider(o) => (o.time, &o.extras),
+                HitObject::Spinner(o) => (o.end_time, &o.extras),
+                HitObject::HoldNote(o) => (o.time, &o.extras),
+            };
+
+            VolumeStep { time, volume: effective_volume(extras, self.timing_point_at(time)) }
+        }));
+
+        // Sorting is stable, so an object's volume wins over a timing point
+        // at the same time
+        changes.sort_by_key(|c| c.time);
+
+        let mut steps: Vec<VolumeStep> = Vec::new();
+        for change in changes {
+            match steps.last_mut() {
+                Some(last) if last.time == change.time => last.volume = change.volume,
+                Some(last) if last.volume == change.volume => continue,
+                _ => steps.push(change),
+            }
+        }
+        // Replacing a step's volume may leave it equal to the one before
+        steps.dedup_by(|b, a| a.volume == b.volume);
+
+        steps
+    }
+
+    /// Copies the hitsounds of `source`, usually another difficulty of the
+    /// same set, onto the objects of this beatmap. Every point where an
+    /// object plays sounds — a circle, each edge of a slider, the end of a
+    /// spinner or the start of a hold note — takes the sounds of the
+    /// closest such point of `source` at most `tolerance` milliseconds
+    /// away: its hitsound flags, sample sets, custom index, volume and
+    /// custom filename. Returns how many points had a match. Points without
+    /// one are left as they are.
+    ///
+    /// Sample sets, custom indices and volumes that `source` takes from its
+    /// timing points are written into the objects where this beatmap's
+    /// timing points differ, so that the copies sound the same. Sliders
+    /// share their custom index, volume and filename between their edges,
+    /// and take the ones of their head. The sound of slider bodies isn't
+    /// copied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use osuparse::{parse_beatmap, Time};
+    ///
+    /// let source = parse_beatmap("osu file format v14
+    ///
+    /// [TimingPoints]
+    /// 0,500,4,2,0,60,1,0
+    ///
+    /// [HitObjects]
+    /// 256,192,1000,1,2,0:0:0:0:
+    /// 256,192,1500,1,8,0:3:0:0:
+    /// ").unwrap();
+    ///
+    /// let mut map = parse_beatmap("osu file format v14
+    ///
+    /// [TimingPoints]
+    /// 0,500,4,1,0,60,1,0
+    ///
+    /// [HitObjects]
+    /// 100,100,1002,1,0,0:0:0:0:
+    /// 100,100,2000,1,0,0:0:0:0:
+    /// ").unwrap();
+    ///
+    /// assert_eq!(map.copy_hitsounds(&source, Time(5.0)), 1);
+    /// match map.hit_objects[0] {
+    ///     osuparse::HitObject::HitCircle(ref c) => {
+    ///         assert_eq!((c.hitsound, c.extras.sample_set), (2, 2));
+    ///     }
+    ///     _ => unreachable!(),
+    /// }
+    /// ```
+    pub fn copy_hitsounds(&mut self, source: &Beatmap, tolerance: Time) -> usize {
+        let mut sounds = Vec::new();
+        let index = source.timing_index();
+        for obj in &source.hit_objects {
+            for (edge, time) in sound_times(obj, &index).into_iter().enumerate() {
+                let (hitsound, sets, extras) = object_sound(obj, edge);
+                let point = index.point_at(time);
+                sounds.push(Sound::inherit(time, hitsound, sets, extras, point));
+            }
+        }
+        sounds.sort_by_key(|s| s.time);
+
+        // Find every match first, as the timing index borrows the beatmap
+        let mut matches = Vec::new();
+        let index = self.timing_index();
+        for (i, obj) in self.hit_objects.iter().enumerate() {
+            for (edge, time) in sound_times(obj, &index).into_iter().enumerate() {
+                if let Some(sound) = closest_sound(&sounds, time, tolerance) {
+                    matches.push((i, edge, sound.relative_to(index.point_at(time))));
+                }
+            }
+        }
+
+        let count = matches.len();
+        for (i, edge, sound) in matches {
+            match self.hit_objects[i] {
+                HitObject::Slider(ref mut o) => {
+                    o.normalize_edges();
+                    o.edge_hitsounds[edge] = sound.hitsound;
+                    o.edge_additions[edge] = sound.sets;
+                    if edge == 0 {
+                        o.extras = HitObjectExtras {
+                            sample_set: o.extras.sample_set,
+                            addition_set: o.extras.addition_set,
+                            ..sound.extras
+                        };
+                    }
+                }
+                HitObject::HitCircle(ref mut o) => sound.apply(&mut o.hitsound, &mut o.extras),
+                HitObject::Spinner(ref mut o) => sound.apply(&mut o.hitsound, &mut o.extras),
+                HitObject::HoldNote(ref mut o) => sound.apply(&mut o.hitsound, &mut o.extras),
+            }
+        }
+
+        count
+    }
+}
+
+/// The sounds played at one point in time by a hit object, as copied by
+/// [`Beatmap::copy_hitsounds`](../struct.Beatmap.html#method.copy_hitsounds).
+#[derive(Debug, Clone)]
+struct Sound {
+    time: Time,
+    hitsound: i32,
+    sets: EdgeSampleSet,
+    /// The custom index, volume and filename. The sample sets are in `sets`.
+    extras: HitObjectExtras,
+}
+
+impl Sound {
+    /// The sound with the values it takes from `point` filled in.
+    fn inherit(
+        time: Time,
+        hitsound: i32,
+        mut sets: EdgeSampleSet,
+        mut extras: HitObjectExtras,
+        point: Option<&TimingPoint>,
+    ) -> Sound {
+        if let Some(point) = point {
+            if sets.sample_set == 0 {
+                sets.sample_set = point.sample_set;
+            }
+            if extras.custom_index == 0 {
+                extras.custom_index = point.sample_index;
+            }
+            extras.sample_volume = effective_volume(&extras, Some(point));
+        }
+
+        Sound { time, hitsound, sets, extras }
+    }
+
+    /// The sound with the values that `point` already gives left for it to
+    /// give.
+    fn relative_to(&self, point: Option<&TimingPoint>) -> Sound {
+        let mut sound = self.clone();
+        if let Some(point) = point {
+            if sound.sets.sample_set == point.sample_set {
+                sound.sets.sample_set = 0;
+            }
+            if sound.extras.custom_index == point.sample_index {
+                sound.extras.custom_index = 0;
+            }
+            if sound.extras.sample_volume == point.volume {
+                sound.extras.sample_volume = 0;
+            }
+        }
+        sound
+    }
+
+    fn apply(self, hitsound: &mut i32, extras: &mut HitObjectExtras) {
+        *hitsound = self.hitsound;
+        *extras = HitObjectExtras {
+            sample_set: self.sets.sample_set,
+            addition_set: self.sets.addition_set,
+            ..self.extras
+        };
+    }
+}
+
+/// The times at which the object plays sounds: each edge of a slider, the
+/// end of a spinner, and the start of anything else.
+fn sound_times(obj: &HitObject, index: &TimingIndex) -> Vec<Time> {
+    match obj {
+        HitObject::Slider(o) => {
+            let edges = o.edge_count();
+            let span = index.slider_duration(o) / (edges - 1) as f64;
+            (0..edges).map(|edge| o.time + span * edge as f64).collect()
+        }
+        HitObject::Spinner(o) => vec![o.end_time],
+        _ => vec![obj.time()],
+    }
+}
+
+/// The hitsound, sample sets and extras of one of the object's sound times.
+fn object_sound(obj: &HitObject, edge: usize) -> (i32, EdgeSampleSet, HitObjectExtras) {
+    let (hitsound, extras) = match obj {
+        HitObject::HitCircle(o) => (o.hitsound, &o.extras),
+        HitObject::Slider(o) => (o.hitsound, &o.extras),
+        HitObject::Spinner(o) => (o.hitsound, &o.extras),
+        HitObject::HoldNote(o) => (o.hitsound, &o.extras),
+    };
+    let sets = EdgeSampleSet { sample_set: extras.sample_set, addition_set: extras.addition_set };
+
+    match obj {
+        HitObject::Slider(o) => (
+            o.edge_hitsounds.get(edge).cloned().unwrap_or(hitsound),
+            o.edge_additions.get(edge).cloned().unwrap_or(sets),
+            extras.clone(),
+        ),
+        _ => (hitsound, sets, extras.clone()),
+    }
+}
+
+/// The sound in `sounds`, sorted by time, closest to `time` and at most
+/// `tolerance` away from it.
+fn closest_sound(sounds: &[Sound], time: Time, tolerance: Time) -> Option<&Sound> {
+    let after = sounds.partition_point(|s| s.time < time);
+    let before = after.checked_sub(1).and_then(|i| sounds.get(i));
+
+    before
+        .into_iter()
+        .chain(sounds.get(after))
+        .filter(|s| (s.time - time).abs() <= tolerance)
+        .min_by_key(|s| (s.time - time).abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_samples() {
+        let input = "osu file format v14
+
+[General]
+SampleSet: Drum
+
+[TimingPoints]
+0,500,4,1,0,60,1,0
+2000,-100,4,0,3,40,0,0
+
+[HitObjects]
+256,192,1000,1,2,0:0:0:0:
+256,192,1500,1,4,2:0:1:80:
+256,192,2500,1,0,0:0:0:0:
+256,192,3000,2,0,B|300:300,2,100,2|0|8,2:0|0:0|0:3,0:0:0:0:
+256,192,4000,1,0,0:0:0:0:hit.wav
+256,192,4500,8,8,6000,3:0:0:0:
+";
+
+        let map = parse_beatmap(input).unwrap();
+        let resolver = HitsoundResolver::new(&map);
+        let files = |samples: Vec<HitSample>| -> Vec<String> {
+            samples.into_iter().map(|s| s.filename).collect()
+        };
+
+        let samples = resolver.samples(&map.hit_objects[0]);
+        assert_eq!(files(samples.clone()), vec!["normal-hitnormal.wav", "normal-hitwhistle.wav"]);
+        assert_eq!((samples[0].volume, samples[0].custom_index), (60, 0));
+
+        let samples = resolver.samples(&map.hit_objects[1]);
+        assert_eq!(files(samples.clone()), vec!["soft-hitnormal.wav", "soft-hitfinish.wav"]);
+        assert_eq!(samples[0].volume, 80);
+
+        let samples = resolver.samples(&map.hit_objects[2]);
+        assert_eq!(files(samples.clone()), vec!["drum-hitnormal3.wav"]);
+        assert_eq!(samples[0].volume, 40);
+
+        let slider = match map.hit_objects[3] {
+            HitObject::Slider(ref s) => s,
+            _ => unreachable!(),
+        };
+        assert_eq!(files(resolver.samples(&map.hit_objects[3])), vec![
+            "soft-hitnormal3.wav",
+            "soft-hitwhistle3.wav",
+        ]);
+        assert_eq!(files(resolver.edge_samples(slider, 2, Time(3400.0))), vec![
+            "drum-hitnormal3.wav",
+            "drum-hitclap3.wav",
+        ]);
+
+        assert_eq!(files(resolver.samples(&map.hit_objects[4])), vec!["hit.wav"]);
+        assert_eq!(files(resolver.samples(&map.hit_objects[5])), vec![
+            "drum-hitnormal3.wav",
+            "drum-hitclap3.wav",
+        ]);
+    }
+
+    #[test]
+    fn test_copy_hitsounds() {
+        let source = parse_beatmap("osu file format v14
+
+[Difficulty]
+SliderMultiplier:1
+
+[TimingPoints]
+0,500,4,2,1,70,1,0
+
+[HitObjects]
+256,192,1000,2,0,L|356:192,2,100,2|0|8,0:0|0:0|3:0,0:0:0:0:
+256,192,3000,8,4,4000,0:0:0:0:
+").unwrap();
+
+        let mut map = parse_beatmap("osu file format v14
+
+[TimingPoints]
+0,500,4,1,1,50,1,0
+
+[HitObjects]
+100,100,1000,1,0,0:0:0:0:
+100,100,1490,1,0,0:0:0:0:
+100,100,2003,1,0,0:0:0:0:
+100,100,3000,1,0,0:0:0:0:
+100,100,3998,1,0,0:0:0:0:
+").unwrap();
+
+        assert_eq!(map.copy_hitsounds(&source, Time(10.0)), 4);
+
+        let sounds: Vec<(i32, i32, i32, i32)> = map.hit_objects
+            .iter()
+            .map(|obj| match obj {
+                HitObject::HitCircle(c) => (c.hitsound, c.extras.sample_set, c.extras.custom_index, c.extras.sample_volume),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(sounds, vec![
+            (2, 2, 0, 70),
+            (0, 2, 0, 70),
+            (8, 3, 0, 70),
+            (0, 0, 0, 0),
+            (4, 2, 0, 70),
+        ]);
+
+        // Copying back gives the source its own sounds
+        let mut copy = source.clone();
+        copy.copy_hitsounds(&map, Time(10.0));
+        assert_eq!(copy.hit_objects, source.hit_objects);
+    }
+
+    #[test]
+    fn test_volume_envelope() {
+        let input = "osu file format v14
+
+[TimingPoints]
+0,500,4,1,0,60,1,0
+1000,-100,4,1,0,60,0,0
+2000,-100,4,1,0,40,0,0
+
+[HitObjects]
+256,192,500,1,0,0:0:0:0:
+256,192,1500,1,0,0:0:0:90:
+256,192,1800,1,0,0:0:0:0:
+256,192,2000,1,0,0:0:0:60:
+256,192,2500,1,0,0:0:0:0:
+";
+
+        let map = parse_beatmap(input).unwrap();
+        let steps: Vec<(f64, i32)> = map.volume_envelope()
+            .into_iter()
+            .map(|s| (s.time.0, s.volume))
+            .collect();
+
+        assert_eq!(steps, vec![
+            (0.0, 60),
+            (1500.0, 90),
+            (1800.0, 60),
+            (2500.0, 40),
+        ]);
+    }
+}
@@ -0,0 +1,73 @@
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+macro_rules! id_type {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+        #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+        pub struct $name(pub i32);
+
+        impl $name {
+            /// Whether the id was given by the osu! website. Beatmaps that
+            /// were never submitted have an id of `0`, or `-1` in older
+            /// files.
+            pub fn is_submitted(self) -> bool {
+                self.0 > 0
+            }
+
+            /// The id, or `None` if the beatmap was never submitted.
+            pub fn get(self) -> Option<i32> {
+                if self.is_submitted() {
+                    Some(self.0)
+                } else {
+                    None
+                }
+            }
+        }
+
+        impl From<i32> for $name {
+            fn from(id: i32) -> Self {
+                $name(id)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseIntError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                s.trim().parse().map($name)
+            }
+        }
+    };
+}
+
+id_type! {
+    /// The id of a single difficulty on the osu! website, as in
+    /// `osu.ppy.sh/b/<id>`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use osuparse::BeatmapId;
+    ///
+    /// let id: BeatmapId = "129891".parse().unwrap();
+    /// assert_eq!(id.get(), Some(129891));
+    /// assert_eq!(id.to_string(), "129891");
+    /// assert!(!BeatmapId(-1).is_submitted());
+    /// ```
+    BeatmapId
+}
+
+id_type! {
+    /// The id of a beatmap set on the osu! website, as in
+    /// `osu.ppy.sh/s/<id>`.
+    BeatmapSetId
+}
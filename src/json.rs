@@ -0,0 +1,38 @@
+use serde_json;
+
+use super::*;
+
+impl Beatmap {
+    /// Serializes the beatmap into its JSON representation.
+    ///
+    /// The representation mirrors the structs in this crate field by field,
+    /// using the Rust field names, and is considered stable across minor
+    /// versions. A few types are encoded specially:
+    ///
+    /// * [`GameMode`](enum.GameMode.html) is one of `"osu"`, `"taiko"`,
+    ///   `"ctb"` or `"mania"`.
+    /// * [`SliderType`](enum.SliderType.html) is one of `"linear"`,
+    ///   `"bezier"`, `"perfect"` or `"catmull"`.
+    /// * [`Colour`](struct.Colour.html) is an `[r, g, b]` array.
+    /// * Each [`HitObject`](enum.HitObject.html) is an object with a `type`
+    ///   field set to `"hit_circle"`, `"slider"`, `"spinner"` or
+    ///   `"hold_note"`, alongside the fields of the corresponding struct.
+    ///
+    /// These names match the dictionaries produced by the Python bindings.
+    ///
+    /// Requires the `json` feature.
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).map_err(Error::Json)
+    }
+
+    /// Same as [`to_json`](#method.to_json), but indented for readability.
+    pub fn to_json_pretty(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(Error::Json)
+    }
+
+    /// Reads a beatmap from the JSON representation described in
+    /// [`to_json`](#method.to_json).
+    pub fn from_json(input: &str) -> Result<Beatmap> {
+        serde_json::from_str(input).map_err(Error::Json)
+    }
+}
@@ -1,23 +1,99 @@
 extern crate unicase;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(feature = "json")]
+extern crate serde_json;
+#[cfg(feature = "tokio")]
+extern crate futures;
+#[cfg(feature = "cache")]
+extern crate bincode;
+#[cfg(feature = "osz")]
+extern crate zip;
+#[cfg(any(feature = "tokio", test))]
+extern crate tokio;
+#[cfg(test)]
+extern crate proptest;
 
 use error::Result;
-pub use error::Error;
+pub use error::{Error, Location, Warning};
 
 #[macro_use]
 mod parse;
 mod error;
+#[cfg(feature = "api")]
+pub mod api;
+pub mod catch;
+pub mod coordinates;
+pub mod curve;
 pub mod deserialize;
+pub mod diff;
+pub mod events;
+pub mod hitsounds;
+pub mod lint;
+pub mod mania;
+pub mod metronome;
+pub mod modes;
+pub mod performance;
+pub mod raw;
+pub mod replay;
+pub mod report;
+pub mod scoring;
+pub mod stats;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "tokio")]
+mod async_fs;
+#[cfg(feature = "cache")]
+pub mod cache;
+mod normalize;
+mod combo;
+mod editor;
+mod hash;
+mod ids;
+mod merge;
+mod metadata;
+mod mods;
+#[cfg(feature = "osz")]
+mod osz;
+mod practice;
+mod set;
+mod spans;
+mod time;
+mod timing;
+mod version;
+#[cfg(test)]
+mod proptests;
 
 use parse::*;
 
+#[cfg(feature = "tokio")]
+pub use async_fs::{
+    parse_beatmap_from_path_async, parse_beatmaps_async, parse_beatmaps_async_with_progress, BatchProgress,
+};
+pub use combo::Combo;
+pub use deserialize::{ToOsuString, WriterOptions};
+pub use diff::beatmap_diff;
+pub use ids::{BeatmapId, BeatmapSetId};
+pub use merge::merge_beatmaps;
+pub use mods::Mods;
+#[cfg(feature = "osz")]
+pub use osz::OszProgress;
+pub use parse::{DuplicateKeyPolicy, ParserOptions, RangePolicy, RepeatedSectionPolicy};
+pub use raw::parse_raw;
+pub use set::BeatmapSet;
+pub use spans::{parse_beatmap_with_spans, Span, Spans};
+pub use time::Time;
+pub use timing::{Timing, TimingContext, TimingIndex};
+pub use version::FormatVersion;
+
 /// Represents an osu! beatmap file. Includes information specified in
 /// the [specification](https://osu.ppy.sh/help/wiki/osu!_File_Formats/Osu_(file_format)).
-///
-/// __NOTE:__ This is missing the Event section, as parsing for this has yet to be
-/// implemented in this crate.
 #[derive(Debug, Default, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Beatmap {
-    /// The version of the .osu file format.
+    /// The version of the .osu file format. See
+    /// [`format_version`](#method.format_version) for what it supports.
     pub version: i32,
     pub general: GeneralSection,
     pub editor: EditorSection,
@@ -25,11 +101,85 @@ pub struct Beatmap {
     pub timing_points: Vec<TimingPoint>,
     pub hit_objects: Vec<HitObject>,
     pub difficulty: DifficultySection,
+    /// The `[Events]` section, along with the `[Variables]` it refers to.
+    pub events: events::Events,
     pub colours: ColoursSection,
+    /// Which keys of the key-value sections actually appeared in the parsed
+    /// file. Empty for beatmaps that weren't parsed.
+    pub present_keys: PresentKeys,
+}
+
+/// The keys that appeared in a key-value section of a parsed file, in the
+/// order they appeared. Keys are stored with the capitalisation used by the
+/// osu! editor, e.g. `ApproachRate`, and compared case-insensitively.
+///
+/// Two key sets are equal if they contain the same keys, regardless of order.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct KeySet(Vec<String>);
+
+impl PartialEq for KeySet {
+    fn eq(&self, other: &KeySet) -> bool {
+        self.len() == other.len() && self.iter().all(|k| other.contains(k))
+    }
+}
+
+impl Eq for KeySet {}
+
+impl KeySet {
+    /// Whether `key` appeared in the section.
+    pub fn contains(&self, key: &str) -> bool {
+        self.0.iter().any(|k| unicase::eq(k.as_str(), key))
+    }
+
+    /// Records `key` as present. Keys already in the set keep their position.
+    pub fn insert(&mut self, key: &str) {
+        if !self.contains(key) {
+            self.0.push(String::from(key));
+        }
+    }
+
+    /// Records every key of `other` as present, after the keys already in
+    /// the set.
+    pub fn extend(&mut self, other: KeySet) {
+        for key in other.0 {
+            self.insert(&key);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|k| k.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Records which keys of each key-value section were found while parsing,
+/// so that a key explicitly set to its default value can be told apart from
+/// a missing one.
+///
+/// When writing a beatmap back out, sections with recorded keys only include
+/// those keys, plus any key whose value differs from its default, keeping
+/// the order the keys were found in.
+#[derive(Debug, Default, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PresentKeys {
+    pub general: KeySet,
+    pub editor: KeySet,
+    pub metadata: KeySet,
+    pub difficulty: KeySet,
 }
 
 /// One of the four currently available osu! gamemodes.
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum GameMode {
     Osu = 0,
     Taiko = 1,
@@ -37,23 +187,64 @@ pub enum GameMode {
     Mania = 3,
 }
 
+impl GameMode {
+    /// The number that stands for the mode in the `Mode` key of a beatmap.
+    pub fn to_raw(self) -> u8 {
+        match self {
+            GameMode::Osu => 0,
+            GameMode::Taiko => 1,
+            GameMode::CTB => 2,
+            GameMode::Mania => 3,
+        }
+    }
+
+    /// The mode a number in the `Mode` key stands for, or `None` if it isn't
+    /// one of the four modes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use osuparse::GameMode;
+    ///
+    /// assert_eq!(GameMode::from_raw(3), Some(GameMode::Mania));
+    /// assert_eq!(GameMode::from_raw(GameMode::CTB.to_raw()), Some(GameMode::CTB));
+    /// assert_eq!(GameMode::from_raw(4), None);
+    /// ```
+    pub fn from_raw(raw: u8) -> Option<GameMode> {
+        match raw {
+            0 => Some(GameMode::Osu),
+            1 => Some(GameMode::Taiko),
+            2 => Some(GameMode::CTB),
+            3 => Some(GameMode::Mania),
+            _ => None,
+        }
+    }
+}
+
 /// General properties of a beatmap.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct GeneralSection {
     pub audio_filename: String,
     /// Is number of milliseconds before the audio file should begin playing.
     /// Useful for audio files that begin immediately.
-    pub audio_lead_in: i32,
+    pub audio_lead_in: Time,
     /// Is the number of milliseconds before the audio file should begin
     /// playing when selected in the song selection menu.
-    pub preview_time: i32,
-    /// Whether or not a countdown should occur before the first hit object
-    /// appears.
-    pub countdown: bool,
+    ///
+    /// A value of `-1`, which is the default, means no preview point was
+    /// set, in which case the game picks one itself.
+    pub preview_time: Time,
+    /// Speed of the countdown before the first hit object. Defaults to
+    /// [`CountdownSpeed::Normal`](enum.CountdownSpeed.html#variant.Normal),
+    /// as in the game.
+    pub countdown: CountdownSpeed,
     /// Specifies which set of hit sounds will be used throughout the beatmap.
     /// Unlike the `sample_set` field in [`HitObjectExtras`](struct.HitObjectExtras.html),
-    /// this value is a string.
+    /// this value is a string. Defaults to `Normal`.
     pub sample_set: String,
+    /// How often closely placed hit objects will be stacked together.
+    /// Defaults to `0.7`.
     pub stack_leniency: f32,
     pub countdown_offset: i32,
     pub skin_preference: String,
@@ -66,18 +257,26 @@ pub struct GeneralSection {
     pub special_style: bool,
     pub epilepsy_warning: bool,
     pub use_skin_sprites: bool,
+    /// Whether or not the playfield should always be shown, even while a
+    /// storyboard is being displayed.
+    pub always_show_playfield: bool,
+    /// Draw order of hit circle overlays compared to hit numbers.
+    pub overlay_position: OverlayPosition,
+    /// Whether or not sound samples will change rate when playing with
+    /// speed-changing mods.
+    pub samples_match_playback_rate: bool,
 }
 
 impl Default for GeneralSection {
     fn default() -> Self {
         GeneralSection {
             audio_filename: String::new(),
-            audio_lead_in: 0,
-            preview_time: 0,
-            countdown: false,
-            sample_set: String::new(),
+            audio_lead_in: Time::ZERO,
+            preview_time: Time(-1.0),
+            countdown: CountdownSpeed::Normal,
+            sample_set: String::from("Normal"),
             skin_preference: String::new(),
-            stack_leniency: 0.0,
+            stack_leniency: 0.7,
             countdown_offset: 0,
             game_mode: GameMode::Osu,
             letterbox_in_breaks: false,
@@ -86,18 +285,54 @@ impl Default for GeneralSection {
             special_style: false,
             epilepsy_warning: false,
             use_skin_sprites: false,
+            always_show_playfield: false,
+            overlay_position: OverlayPosition::NoChange,
+            samples_match_playback_rate: false,
         }
     }
 }
 
+/// Speed of the countdown shown before the first hit object.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum CountdownSpeed {
+    /// No countdown.
+    None = 0,
+    Normal = 1,
+    Half = 2,
+    Double = 3,
+}
+
+/// Draw order of hit circle overlays compared to hit numbers.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum OverlayPosition {
+    /// Use the skin's setting.
+    NoChange,
+    /// Draw overlays under numbers.
+    Below,
+    /// Draw overlays on top of numbers.
+    Above,
+}
+
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// Properties relating to the beatmap editor state
 pub struct EditorSection {
-    pub bookmarks: Vec<i32>,
+    pub bookmarks: Vec<Time>,
     pub distance_spacing: f32,
     pub beat_divisor: i32,
     pub grid_size: i32,
     pub timeline_zoom: f32,
+    /// The time the editor was at when the beatmap was saved, in
+    /// milliseconds. Only older versions of the editor write it.
+    pub current_time: Option<Time>,
+    /// Keys of the section that aren't known to this crate, along with
+    /// their values, in the order they appeared. They are written back
+    /// unchanged.
+    pub unknown: Vec<(String, String)>,
 }
 
 impl Default for EditorSection {
@@ -108,11 +343,14 @@ impl Default for EditorSection {
             beat_divisor: 4,
             grid_size: 4,
             timeline_zoom: 1.0,
+            current_time: None,
+            unknown: Vec::new(),
         }
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Default, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// Metadata relating to the beatmap
 pub struct MetadataSection {
     /// Is the title of the song limited to ASCII characters, e.g. `Yoru Naku Usagi wa Yume o Miru`.
@@ -131,29 +369,13 @@ pub struct MetadataSection {
     pub source: String,
     pub tags: Vec<String>,
     /// Is the ID of the single beatmap.
-    pub beatmap_id: i32,
+    pub beatmap_id: BeatmapId,
     /// Is the ID of the beatmap set that the beatmap belongs to.
-    pub beatmap_set_id: i32,
-}
-
-impl Default for MetadataSection {
-    fn default() -> Self {
-        MetadataSection {
-            title: String::new(),
-            title_unicode: String::new(),
-            artist: String::new(),
-            artist_unicode: String::new(),
-            creator: String::new(),
-            version: String::new(),
-            source: String::new(),
-            tags: Vec::new(),
-            beatmap_id: 0,
-            beatmap_set_id: 0,
-        }
-    }
+    pub beatmap_set_id: BeatmapSetId,
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// Difficulty modifiers for the beatmap
 pub struct DifficultySection {
     pub hp_drain_rate: f32,
@@ -172,6 +394,9 @@ pub struct DifficultySection {
     pub overall_difficulty: f32,
     /// Defines when hit objects start to fade in relatively to when they
     /// should be hit.
+    ///
+    /// Format versions 7 and older don't have this property, in which case
+    /// it is set to the overall difficulty.
     pub approach_rate: f32,
     /// Specifies the multiplier of the slider velocity. The velocity at slider
     /// multiplier = 1 is 100 osu!pixels per beat. A slider multiplier of `2`
@@ -200,12 +425,18 @@ impl Default for DifficultySection {
 
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// Represents a single timing point
+///
+/// Old maps may leave out any of the fields after `ms_per_beat`. When they
+/// do, the parser uses a meter of `4`, a sample set and sample index of `0`,
+/// a volume of `100`, `inherited` set to `true`, and no effects, as the game
+/// does.
 pub struct TimingPoint {
     /// Is the number of milliseconds from the start of the song, and defines
     /// when the timing point starts. A timing point ends when the next one
     /// starts. The first timing point starts at 0, disregarding its offset.
-    pub offset: f32,
+    pub offset: Time,
     /// Defines the duration of one beat. It affect the scrolling speed in
     /// osu!taiko or osu!mania, and the slider speed in osu!standard, among
     /// other things.
@@ -215,7 +446,7 @@ pub struct TimingPoint {
     /// instance, 3 consecutive timing points with `500`, `-50`, `-100`
     /// will have a resulting beat duration of half a second, a quarter of
     /// a second, and half a second, respectively.
-    pub ms_per_beat: f32,
+    pub ms_per_beat: f64,
     /// Defines the number of beats in a [measure](https://en.wikipedia.org/wiki/Bar_(music)).
     pub meter: i32,
     /// Defines the default sample that hit objects inherit if the
@@ -234,29 +465,143 @@ pub struct TimingPoint {
     /// per beat implies inherited is `true`, and a negative one implies it is
     /// `false`.
     pub inherited: bool,
-    /// Defines whether or not [Kiai Time](https://osu.ppy.sh/help/wiki/Beatmap_Editor/Kiai_Time)
-    /// effects are active.
-    pub kiai_mode: bool,
+    /// Extra effects active during the timing point, such as
+    /// [Kiai Time](https://osu.ppy.sh/help/wiki/Beatmap_Editor/Kiai_Time).
+    pub effects: Effects,
+}
+
+impl TimingPoint {
+    /// Whether this is an uninherited timing point, which sets the tempo
+    /// rather than the slider velocity. These are the red lines in the
+    /// editor.
+    pub fn is_uninherited(&self) -> bool {
+        self.ms_per_beat >= 0.0
+    }
+
+    /// The tempo set by an uninherited timing point, in beats per minute.
+    /// Inherited timing points don't set a tempo, so this returns `None`
+    /// for them.
+    ///
+    /// ```
+    /// use osuparse::TimingPoint;
+    ///
+    /// let point = TimingPoint { ms_per_beat: 500.0, ..Default::default() };
+    /// assert_eq!(point.bpm(), Some(120.0));
+    ///
+    /// let point = TimingPoint { ms_per_beat: -50.0, ..Default::default() };
+    /// assert_eq!(point.bpm(), None);
+    /// ```
+    pub fn bpm(&self) -> Option<f64> {
+        if self.is_uninherited() {
+            Some(60_000.0 / self.ms_per_beat)
+        } else {
+            None
+        }
+    }
+
+    /// The slider velocity multiplier set by an inherited timing point,
+    /// shown as e.g. `2.00x` in the editor. Uninherited timing points reset
+    /// the multiplier, so this returns `1.0` for them.
+    ///
+    /// ```
+    /// use osuparse::TimingPoint;
+    ///
+    /// let point = TimingPoint { ms_per_beat: -50.0, ..Default::default() };
+    /// assert_eq!(point.slider_velocity_multiplier(), 2.0);
+    /// ```
+    pub fn slider_velocity_multiplier(&self) -> f32 {
+        if self.is_uninherited() {
+            1.0
+        } else {
+            (-100.0 / self.ms_per_beat) as f32
+        }
+    }
+}
+
+/// Bit flags giving extra effects to a timing point.
+///
+/// Bits without a known meaning are kept as they were read, so they survive
+/// being written back out.
+///
+/// ```
+/// use osuparse::Effects;
+///
+/// let effects = Effects::from_bits(9);
+/// assert!(effects.kiai());
+/// assert!(effects.omit_first_barline());
+/// assert_eq!(effects, Effects::KIAI | Effects::OMIT_FIRST_BARLINE);
+/// ```
+#[derive(Debug, Default, Eq, PartialEq, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Effects(i32);
+
+impl Effects {
+    /// Whether or not Kiai Time effects are active.
+    pub const KIAI: Effects = Effects(1);
+    /// Whether or not the first barline of the timing point is hidden. Only
+    /// used in osu!taiko and osu!mania.
+    pub const OMIT_FIRST_BARLINE: Effects = Effects(8);
+
+    pub fn from_bits(bits: i32) -> Effects {
+        Effects(bits)
+    }
+
+    pub fn bits(self) -> i32 {
+        self.0
+    }
+
+    /// Whether all of the flags in `other` are set.
+    pub fn contains(self, other: Effects) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Sets or clears all of the flags in `other`.
+    pub fn set(&mut self, other: Effects, value: bool) {
+        if value {
+            self.0 |= other.0;
+        } else {
+            self.0 &= !other.0;
+        }
+    }
+
+    pub fn kiai(self) -> bool {
+        self.contains(Effects::KIAI)
+    }
+
+    pub fn omit_first_barline(self) -> bool {
+        self.contains(Effects::OMIT_FIRST_BARLINE)
+    }
+}
+
+impl std::ops::BitOr for Effects {
+    type Output = Effects;
+
+    fn bitor(self, other: Effects) -> Effects {
+        Effects(self.0 | other.0)
+    }
 }
 
 impl Default for TimingPoint {
     fn default() -> Self {
         TimingPoint {
-            offset: 0.0,
-            /// This equals 200 beats per minute
+            offset: Time::ZERO,
+            // This equals 200 beats per minute
             ms_per_beat: 300.0,
-            /// Four beats per measure is standard for a lot of music
+            // Four beats per measure is standard for a lot of music
             meter: 4,
             sample_set: 0,
             sample_index: 0,
             volume: 100,
             inherited: false,
-            kiai_mode: false,
+            effects: Effects::default(),
         }
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
 /// One of the four possible hit objects appearing on an osu! map.
 pub enum HitObject {
     HitCircle(HitCircle),
@@ -265,18 +610,103 @@ pub enum HitObject {
     HoldNote(HoldNote),
 }
 
-#[derive(Debug, PartialEq, Clone)]
+impl HitObject {
+    /// The time in milliseconds at which the object should be hit.
+    pub fn time(&self) -> Time {
+        match self {
+            HitObject::HitCircle(o) => o.time,
+            HitObject::Slider(o) => o.time,
+            HitObject::Spinner(o) => o.time,
+            HitObject::HoldNote(o) => o.time,
+        }
+    }
+
+    /// The position of the object in osu!pixels.
+    pub fn position(&self) -> (i32, i32) {
+        match self {
+            HitObject::HitCircle(o) => (o.x, o.y),
+            HitObject::Slider(o) => (o.x, o.y),
+            HitObject::Spinner(o) => (o.x, o.y),
+            HitObject::HoldNote(o) => (o.x, o.y),
+        }
+    }
+
+    /// Whether the object is flagged as starting a new combo in the file.
+    /// See [`Beatmap::combos`](struct.Beatmap.html#method.combos) for
+    /// whether it actually does.
+    pub fn new_combo(&self) -> bool {
+        match self {
+            HitObject::HitCircle(o) => o.new_combo,
+            HitObject::Slider(o) => o.new_combo,
+            HitObject::Spinner(o) => o.new_combo,
+            HitObject::HoldNote(o) => o.new_combo,
+        }
+    }
+
+    /// Number of combo colours to skip when this object starts a new combo.
+    pub fn color_skip(&self) -> i32 {
+        match self {
+            HitObject::HitCircle(o) => o.color_skip,
+            HitObject::Slider(o) => o.color_skip,
+            HitObject::Spinner(o) => o.color_skip,
+            HitObject::HoldNote(o) => o.color_skip,
+        }
+    }
+
+    /// The object's `type` value exactly as it was read from the file.
+    ///
+    /// The object type, new combo and color skip bits are also available as
+    /// fields, and take precedence over this value when the object is written
+    /// back out. Any other bits, which have no meaning yet, are written out
+    /// unchanged. Objects created by hand can leave this at `0`.
+    pub fn raw_type(&self) -> i32 {
+        match self {
+            HitObject::HitCircle(o) => o.raw_type,
+            HitObject::Slider(o) => o.raw_type,
+            HitObject::Spinner(o) => o.raw_type,
+            HitObject::HoldNote(o) => o.raw_type,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct HitCircle {
     pub x: i32,
     pub y: i32,
     pub new_combo: bool,
     pub color_skip: i32,
-    pub time: i32,
+    /// See [`HitObject::raw_type`](enum.HitObject.html#method.raw_type).
+    pub raw_type: i32,
+    pub time: Time,
     pub hitsound: i32,
     pub extras: HitObjectExtras,
 }
 
+impl HitCircle {
+    /// A circle at `(x, y)` to be hit at `time`, with the default hitsound.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use osuparse::{HitCircle, HitObject, Time, ToOsuString};
+    ///
+    /// let circle = HitObject::from(HitCircle::new(256, 192, Time(1000.0)));
+    /// assert_eq!(circle.to_osu_string(), "256,192,1000,1,0,0:0:0:0:");
+    /// ```
+    pub fn new(x: i32, y: i32, time: Time) -> Self {
+        HitCircle {
+            x,
+            y,
+            time,
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 /// Type of slider curve
 pub enum SliderType {
     Linear,
@@ -286,50 +716,231 @@ pub enum SliderType {
     Catmull,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+impl Default for SliderType {
+    /// The editor makes new sliders as bezier curves.
+    fn default() -> Self {
+        SliderType::Bezier
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Slider {
     pub x: i32,
     pub y: i32,
     pub new_combo: bool,
     pub color_skip: i32,
-    pub time: i32,
+    /// See [`HitObject::raw_type`](enum.HitObject.html#method.raw_type).
+    pub raw_type: i32,
+    pub time: Time,
     pub slider_type: SliderType,
     pub curve_points: Vec<(i32, i32)>,
     pub repeat: i32,
-    pub pixel_length: f32,
+    pub pixel_length: f64,
+    /// Hitsounds played on each of the slider's edges: its head, every
+    /// repeat and its tail.
     pub edge_hitsounds: Vec<i32>,
-    pub edge_additions: Vec<(i32, i32)>,
+    /// Sample sets used on each of the slider's edges, in the same order as
+    /// `edge_hitsounds`.
+    pub edge_additions: Vec<EdgeSampleSet>,
     pub hitsound: i32,
     pub extras: HitObjectExtras,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+impl Slider {
+    /// A slider starting at `(x, y)` at `time`, following `curve_points`
+    /// for `pixel_length` osu!pixels and going over it `repeat` times, with
+    /// the default hitsound on every edge.
+    pub fn new(
+        x: i32,
+        y: i32,
+        time: Time,
+        slider_type: SliderType,
+        curve_points: Vec<(i32, i32)>,
+        repeat: i32,
+        pixel_length: f64,
+    ) -> Self {
+        let mut slider = Slider {
+            x,
+            y,
+            time,
+            slider_type,
+            curve_points,
+            repeat,
+            pixel_length,
+            ..Default::default()
+        };
+        slider.normalize_edges();
+        slider
+    }
+
+    /// A straight slider from `(x, y)` to `end`, going over it once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use osuparse::{HitObject, Slider, Time, ToOsuString};
+    ///
+    /// let slider = HitObject::from(Slider::linear(100, 100, Time(1000.0), (400, 500)));
+    /// assert_eq!(slider.to_osu_string(), "100,100,1000,2,0,L|400:500,1,500,0|0,0:0|0:0,0:0:0:0:");
+    /// ```
+    pub fn linear(x: i32, y: i32, time: Time, end: (i32, i32)) -> Self {
+        let (dx, dy) = (f64::from(end.0 - x), f64::from(end.1 - y));
+        Slider::new(x, y, time, SliderType::Linear, vec![end], 1, dx.hypot(dy))
+    }
+
+    /// Number of edges the slider has: its head, every repeat and its tail.
+    pub fn edge_count(&self) -> usize {
+        self.repeat.max(1) as usize + 1
+    }
+
+    /// Whether [`edge_hitsounds`](#structfield.edge_hitsounds) and
+    /// [`edge_additions`](#structfield.edge_additions) each have either one
+    /// entry per edge, or none at all, as in older beatmaps.
+    pub fn has_valid_edges(&self) -> bool {
+        let valid = |len: usize| len == 0 || len == self.edge_count();
+        valid(self.edge_hitsounds.len()) && valid(self.edge_additions.len())
+    }
+
+    /// Gives [`edge_hitsounds`](#structfield.edge_hitsounds) and
+    /// [`edge_additions`](#structfield.edge_additions) exactly one entry per
+    /// edge, so that they can be indexed by edge. Like the game, missing
+    /// edges take the slider's own hitsound and sample sets, and entries
+    /// past the last edge are dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use osuparse::{parse_beatmap, HitObject};
+    ///
+    /// let map = parse_beatmap("osu file format v14\n\n[HitObjects]\n0,0,0,2,2,L|100:0,2,100,8|0|4|0\n").unwrap();
+    /// let mut slider = match map.hit_objects[0] {
+    ///     HitObject::Slider(ref s) => s.clone(),
+    ///     _ => unreachable!(),
+    /// };
+    /// assert!(!slider.has_valid_edges());
+    ///
+    /// slider.normalize_edges();
+    /// assert_eq!(slider.edge_hitsounds, vec![8, 0, 4]);
+    /// assert_eq!(slider.edge_additions.len(), 3);
+    /// ```
+    pub fn normalize_edges(&mut self) {
+        let edges = self.edge_count();
+        let sets = EdgeSampleSet {
+            sample_set: self.extras.sample_set,
+            addition_set: self.extras.addition_set,
+        };
+
+        self.edge_hitsounds.resize(edges, self.hitsound);
+        self.edge_additions.resize(edges, sets);
+    }
+}
+
+/// Sample sets used on a single edge of a slider, written as
+/// `sample_set:addition_set` in the .osu file.
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EdgeSampleSet {
+    /// Sample set of the __normal__ hit sound on this edge. Follows the same
+    /// values as [`HitObjectExtras::sample_set`](struct.HitObjectExtras.html#structfield.sample_set).
+    pub sample_set: i32,
+    /// Sample set of the whistle, finish and clap sounds on this edge.
+    pub addition_set: i32,
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Spinner {
     pub x: i32,
     pub y: i32,
     pub new_combo: bool,
     pub color_skip: i32,
-    pub time: i32,
+    /// See [`HitObject::raw_type`](enum.HitObject.html#method.raw_type).
+    pub raw_type: i32,
+    pub time: Time,
     pub hitsound: i32,
-    pub end_time: i32,
+    pub end_time: Time,
     pub extras: HitObjectExtras,
 }
 
+impl Spinner {
+    /// A spinner from `time` to `end_time`, placed in the centre of the
+    /// playfield as the editor does.
+    pub fn new(time: Time, end_time: Time) -> Self {
+        Spinner {
+            x: 256,
+            y: 192,
+            time,
+            end_time,
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Default, Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct HoldNote {
     pub x: i32,
     pub y: i32,
     pub new_combo: bool,
     pub color_skip: i32,
-    pub time: i32,
+    /// See [`HitObject::raw_type`](enum.HitObject.html#method.raw_type).
+    pub raw_type: i32,
+    pub time: Time,
     pub hitsound: i32,
-    pub end_time: i32,
+    pub end_time: Time,
     pub extras: HitObjectExtras,
 }
 
+impl HoldNote {
+    /// A hold note from `time` to `end_time` in the column at `x`.
+    pub fn new(x: i32, time: Time, end_time: Time) -> Self {
+        HoldNote {
+            x,
+            y: 192,
+            time,
+            end_time,
+            ..Default::default()
+        }
+    }
+
+    /// A hold note in `column`, counting from 0 on the left, of a beatmap
+    /// with `keys` keys. It is placed in the middle of the column, as the
+    /// editor does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use osuparse::{HoldNote, Time};
+    ///
+    /// let (time, end_time) = (Time(1000.0), Time(1500.0));
+    /// assert_eq!(HoldNote::in_column(0, 4, time, end_time).x, 64);
+    /// assert_eq!(HoldNote::in_column(6, 7, time, end_time).x, 475);
+    /// ```
+    pub fn in_column(column: usize, keys: usize, time: Time, end_time: Time) -> Self {
+        let x = (column as f32 + 0.5) * 512.0 / keys as f32;
+        HoldNote::new(x as i32, time, end_time)
+    }
+}
+
+macro_rules! hit_object_from {
+    ($($variant:ident),*) => {
+        $(
+            impl From<$variant> for HitObject {
+                fn from(object: $variant) -> Self {
+                    HitObject::$variant(object)
+                }
+            }
+        )*
+    };
+}
+
+hit_object_from!(HitCircle, Slider, Spinner, HoldNote);
+
 /// The extras field is optional and define additional parameters related to
 /// the hit sound samples.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Default, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct HitObjectExtras {
     /// Changes the sample set of the __normal__ hit sound.
     ///
@@ -358,41 +969,38 @@ pub struct HitObjectExtras {
     pub filename: String,
 }
 
-impl Default for HitObjectExtras {
-    fn default() -> Self {
-        HitObjectExtras {
-            sample_set: 0,
-            addition_set: 0,
-            custom_index: 0,
-            sample_volume: 0,
-            filename: String::new(),
-        }
-    }
-}
-
 /// An RGB triplet representing a colour.
 #[derive(Debug, Default, PartialEq, PartialOrd, Eq, Ord, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Colour(i32, i32, i32);
 
 /// Includes a beatmap's combo colours as well as slider colour overrides.
 #[derive(Debug, Default, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ColoursSection {
     pub colours: Vec<Colour>,
-    pub slider_body: Colour,
-    pub slider_track_override: Colour,
-    pub slider_border: Colour,
+    /// Colour of slider bodies, or `None` to use the skin's.
+    pub slider_body: Option<Colour>,
+    /// Colour of slider tracks, overriding the combo colour they would
+    /// otherwise take, or `None` to leave them alone.
+    pub slider_track_override: Option<Colour>,
+    /// Colour of slider borders, or `None` to use the skin's.
+    pub slider_border: Option<Colour>,
 }
 
 #[derive(Debug)]
-enum Section {
-    General(GeneralSection),
-    Editor(EditorSection),
-    Metadata(MetadataSection),
+enum Section<'a> {
+    General(GeneralSection, KeySet),
+    Editor(EditorSection, KeySet),
+    Metadata(MetadataSection, KeySet),
     TimingPoints(Vec<TimingPoint>),
     HitObjects(Vec<HitObject>),
-    Difficulty(DifficultySection),
+    Difficulty(DifficultySection, KeySet),
     Colours(ColoursSection),
-    Events,
+    /// Lines of the section with their index, parsed once all variables
+    /// are known.
+    Events(Vec<(usize, &'a str)>),
+    Variables(Vec<events::Variable>),
     None,
 }
 
@@ -413,9 +1021,125 @@ enum Section {
 /// parse_beatmap(contents.as_str()).unwrap();
 /// ```
 pub fn parse_beatmap(input: &str) -> Result<Beatmap> {
-    let mut state = ParseState::new(input);
+    parse_beatmap_with_options(input, &ParserOptions::default()).map(|(map, _)| map)
+}
+
+/// Same as [`parse_beatmap`](fn.parse_beatmap.html), but reads the beatmap
+/// according to the given [`ParserOptions`](struct.ParserOptions.html), and
+/// also returns any warnings produced along the way.
+///
+/// # Examples
+///
+/// ```
+/// use osuparse::{parse_beatmap_with_options, ParserOptions, RangePolicy};
+///
+/// let input = "osu file format v14\n[HitObjects]\n256,192,1000,1,0,0:0:0:150:\n";
+///
+/// let (_, warnings) = parse_beatmap_with_options(input, &ParserOptions::default()).unwrap();
+/// assert_eq!(warnings.len(), 1);
+///
+/// let options = ParserOptions { range_policy: RangePolicy::Error, ..Default::default() };
+/// assert!(parse_beatmap_with_options(input, &options).is_err());
+/// ```
+pub fn parse_beatmap_with_options(
+    input: &str,
+    options: &ParserOptions,
+) -> Result<(Beatmap, Vec<Warning>)> {
+    parse_beatmap_with_progress(input, options, |_| {})
+}
+
+/// How far along [`parse_beatmap_with_progress`](fn.parse_beatmap_with_progress.html)
+/// is.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct Progress {
+    /// Number of lines read so far.
+    pub lines: usize,
+    /// Total number of lines in the input.
+    pub total_lines: usize,
+    /// Number of sections read so far.
+    pub sections: usize,
+}
+
+/// Same as [`parse_beatmap_with_options`](fn.parse_beatmap_with_options.html),
+/// but calls `progress` each time a section has been read, so that tools
+/// parsing very large files can show how far along they are.
+///
+/// # Examples
+///
+/// ```
+/// use osuparse::{parse_beatmap_with_progress, ParserOptions};
+///
+/// let input = "osu file format v14\n[General]\nMode: 0\n[HitObjects]\n256,192,1000,1,0\n";
+///
+/// let mut updates = Vec::new();
+/// parse_beatmap_with_progress(input, &ParserOptions::default(), |p| updates.push(p)).unwrap();
+///
+/// assert_eq!(updates.len(), 2);
+/// assert_eq!((updates[0].lines, updates[0].sections), (3, 1));
+/// assert_eq!((updates[1].lines, updates[1].sections), (5, 2));
+/// ```
+pub fn parse_beatmap_with_progress<F>(
+    input: &str,
+    options: &ParserOptions,
+    progress: F,
+) -> Result<(Beatmap, Vec<Warning>)>
+where
+    F: FnMut(Progress),
+{
+    parse_beatmap_with_state(input, options, false, progress).map(|(map, state)| (map, state.warnings))
+}
+
+/// Same as [`parse_beatmap`](fn.parse_beatmap.html), but carries on past
+/// errors instead of stopping at the first one, so that tools can still show
+/// what's readable in a corrupt file. Lines that can't be read are skipped,
+/// along with the rest of any section that can't be read, and the errors are
+/// returned with everything that could be read.
+///
+/// # Examples
+///
+/// ```
+/// use osuparse::parse_beatmap_partial;
+///
+/// let input = "osu file format v14\n[Metadata]\nTitle:Title\n[HitObjects]\n256,192,1000,1,0\nbroken\n";
+///
+/// let (map, errors) = parse_beatmap_partial(input);
+/// assert_eq!(map.metadata.title, "Title");
+/// assert_eq!(map.hit_objects.len(), 1);
+/// assert_eq!(errors.len(), 1);
+/// ```
+pub fn parse_beatmap_partial(input: &str) -> (Beatmap, Vec<Error>) {
+    match parse_beatmap_with_state(input, &ParserOptions::default(), true, |_| {}) {
+        Ok((map, state)) => (map, state.errors.unwrap_or_default()),
+        Err(err) => (Beatmap::default(), vec![err]),
+    }
+}
 
-    let version = parse_version_string(&mut state)?;
+/// Parses a beatmap, returning it along with the final parser state, which
+/// holds the warnings and spans recorded along the way, and the errors
+/// recovered from if `recover` is set.
+fn parse_beatmap_with_state<'a, F>(
+    input: &'a str,
+    options: &ParserOptions,
+    recover: bool,
+    mut progress: F,
+) -> Result<(Beatmap, ParseState<'a>)>
+where
+    F: FnMut(Progress),
+{
+    let mut state = ParseState::with_options(input, *options);
+    if recover {
+        state.errors = Some(Vec::new());
+    }
+    let total_lines = input.lines().count();
+    let mut sections = 0;
+    let mut seen_sections = Vec::new();
+    let mut event_lines = Vec::new();
+    let mut variables = Vec::new();
+
+    // Without a version line, assume the file is in the latest format
+    let version = parse_version_string(&mut state);
+    let version = state.recover(version)?.unwrap_or(FormatVersion::LATEST.0);
+    state.version = FormatVersion(version);
     state.read_next_line();
 
     let mut map = Beatmap {
@@ -424,25 +1148,187 @@ pub fn parse_beatmap(input: &str) -> Result<Beatmap> {
     };
 
     loop {
-        let section = parse_section(&mut state);
+        let name = state
+            .get_current_line()
+            .and_then(match_header_line)
+            .map(canonical_section_name);
+        if let Some(name) = name {
+            if seen_sections.contains(&name) {
+                let message = format!("Section {} appears more than once", name);
+                match options.repeated_sections {
+                    RepeatedSectionPolicy::Merge => state.warn(message),
+                    RepeatedSectionPolicy::Replace => {
+                        state.warn(message);
+                        clear_section(&mut map, &mut state, name);
+                        match name {
+                            "Events" => event_lines.clear(),
+                            "Variables" => variables.clear(),
+                            _ => {}
+                        }
+                    }
+                    RepeatedSectionPolicy::Error => {
+                        let err = state.syntax_error(&message);
+                        state.recover::<()>(Err(err))?;
+                        skip_section(&mut state);
+                        continue;
+                    }
+                }
+            } else {
+                seen_sections.push(name);
+            }
+        }
+
+        let section = parse_section(&mut state, &map);
         let section = state.wrap_syntax_error(section);
-        match section? {
-            Section::General(s) => map.general = s,
-            Section::Editor(s) => map.editor = s,
-            Section::Metadata(s) => map.metadata = s,
-            Section::TimingPoints(s) => map.timing_points = s,
-            Section::HitObjects(s) => map.hit_objects = s,
-            Section::Difficulty(s) => map.difficulty = s,
+        let section = match state.recover(section)? {
+            Some(section) => section,
+            None => {
+                skip_section(&mut state);
+                continue;
+            }
+        };
+
+        match section {
+            // A section that appears again adds to the earlier one
+            Section::General(s, keys) => {
+                map.general = s;
+                map.present_keys.general.extend(keys);
+            }
+            Section::Editor(s, keys) => {
+                map.editor = s;
+                map.present_keys.editor.extend(keys);
+            }
+            Section::Metadata(s, keys) => {
+                map.metadata = s;
+                map.present_keys.metadata.extend(keys);
+            }
+            Section::TimingPoints(mut s) => map.timing_points.append(&mut s),
+            Section::HitObjects(mut s) => map.hit_objects.append(&mut s),
+            Section::Difficulty(s, keys) => {
+                map.difficulty = s;
+                map.present_keys.difficulty.extend(keys);
+            }
             Section::Colours(s) => map.colours = s,
-            Section::Events => {}
+            Section::Events(lines) => event_lines.extend(lines),
+            Section::Variables(v) => variables.extend(v),
             Section::None => break,
         }
+
+        sections += 1;
+        progress(Progress {
+            lines: state.line_number().unwrap_or(total_lines),
+            total_lines,
+            sections,
+        });
+    }
+
+    let (events, warnings) = events::parse_events(&event_lines, variables);
+    map.events = events;
+    state.warnings.extend(warnings);
+
+    if options.check_object_types {
+        check_object_types(&map, &mut state);
+    }
+
+    Ok((map, state))
+}
+
+/// Warns about every hit object that can't appear in the beatmap's game
+/// mode.
+fn check_object_types(map: &Beatmap, state: &mut ParseState) {
+    let mode = map.general.game_mode;
+
+    for (i, obj) in map.hit_objects.iter().enumerate() {
+        let illegal = match obj {
+            HitObject::HitCircle(_) => None,
+            HitObject::Slider(_) if mode == GameMode::Mania => Some("sliders"),
+            HitObject::Spinner(_) if mode == GameMode::Mania => Some("spinners"),
+            HitObject::HoldNote(_) if mode != GameMode::Mania => Some("hold notes"),
+            _ => None,
+        };
+
+        if let Some(objects) = illegal {
+            let line = state.hit_object_spans.get(i).map(|span| span.line);
+            state.warnings.push(Warning {
+                line,
+                message: format!("{:?} beatmaps can't contain {}", mode, objects),
+            });
+        }
+    }
+}
+
+/// Names of the sections of a .osu file, in the order the editor writes
+/// them.
+const SECTION_NAMES: [&str; 9] = [
+    "General",
+    "Editor",
+    "Metadata",
+    "Difficulty",
+    "Variables",
+    "Events",
+    "TimingPoints",
+    "Colours",
+    "HitObjects",
+];
+
+/// The section a header refers to, named as the editor writes it.
+/// Hand-edited files may pad the name with spaces, change its case, or spell
+/// `Colours` as `Colors`. Unknown names are returned trimmed.
+fn canonical_section_name(name: &str) -> &str {
+    let name = name.trim();
+    if unicase::eq(name, "Colors") {
+        return "Colours";
     }
 
-    Ok(map)
+    SECTION_NAMES
+        .iter()
+        .find(|known| unicase::eq(**known, name))
+        .map_or(name, |known| *known)
+}
+
+/// Forget what was read from earlier occurrences of the section `name`, so
+/// that its next occurrence replaces them.
+fn clear_section(map: &mut Beatmap, state: &mut ParseState, name: &str) {
+    match name {
+        "General" => {
+            map.general = Default::default();
+            map.present_keys.general = Default::default();
+        }
+        "Editor" => {
+            map.editor = Default::default();
+            map.present_keys.editor = Default::default();
+        }
+        "Metadata" => {
+            map.metadata = Default::default();
+            map.present_keys.metadata = Default::default();
+        }
+        "Difficulty" => {
+            map.difficulty = Default::default();
+            map.present_keys.difficulty = Default::default();
+        }
+        "Colours" => map.colours = Default::default(),
+        "TimingPoints" => {
+            map.timing_points.clear();
+            state.timing_point_spans.clear();
+        }
+        "HitObjects" => {
+            map.hit_objects.clear();
+            state.hit_object_spans.clear();
+        }
+        _ => {}
+    }
+}
+
+/// Skip to the next section header, after an error in the current section.
+fn skip_section(state: &mut ParseState) {
+    while let Some(line) = state.read_next_line() {
+        if match_header_line(line).is_some() {
+            break;
+        }
+    }
 }
 
-fn match_header_line<'a>(line: &'a str) -> Option<&'a str> {
+fn match_header_line(line: &str) -> Option<&str> {
     let line = line.trim_end();
     let mut chars = line.chars();
 
@@ -451,79 +1337,115 @@ fn match_header_line<'a>(line: &'a str) -> Option<&'a str> {
         .map(|_| &line[1..line.len() - 1])
 }
 
-fn parse_section(state: &mut ParseState) -> Result<Section> {
+/// Parse the section starting at the current line. Key-value pairs and
+/// colours are read on top of those already in `map`, so that a section
+/// appearing twice only changes the keys given again, as in the game.
+fn parse_section<'a>(state: &mut ParseState<'a>, map: &Beatmap) -> Result<Section<'a>> {
     if let Some(header_line) = state.get_current_line() {
         let section_title = match_header_line(header_line)
+            .map(canonical_section_name)
             .ok_or_else(|| state.syntax_error("Malformed section header"))?;
 
+        state.strip_comments = section_title != "Metadata";
+        state.section = section_title;
+
         match section_title {
-            "General" => Ok(Section::General(parse_kv_section! {
-                |GeneralSection, state| {
-                    "AudioFilename" => audio_filename: parse_string;
-                    "AudioLeadIn" => audio_lead_in: parse_num;
-                    "PreviewTime" => preview_time: parse_num;
-                    "Countdown" => countdown: parse_bool;
-                    "CountdownOffset" => countdown_offset: parse_num;
-                    "SampleSet" => sample_set: parse_string;
-                    "SkinPreference" => skin_preference: parse_string;
-                    "StackLeniency" => stack_leniency: parse_num;
-                    "Mode" => game_mode: parse_mode;
-                    "LetterboxInBreaks" => letterbox_in_breaks: parse_bool;
-                    "WidescreenStoryboard" => widescreen_storyboard: parse_bool;
-                    "EpilepsyWarning" => epilepsy_warning: parse_bool;
-                    "StoryFireInFront" => story_fire_in_front: parse_bool;
-                    "SpecialStyle" => special_style: parse_bool;
-                    "UseSkinSprites" => use_skin_sprites: parse_bool;
-                }
-            })),
-
-            "Editor" => Ok(Section::Editor(parse_kv_section! {
-                |EditorSection, state| {
-                    "Bookmarks" => bookmarks: parse_num, ",";
-                    "DistanceSpacing" => distance_spacing: parse_num;
-                    "BeatDivisor" => beat_divisor: parse_num;
-                    "GridSize" => grid_size: parse_num;
-                    "TimelineZoom" => timeline_zoom: parse_num;
-                }
-            })),
-
-            "Metadata" => Ok(Section::Metadata(parse_kv_section! {
-                |MetadataSection, state| {
-                    "Title" => title: parse_string;
-                    "TitleUnicode" => title_unicode: parse_string;
-                    "Artist" => artist: parse_string;
-                    "ArtistUnicode" => artist_unicode: parse_string;
-                    "Creator" => creator: parse_string;
-                    "Version" => version: parse_string;
-                    "Source" => source: parse_string;
-                    "Tags" => tags: parse_string, " ";
-                    "BeatmapID" => beatmap_id: parse_num;
-                    "BeatmapSetID" => beatmap_set_id: parse_num;
-                }
-            })),
-
-            "Difficulty" => Ok(Section::Difficulty(parse_kv_section! {
-                |DifficultySection, state| {
-                    "HPDrainRate" => hp_drain_rate: parse_num;
-                    "CircleSize" => circle_size: parse_num;
-                    "OverallDifficulty" => overall_difficulty: parse_num;
-                    "ApproachRate" => approach_rate: parse_num;
-                    "SliderMultiplier" => slider_multiplier: parse_num;
-                    "SliderTickRate" => slider_tick_rate: parse_num;
+            "General" => {
+                let mut keys = KeySet::default();
+                let section = parse_kv_section! {
+                    |map.general.clone(), state, keys| {
+                        "AudioFilename" => audio_filename: parse_string;
+                        "AudioLeadIn" => audio_lead_in: parse_time;
+                        "PreviewTime" => preview_time: parse_time;
+                        "Countdown" => countdown: parse_countdown;
+                        "CountdownOffset" => countdown_offset: parse_num;
+                        "SampleSet" => sample_set: parse_string;
+                        "SkinPreference" => skin_preference: parse_string;
+                        "StackLeniency" => stack_leniency: parse_num;
+                        "Mode" => game_mode: parse_mode;
+                        "LetterboxInBreaks" => letterbox_in_breaks: parse_bool;
+                        "WidescreenStoryboard" => widescreen_storyboard: parse_bool;
+                        "EpilepsyWarning" => epilepsy_warning: parse_bool;
+                        "StoryFireInFront" => story_fire_in_front: parse_bool;
+                        "SpecialStyle" => special_style: parse_bool;
+                        "UseSkinSprites" => use_skin_sprites: parse_bool;
+                        "AlwaysShowPlayfield" => always_show_playfield: parse_bool;
+                        "OverlayPosition" => overlay_position: parse_overlay_position;
+                        "SamplesMatchPlaybackRate" => samples_match_playback_rate: parse_bool;
+                    }
+                };
+
+                Ok(Section::General(section, keys))
+            }
+
+            "Editor" => {
+                let mut keys = KeySet::default();
+                let section = parse_kv_section! {
+                    |map.editor.clone(), state, keys, unknown| {
+                        "Bookmarks" => bookmarks: parse_time, ',';
+                        "DistanceSpacing" => distance_spacing: parse_num;
+                        "BeatDivisor" => beat_divisor: parse_num;
+                        "GridSize" => grid_size: parse_num;
+                        "TimelineZoom" => timeline_zoom: parse_num;
+                        "CurrentTime" => current_time: |v| parse_time(v).map(Some);
+                    }
+                };
+
+                Ok(Section::Editor(section, keys))
+            }
+
+            "Metadata" => {
+                let mut keys = KeySet::default();
+                let section = parse_kv_section! {
+                    |map.metadata.clone(), state, keys| {
+                        "Title" => title: parse_string;
+                        "TitleUnicode" => title_unicode: parse_string;
+                        "Artist" => artist: parse_string;
+                        "ArtistUnicode" => artist_unicode: parse_string;
+                        "Creator" => creator: parse_string;
+                        "Version" => version: parse_string;
+                        "Source" => source: parse_string;
+                        "Tags" => tags: parse_string, ' ';
+                        "BeatmapID" => beatmap_id: parse_num;
+                        "BeatmapSetID" => beatmap_set_id: parse_num;
+                    }
+                };
+
+                Ok(Section::Metadata(section, keys))
+            }
+
+            "Difficulty" => {
+                let mut keys = KeySet::default();
+                let mut section = parse_kv_section! {
+                    |map.difficulty, state, keys| {
+                        "HPDrainRate" => hp_drain_rate: parse_num;
+                        "CircleSize" => circle_size: parse_num;
+                        "OverallDifficulty" => overall_difficulty: parse_num;
+                        "ApproachRate" => approach_rate: parse_num;
+                        "SliderMultiplier" => slider_multiplier: parse_num;
+                        "SliderTickRate" => slider_tick_rate: parse_num;
+                    }
+                };
+
+                // Older maps use their overall difficulty instead
+                let has_approach_rate = keys.contains("ApproachRate")
+                    || map.present_keys.difficulty.contains("ApproachRate");
+                if !state.version.has_approach_rate() && !has_approach_rate {
+                    section.approach_rate = section.overall_difficulty;
                 }
-            })),
 
-            "Events" => {
-                // Just skipping this for now
-                skip_section(state);
-                Ok(Section::Events)
+                Ok(Section::Difficulty(section, keys))
             }
 
-            "TimingPoints" => parse_timing_points(state).map(|s| Section::TimingPoints(s)),
+            "Events" => Ok(Section::Events(read_section_lines(state))),
+
+            "Variables" => Ok(Section::Variables(parse_variables(state))),
+
+            "TimingPoints" => parse_timing_points(state).map(Section::TimingPoints),
 
-            "HitObjects" => parse_hit_objects(state).map(|s| Section::HitObjects(s)),
+            "HitObjects" => parse_hit_objects(state).map(Section::HitObjects),
 
-            "Colours" => parse_colours(state).map(|s| Section::Colours(s)),
+            "Colours" => parse_colours(state, &map.colours).map(Section::Colours),
 
             _ => Err(state.syntax_error("Unknown section header")),
         }
@@ -532,13 +1454,36 @@ fn parse_section(state: &mut ParseState) -> Result<Section> {
     }
 }
 
-fn skip_section(state: &mut ParseState) {
+/// Read the remaining lines of a section, along with their index.
+fn read_section_lines<'a>(state: &mut ParseState<'a>) -> Vec<(usize, &'a str)> {
+    let mut lines = Vec::new();
+
     loop {
         match state.read_next_line() {
-            Some(l) if match_header_line(l).is_none() => {}
+            Some(l) if match_header_line(l).is_none() => {
+                lines.push((state.line_number().unwrap_or(0), l));
+            }
             _ => break,
         }
     }
+
+    lines
+}
+
+fn parse_variables(state: &mut ParseState) -> Vec<events::Variable> {
+    let mut variables = Vec::new();
+
+    loop {
+        match state.read_next_line() {
+            Some(l) if match_header_line(l).is_none() => match events::parse_variable(l) {
+                Ok(variable) => variables.push(variable),
+                Err(err) => state.warn(format!("Skipped variable: {}", err)),
+            },
+            _ => break,
+        }
+    }
+
+    variables
 }
 
 fn parse_version_string(state: &mut ParseState) -> Result<i32> {
@@ -551,21 +1496,21 @@ fn parse_version_string(state: &mut ParseState) -> Result<i32> {
 
 fn parse_timing_points(state: &mut ParseState) -> Result<Vec<TimingPoint>> {
     let mut timing_points = Vec::with_capacity(100);
+
     loop {
         match state.read_next_line() {
             Some(l) if match_header_line(l).is_none() => {
-                let timing_point = parse_into_struct!(",", TimingPoint, l; {
-                    offset: parse_num,
-                    ms_per_beat: parse_num,
-                    meter: parse_num,
-                    sample_set: parse_num,
-                    sample_index: parse_num,
-                    volume: parse_num,
-                    inherited: parse_bool,
-                    kiai_mode: parse_bool
-                });
-
-                timing_points.push(timing_point)
+                let timing_point = state.wrap_syntax_error(parse_timing_point(l));
+                let timing_point = match state.recover(timing_point)? {
+                    Some(timing_point) => timing_point,
+                    None => continue,
+                };
+
+                let volume = state.check_range(timing_point.volume, 0, 100, "Volume");
+                if let Some(volume) = state.recover(volume)? {
+                    timing_points.push(TimingPoint { volume, ..timing_point });
+                    state.timing_point_spans.extend(state.current_span());
+                }
             }
             _ => break,
         };
@@ -574,32 +1519,54 @@ fn parse_timing_points(state: &mut ParseState) -> Result<Vec<TimingPoint>> {
     Ok(timing_points)
 }
 
-fn parse_colours(state: &mut ParseState) -> Result<ColoursSection> {
-    let mut section: ColoursSection = Default::default();
+fn parse_colours(state: &mut ParseState, start: &ColoursSection) -> Result<ColoursSection> {
+    let mut section = start.clone();
 
-    let mut colours = Vec::with_capacity(10);
+    let mut colours: Vec<(i32, Colour)> = Vec::with_capacity(10);
+    let mut seen = KeySet::default();
+    let colour = |state: &ParseState, v| parse_colour(v).map_err(|e| e.in_field(state.column_of(v)));
 
     loop {
         state.read_next_line();
-        match parse_kv_pair(state) {
-            Some((k, v)) if k.starts_with("Combo") => {
-                let n: i32 = parse_num(&k[5..])?;
-                colours.push((n, parse_colour(v)?));
-            }
-
-            Some((k, v)) if unicase::eq("SliderBody", k) => section.slider_body = parse_colour(v)?,
+        let (k, v) = match parse_kv_pair(state) {
+            Some(pair) => pair,
+            None => break,
+        };
 
-            Some((k, v)) if unicase::eq("SliderTrackOverride", k) => {
-                section.slider_track_override = parse_colour(v)?
-            }
+        let key = if k.starts_with("Combo") {
+            k
+        } else if let Some(key) = ["SliderBody", "SliderTrackOverride", "SliderBorder"]
+            .iter()
+            .find(|key| unicase::eq(**key, k))
+        {
+            key
+        } else {
+            continue;
+        };
 
-            Some((k, v)) if unicase::eq("SliderBorder", k) => {
-                section.slider_border = parse_colour(v)?
+        if seen.contains(key) && !state.keep_duplicate(key)? {
+            continue;
+        }
+        seen.insert(key);
+
+        match key {
+            "SliderBody" => section.slider_body = Some(colour(state, v)?),
+            "SliderTrackOverride" => section.slider_track_override = Some(colour(state, v)?),
+            "SliderBorder" => section.slider_border = Some(colour(state, v)?),
+            _ => {
+                let n: i32 = parse_num(&k[5..]).map_err(|e| e.in_field(state.column_of(&k[5..])))?;
+                let c = colour(state, v)?;
+                colours.retain(|&(m, _)| m != n);
+                colours.push((n, c));
             }
+        }
+    }
 
-            Some(_) => {},
-
-            _ => break,
+    // Combo colours from an earlier section are kept unless set again
+    for (i, &colour) in start.colours.iter().enumerate() {
+        let n = i as i32 + 1;
+        if !colours.iter().any(|&(m, _)| m == n) {
+            colours.push((n, colour));
         }
     }
 
@@ -610,12 +1577,22 @@ fn parse_colours(state: &mut ParseState) -> Result<ColoursSection> {
 }
 
 fn parse_hit_objects(state: &mut ParseState) -> Result<Vec<HitObject>> {
-    let mut hit_objects = Vec::with_capacity(100);
+    // Hit objects are almost always the last section, so nearly all of the
+    // remaining lines will be hit objects.
+    let mut hit_objects = Vec::with_capacity(state.remaining_lines());
 
     loop {
         match state.read_next_line() {
             Some(l) if match_header_line(l).is_none() => {
-                hit_objects.push(parse_hit_object(l)?);
+                let obj = state.wrap_syntax_error(parse_hit_object(l));
+                let obj = match state.recover(obj)? {
+                    Some(obj) => check_hit_object(state, obj),
+                    None => continue,
+                };
+                if let Some(obj) = state.recover(obj)? {
+                    hit_objects.push(obj);
+                    state.hit_object_spans.extend(state.current_span());
+                }
             }
             _ => break,
         }
@@ -624,12 +1601,47 @@ fn parse_hit_objects(state: &mut ParseState) -> Result<Vec<HitObject>> {
     Ok(hit_objects)
 }
 
+/// Largest coordinate, in either direction, accepted for hit objects and
+/// slider curve points.
+const MAX_COORDINATE: i32 = 131_072;
+
+fn check_coord(state: &mut ParseState, (x, y): (i32, i32)) -> Result<(i32, i32)> {
+    Ok((
+        state.check_range(x, -MAX_COORDINATE, MAX_COORDINATE, "X coordinate")?,
+        state.check_range(y, -MAX_COORDINATE, MAX_COORDINATE, "Y coordinate")?,
+    ))
+}
+
+fn check_hit_object(state: &mut ParseState, mut obj: HitObject) -> Result<HitObject> {
+    if let HitObject::Slider(ref mut slider) = obj {
+        for point in &mut slider.curve_points {
+            *point = check_coord(state, *point)?;
+        }
+    }
+
+    {
+        let (x, y, extras) = match obj {
+            HitObject::HitCircle(ref mut o) => (&mut o.x, &mut o.y, &mut o.extras),
+            HitObject::Slider(ref mut o) => (&mut o.x, &mut o.y, &mut o.extras),
+            HitObject::Spinner(ref mut o) => (&mut o.x, &mut o.y, &mut o.extras),
+            HitObject::HoldNote(ref mut o) => (&mut o.x, &mut o.y, &mut o.extras),
+        };
+
+        let (checked_x, checked_y) = check_coord(state, (*x, *y))?;
+        *x = checked_x;
+        *y = checked_y;
+        extras.sample_volume = state.check_range(extras.sample_volume, 0, 100, "Sample volume")?;
+    }
+
+    Ok(obj)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs::File;
     use std::io::prelude::*;
-    use deserialize::Parsable;
+    use deserialize::ToOsuString;
 
     #[test]
     fn test_parse_version_string() {
@@ -640,6 +1652,481 @@ mod tests {
         assert_eq!(version, 14)
     }
 
+    #[test]
+    fn test_approach_rate_fallback() {
+        let input = |version| {
+            format!("osu file format v{}\n\n[Difficulty]\nOverallDifficulty:7\n", version)
+        };
+
+        let old = parse_beatmap(&input(7)).unwrap();
+        assert_eq!(old.difficulty.approach_rate, 7.0);
+
+        let new = parse_beatmap(&input(8)).unwrap();
+        assert_eq!(new.difficulty.approach_rate, 5.0);
+    }
+
+    #[test]
+    fn test_general_defaults() {
+        let map = parse_beatmap("osu file format v14\n\n[General]\nAudioFilename: a.mp3\n").unwrap();
+
+        assert_eq!(map.general.preview_time, Time(-1.0));
+        assert_eq!(map.general.countdown, CountdownSpeed::Normal);
+        assert_eq!(map.general.sample_set, "Normal");
+        assert_eq!(map.general.stack_leniency, 0.7);
+    }
+
+    #[test]
+    fn test_named_game_modes() {
+        for &(value, mode) in &[
+            ("1", GameMode::Taiko),
+            ("Taiko", GameMode::Taiko),
+            ("catchthebeat", GameMode::CTB),
+            ("Catch", GameMode::CTB),
+            ("MANIA", GameMode::Mania),
+            ("Standard", GameMode::Osu),
+        ] {
+            let map = parse_beatmap(&format!("osu file format v14\n[General]\nMode: {}\n", value)).unwrap();
+            assert_eq!(map.general.game_mode, mode);
+        }
+
+        assert!(parse_beatmap("osu file format v14\n[General]\nMode: 4\n").is_err());
+    }
+
+    #[test]
+    fn test_present_keys() {
+        let map = parse_beatmap(
+            "osu file format v14\n\n[General]\nCountdown: 2\n\n[Difficulty]\nApproachRate:5\n",
+        ).unwrap();
+
+        assert_eq!(map.general.countdown, CountdownSpeed::Half);
+        assert!(map.present_keys.general.contains("countdown"));
+        assert!(!map.present_keys.general.contains("SampleSet"));
+        assert!(map.present_keys.difficulty.contains("ApproachRate"));
+        assert!(map.present_keys.metadata.is_empty());
+
+        let output = map.to_osu_string();
+        assert!(output.contains("Countdown: 2"));
+        assert!(!output.contains("SampleSet"));
+        assert!(output.contains("ApproachRate: 5"));
+        assert!(!output.contains("CircleSize"));
+        assert!(output.contains("Title: "));
+    }
+
+    #[test]
+    fn test_unknown_editor_keys() {
+        let map = parse_beatmap(
+            "osu file format v9\n\n[Editor]\nBeatDivisor: 4\nCurrentTime: 52416\nAudioLength: 180000\n",
+        ).unwrap();
+
+        assert_eq!(map.editor.current_time, Some(Time(52416.0)));
+        assert_eq!(map.editor.unknown, vec![("AudioLength".to_string(), "180000".to_string())]);
+
+        let output = map.to_osu_string();
+        assert!(output.contains("CurrentTime: 52416"));
+        assert!(output.contains("AudioLength: 180000"));
+        assert!(!Beatmap::default().to_osu_string().contains("CurrentTime"));
+    }
+
+    #[test]
+    fn test_short_timing_points() {
+        let map = parse_beatmap("osu file format v3\n\n[TimingPoints]\n764,363.63\n1000,-50,3,2\n").unwrap();
+
+        assert_eq!(map.timing_points[0], TimingPoint {
+            offset: Time(764.0),
+            ms_per_beat: 363.63,
+            meter: 4,
+            sample_set: 0,
+            sample_index: 0,
+            volume: 100,
+            inherited: true,
+            effects: Effects::default(),
+        });
+        assert_eq!(map.timing_points[1].meter, 3);
+        assert_eq!(map.timing_points[1].sample_set, 2);
+        assert_eq!(map.timing_points[1].volume, 100);
+
+        assert!(parse_beatmap("osu file format v3\n\n[TimingPoints]\n764\n").is_err());
+    }
+
+    #[test]
+    fn test_section_layouts() {
+        // No [Editor], [Colours] or [Events], and sections out of order
+        let input = "osu file format v14\n\n[HitObjects]\n256,192,1000,1,0\n\n\
+                     [TimingPoints]\n0,500,4,1,0,100,1,0\n\n[Metadata]\nTitle:Title\n\n\
+                     [General]\nMode: 1\n";
+        let map = parse_beatmap(input).unwrap();
+        assert_eq!(map.hit_objects.len(), 1);
+        assert_eq!(map.timing_points.len(), 1);
+        assert_eq!(map.metadata.title, "Title");
+        assert_eq!(map.general.game_mode, GameMode::Taiko);
+        assert_eq!(map.editor, EditorSection::default());
+        assert!(map.colours.colours.is_empty());
+        assert!(map.events.background.is_none());
+
+        // Repeated sections add to the earlier ones
+        let input = "osu file format v14\n\n[General]\nAudioFilename: a.mp3\nMode: 1\n\n\
+                     [Colours]\nCombo1 : 1,1,1\nCombo2 : 2,2,2\n\n[HitObjects]\n256,192,1000,1,0\n\n\
+                     [General]\nMode: 3\n\n[Colours]\nCombo2 : 3,3,3\n\n[HitObjects]\n256,192,2000,1,0\n";
+        let (map, spans) = parse_beatmap_with_spans(input).unwrap();
+        assert_eq!(map.general.audio_filename, "a.mp3");
+        assert_eq!(map.general.game_mode, GameMode::Mania);
+        assert_eq!(map.present_keys.general.len(), 2);
+        assert_eq!(map.colours.colours, vec![Colour(1, 1, 1), Colour(3, 3, 3)]);
+        assert_eq!(map.hit_objects.iter().map(HitObject::time).collect::<Vec<_>>(), vec![Time(1000.0), Time(2000.0)]);
+        assert_eq!(spans.hit_object(1).unwrap().line, 21);
+    }
+
+    #[test]
+    fn test_decimal_times() {
+        let input = "osu file format v14\n\n[General]\nPreviewTime: 764.5\n\n[Events]\n2,1200.5,1800\n\n\
+                     [TimingPoints]\n764.5,500,4,1,0,100,1,0\n\n\
+                     [HitObjects]\n256.0,192.7,764.5,1,0\n256,192,2000.9,8,0,3000.5\n";
+        let map = parse_beatmap(input).unwrap();
+
+        assert_eq!(map.general.preview_time, Time(764.5));
+        assert_eq!(map.events.breaks[0].start_time, Time(1200.5));
+        assert_eq!(map.hit_objects[0].position(), (256, 192));
+        assert_eq!(map.hit_objects[0].time(), Time(764.5));
+        assert_eq!(map.hit_objects[0].time(), map.timing_points[0].offset);
+        match map.hit_objects[1] {
+            HitObject::Spinner(ref spinner) => assert_eq!((spinner.time, spinner.end_time), (Time(2000.9), Time(3000.5))),
+            ref o => panic!("expected a spinner, got {:?}", o),
+        }
+
+        let output = map.to_osu_string();
+        assert!(output.contains("PreviewTime: 764.5"));
+        assert!(output.contains("2,1200.5,1800"));
+        assert!(output.contains("256,192,764.5,1,0"));
+        assert!(output.contains("256,192,2000.9,8,0,3000.5"));
+
+        assert!(parse_beatmap("osu file format v14\n\n[HitObjects]\n256,192,1e20,1,0\n").is_err());
+        assert!(parse_beatmap("osu file format v14\n\n[HitObjects]\n256,192,NaN,1,0\n").is_err());
+    }
+
+    #[test]
+    fn test_comments() {
+        let map = parse_beatmap(r"osu file format v14
+// A comment before any section
+
+[Metadata]
+Title:Title // not a comment
+
+[TimingPoints]
+0,500,4,2,1,50,1,0 // trailing comment
+  // indented comment
+
+[HitObjects]
+256,192,1000,1,0,0:0:0:0:
+// comment between hit objects
+256,192,2000,1,0,0:0:0:0:
+").unwrap();
+
+        assert_eq!(map.metadata.title, "Title // not a comment");
+        assert_eq!(map.timing_points.len(), 1);
+        assert_eq!(map.timing_points[0].effects, Effects::default());
+        assert_eq!(map.hit_objects.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_partial() {
+        let input = "not a version line
+
+[General]
+Mode: 1
+StackLeniency: lots
+
+[Unknown]
+Key: value
+
+[TimingPoints]
+0,500,4,1,0,100,1,0
+oops
+1000,-100,4,1,0,100,0,0
+
+[HitObjects]
+256,192,1000,1,0
+256,192,oops,1,0
+256,192,2000,1,0
+";
+
+        assert!(parse_beatmap(input).is_err());
+
+        let (map, errors) = parse_beatmap_partial(input);
+        assert_eq!(errors.len(), 5);
+        assert_eq!(map.version, 14);
+        assert_eq!(map.general.game_mode, GameMode::Taiko);
+        assert_eq!(map.timing_points.len(), 2);
+        assert_eq!(map.hit_objects.len(), 2);
+
+        assert!(errors[2].to_string().contains("Unknown section header"));
+        assert!(errors[4].to_string().starts_with("Syntax error on line 17"));
+    }
+
+    #[test]
+    fn test_lenient_hit_objects() {
+        let map = parse_beatmap(r"osu file format v14
+
+[HitObjects]
+256,192,1000,1,0,1:2:0:0:,extra,fields
+256,192,1500,1,0,
+256,192,2000,8,0,3000,0:0:0:0:,
+64,192,3000,128,0,4000
+64,192,4000,128,0,5000:1:2
+256,192,5000,1,0,2:3
+").unwrap();
+
+        assert_eq!(map.hit_objects.len(), 6);
+
+        match map.hit_objects[0] {
+            HitObject::HitCircle(ref c) => {
+                assert_eq!((c.extras.sample_set, c.extras.addition_set), (1, 2))
+            }
+            _ => panic!("expected a hit circle"),
+        }
+
+        match map.hit_objects[3] {
+            HitObject::HoldNote(ref n) => assert_eq!(n.end_time, Time(4000.0)),
+            _ => panic!("expected a hold note"),
+        }
+
+        match map.hit_objects[4] {
+            HitObject::HoldNote(ref n) => {
+                assert_eq!(n.end_time, Time(5000.0));
+                assert_eq!((n.extras.sample_set, n.extras.addition_set), (1, 2));
+            }
+            _ => panic!("expected a hold note"),
+        }
+
+        match map.hit_objects[5] {
+            HitObject::HitCircle(ref c) => {
+                assert_eq!((c.extras.sample_set, c.extras.addition_set), (2, 3))
+            }
+            _ => panic!("expected a hit circle"),
+        }
+
+        // Fields that are there but malformed aren't mistaken for missing ones
+        assert!(parse::parse_hit_object("256,192,1000,2,0,L|356:192,2,100,2|x|0").is_err());
+        assert!(parse::parse_hit_object("256,192,1000,2,0,L|356:192,2,100,0|0|0,0:0|x|0:0").is_err());
+        assert!(parse::parse_hit_object("256,192,1000,1,0,1:x:0:0:").is_err());
+        assert!(parse::parse_hit_object("64,192,1000,128,0,2000:x:0:0:0:").is_err());
+
+        let (map, errors) = parse_beatmap_partial("osu file format v14\n\n[HitObjects]\n\
+                                                   256,192,1000,1,0,1:x:0:0:\n256,192,2000,1,0\n");
+        assert_eq!(map.hit_objects.len(), 1);
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_raw_type() {
+        let input = "osu file format v14
+
+[HitObjects]
+256,192,1000,261,0,0:0:0:0:
+256,192,2000,44,0,3000,0:0:0:0:
+";
+
+        let map = parse_beatmap(input).unwrap();
+        assert_eq!(map.hit_objects[0].raw_type(), 261);
+        match map.hit_objects[1] {
+            HitObject::Spinner(ref s) => {
+                assert_eq!(s.raw_type, 44);
+                assert!(s.new_combo);
+                assert_eq!(s.color_skip, 2);
+            }
+            _ => panic!("expected a spinner"),
+        }
+
+        let output = map.to_osu_string();
+        assert!(output.contains("\n256,192,1000,261,0,"));
+        assert!(output.contains("\n256,192,2000,44,0,3000,"));
+    }
+
+    #[test]
+    fn test_range_policy() {
+        let input = "osu file format v14
+
+[TimingPoints]
+0,500,4,1,0,120,1,0
+
+[HitObjects]
+256,192,1000,1,0,0:0:0:70:
+200000,192,2000,2,0,L|300:-150000,1,100
+";
+
+        let (map, warnings) = parse_beatmap_with_options(input, &Default::default()).unwrap();
+        assert_eq!(map.timing_points[0].volume, 120);
+        assert_eq!(map.hit_objects[1].position(), (200000, 192));
+        assert_eq!(warnings.len(), 3);
+        assert_eq!(warnings[0].line, Some(4));
+        assert_eq!(warnings[2].line, Some(8));
+
+        let clamp = ParserOptions { range_policy: RangePolicy::Clamp, ..Default::default() };
+        let (map, warnings) = parse_beatmap_with_options(input, &clamp).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(map.timing_points[0].volume, 100);
+        match map.hit_objects[1] {
+            HitObject::Slider(ref s) => {
+                assert_eq!((s.x, s.y), (131_072, 192));
+                assert_eq!(s.curve_points, vec![(300, -131_072)]);
+            }
+            _ => panic!("expected a slider"),
+        }
+
+        let error = ParserOptions { range_policy: RangePolicy::Error, ..Default::default() };
+        match parse_beatmap_with_options(input, &error) {
+            Err(Error::Syntax(Some(ref location), _)) if location.line == 3 => {}
+            other => panic!("expected a syntax error on line 4, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_loose_section_headers() {
+        let input = "osu file format v14
+
+[ general ]
+Mode: 3
+
+[METADATA]
+Title:a // b
+
+[Colors]
+Combo1 : 255,0,0
+";
+
+        let map = parse_beatmap(input).unwrap();
+        assert_eq!(map.general.game_mode, GameMode::Mania);
+        assert_eq!(map.metadata.title, "a // b");
+        assert_eq!(map.colours.colours, vec![Colour(255, 0, 0)]);
+
+        let raw = parse_raw(input).unwrap();
+        assert_eq!(raw.section("Colours").unwrap().name, "Colors");
+        assert_eq!(raw.section("General").unwrap().value("Mode"), Some("3"));
+    }
+
+    #[test]
+    fn test_error_snippets() {
+        let location = |input: &str| match parse_beatmap(input) {
+            Err(Error::Syntax(Some(location), _)) => location,
+            other => panic!("expected a syntax error, got {:?}", other),
+        };
+
+        let l = location("osu file format v14\n\n[TimingPoints]\n0,500,4,x,0,100,1,0\n");
+        assert_eq!((l.line, l.column, l.section.as_deref()), (3, Some(8), Some("TimingPoints")));
+
+        let l = location("osu file format v14\n\n[HitObjects]\n0,0,100,2,0,B|10:20|30:y,1,100\n");
+        assert_eq!(l.column, Some(23));
+        assert_eq!(l.snippet(), "  4 | 0,0,100,2,0,B|10:20|30:y,1,100\n    |                        ^");
+
+        let l = location("osu file format v14\n\n[Colours]\nCombo1 : 255,abc,0\n");
+        assert_eq!(l.column, Some(13));
+
+        let error = parse_beatmap("osu file format v14\n\n[Difficulty]\nHPDrainRate: high\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Syntax error on line 4 in [Difficulty]: Unable to parse number\n  4 | HPDrainRate: high\n    |              ^^^^"
+        );
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let input = "osu file format v14\n\n[Metadata]\nTitle:First\ntitle:Second\nArtist:Artist\n";
+        let parse = |duplicate_keys| {
+            let options = ParserOptions { duplicate_keys, ..Default::default() };
+            parse_beatmap_with_options(input, &options)
+        };
+
+        let (map, warnings) = parse(DuplicateKeyPolicy::LastWins).unwrap();
+        assert_eq!(map.metadata.title, "Second");
+        assert!(warnings.is_empty());
+
+        let (map, warnings) = parse(DuplicateKeyPolicy::FirstWins).unwrap();
+        assert_eq!(map.metadata.title, "First");
+        assert!(warnings.is_empty());
+
+        let (map, warnings) = parse(DuplicateKeyPolicy::Warn).unwrap();
+        assert_eq!(map.metadata.title, "Second");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line, Some(5));
+
+        match parse(DuplicateKeyPolicy::Error) {
+            Err(Error::Syntax(Some(ref location), _)) if location.line == 4 => {}
+            other => panic!("expected a syntax error on line 5, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_duplicate_colour_keys() {
+        let input = "osu file format v14\n\n[Colours]\nCombo1 : 1,1,1\nSliderBody : 2,2,2\n\
+                     Combo1 : 3,3,3\nSliderBody : 4,4,4\nCombo2 : 5,5,5\n";
+        let parse = |duplicate_keys| {
+            let options = ParserOptions { duplicate_keys, ..Default::default() };
+            parse_beatmap_with_options(input, &options)
+        };
+
+        let (map, warnings) = parse(DuplicateKeyPolicy::LastWins).unwrap();
+        assert_eq!(map.colours.colours, vec![Colour(3, 3, 3), Colour(5, 5, 5)]);
+        assert_eq!(map.colours.slider_body, Some(Colour(4, 4, 4)));
+        assert!(warnings.is_empty());
+
+        let (map, _) = parse(DuplicateKeyPolicy::FirstWins).unwrap();
+        assert_eq!(map.colours.colours, vec![Colour(1, 1, 1), Colour(5, 5, 5)]);
+        assert_eq!(map.colours.slider_body, Some(Colour(2, 2, 2)));
+
+        let (_, warnings) = parse(DuplicateKeyPolicy::Warn).unwrap();
+        assert_eq!(warnings.len(), 2);
+
+        assert!(parse(DuplicateKeyPolicy::Error).is_err());
+    }
+
+    #[test]
+    fn test_repeated_sections() {
+        let input = "osu file format v14\n\n[Metadata]\nTitle:Title\nArtist:Artist\n\n\
+                     [HitObjects]\n256,192,1000,1,0\n\n[Metadata]\nTitle:Patched\n\n\
+                     [HitObjects]\n256,192,2000,1,0\n";
+        let parse = |repeated_sections| {
+            let options = ParserOptions { repeated_sections, ..Default::default() };
+            parse_beatmap_with_options(input, &options)
+        };
+
+        let (map, warnings) = parse(RepeatedSectionPolicy::Merge).unwrap();
+        assert_eq!((map.metadata.title.as_str(), map.metadata.artist.as_str()), ("Patched", "Artist"));
+        assert_eq!(map.hit_objects.len(), 2);
+        assert_eq!(warnings.iter().map(|w| w.line).collect::<Vec<_>>(), vec![Some(10), Some(13)]);
+        assert_eq!(warnings[0].message, "Section Metadata appears more than once");
+
+        let (map, warnings) = parse(RepeatedSectionPolicy::Replace).unwrap();
+        assert_eq!((map.metadata.title.as_str(), map.metadata.artist.as_str()), ("Patched", ""));
+        assert_eq!(map.hit_objects.iter().map(HitObject::time).collect::<Vec<_>>(), vec![Time(2000.0)]);
+        assert_eq!(warnings.len(), 2);
+
+        match parse(RepeatedSectionPolicy::Error) {
+            Err(Error::Syntax(Some(ref location), _)) if location.line == 9 => {}
+            other => panic!("expected a syntax error on line 10, got {:?}", other),
+        }
+
+        let (map, errors) = parse_beatmap_partial(input);
+        assert!(errors.is_empty());
+        assert_eq!(map.hit_objects.len(), 2);
+    }
+
+    #[test]
+    fn test_check_object_types() {
+        let input = "osu file format v14\n\n[General]\nMode: 3\n\n[HitObjects]\n\
+                     64,192,1000,1,0\n64,192,2000,2,0,L|64:292,1,100\n64,192,3000,128,0,3500:0:0:0:0:\n";
+        let options = ParserOptions { check_object_types: true, ..Default::default() };
+
+        let (_, warnings) = parse_beatmap_with_options(input, &options).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].to_string(), "Warning on line 8: Mania beatmaps can't contain sliders");
+
+        let (_, warnings) = parse_beatmap_with_options(&input.replace("Mode: 3", "Mode: 0"), &options).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line, Some(9));
+
+        let (_, warnings) = parse_beatmap_with_options(input, &Default::default()).unwrap();
+        assert!(warnings.is_empty());
+    }
+
     #[test]
     fn test_parse_file() {
         let mut file = File::open("test.osu").unwrap();
@@ -672,6 +2159,9 @@ StackLeniency: 0.7
 Mode: 0
 LetterboxInBreaks: 1
 WidescreenStoryboard: 0
+AlwaysShowPlayfield: 1
+OverlayPosition: Above
+SamplesMatchPlaybackRate: 1
 
 [Editor]
 Bookmarks: 5,6
@@ -695,7 +2185,7 @@ BeatmapSetID:289074
 [TimingPoints]
 764,363.636363636364,4,2,1,50,1,0
 764,-133.333333333333,4,2,1,50,0,0
-3480,363.636363636364,4,2,1,50,1,8
+3480,363.636363636364,4,2,1,50,1,9
 3661,363.636363636364,4,2,1,50,1,0
 3661,-133.333333333333,4,2,1,50,0,0
 9479,-133.333333333333,4,1,0,50,0,0
@@ -732,11 +2222,32 @@ BeatmapSetID:289074
             map.general.audio_filename,
             "Bakemonogatari_-_Kimi_no_Shiranai_Monogatari.mp3"
         );
-        assert_eq!(map.general.audio_lead_in, 0);
-        assert_eq!(map.general.preview_time, 239594);
+        assert_eq!(map.general.audio_lead_in, Time(0.0));
+        assert_eq!(map.general.preview_time, Time(239594.0));
         assert_eq!(map.general.stack_leniency, 0.7);
         assert_eq!(map.general.sample_set, "Soft");
-        assert_eq!(map.editor.bookmarks, vec![5, 6]);
+        assert!(map.general.always_show_playfield);
+        assert_eq!(map.general.overlay_position, OverlayPosition::Above);
+        assert!(map.general.samples_match_playback_rate);
+        assert_eq!(map.editor.bookmarks, vec![Time(5.0), Time(6.0)]);
+
+        assert!(!map.timing_points[0].effects.kiai());
+        assert!(map.timing_points[2].effects.kiai());
+        assert!(map.timing_points[2].effects.omit_first_barline());
+
+        match map.hit_objects[1] {
+            HitObject::Slider(ref slider) => assert_eq!(
+                slider.edge_additions,
+                vec![
+                    EdgeSampleSet {
+                        sample_set: 0,
+                        addition_set: 2,
+                    },
+                    EdgeSampleSet::default(),
+                ]
+            ),
+            _ => panic!("expected a slider"),
+        }
     }
 
     #[test]
@@ -748,11 +2259,26 @@ BeatmapSetID:289074
         let map = parse_beatmap(contents.as_str()).unwrap();
 
         // Deserialize to string
-        let map_string = map.as_parsed();
+        let map_string = map.to_osu_string();
         println!("{}", map_string);
 
         // Serialize from string
         let map2 = parse_beatmap(map_string.as_str()).unwrap();
         assert_eq!(map, map2);
     }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_roundtrip() {
+        let mut file = File::open("map.osu").unwrap();
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        let map = parse_beatmap(contents.as_str()).unwrap();
+
+        let json = map.to_json().unwrap();
+        assert!(json.contains(r#""type":"slider""#));
+        assert!(json.contains(r#""game_mode":"osu""#));
+
+        assert_eq!(Beatmap::from_json(&json).unwrap(), map);
+    }
 }
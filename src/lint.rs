@@ -0,0 +1,307 @@
+//! Checks of a beatmap against the ranking criteria.
+//!
+//! Each check is identified by a rule name, so that tools can filter or
+//! explain findings. The checks cover the most common issues, and passing
+//! them doesn't mean a beatmap meets every criterion.
+
+use super::*;
+
+/// Minimum length of a spinner in milliseconds.
+const MIN_SPINNER_LENGTH: Time = Time(200.0);
+
+/// How serious a finding is.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Copy, Clone)]
+pub enum Severity {
+    /// Worth knowing, but not an issue by itself.
+    Info,
+    /// Usually an issue, but allowed in some cases.
+    Warning,
+    /// Prevents the beatmap from being ranked.
+    Problem,
+}
+
+/// An issue found in a beatmap.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Finding {
+    /// Name of the check that found the issue, e.g. `duplicate-timing-point`.
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    /// Where in the beatmap the issue is, if it is tied to a point in time.
+    pub time: Option<Time>,
+}
+
+impl Finding {
+    fn new(rule: &'static str, severity: Severity, message: String, time: Option<Time>) -> Self {
+        Finding { rule, severity, message, time }
+    }
+}
+
+impl Beatmap {
+    /// Runs every check, returning what was found ordered by time. Issues
+    /// with the beatmap as a whole come first.
+    pub fn lint(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+
+        check_metadata(self, &mut findings);
+        check_difficulty(self, &mut findings);
+        check_combo_colours(self, &mut findings);
+        check_timing_points(self, &mut findings);
+        check_objects(self, &mut findings);
+
+        // Sorting is stable, so findings at the same time stay grouped by rule
+        findings.sort_by_key(|f| f.time);
+        findings
+    }
+}
+
+fn check_metadata(map: &Beatmap, findings: &mut Vec<Finding>) {
+    let metadata = &map.metadata;
+    let mut check = |name: &str, romanised: &str, unicode: &str| {
+        if !romanised.is_ascii() {
+            findings.push(Finding::new(
+                "romanised-metadata",
+                Severity::Problem,
+                format!("Romanised {} \"{}\" has characters outside of ASCII", name, romanised),
+                None,
+            ));
+        }
+
+        if unicode.is_empty() && !romanised.is_empty() {
+            findings.push(Finding::new(
+                "unicode-metadata",
+                Severity::Problem,
+                format!("Unicode {} isn't set", name),
+                None,
+            ));
+        } else if unicode != romanised && unicode.is_ascii() && romanised.is_ascii() {
+            // An ASCII title needs no romanisation, so both should be the same
+            findings.push(Finding::new(
+                "unicode-metadata",
+                Severity::Warning,
+                format!("Unicode {} \"{}\" differs from the romanised one", name, unicode),
+                None,
+            ));
+        }
+    };
+
+    check("title", &metadata.title, &metadata.title_unicode);
+    check("artist", &metadata.artist, &metadata.artist_unicode);
+}
+
+fn check_difficulty(map: &Beatmap, findings: &mut Vec<Finding>) {
+    let difficulty = &map.difficulty;
+    let mut check = |name: &str, value: f32, min: f32, max: f32| {
+        if value < min || value > max {
+            findings.push(Finding::new(
+                "difficulty-range",
+                Severity::Problem,
+                format!("{} {} is outside of the range {} to {}", name, value, min, max),
+                None,
+            ));
+        }
+    };
+
+    check("HP drain rate", difficulty.hp_drain_rate, 0.0, 10.0);
+    check("Overall difficulty", difficulty.overall_difficulty, 0.0, 10.0);
+    match map.general.game_mode {
+        GameMode::Osu | GameMode::CTB => {
+            check("Circle size", difficulty.circle_size, 2.0, 7.0);
+            check("Approach rate", difficulty.approach_rate, 0.0, 10.0);
+        }
+        GameMode::Mania => check("Key count", difficulty.circle_size, 4.0, 10.0),
+        GameMode::Taiko => {}
+    }
+}
+
+fn check_combo_colours(map: &Beatmap, findings: &mut Vec<Finding>) {
+    let uses_colours = map.general.game_mode == GameMode::Osu || map.general.game_mode == GameMode::CTB;
+    if uses_colours && map.colours.colours.is_empty() {
+        findings.push(Finding::new(
+            "missing-combo-colours",
+            Severity::Warning,
+            "No combo colours are set, so the player's skin colours are used".to_string(),
+            None,
+        ));
+    }
+}
+
+fn check_timing_points(map: &Beatmap, findings: &mut Vec<Finding>) {
+    let first_uninherited = map.timing_points.iter().find(|p| p.is_uninherited());
+    let first_uninherited = match first_uninherited {
+        Some(p) => p,
+        None => {
+            findings.push(Finding::new(
+                "uninherited-placement",
+                Severity::Problem,
+                "There are no uninherited timing points".to_string(),
+                None,
+            ));
+            return;
+        }
+    };
+
+    if let Some(first) = map.hit_objects.iter().map(HitObject::time).min() {
+        if first < first_uninherited.offset {
+            findings.push(Finding::new(
+                "uninherited-placement",
+                Severity::Problem,
+                "Hit object before the first uninherited timing point".to_string(),
+                Some(first),
+            ));
+        }
+    }
+
+    for p in map.timing_points.iter().filter(|p| !p.is_uninherited()) {
+        if p.offset < first_uninherited.offset {
+            findings.push(Finding::new(
+                "uninherited-placement",
+                Severity::Warning,
+                "Inherited timing point before the first uninherited one".to_string(),
+                Some(p.offset),
+            ));
+        }
+    }
+
+    for w in map.timing_points.windows(2) {
+        let (a, b) = (&w[0], &w[1]);
+        if a.offset == b.offset && a.is_uninherited() == b.is_uninherited() {
+            findings.push(Finding::new(
+                "duplicate-timing-point",
+                Severity::Warning,
+                "Two timing points of the same kind at the same time; only the last one is used".to_string(),
+                Some(b.offset),
+            ));
+        } else if !b.is_uninherited() && a.offset != b.offset && changes_nothing(a, b) {
+            findings.push(Finding::new(
+                "duplicate-timing-point",
+                Severity::Info,
+                "Inherited timing point changes nothing".to_string(),
+                Some(b.offset),
+            ));
+        }
+    }
+}
+
+/// Whether the inherited timing point `b` keeps every setting of the point
+/// before it, `a`.
+fn changes_nothing(a: &TimingPoint, b: &TimingPoint) -> bool {
+    a.slider_velocity_multiplier() == b.slider_velocity_multiplier()
+        && a.sample_set == b.sample_set
+        && a.sample_index == b.sample_index
+        && a.volume == b.volume
+        && a.effects == b.effects
+}
+
+fn check_objects(map: &Beatmap, findings: &mut Vec<Finding>) {
+    let index = map.timing_index();
+    for (i, obj) in map.hit_objects.iter().enumerate() {
+        let next_time = map.hit_objects.get(i + 1).map(HitObject::time);
+
+        match obj {
+            HitObject::Spinner(s) if s.end_time - s.time < MIN_SPINNER_LENGTH => {
+                findings.push(Finding::new(
+                    "short-spinner",
+                    Severity::Problem,
+                    format!("Spinner lasts {}, less than {}", s.end_time - s.time, MIN_SPINNER_LENGTH),
+                    Some(s.time),
+                ));
+            }
+            HitObject::Spinner(s) => {
+                let beat_length = index.beat_length_at(s.time).unwrap_or(0.0);
+                if (s.end_time - s.time).0 < beat_length {
+                    findings.push(Finding::new(
+                        "short-spinner",
+                        Severity::Warning,
+                        "Spinner lasts less than a beat".to_string(),
+                        Some(s.time),
+                    ));
+                }
+            }
+            HitObject::Slider(s) => {
+                if !s.has_valid_edges() {
+                    findings.push(Finding::new(
+                        "slider-edges",
+                        Severity::Warning,
+                        format!(
+                            "Slider has {} edges, but {} edge hitsounds and {} edge sample sets",
+                            s.edge_count(),
+                            s.edge_hitsounds.len(),
+                            s.edge_additions.len()
+                        ),
+                        Some(s.time),
+                    ));
+                }
+
+                let end_time = index.object_end_time(obj);
+                if let Some(next) = next_time {
+                    if end_time > next {
+                        findings.push(Finding::new(
+                            "long-slider",
+                            Severity::Problem,
+                            "Slider ends after the next object starts".to_string(),
+                            Some(s.time),
+                        ));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint() {
+        let input = "osu file format v14
+
+[Metadata]
+Title:Yoru ni ☆
+TitleUnicode:夜に☆
+Artist:Artist
+
+[Difficulty]
+CircleSize:8
+SliderMultiplier:1
+
+[TimingPoints]
+500,-100,4,1,0,100,0,0
+1000,500,4,1,0,100,1,0
+1000,400,4,1,0,100,1,0
+2000,-100,4,1,0,100,0,0
+3000,-100,4,1,0,100,0,0
+
+[HitObjects]
+0,0,800,1,0
+0,0,2000,2,0,L|200:0,1,200
+0,0,2500,8,0,2600
+0,0,3000,8,0,3300
+0,0,4000,2,0,L|100:0,1,100,0|0|0
+";
+
+        let map = parse_beatmap(input).unwrap();
+        let findings: Vec<(&str, Severity, Option<Time>)> = map.lint()
+            .iter()
+            .map(|f| (f.rule, f.severity, f.time))
+            .collect();
+
+        assert_eq!(findings, vec![
+            ("romanised-metadata", Severity::Problem, None),
+            ("unicode-metadata", Severity::Problem, None),
+            ("difficulty-range", Severity::Problem, None),
+            ("missing-combo-colours", Severity::Warning, None),
+            ("uninherited-placement", Severity::Warning, Some(Time(500.0))),
+            ("uninherited-placement", Severity::Problem, Some(Time(800.0))),
+            ("duplicate-timing-point", Severity::Warning, Some(Time(1000.0))),
+            ("duplicate-timing-point", Severity::Info, Some(Time(2000.0))),
+            ("long-slider", Severity::Problem, Some(Time(2000.0))),
+            ("short-spinner", Severity::Problem, Some(Time(2500.0))),
+            ("duplicate-timing-point", Severity::Info, Some(Time(3000.0))),
+            ("short-spinner", Severity::Warning, Some(Time(3000.0))),
+            ("slider-edges", Severity::Warning, Some(Time(4000.0))),
+        ]);
+    }
+}
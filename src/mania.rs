@@ -0,0 +1,535 @@
+//! Checks over the notes of an osu!mania beatmap, for reviewing charts.
+//!
+//! [`ManiaBeatmap::overlaps`](../modes/struct.ManiaBeatmap.html#method.overlaps)
+//! finds notes that can't be played because they start before the previous
+//! note in their column is released,
+//! [`chords`](../modes/struct.ManiaBeatmap.html#method.chords) groups the
+//! notes by the time they are hit and
+//! [`patterns`](../modes/struct.ManiaBeatmap.html#method.patterns) finds
+//! jacks and rolls. [`mirror`](../modes/struct.ManiaBeatmap.html#method.mirror)
+//! and [`remap_columns`](../modes/struct.ManiaBeatmap.html#method.remap_columns)
+//! move notes between columns, and
+//! [`convert_keys`](../modes/struct.ManiaBeatmap.html#method.convert_keys)
+//! changes the key count.
+
+use super::*;
+use modes::{ManiaBeatmap, ManiaNote, MAX_MANIA_KEYS};
+
+/// Fewest notes making up a roll.
+const MIN_ROLL_NOTES: usize = 3;
+
+/// A note that starts before the previous note in its column has ended.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Overlap<'a> {
+    pub column: usize,
+    /// The earlier note, which is still held or hit at the same time.
+    pub first: ManiaNote<'a>,
+    pub second: ManiaNote<'a>,
+}
+
+/// The notes hit at a single point in time.
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub struct Chord {
+    pub time: Time,
+    /// The columns with a note at `time`, from left to right.
+    pub columns: Vec<usize>,
+}
+
+impl Chord {
+    /// Number of notes in the chord. `1` for a single note.
+    pub fn size(&self) -> usize {
+        self.columns.len()
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub enum PatternKind {
+    /// Notes hit repeatedly in the same column.
+    Jack,
+    /// Single notes stepping through neighbouring columns in one direction,
+    /// such as `1234` or `4321`.
+    Roll,
+}
+
+/// A run of notes forming a jack or a roll.
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    /// Time of the first note.
+    pub start: Time,
+    /// Time of the last note.
+    pub end: Time,
+    /// The column of each note, in order. Every entry is the same for a
+    /// jack.
+    pub columns: Vec<usize>,
+}
+
+impl Pattern {
+    pub fn notes(&self) -> usize {
+        self.columns.len()
+    }
+}
+
+/// How [`ManiaBeatmap::convert_keys`](../modes/struct.ManiaBeatmap.html#method.convert_keys)
+/// picks the new column of each note.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ColumnMapping {
+    /// Keep each note at the same place across the playfield, so that going
+    /// from 7 keys to 4 puts the notes of columns 0 and 1 in column 0.
+    Proportional,
+    /// Wrap around, so that going from 7 keys to 4 puts the notes of columns
+    /// 0 and 4 in column 0. Expanding the key count leaves notes where they
+    /// are.
+    Wrap,
+}
+
+impl ColumnMapping {
+    fn column(self, column: usize, from: usize, to: usize) -> usize {
+        match self {
+            ColumnMapping::Proportional => ((column as f32 + 0.5) * to as f32 / from as f32) as usize,
+            ColumnMapping::Wrap => column % to,
+        }
+    }
+}
+
+impl<'a> ManiaBeatmap<'a> {
+    /// Notes that start before the previous note in their column ends,
+    /// including notes stacked at the same time and notes starting at the
+    /// very moment a hold note is released. Sorted by column, then by time.
+    ///
+    /// ```
+    /// use osuparse::Time;
+    ///
+    /// let map = osuparse::parse_beatmap("osu file format v14
+    ///
+    /// [General]
+    /// Mode: 3
+    ///
+    /// [Difficulty]
+    /// CircleSize:4
+    ///
+    /// [HitObjects]
+    /// 64,192,1000,128,0,1500:0:0:0:0:
+    /// 64,192,1200,1,0
+    /// 192,192,1200,1,0
+    /// ").unwrap();
+    ///
+    /// let overlaps = map.as_mania().unwrap().overlaps();
+    ///
+    /// assert_eq!(overlaps.len(), 1);
+    /// assert_eq!((overlaps[0].column, overlaps[0].second.time()), (0, Time(1200.0)));
+    /// ```
+    pub fn overlaps(&self) -> Vec<Overlap<'a>> {
+        let mut overlaps = Vec::new();
+        for (column, notes) in self.columns.iter().enumerate() {
+            let mut sorted = notes.clone();
+            sorted.sort_by_key(ManiaNote::time);
+
+            // The note released last so far, which a long hold note can keep
+            // being for several notes
+            let mut held: Option<ManiaNote<'a>> = None;
+            for note in sorted {
+                if let Some(first) = held {
+                    if note.time() <= first.end_time() {
+                        overlaps.push(Overlap { column, first, second: note });
+                    }
+                }
+                if held.map_or(true, |h| note.end_time() > h.end_time()) {
+                    held = Some(note);
+                }
+            }
+        }
+        overlaps
+    }
+
+    /// Every point in time with at least one note, with the columns hit
+    /// there, in order.
+    pub fn chords(&self) -> Vec<Chord> {
+        let mut notes: Vec<(Time, usize)> = self.columns
+            .iter()
+            .enumerate()
+            .flat_map(|(column, notes)| notes.iter().map(move |n| (n.time(), column)))
+            .collect();
+        notes.sort_unstable();
+
+        let mut chords: Vec<Chord> = Vec::new();
+        for (time, column) in notes {
+            match chords.last_mut() {
+                Some(chord) if chord.time == time => {
+                    // Stacked notes only count once
+                    if chord.columns.last() != Some(&column) {
+                        chord.columns.push(column);
+                    }
+                }
+                _ => chords.push(Chord { time, columns: vec![column] }),
+            }
+        }
+        chords
+    }
+
+    /// The jacks and rolls in the beatmap, sorted by start time. A jack is
+    /// two or more chords in a row sharing a column, and a roll is three or
+    /// more single notes in a row moving one column at a time in the same
+    /// direction. Notes more than `max_interval` milliseconds apart don't
+    /// continue a pattern.
+    ///
+    /// ```
+    /// use osuparse::Time;
+    /// use osuparse::mania::PatternKind;
+    ///
+    /// let map = osuparse::parse_beatmap("osu file format v14
+    ///
+    /// [General]
+    /// Mode: 3
+    ///
+    /// [Difficulty]
+    /// CircleSize:4
+    ///
+    /// [HitObjects]
+    /// 64,192,1000,1,0
+    /// 192,192,1100,1,0
+    /// 320,192,1200,1,0
+    /// 448,192,1300,1,0
+    /// 448,192,1400,1,0
+    /// ").unwrap();
+    ///
+    /// let patterns = map.as_mania().unwrap().patterns(Time(200.0));
+    /// let kinds: Vec<PatternKind> = patterns.iter().map(|p| p.kind).collect();
+    ///
+    /// assert_eq!(kinds, vec![PatternKind::Roll, PatternKind::Jack]);
+    /// assert_eq!(patterns[0].columns, vec![0, 1, 2, 3]);
+    /// assert_eq!((patterns[1].start, patterns[1].end), (Time(1300.0), Time(1400.0)));
+    /// ```
+    pub fn patterns(&self, max_interval: Time) -> Vec<Pattern> {
+        let chords = self.chords();
+        let mut patterns = Vec::new();
+
+        let mut jacks: Vec<Option<Pattern>> = vec![None; self.keys];
+        let mut roll: Option<Pattern> = None;
+        let mut last_time = None;
+        for chord in &chords {
+            let continues = last_time.is_some_and(|t| chord.time - t <= max_interval);
+
+            for (column, jack) in jacks.iter_mut().enumerate() {
+                let hit = chord.columns.contains(&column);
+                if let Some(ref mut run) = *jack {
+                    if hit && continues {
+                        run.end = chord.time;
+                        run.columns.push(column);
+                        continue;
+                    }
+                }
+
+                patterns.extend(jack.take().filter(|j| j.notes() > 1));
+                if hit {
+                    *jack = Some(Pattern {
+                        kind: PatternKind::Jack,
+                        start: chord.time,
+                        end: chord.time,
+                        columns: vec![column],
+                    });
+                }
+            }
+
+            roll = match (roll, chord.size()) {
+                (Some(mut run), 1) if continues => {
+                    let column = chord.columns[0];
+                    let n = run.notes();
+                    let last = run.columns[n - 1];
+                    let step = column as isize - last as isize;
+                    let direction = if n > 1 { last as isize - run.columns[n - 2] as isize } else { step };
+
+                    if step.abs() == 1 && step == direction {
+                        run.end = chord.time;
+                        run.columns.push(column);
+                        Some(run)
+                    } else {
+                        // Turning around starts a new roll from the last note
+                        let (start, columns) = if step.abs() == 1 {
+                            (run.end, vec![last, column])
+                        } else {
+                            (chord.time, vec![column])
+                        };
+                        if run.notes() >= MIN_ROLL_NOTES {
+                            patterns.push(run);
+                        }
+                        Some(Pattern { kind: PatternKind::Roll, start, end: chord.time, columns })
+                    }
+                }
+                (run, size) => {
+                    patterns.extend(run.filter(|r| r.notes() >= MIN_ROLL_NOTES));
+                    if size == 1 {
+                        Some(Pattern {
+                            kind: PatternKind::Roll,
+                            start: chord.time,
+                            end: chord.time,
+                            columns: chord.columns.clone(),
+                        })
+                    } else {
+                        None
+                    }
+                }
+            };
+
+            last_time = Some(chord.time);
+        }
+
+        patterns.extend(jacks.into_iter().flatten().filter(|j| j.notes() > 1));
+        patterns.extend(roll.filter(|r| r.notes() >= MIN_ROLL_NOTES));
+        patterns.sort_by_key(|p| (p.start, p.end));
+        patterns
+    }
+
+    /// A copy of the beatmap with its columns flipped from left to right.
+    pub fn mirror(&self) -> Beatmap {
+        let permutation: Vec<usize> = (0..self.keys).rev().collect();
+        self.remap(&permutation)
+    }
+
+    /// A copy of the beatmap with the notes of column `i` moved to column
+    /// `permutation[i]`. Every note is placed in the middle of its new
+    /// column, as the editor does. Fails unless `permutation` holds each
+    /// column exactly once.
+    ///
+    /// ```
+    /// let map = osuparse::parse_beatmap("osu file format v14
+    ///
+    /// [General]
+    /// Mode: 3
+    ///
+    /// [Difficulty]
+    /// CircleSize:4
+    ///
+    /// [HitObjects]
+    /// 64,192,1000,1,0
+    /// 200,192,1100,128,0,1500:0:0:0:0:
+    /// ").unwrap();
+    ///
+    /// let mania = map.as_mania().unwrap();
+    /// let remapped = mania.remap_columns(&[2, 0, 1, 3]).unwrap();
+    /// let xs: Vec<i32> = remapped.hit_objects.iter().map(|o| o.position().0).collect();
+    ///
+    /// assert_eq!(xs, vec![320, 64]);
+    /// assert_eq!(mania.mirror().hit_objects[0].position().0, 448);
+    /// assert!(mania.remap_columns(&[0, 0, 1, 2]).is_err());
+    /// ```
+    pub fn remap_columns(&self, permutation: &[usize]) -> Result<Beatmap> {
+        let mut seen = vec![false; self.keys];
+        for &column in permutation {
+            match seen.get_mut(column) {
+                Some(seen) if !*seen => *seen = true,
+                _ => return Err(Error::Message("Invalid column permutation")),
+            }
+        }
+        if permutation.len() != self.keys {
+            return Err(Error::Message("Invalid column permutation"));
+        }
+
+        Ok(self.remap(permutation))
+    }
+
+    /// A copy of the beatmap with `keys` keys, its circle size changed to
+    /// match and its notes moved to new columns picked by `mapping`.
+    ///
+    /// When fewer keys put a note in a column that is still held, or already
+    /// has a note at the same time, it goes to the nearest free column
+    /// instead, preferring the one on the left. Notes that fit nowhere are
+    /// left out, and returned alongside the new beatmap as they were.
+    ///
+    /// The beatmap id is reset, as the converted map isn't the submitted
+    /// difficulty.
+    ///
+    /// ```
+    /// use osuparse::mania::ColumnMapping;
+    ///
+    /// let map = osuparse::parse_beatmap("osu file format v14
+    ///
+    /// [General]
+    /// Mode: 3
+    ///
+    /// [Difficulty]
+    /// CircleSize:7
+    ///
+    /// [HitObjects]
+    /// 36,192,1000,1,0
+    /// 109,192,1000,1,0
+    /// 475,192,1000,1,0
+    /// ").unwrap();
+    ///
+    /// let (converted, dropped) = map.as_mania().unwrap().convert_keys(4, ColumnMapping::Proportional).unwrap();
+    /// let xs: Vec<i32> = converted.hit_objects.iter().map(|o| o.position().0).collect();
+    ///
+    /// assert_eq!(converted.difficulty.circle_size, 4.0);
+    /// assert_eq!(xs, vec![64, 192, 448]);
+    /// assert!(dropped.is_empty());
+    /// ```
+    pub fn convert_keys(&self, keys: usize, mapping: ColumnMapping) -> Result<(Beatmap, Vec<HitObject>)> {
+        if !(1..=MAX_MANIA_KEYS).contains(&keys) {
+            return Err(Error::Message("Invalid osu!mania key count"));
+        }
+
+        let mut map = self.beatmap.clone();
+        map.difficulty.circle_size = keys as f32;
+        map.metadata.beatmap_id = BeatmapId(0);
+
+        let mut order: Vec<usize> = (0..map.hit_objects.len()).collect();
+        order.sort_by_key(|&i| map.hit_objects[i].time());
+
+        // When each new column is free again
+        let mut held_until: Vec<Option<Time>> = vec![None; keys];
+        let mut kept = vec![false; map.hit_objects.len()];
+        for i in order {
+            let obj = &mut map.hit_objects[i];
+            let (time, end_time) = match obj {
+                HitObject::HoldNote(o) => (o.time, o.end_time),
+                _ => (obj.time(), obj.time()),
+            };
+            let target = mapping.column(obj.mania_column(self.keys), self.keys, keys).min(keys - 1);
+
+            let free = |column: usize| held_until[column].map_or(true, |until| until < time);
+            let column = (0..keys)
+                .flat_map(|distance| vec![target.checked_sub(distance), Some(target + distance)])
+                .flatten()
+                .find(|&column| column < keys && free(column));
+
+            if let Some(column) = column {
+                held_until[column] = Some(end_time);
+                kept[i] = true;
+                set_x(obj, column_x(column, keys));
+            }
+        }
+
+        // Dropped notes were never moved, so they are as they were
+        let mut kept = kept.into_iter();
+        let (objects, dropped) = map.hit_objects.into_iter().partition(|_| kept.next() == Some(true));
+        map.hit_objects = objects;
+        Ok((map, dropped))
+    }
+
+    fn remap(&self, permutation: &[usize]) -> Beatmap {
+        let mut map = self.beatmap.clone();
+        for obj in &mut map.hit_objects {
+            let x = column_x(permutation[obj.mania_column(self.keys)], self.keys);
+            set_x(obj, x);
+        }
+        map
+    }
+}
+
+fn set_x(obj: &mut HitObject, x: i32) {
+    match obj {
+        HitObject::HitCircle(o) => o.x = x,
+        HitObject::HoldNote(o) => o.x = x,
+        HitObject::Slider(o) => o.x = x,
+        HitObject::Spinner(o) => o.x = x,
+    }
+}
+
+/// The x position of the middle of a column.
+fn column_x(column: usize, keys: usize) -> i32 {
+    ((column as f32 + 0.5) * 512.0 / keys as f32) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_keys() {
+        let map = parse_beatmap("osu file format v14
+
+[General]
+Mode: 3
+
+[Metadata]
+BeatmapID:1234
+
+[Difficulty]
+CircleSize:7
+
+[HitObjects]
+36,192,1000,128,0,2000:0:0:0:0:
+109,192,1500,1,0
+182,192,1500,1,0
+256,192,1500,1,0
+329,192,1500,1,0
+402,192,1500,1,0
+475,192,1500,1,0
+329,192,3000,1,0
+").unwrap();
+        let mania = map.as_mania().unwrap();
+
+        let columns = |map: &Beatmap| -> Vec<(i32, usize)> {
+            map.hit_objects.iter().map(|o| (o.time().round(), o.mania_column(4))).collect()
+        };
+
+        // Column 0 is held, so only three of the six notes at 1500 fit
+        let (converted, dropped) = mania.convert_keys(4, ColumnMapping::Proportional).unwrap();
+        assert_eq!(columns(&converted), vec![(1000, 0), (1500, 1), (1500, 2), (1500, 3), (3000, 2)]);
+        let dropped: Vec<(i32, i32)> = dropped.iter().map(|o| (o.time().round(), o.position().0)).collect();
+        assert_eq!(dropped, vec![(1500, 329), (1500, 402), (1500, 475)]);
+        assert_eq!(converted.metadata.beatmap_id, BeatmapId(0));
+
+        let (converted, dropped) = mania.convert_keys(4, ColumnMapping::Wrap).unwrap();
+        assert_eq!(columns(&converted), vec![(1000, 0), (1500, 1), (1500, 2), (1500, 3), (3000, 0)]);
+        assert_eq!(dropped.len(), 3);
+
+        let (expanded, dropped) = converted.as_mania().unwrap().convert_keys(8, ColumnMapping::Proportional).unwrap();
+        assert!(dropped.is_empty());
+        let xs: Vec<i32> = expanded.hit_objects.iter().map(|o| o.position().0).collect();
+        assert_eq!(xs, vec![96, 224, 352, 480, 96]);
+
+        assert!(mania.convert_keys(0, ColumnMapping::Wrap).is_err());
+    }
+
+    #[test]
+    fn test_mania_analysis() {
+        let map = parse_beatmap("osu file format v14
+
+[General]
+Mode: 3
+
+[Difficulty]
+CircleSize:4
+
+[HitObjects]
+64,192,1000,128,0,2000:0:0:0:0:
+64,192,1500,1,0
+64,192,2000,1,0
+192,192,1000,1,0
+192,192,1000,1,0
+320,192,3000,1,0
+448,192,3100,1,0
+320,192,3200,1,0
+192,192,3300,1,0
+64,192,3400,1,0
+192,192,3400,1,0
+192,192,3500,1,0
+").unwrap();
+        let mania = map.as_mania().unwrap();
+
+        let overlaps: Vec<(usize, i32, i32)> = mania
+            .overlaps()
+            .iter()
+            .map(|o| (o.column, o.first.time().round(), o.second.time().round()))
+            .collect();
+        assert_eq!(overlaps, vec![(0, 1000, 1500), (0, 1000, 2000), (1, 1000, 1000)]);
+
+        let sizes: Vec<(i32, usize)> = mania.chords().iter().map(|c| (c.time.round(), c.size())).collect();
+        assert_eq!(sizes, vec![
+            (1000, 2), (1500, 1), (2000, 1), (3000, 1), (3100, 1),
+            (3200, 1), (3300, 1), (3400, 2), (3500, 1),
+        ]);
+
+        let patterns: Vec<(PatternKind, i32, i32, Vec<usize>)> = mania
+            .patterns(Time(500.0))
+            .into_iter()
+            .map(|p| (p.kind, p.start.round(), p.end.round(), p.columns))
+            .collect();
+        assert_eq!(patterns, vec![
+            (PatternKind::Jack, 1000, 2000, vec![0, 0, 0]),
+            (PatternKind::Roll, 3100, 3300, vec![3, 2, 1]),
+            (PatternKind::Jack, 3300, 3500, vec![1, 1, 1]),
+        ]);
+    }
+}
@@ -0,0 +1,223 @@
+use super::*;
+
+impl Beatmap {
+    /// Moves everything timed in the beatmap `offset` milliseconds later, or
+    /// earlier if negative: hit objects, timing points, events, bookmarks
+    /// and the preview point, if one is set. See
+    /// [`Events::shift_time`](events/struct.Events.html#method.shift_time).
+    pub fn shift_time(&mut self, offset: Time) {
+        for obj in &mut self.hit_objects {
+            let (time, end_time) = match obj {
+                HitObject::HitCircle(o) => (&mut o.time, None),
+                HitObject::Slider(o) => (&mut o.time, None),
+                HitObject::Spinner(o) => (&mut o.time, Some(&mut o.end_time)),
+                HitObject::HoldNote(o) => (&mut o.time, Some(&mut o.end_time)),
+            };
+            for t in Some(time).into_iter().chain(end_time) {
+                *t += offset;
+            }
+        }
+
+        for point in &mut self.timing_points {
+            point.offset += offset;
+        }
+        for bookmark in &mut self.editor.bookmarks {
+            *bookmark += offset;
+        }
+        if self.general.preview_time != Time(-1.0) {
+            self.general.preview_time += offset;
+        }
+
+        self.events.shift_time(offset);
+    }
+}
+
+/// Combines several beatmaps into one, as for a marathon or compilation.
+/// Each segment is given with the time its beatmap is moved by, using
+/// [`Beatmap::shift_time`](struct.Beatmap.html#method.shift_time). Hit
+/// objects, timing points, bookmarks and events are combined and sorted by
+/// time, and the first hit object of every segment starts a new combo.
+///
+/// The settings, metadata, background and colours of the first beatmap are
+/// used for the result. Segments whose slider multiplier differs get
+/// inherited timing points that keep their sliders at their original speed,
+/// though the speed is limited to the 0.1x–10x the game allows. Storyboard
+/// variables are dropped, since the events are already substituted, and so
+/// are events of unknown types, whose times can't be moved.
+///
+/// # Examples
+///
+/// ```
+/// use osuparse::{merge_beatmaps, parse_beatmap, Time};
+///
+/// let map = parse_beatmap("osu file format v14
+///
+/// [TimingPoints]
+/// 0,500,4,1,0,100,1,0
+///
+/// [HitObjects]
+/// 256,192,1000,1,0
+/// 256,192,1500,1,0
+/// ").unwrap();
+///
+/// let marathon = merge_beatmaps(&[(&map, Time::ZERO), (&map, Time(5000.0))]);
+/// let times: Vec<Time> = marathon.hit_objects.iter().map(|o| o.time()).collect();
+/// assert_eq!(times, vec![Time(1000.0), Time(1500.0), Time(6000.0), Time(6500.0)]);
+/// assert_eq!(marathon.timing_points.len(), 2);
+/// ```
+pub fn merge_beatmaps(segments: &[(&Beatmap, Time)]) -> Beatmap {
+    let mut merged = match segments.first() {
+        Some(&(first, _)) => Beatmap {
+            hit_objects: Vec::new(),
+            timing_points: Vec::new(),
+            ..first.clone()
+        },
+        None => return Beatmap::default(),
+    };
+    merged.editor.bookmarks.clear();
+    merged.events.breaks.clear();
+    merged.events.videos.clear();
+    merged.events.colour_transforms.clear();
+    merged.events.unknown_lines.clear();
+    merged.events.storyboard = Default::default();
+    merged.events.variables.clear();
+    merged.events.raw_lines.clear();
+
+    let slider_multiplier = merged.difficulty.slider_multiplier;
+    let mut preview_time = None;
+
+    for &(map, offset) in segments {
+        let mut segment = map.clone();
+        segment.shift_time(offset);
+
+        if let Some(obj) = segment.hit_objects.iter_mut().min_by_key(|o| o.time()) {
+            set_new_combo(obj);
+        }
+        if segment.difficulty.slider_multiplier != slider_multiplier && slider_multiplier > 0.0 {
+            let ratio = segment.difficulty.slider_multiplier / slider_multiplier;
+            segment.scale_slider_velocities(ratio);
+        }
+        if preview_time.is_none() && segment.general.preview_time != Time(-1.0) {
+            preview_time = Some(segment.general.preview_time);
+        }
+
+        merged.hit_objects.append(&mut segment.hit_objects);
+        merged.timing_points.append(&mut segment.timing_points);
+        merged.editor.bookmarks.append(&mut segment.editor.bookmarks);
+
+        let events = &mut segment.events;
+        merged.events.videos.append(&mut events.videos);
+        merged.events.breaks.append(&mut events.breaks);
+        merged.events.colour_transforms.append(&mut events.colour_transforms);
+        merged.events.storyboard.objects.append(&mut events.storyboard.objects);
+        merged.events.storyboard.samples.append(&mut events.storyboard.samples);
+    }
+
+    // Sorting is stable, so points at the same offset keep their order
+    merged.hit_objects.sort_by_key(HitObject::time);
+    merged.timing_points.sort_by_key(|p| p.offset);
+    merged.events.breaks.sort_by_key(|b| b.start_time);
+    merged.events.colour_transforms.sort_by_key(|t| t.time);
+    merged.editor.sort_bookmarks();
+    merged.general.preview_time = preview_time.unwrap_or(Time(-1.0));
+
+    merged
+}
+
+fn set_new_combo(obj: &mut HitObject) {
+    match obj {
+        HitObject::HitCircle(o) => o.new_combo = true,
+        HitObject::Slider(o) => o.new_combo = true,
+        HitObject::Spinner(o) => o.new_combo = true,
+        HitObject::HoldNote(o) => o.new_combo = true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_beatmaps() {
+        let first = parse_beatmap("osu file format v14
+
+[General]
+PreviewTime: -1
+
+[Editor]
+Bookmarks: 500
+
+[Difficulty]
+SliderMultiplier:1
+
+[Events]
+2,2000,3000
+
+[TimingPoints]
+0,500,4,1,0,100,1,0
+
+[HitObjects]
+256,192,1000,1,0
+256,192,4000,1,0
+").unwrap();
+
+        let second = parse_beatmap("osu file format v14
+
+[General]
+PreviewTime: 1500
+
+[Difficulty]
+SliderMultiplier:2
+
+[Events]
+Sprite,Foreground,Centre,\"sb/a.png\",320,240
+ F,0,1000,2000,0,1
+
+[TimingPoints]
+0,400,4,1,0,100,1,0
+1000,-50,4,1,0,100,0,0
+
+[HitObjects]
+256,192,1000,2,0,L|256:292,1,100
+256,192,2000,8,0,3000
+").unwrap();
+
+        let merged = merge_beatmaps(&[(&first, Time::ZERO), (&second, Time(10000.0))]);
+
+        let times: Vec<f64> = merged.hit_objects.iter().map(|o| o.time().0).collect();
+        assert_eq!(times, vec![1000.0, 4000.0, 11000.0, 12000.0]);
+        assert!(merged.hit_objects[2].new_combo());
+        assert_eq!(merged.difficulty.slider_multiplier, 1.0);
+
+        let points: Vec<(f64, f64)> = merged.timing_points.iter().map(|p| (p.offset.0, p.ms_per_beat)).collect();
+        assert_eq!(points, vec![(0.0, 500.0), (10000.0, 400.0), (10000.0, -50.0), (11000.0, -25.0)]);
+        // The slider keeps its length in time
+        assert_eq!(merged.object_end_time(&merged.hit_objects[2]), Time(11100.0));
+        assert_eq!(second.object_end_time(&second.hit_objects[0]), Time(1100.0));
+
+        assert_eq!(merged.editor.bookmarks, vec![Time(500.0)]);
+        assert_eq!(merged.general.preview_time, Time(11500.0));
+        assert_eq!(merged.events.breaks.len(), 1);
+        assert_eq!(merged.events.storyboard.objects[0].sprite().start_time(), Some(Time(11000.0)));
+    }
+
+    #[test]
+    fn test_merge_colour_transforms() {
+        let map = parse_beatmap("osu file format v5
+
+[Events]
+3,100,163,162,255
+3,2000,0,0,0
+9,500,\"unknown\"
+
+[HitObjects]
+256,192,1000,1,0
+").unwrap();
+
+        let merged = merge_beatmaps(&[(&map, Time(500.0)), (&map, Time(1000.0))]);
+        let transforms: Vec<f64> = merged.events.colour_transforms.iter().map(|t| t.time.0).collect();
+        assert_eq!(transforms, vec![600.0, 1100.0, 2500.0, 3000.0]);
+        assert_eq!(merged.events.colour_transforms[1].colour, Colour(163, 162, 255));
+        assert!(merged.events.unknown_lines.is_empty());
+    }
+}
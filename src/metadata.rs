@@ -0,0 +1,234 @@
+use super::*;
+
+/// Characters that can't appear in a filename on at least one of the
+/// platforms osu! runs on.
+const INVALID_FILENAME_CHARS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Removes the characters that can't appear in a filename.
+fn sanitize(part: &str) -> String {
+    part.chars()
+        .filter(|c| !c.is_control() && !INVALID_FILENAME_CHARS.contains(c))
+        .collect()
+}
+
+/// `preferred`, unless it is empty.
+fn prefer<'a>(preferred: &'a str, fallback: &'a str) -> &'a str {
+    if preferred.is_empty() {
+        fallback
+    } else {
+        preferred
+    }
+}
+
+impl MetadataSection {
+    /// The name osu! gives the beatmap's file, in the form
+    /// `Artist - Title (Creator) [Version].osu`, with the characters that
+    /// aren't allowed in filenames removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use osuparse::MetadataSection;
+    ///
+    /// let metadata = MetadataSection {
+    ///     artist: "MISATO".to_string(),
+    ///     title: "Yoru Naku Usagi wa Yume o Miru".to_string(),
+    ///     creator: "Sotarks".to_string(),
+    ///     version: "Insane?".to_string(),
+    ///     ..Default::default()
+    /// };
+    ///
+    /// assert_eq!(
+    ///     metadata.filename(),
+    ///     "MISATO - Yoru Naku Usagi wa Yume o Miru (Sotarks) [Insane].osu"
+    /// );
+    /// ```
+    pub fn filename(&self) -> String {
+        format!(
+            "{} - {} ({}) [{}].osu",
+            sanitize(&self.artist),
+            sanitize(&self.title),
+            sanitize(&self.creator),
+            sanitize(&self.version)
+        )
+    }
+
+    /// Guesses the artist, title, creator and version of a beatmap from a
+    /// filename in the form produced by
+    /// [`filename`](#method.filename). The other fields are left empty.
+    ///
+    /// Characters removed when the filename was made can't be recovered, and
+    /// an artist containing ` - ` is split in the wrong place. Returns `None`
+    /// if the filename isn't in the expected form.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use osuparse::MetadataSection;
+    ///
+    /// let metadata = MetadataSection::from_filename("Camellia - Exit This Earth's Atomosphere (ProfessionalBox) [Evolution].osu").unwrap();
+    /// assert_eq!(metadata.artist, "Camellia");
+    /// assert_eq!(metadata.title, "Exit This Earth's Atomosphere");
+    /// assert_eq!(metadata.creator, "ProfessionalBox");
+    /// assert_eq!(metadata.version, "Evolution");
+    /// ```
+    pub fn from_filename(filename: &str) -> Option<MetadataSection> {
+        // Only the last path component is the filename
+        let name = filename.rsplit(['/', '\\']).next().unwrap_or(filename);
+        let name = name.strip_suffix(".osu").unwrap_or(name);
+
+        // Parse from the end, since the title may contain brackets itself
+        let name = name.strip_suffix(']')?;
+        let open = name.rfind(" [")?;
+        let (name, version) = (&name[..open], &name[open + 2..]);
+
+        let name = name.strip_suffix(')')?;
+        let open = name.rfind(" (")?;
+        let (name, creator) = (&name[..open], &name[open + 2..]);
+
+        let separator = name.find(" - ")?;
+        let (artist, title) = (&name[..separator], &name[separator + 3..]);
+
+        Some(MetadataSection {
+            artist: artist.to_string(),
+            title: title.to_string(),
+            creator: creator.to_string(),
+            version: version.to_string(),
+            ..Default::default()
+        })
+    }
+
+    /// The title in its original language, or the romanised title if the
+    /// beatmap doesn't have one. This is what osu! shows with "Prefer
+    /// metadata in original language" turned on.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use osuparse::MetadataSection;
+    ///
+    /// let mut metadata = MetadataSection {
+    ///     title: "Yoru Naku Usagi wa Yume o Miru".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(metadata.display_title(), "Yoru Naku Usagi wa Yume o Miru");
+    ///
+    /// metadata.title_unicode = "夜啼く兎は夢を見る".to_string();
+    /// assert_eq!(metadata.display_title(), "夜啼く兎は夢を見る");
+    /// assert_eq!(metadata.romanised_title(), "Yoru Naku Usagi wa Yume o Miru");
+    /// ```
+    pub fn display_title(&self) -> &str {
+        prefer(&self.title_unicode, &self.title)
+    }
+
+    /// The artist in their original language, or the romanised artist if
+    /// the beatmap doesn't have one.
+    pub fn display_artist(&self) -> &str {
+        prefer(&self.artist_unicode, &self.artist)
+    }
+
+    /// The romanised title, or the title in its original language if the
+    /// beatmap doesn't have one. This is what osu! shows by default.
+    pub fn romanised_title(&self) -> &str {
+        prefer(&self.title, &self.title_unicode)
+    }
+
+    /// The romanised artist, or the artist in their original language if
+    /// the beatmap doesn't have one.
+    pub fn romanised_artist(&self) -> &str {
+        prefer(&self.artist, &self.artist_unicode)
+    }
+
+    /// Whether the beatmap matches a search query the way osu!'s song
+    /// select does: the query is split into space-separated terms, and every
+    /// term must appear, ignoring case, in the artist, title, creator,
+    /// source or tags. Both the ASCII and unicode artist and title are
+    /// searched. An empty query matches every beatmap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use osuparse::MetadataSection;
+    ///
+    /// let metadata = MetadataSection {
+    ///     artist: "MISATO".to_string(),
+    ///     title: "Yoru Naku Usagi wa Yume o Miru".to_string(),
+    ///     tags: vec!["touhou".to_string(), "imperishable".to_string()],
+    ///     ..Default::default()
+    /// };
+    ///
+    /// assert!(metadata.matches_query("misato usagi"));
+    /// assert!(metadata.matches_query("Touhou"));
+    /// assert!(!metadata.matches_query("misato camellia"));
+    /// ```
+    pub fn matches_query(&self, query: &str) -> bool {
+        let fields = [
+            &self.artist,
+            &self.artist_unicode,
+            &self.title,
+            &self.title_unicode,
+            &self.creator,
+            &self.source,
+        ];
+        let haystack: Vec<String> = fields
+            .iter()
+            .map(|f| f.as_str())
+            .chain(self.tags.iter().map(String::as_str))
+            .map(str::to_lowercase)
+            .collect();
+
+        query
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .all(|term| haystack.iter().any(|field| field.contains(&term)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filename_roundtrip() {
+        let metadata = MetadataSection {
+            artist: "AC/DC".to_string(),
+            title: "Who Made Who (Live)".to_string(),
+            creator: "someone".to_string(),
+            version: "[Hard]".to_string(),
+            ..Default::default()
+        };
+
+        let filename = metadata.filename();
+        assert_eq!(filename, "ACDC - Who Made Who (Live) (someone) [[Hard]].osu");
+
+        let guess = MetadataSection::from_filename(&format!("Songs/1 ACDC/{}", filename)).unwrap();
+        assert_eq!(guess.artist, "ACDC");
+        assert_eq!(guess.title, "Who Made Who (Live)");
+        assert_eq!(guess.creator, "someone");
+        assert_eq!(guess.version, "[Hard]");
+
+        assert_eq!(MetadataSection::from_filename("map.osu"), None);
+        assert_eq!(MetadataSection::from_filename("Artist - Title [Hard].osu"), None);
+    }
+
+    #[test]
+    fn test_matches_query() {
+        let metadata = MetadataSection {
+            artist: "Camellia".to_string(),
+            title_unicode: "光線チューニング".to_string(),
+            creator: "Sotarks".to_string(),
+            source: "BOFU2015".to_string(),
+            version: "Extra".to_string(),
+            tags: vec!["Electronic".to_string()],
+            ..Default::default()
+        };
+
+        assert!(metadata.matches_query(""));
+        assert!(metadata.matches_query("  CAMELLIA   sotarks "));
+        assert!(metadata.matches_query("チューニング bofu"));
+        assert!(metadata.matches_query("electro"));
+        // The difficulty name isn't searched
+        assert!(!metadata.matches_query("extra"));
+        assert!(!metadata.matches_query("camellia nekodex"));
+    }
+}
@@ -0,0 +1,141 @@
+//! Metronome ticks following a beatmap's timing, for checking it outside
+//! the editor.
+
+use super::*;
+
+/// A single metronome tick.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Tick {
+    pub time: Time,
+    /// Whether the tick is on the first beat of a measure.
+    pub downbeat: bool,
+}
+
+impl Beatmap {
+    /// A tick on every beat from the first uninherited timing point until
+    /// `end_time`, such as the length of the song. Each uninherited timing
+    /// point starts a new measure with its own tempo and meter.
+    ///
+    /// ```
+    /// use osuparse::Time;
+    ///
+    /// let input = "osu file format v14
+    ///
+    /// [TimingPoints]
+    /// 100,500,3,1,0,100,1,0
+    /// ";
+    ///
+    /// let map = osuparse::parse_beatmap(input).unwrap();
+    /// let ticks = map.metronome_ticks(Time(2000.0));
+    ///
+    /// assert_eq!(ticks.len(), 4);
+    /// assert_eq!(ticks[3].time, Time(1600.0));
+    /// assert!(ticks[0].downbeat && ticks[3].downbeat);
+    /// ```
+    pub fn metronome_ticks(&self, end_time: Time) -> Vec<Tick> {
+        let points: Vec<&TimingPoint> = self.timing_points
+            .iter()
+            .filter(|p| p.is_uninherited() && p.ms_per_beat > 0.0 && p.ms_per_beat.is_finite())
+            .collect();
+
+        let mut ticks = Vec::new();
+        for (i, point) in points.iter().enumerate() {
+            let section_end = points.get(i + 1).map_or(end_time, |next| next.offset.min(end_time));
+            let meter = point.meter.max(1) as usize;
+
+            // Computing each time from the start avoids accumulating error
+            for beat in 0.. {
+                let time = point.beat_time(beat as f64);
+                if time >= section_end {
+                    break;
+                }
+                ticks.push(Tick { time, downbeat: beat % meter == 0 });
+            }
+        }
+
+        ticks
+    }
+}
+
+/// Renders ticks into a 16-bit mono WAV file of the given length.
+/// Downbeats are higher pitched than other beats.
+#[cfg(feature = "wav")]
+pub fn click_track_wav(ticks: &[Tick], length: Time, sample_rate: u32) -> Vec<u8> {
+    const CLICK_LENGTH: f32 = 0.03;
+
+    let samples_per_ms = f64::from(sample_rate) / 1000.0;
+    let total = (length.0.max(0.0) * samples_per_ms) as usize;
+    let mut samples = vec![0f32; total];
+
+    let click_samples = (CLICK_LENGTH * sample_rate as f32) as usize;
+    for tick in ticks {
+        let frequency = if tick.downbeat { 1500.0 } else { 1000.0 };
+        let start = (tick.time.0.max(0.0) * samples_per_ms) as usize;
+        for i in 0..click_samples.min(total.saturating_sub(start)) {
+            let t = i as f32 / sample_rate as f32;
+            let envelope = 1.0 - i as f32 / click_samples as f32;
+            samples[start + i] += (2.0 * std::f32::consts::PI * frequency * t).sin() * envelope * 0.8;
+        }
+    }
+
+    let data_len = (samples.len() * 2) as u32;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+        wav.extend_from_slice(&value.to_le_bytes());
+    }
+
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metronome_ticks() {
+        let input = "osu file format v14
+
+[TimingPoints]
+0,250,4,1,0,100,1,0
+500,-50,4,1,0,100,0,0
+900,300,3,1,0,100,1,0
+";
+
+        let map = parse_beatmap(input).unwrap();
+        let ticks: Vec<(f64, bool)> = map.metronome_ticks(Time(1600.0)).iter().map(|t| (t.time.0, t.downbeat)).collect();
+
+        assert_eq!(ticks, vec![
+            (0.0, true),
+            (250.0, false),
+            (500.0, false),
+            (750.0, false),
+            (900.0, true),
+            (1200.0, false),
+            (1500.0, false),
+        ]);
+    }
+
+    #[cfg(feature = "wav")]
+    #[test]
+    fn test_click_track_wav() {
+        let ticks = [Tick { time: Time::ZERO, downbeat: true }];
+        let wav = click_track_wav(&ticks, Time(100.0), 8000);
+
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(wav.len(), 44 + 800 * 2);
+        assert!(wav[44..].iter().any(|&b| b != 0));
+    }
+}
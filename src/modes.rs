@@ -0,0 +1,379 @@
+//! Views of a [`Beatmap`](../struct.Beatmap.html) that only expose the hit
+//! objects meaningful to a single game mode.
+//!
+//! Each view is created with one of [`Beatmap::as_std`](../struct.Beatmap.html#method.as_std),
+//! [`as_taiko`](../struct.Beatmap.html#method.as_taiko),
+//! [`as_catch`](../struct.Beatmap.html#method.as_catch) or
+//! [`as_mania`](../struct.Beatmap.html#method.as_mania), which fail if the
+//! beatmap is for a different mode or holds objects the mode can't have.
+
+use super::*;
+
+/// Largest key count supported by osu!mania.
+pub const MAX_MANIA_KEYS: usize = 18;
+
+/// Hitsound bits that turn a taiko hit into a kat.
+const KAT_HITSOUNDS: i32 = 2 | 8;
+/// Hitsound bit that makes a taiko hit big.
+const FINISH_HITSOUND: i32 = 4;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum StdObject<'a> {
+    Circle(&'a HitCircle),
+    Slider(&'a Slider),
+    Spinner(&'a Spinner),
+}
+
+/// An osu!standard beatmap.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StdBeatmap<'a> {
+    pub beatmap: &'a Beatmap,
+    pub objects: Vec<StdObject<'a>>,
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum TaikoColour {
+    Don,
+    Kat,
+}
+
+/// A single taiko note, which is a hit circle whose colour and size are
+/// taken from its hitsounds: whistles and claps make a kat, finishes make
+/// it big.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct TaikoHit<'a> {
+    pub circle: &'a HitCircle,
+    pub colour: TaikoColour,
+    pub big: bool,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum TaikoObject<'a> {
+    Hit(TaikoHit<'a>),
+    /// Sliders are played as drumrolls. They are big if the slider has a
+    /// finish.
+    Drumroll(&'a Slider),
+    /// Spinners are played as swells.
+    Swell(&'a Spinner),
+}
+
+impl<'a> TaikoObject<'a> {
+    pub fn time(&self) -> Time {
+        match self {
+            TaikoObject::Hit(h) => h.circle.time,
+            TaikoObject::Drumroll(s) => s.time,
+            TaikoObject::Swell(s) => s.time,
+        }
+    }
+}
+
+/// An osu!taiko beatmap.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TaikoBeatmap<'a> {
+    pub beatmap: &'a Beatmap,
+    pub objects: Vec<TaikoObject<'a>>,
+}
+
+impl<'a> TaikoBeatmap<'a> {
+    /// How fast notes scroll across the playfield at the given time, in
+    /// osu!pixels per millisecond: the distance a slider would travel, which
+    /// depends on the slider multiplier, the slider velocity and the tempo.
+    /// `0` if there are no uninherited timing points.
+    ///
+    /// ```
+    /// use osuparse::Time;
+    ///
+    /// let map = osuparse::parse_beatmap("osu file format v14
+    ///
+    /// [General]
+    /// Mode: 1
+    ///
+    /// [Difficulty]
+    /// SliderMultiplier:1.4
+    ///
+    /// [TimingPoints]
+    /// 0,500,4,1,0,100,1,0
+    /// 2000,-50,4,1,0,100,0,0
+    /// ").unwrap();
+    ///
+    /// let taiko = map.as_taiko().unwrap();
+    /// assert_eq!(taiko.scroll_speed(Time(1000.0)), 0.28);
+    /// assert_eq!(taiko.scroll_speed(Time(2000.0)), 0.56);
+    /// ```
+    pub fn scroll_speed(&self, time: Time) -> f32 {
+        scroll_speed(&self.beatmap.timing_index(), self.beatmap.difficulty.slider_multiplier, time)
+    }
+
+    /// The [`scroll_speed`](#method.scroll_speed) of every object when it
+    /// is hit, in the order of [`objects`](#structfield.objects).
+    pub fn scroll_speeds(&self) -> Vec<f32> {
+        let index = self.beatmap.timing_index();
+        let multiplier = self.beatmap.difficulty.slider_multiplier;
+
+        self.objects
+            .iter()
+            .map(|obj| scroll_speed(&index, multiplier, obj.time()))
+            .collect()
+    }
+
+    /// The times of the barlines shown between the notes. Each uninherited
+    /// timing point starts a barline, unless it omits its first one, and
+    /// another follows every [`meter`](../struct.TimingPoint.html#structfield.meter)
+    /// beats until the next uninherited point. The last barline is before
+    /// the end of the last object.
+    ///
+    /// ```
+    /// use osuparse::Time;
+    ///
+    /// let map = osuparse::parse_beatmap("osu file format v14
+    ///
+    /// [General]
+    /// Mode: 1
+    ///
+    /// [TimingPoints]
+    /// 0,500,4,1,0,100,1,0
+    /// 3000,250,3,1,0,100,1,8
+    ///
+    /// [HitObjects]
+    /// 256,192,4500,1,0
+    /// ").unwrap();
+    ///
+    /// let taiko = map.as_taiko().unwrap();
+    /// assert_eq!(taiko.barlines(), vec![Time(0.0), Time(2000.0), Time(3750.0)]);
+    /// ```
+    pub fn barlines(&self) -> Vec<Time> {
+        let index = self.beatmap.timing_index();
+        let end = self.beatmap.hit_objects
+            .iter()
+            .map(|obj| index.object_end_time(obj))
+            .max()
+            .unwrap_or(Time(f64::NEG_INFINITY));
+        let points = index.timing().points();
+
+        let mut barlines = Vec::new();
+        for (i, point) in points.iter().enumerate() {
+            let next = points.get(i + 1).map_or(end, |p| p.offset.min(end));
+            let mut bar = 0;
+            loop {
+                let time = point.beat_time(f64::from(bar * point.meter.max(1)));
+                if time >= next {
+                    break;
+                }
+                if bar > 0 || !point.effects.omit_first_barline() {
+                    barlines.push(time);
+                }
+                bar += 1;
+            }
+        }
+
+        barlines
+    }
+}
+
+fn scroll_speed(index: &TimingIndex, slider_multiplier: f32, time: Time) -> f32 {
+    match index.beat_length_at(time) {
+        Some(beat_length) => 100.0 * slider_multiplier * index.slider_velocity_at(time) / beat_length as f32,
+        None => 0.0,
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum CatchObject<'a> {
+    Fruit(&'a HitCircle),
+    JuiceStream(&'a Slider),
+    BananaShower(&'a Spinner),
+}
+
+/// An osu!catch beatmap.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CatchBeatmap<'a> {
+    pub beatmap: &'a Beatmap,
+    pub objects: Vec<CatchObject<'a>>,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ManiaNote<'a> {
+    Note(&'a HitCircle),
+    Hold(&'a HoldNote),
+}
+
+impl<'a> ManiaNote<'a> {
+    pub fn time(&self) -> Time {
+        match self {
+            ManiaNote::Note(n) => n.time,
+            ManiaNote::Hold(n) => n.time,
+        }
+    }
+
+    /// When the note is released: the end of a hold note, or the same as
+    /// [`time`](#method.time) for a normal note.
+    pub fn end_time(&self) -> Time {
+        match self {
+            ManiaNote::Note(n) => n.time,
+            ManiaNote::Hold(n) => n.end_time,
+        }
+    }
+}
+
+/// An osu!mania beatmap, with its notes split into columns.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ManiaBeatmap<'a> {
+    pub beatmap: &'a Beatmap,
+    /// Number of keys, taken from the beatmap's circle size.
+    pub keys: usize,
+    /// The notes in each column, from left to right, in the order they appear
+    /// in the beatmap.
+    pub columns: Vec<Vec<ManiaNote<'a>>>,
+}
+
+/// The column a mania note at the given x position falls in.
+fn mania_column(x: i32, keys: usize) -> usize {
+    let column = (i64::from(x) * keys as i64).div_euclid(512);
+    column.max(0).min(keys as i64 - 1) as usize
+}
+
+impl HitObject {
+    /// The osu!mania column the object falls in, counting from 0 on the
+    /// left, for a beatmap with `keys` keys.
+    pub fn mania_column(&self, keys: usize) -> usize {
+        mania_column(self.position().0, keys.max(1))
+    }
+}
+
+fn check_mode(map: &Beatmap, mode: GameMode) -> Result<()> {
+    if map.general.game_mode == mode {
+        Ok(())
+    } else {
+        Err(Error::Message("Beatmap is for a different game mode"))
+    }
+}
+
+impl Beatmap {
+    /// Views the beatmap as an osu!standard beatmap.
+    pub fn as_std(&self) -> Result<StdBeatmap<'_>> {
+        check_mode(self, GameMode::Osu)?;
+
+        let objects = self.hit_objects.iter()
+            .map(|obj| match obj {
+                HitObject::HitCircle(o) => Ok(StdObject::Circle(o)),
+                HitObject::Slider(o) => Ok(StdObject::Slider(o)),
+                HitObject::Spinner(o) => Ok(StdObject::Spinner(o)),
+                HitObject::HoldNote(_) => Err(Error::Message("Hold notes only appear in osu!mania")),
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(StdBeatmap { beatmap: self, objects })
+    }
+
+    /// Views the beatmap as an osu!taiko beatmap.
+    pub fn as_taiko(&self) -> Result<TaikoBeatmap<'_>> {
+        check_mode(self, GameMode::Taiko)?;
+
+        let objects = self.hit_objects.iter()
+            .map(|obj| match obj {
+                HitObject::HitCircle(o) => Ok(TaikoObject::Hit(TaikoHit {
+                    circle: o,
+                    colour: if o.hitsound & KAT_HITSOUNDS != 0 {
+                        TaikoColour::Kat
+                    } else {
+                        TaikoColour::Don
+                    },
+                    big: o.hitsound & FINISH_HITSOUND != 0,
+                })),
+                HitObject::Slider(o) => Ok(TaikoObject::Drumroll(o)),
+                HitObject::Spinner(o) => Ok(TaikoObject::Swell(o)),
+                HitObject::HoldNote(_) => Err(Error::Message("Hold notes only appear in osu!mania")),
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(TaikoBeatmap { beatmap: self, objects })
+    }
+
+    /// Views the beatmap as an osu!catch beatmap.
+    pub fn as_catch(&self) -> Result<CatchBeatmap<'_>> {
+        check_mode(self, GameMode::CTB)?;
+
+        let objects = self.hit_objects.iter()
+            .map(|obj| match obj {
+                HitObject::HitCircle(o) => Ok(CatchObject::Fruit(o)),
+                HitObject::Slider(o) => Ok(CatchObject::JuiceStream(o)),
+                HitObject::Spinner(o) => Ok(CatchObject::BananaShower(o)),
+                HitObject::HoldNote(_) => Err(Error::Message("Hold notes only appear in osu!mania")),
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(CatchBeatmap { beatmap: self, objects })
+    }
+
+    /// Views the beatmap as an osu!mania beatmap. Each note is placed in a
+    /// column according to its x position and the key count.
+    pub fn as_mania(&self) -> Result<ManiaBeatmap<'_>> {
+        check_mode(self, GameMode::Mania)?;
+
+        let keys = self.difficulty.circle_size.round();
+        if keys < 1.0 || keys > MAX_MANIA_KEYS as f32 {
+            return Err(Error::Message("Invalid osu!mania key count"));
+        }
+        let keys = keys as usize;
+
+        let mut columns = vec![Vec::new(); keys];
+        for obj in &self.hit_objects {
+            let note = match obj {
+                HitObject::HitCircle(o) => ManiaNote::Note(o),
+                HitObject::HoldNote(o) => ManiaNote::Hold(o),
+                HitObject::Slider(_) => return Err(Error::Message("Sliders don't appear in osu!mania")),
+                HitObject::Spinner(_) => return Err(Error::Message("Spinners don't appear in osu!mania")),
+            };
+
+            columns[mania_column(obj.position().0, keys)].push(note);
+        }
+
+        Ok(ManiaBeatmap { beatmap: self, keys, columns })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::File;
+    use std::io::prelude::*;
+
+    fn read_map(name: &str) -> Beatmap {
+        let mut contents = String::new();
+        File::open(name).unwrap().read_to_string(&mut contents).unwrap();
+        parse_beatmap(&contents).unwrap()
+    }
+
+    #[test]
+    fn test_mode_views() {
+        let std_map = read_map("map.osu");
+        let std = std_map.as_std().unwrap();
+        assert_eq!(std.objects.len(), std_map.hit_objects.len());
+        assert!(std_map.as_mania().is_err());
+
+        let mania_map = read_map("omtest.osu");
+        assert!(mania_map.as_std().is_err());
+        let mania = mania_map.as_mania().unwrap();
+        assert_eq!(mania.keys, 7);
+        assert_eq!(mania.columns.iter().map(Vec::len).sum::<usize>(), mania_map.hit_objects.len());
+        assert_eq!(mania_column(36, 7), 0);
+        assert_eq!(mania_column(475, 7), 6);
+        assert_eq!(mania_column(600, 7), 6);
+
+        let mut taiko_map = Beatmap::default();
+        taiko_map.general.game_mode = GameMode::Taiko;
+        taiko_map.hit_objects = parse_beatmap(
+            "osu file format v14\n[HitObjects]\n256,192,1000,1,0\n256,192,1500,1,6\n",
+        ).unwrap().hit_objects;
+
+        let taiko = taiko_map.as_taiko().unwrap();
+        match (taiko.objects[0], taiko.objects[1]) {
+            (TaikoObject::Hit(don), TaikoObject::Hit(kat)) => {
+                assert_eq!((don.colour, don.big), (TaikoColour::Don, false));
+                assert_eq!((kat.colour, kat.big), (TaikoColour::Kat, true));
+            }
+            other => panic!("expected two hits, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+/// Bit flags for the mods a score was set with, using the same values as
+/// osu! replays and the osu! API.
+///
+/// ```
+/// use osuparse::Mods;
+///
+/// let mods = Mods::HIDDEN | Mods::DOUBLE_TIME;
+/// assert_eq!(mods.bits(), 72);
+/// assert_eq!(mods.clock_rate(), 1.5);
+/// ```
+#[derive(Debug, Default, Eq, PartialEq, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Mods(u32);
+
+impl Mods {
+    pub const NONE: Mods = Mods(0);
+    pub const NO_FAIL: Mods = Mods(1);
+    pub const EASY: Mods = Mods(1 << 1);
+    pub const TOUCH_DEVICE: Mods = Mods(1 << 2);
+    pub const HIDDEN: Mods = Mods(1 << 3);
+    pub const HARD_ROCK: Mods = Mods(1 << 4);
+    pub const SUDDEN_DEATH: Mods = Mods(1 << 5);
+    pub const DOUBLE_TIME: Mods = Mods(1 << 6);
+    pub const RELAX: Mods = Mods(1 << 7);
+    pub const HALF_TIME: Mods = Mods(1 << 8);
+    /// Always set together with `DOUBLE_TIME`.
+    pub const NIGHTCORE: Mods = Mods(1 << 9);
+    pub const FLASHLIGHT: Mods = Mods(1 << 10);
+    pub const AUTOPLAY: Mods = Mods(1 << 11);
+    pub const SPUN_OUT: Mods = Mods(1 << 12);
+    pub const AUTOPILOT: Mods = Mods(1 << 13);
+    /// Always set together with `SUDDEN_DEATH`.
+    pub const PERFECT: Mods = Mods(1 << 14);
+
+    pub fn from_bits(bits: u32) -> Mods {
+        Mods(bits)
+    }
+
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Whether all of the flags in `other` are set.
+    pub fn contains(self, other: Mods) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// How much faster than normal the beatmap is played.
+    pub fn clock_rate(self) -> f64 {
+        if self.contains(Mods::DOUBLE_TIME) || self.contains(Mods::NIGHTCORE) {
+            1.5
+        } else if self.contains(Mods::HALF_TIME) {
+            0.75
+        } else {
+            1.0
+        }
+    }
+}
+
+impl Mods {
+    /// The factor score v1 gives to combo bonuses with these mods.
+    pub fn score_multiplier(self) -> f64 {
+        self.multiply(&[
+            (Mods::EASY, 0.5),
+            (Mods::NO_FAIL, 0.5),
+            (Mods::HALF_TIME, 0.3),
+            (Mods::HARD_ROCK, 1.06),
+            (Mods::DOUBLE_TIME, 1.12),
+            (Mods::HIDDEN, 1.06),
+            (Mods::FLASHLIGHT, 1.12),
+            (Mods::SPUN_OUT, 0.9),
+        ])
+    }
+
+    /// The factor score v2 scales scores by with these mods.
+    pub fn score_multiplier_v2(self) -> f64 {
+        self.multiply(&[
+            (Mods::EASY, 0.5),
+            (Mods::HALF_TIME, 0.3),
+            (Mods::HARD_ROCK, 1.1),
+            (Mods::DOUBLE_TIME, 1.2),
+            (Mods::HIDDEN, 1.06),
+            (Mods::FLASHLIGHT, 1.12),
+            (Mods::SPUN_OUT, 0.9),
+        ])
+    }
+
+    fn multiply(self, factors: &[(Mods, f64)]) -> f64 {
+        factors
+            .iter()
+            .filter(|&&(m, _)| self.contains(m))
+            .map(|&(_, factor)| factor)
+            .product()
+    }
+}
+
+impl std::ops::BitOr for Mods {
+    type Output = Mods;
+
+    fn bitor(self, other: Mods) -> Mods {
+        Mods(self.0 | other.0)
+    }
+}
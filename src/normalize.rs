@@ -0,0 +1,157 @@
+use std::cmp::Ordering;
+
+use super::*;
+
+fn clamp<T: PartialOrd>(value: T, min: T, max: T) -> T {
+    if value < min {
+        min
+    } else if value > max {
+        max
+    } else {
+        value
+    }
+}
+
+impl Beatmap {
+    /// Cleans up the beatmap into a canonical form, as a mapper would want
+    /// it before submission:
+    ///
+    /// * Hit objects and timing points are sorted by time. Objects sharing
+    ///   a time keep their relative order.
+    /// * Duplicate timing points are removed. Of several uninherited (or
+    ///   several inherited) points at the same offset only the last one is
+    ///   kept, as that is the one the game uses.
+    /// * Values outside the ranges the editor allows are clamped: difficulty
+    ///   settings to 0–10, slider multiplier to 0.4–3.6, slider tick rate to
+    ///   0.5–8, stack leniency to 0–1, volumes to 0–100 and meters to at
+    ///   least 1. Spinner and hold note end times are made no earlier than
+    ///   their start.
+    /// * Every slider gets one edge hitsound and one set of edge sample sets
+    ///   per edge, see
+    ///   [`Slider::normalize_edges`](struct.Slider.html#method.normalize_edges).
+    /// * Missing values are filled in: the unicode title and artist fall back
+    ///   to their romanised versions, and an empty sample set becomes
+    ///   `Normal`.
+    pub fn normalize(&mut self) {
+        self.hit_objects.sort_by_key(|obj| obj.time());
+
+        self.timing_points.sort_by(|a, b| {
+            a.offset.partial_cmp(&b.offset).unwrap_or(Ordering::Equal)
+        });
+
+        let mut timing_points: Vec<TimingPoint> = Vec::with_capacity(self.timing_points.len());
+        for point in self.timing_points.drain(..) {
+            let duplicate = timing_points.iter().rposition(|p| {
+                p.offset == point.offset && p.inherited == point.inherited
+            });
+            if let Some(i) = duplicate {
+                timing_points.remove(i);
+            }
+            timing_points.push(point);
+        }
+        self.timing_points = timing_points;
+
+        for point in &mut self.timing_points {
+            point.volume = clamp(point.volume, 0, 100);
+            point.meter = point.meter.max(1);
+        }
+
+        for obj in &mut self.hit_objects {
+            match obj {
+                HitObject::HitCircle(o) => {
+                    o.extras.sample_volume = clamp(o.extras.sample_volume, 0, 100)
+                }
+                HitObject::Slider(o) => {
+                    o.extras.sample_volume = clamp(o.extras.sample_volume, 0, 100);
+                    o.normalize_edges();
+                }
+                HitObject::Spinner(o) => {
+                    o.end_time = o.end_time.max(o.time);
+                    o.extras.sample_volume = clamp(o.extras.sample_volume, 0, 100);
+                }
+                HitObject::HoldNote(o) => {
+                    o.end_time = o.end_time.max(o.time);
+                    o.extras.sample_volume = clamp(o.extras.sample_volume, 0, 100);
+                }
+            }
+        }
+
+        let difficulty = &mut self.difficulty;
+        difficulty.hp_drain_rate = clamp(difficulty.hp_drain_rate, 0.0, 10.0);
+        difficulty.circle_size = clamp(difficulty.circle_size, 0.0, 10.0);
+        difficulty.overall_difficulty = clamp(difficulty.overall_difficulty, 0.0, 10.0);
+        difficulty.approach_rate = clamp(difficulty.approach_rate, 0.0, 10.0);
+        difficulty.slider_multiplier = clamp(difficulty.slider_multiplier, 0.4, 3.6);
+        difficulty.slider_tick_rate = clamp(difficulty.slider_tick_rate, 0.5, 8.0);
+
+        self.general.stack_leniency = clamp(self.general.stack_leniency, 0.0, 1.0);
+        if self.general.sample_set.is_empty() {
+            self.general.sample_set = "Normal".to_string();
+        }
+
+        let metadata = &mut self.metadata;
+        if metadata.title_unicode.is_empty() {
+            metadata.title_unicode = metadata.title.clone();
+        }
+        if metadata.artist_unicode.is_empty() {
+            metadata.artist_unicode = metadata.artist.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn circle(time: i32) -> HitObject {
+        HitObject::HitCircle(HitCircle {
+            x: 256,
+            y: 192,
+            new_combo: false,
+            color_skip: 0,
+            raw_type: 0,
+            time: Time::from(time),
+            hitsound: 0,
+            extras: Default::default(),
+        })
+    }
+
+    #[test]
+    fn test_normalize() {
+        let mut map = Beatmap {
+            hit_objects: vec![circle(300), circle(100), circle(200)],
+            timing_points: vec![
+                TimingPoint {
+                    offset: Time(500.0),
+                    volume: 150,
+                    ..Default::default()
+                },
+                TimingPoint {
+                    offset: Time(0.0),
+                    ..Default::default()
+                },
+                TimingPoint {
+                    offset: Time(500.0),
+                    volume: 40,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        map.difficulty.approach_rate = 11.0;
+        map.metadata.title = "Title".to_string();
+
+        map.normalize();
+
+        let times: Vec<i32> = map.hit_objects.iter().map(|o| o.time().round()).collect();
+        assert_eq!(times, vec![100, 200, 300]);
+
+        assert_eq!(map.timing_points.len(), 2);
+        assert_eq!(map.timing_points[0].offset, Time(0.0));
+        assert_eq!(map.timing_points[1].volume, 40);
+
+        assert_eq!(map.difficulty.approach_rate, 10.0);
+        assert_eq!(map.metadata.title_unicode, "Title");
+        assert_eq!(map.general.sample_set, "Normal");
+    }
+}
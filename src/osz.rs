@@ -0,0 +1,196 @@
+use std::fs;
+use std::io::{Seek, Write};
+use std::path::Path;
+
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use super::*;
+use deserialize::ToOsuString;
+use set::resource_path;
+
+/// How far along writing an .osz archive with
+/// [`BeatmapSet::write_osz_with_progress`](struct.BeatmapSet.html#method.write_osz_with_progress)
+/// is.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct OszProgress<'a> {
+    /// The name within the archive of the file that was just added.
+    pub name: &'a str,
+    /// Number of files added so far, including this one.
+    pub files: usize,
+    /// Total number of difficulties and resources to add.
+    pub total_files: usize,
+}
+
+impl BeatmapSet {
+    /// Packs the set into an .osz archive, which osu! installs when the file
+    /// is opened. Every difficulty is stored under the filename osu! would
+    /// give it, followed by the `resources`, given as their path within the
+    /// set and their contents. The writer is returned once the archive is
+    /// finished.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use osuparse::{parse_beatmap, BeatmapSet};
+    ///
+    /// let map = parse_beatmap("osu file format v14
+    ///
+    /// [General]
+    /// AudioFilename: audio.mp3
+    /// ").unwrap();
+    ///
+    /// let set = BeatmapSet::new(vec![map]);
+    /// let audio = vec![0u8; 16];
+    /// let osz = set.write_osz(Cursor::new(Vec::new()), &[("audio.mp3", &audio)]).unwrap();
+    /// assert!(osz.into_inner().starts_with(b"PK"));
+    /// ```
+    pub fn write_osz<W: Write + Seek>(&self, writer: W, resources: &[(&str, &[u8])]) -> Result<W> {
+        self.write_osz_with_progress(writer, resources, |_| {})
+    }
+
+    /// Same as [`write_osz`](#method.write_osz), but calls `progress` after
+    /// each file is added to the archive, for driving progress bars.
+    pub fn write_osz_with_progress<W, F>(&self, writer: W, resources: &[(&str, &[u8])], mut progress: F) -> Result<W>
+    where
+        W: Write + Seek,
+        F: FnMut(OszProgress),
+    {
+        let total_files = self.beatmaps.len() + resources.len();
+        let mut zip = ZipWriter::new(writer);
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        let mut names = Vec::with_capacity(self.beatmaps.len() + resources.len());
+        for map in &self.beatmaps {
+            let name = map.metadata.filename();
+            if names.contains(&name) {
+                return Err(Error::Message("Two difficulties have the same filename"));
+            }
+
+            zip.start_file(name.as_str(), options).map_err(Error::Zip)?;
+            zip.write_all(map.to_osu_string().as_bytes()).map_err(Error::Io)?;
+            names.push(name);
+            progress(OszProgress {
+                name: names.last().unwrap(),
+                files: names.len(),
+                total_files,
+            });
+        }
+
+        for (i, &(name, contents)) in resources.iter().enumerate() {
+            // Archives always separate directories with forward slashes
+            let name = name.replace('\\', "/");
+            if !names.contains(&name) {
+                zip.start_file(name.as_str(), options).map_err(Error::Zip)?;
+                zip.write_all(contents).map_err(Error::Io)?;
+            }
+
+            progress(OszProgress {
+                name: &name,
+                files: self.beatmaps.len() + i + 1,
+                total_files,
+            });
+            names.push(name);
+        }
+
+        zip.finish().map_err(Error::Zip)
+    }
+
+    /// Like [`write_osz`](#method.write_osz), but reads the files referred
+    /// to by the set from `dir`. Returns the writer along with the files
+    /// that were left out because they aren't in `dir`, or because their
+    /// path leads outside of it.
+    pub fn write_osz_from_dir<W, P>(&self, writer: W, dir: P) -> Result<(W, Vec<String>)>
+    where
+        W: Write + Seek,
+        P: AsRef<Path>,
+    {
+        self.write_osz_from_dir_with_progress(writer, dir, |_| {})
+    }
+
+    /// Same as [`write_osz_from_dir`](#method.write_osz_from_dir), but calls
+    /// `progress` after each file is added to the archive.
+    pub fn write_osz_from_dir_with_progress<W, P, F>(
+        &self,
+        writer: W,
+        dir: P,
+        progress: F,
+    ) -> Result<(W, Vec<String>)>
+    where
+        W: Write + Seek,
+        P: AsRef<Path>,
+        F: FnMut(OszProgress),
+    {
+        let dir = dir.as_ref();
+        let mut found = Vec::new();
+        let mut missing = Vec::new();
+
+        for file in self.referenced_files() {
+            match resource_path(&file).map(|p| dir.join(p)) {
+                Some(ref path) if path.is_file() => {
+                    let contents = fs::read(path).map_err(Error::Io)?;
+                    found.push((file, contents));
+                }
+                _ => missing.push(file),
+            }
+        }
+
+        let resources: Vec<(&str, &[u8])> = found
+            .iter()
+            .map(|(name, contents)| (name.as_str(), contents.as_slice()))
+            .collect();
+
+        Ok((self.write_osz_with_progress(writer, &resources, progress)?, missing))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{Cursor, Read};
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_write_osz() {
+        let map = |version: &str| {
+            let input = format!(
+                "osu file format v14\n\n[General]\nAudioFilename: audio.mp3\n\n\
+                 [Metadata]\nArtist:Artist\nTitle:Title\nCreator:Mapper\nVersion:{}\n\n\
+                 [Events]\n0,0,\"sb\\bg.jpg\",0,0\n",
+                version
+            );
+            parse_beatmap(&input).unwrap()
+        };
+        let set = BeatmapSet::new(vec![map("Easy"), map("Hard")]);
+
+        let dir = std::env::temp_dir().join(format!("osuparse-osz-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sb")).unwrap();
+        fs::write(dir.join("sb").join("bg.jpg"), "background").unwrap();
+
+        let mut updates = Vec::new();
+        let (cursor, missing) = set
+            .write_osz_from_dir_with_progress(Cursor::new(Vec::new()), &dir, |p| {
+                updates.push((p.name.to_string(), p.files, p.total_files))
+            })
+            .unwrap();
+        assert_eq!(missing, vec!["audio.mp3"]);
+        assert_eq!(updates.last(), Some(&("sb/bg.jpg".to_string(), 3, 3)));
+
+        let mut archive = ZipArchive::new(cursor).unwrap();
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(names, vec![
+            "Artist - Title (Mapper) [Easy].osu",
+            "Artist - Title (Mapper) [Hard].osu",
+            "sb/bg.jpg",
+        ]);
+
+        let mut contents = String::new();
+        archive.by_name("Artist - Title (Mapper) [Hard].osu").unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!(parse_beatmap(&contents).unwrap().metadata.version, "Hard");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
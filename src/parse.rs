@@ -1,20 +1,129 @@
 use std;
+use std::fmt::Display;
 
 use super::*;
-use error::{Error, Result};
+use error::{Error, Location, Result, Warning};
+
+/// What to do with values that parse, but fall outside the range the game
+/// accepts, such as a volume above 100.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum RangePolicy {
+    /// Silently clamp the value into range.
+    Clamp,
+    /// Keep the value as is, and record a [`Warning`](struct.Warning.html).
+    Warn,
+    /// Fail with a syntax error.
+    Error,
+}
+
+/// What to do with a key that appears more than once in the same section.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum DuplicateKeyPolicy {
+    /// Use the value of the last occurrence, as the game does.
+    LastWins,
+    /// Use the value of the first occurrence, ignoring the rest.
+    FirstWins,
+    /// Use the value of the last occurrence, and record a
+    /// [`Warning`](struct.Warning.html) for every repeat.
+    Warn,
+    /// Fail with a syntax error.
+    Error,
+}
+
+/// What to do with a section that appears more than once, as in files that
+/// were concatenated or patched by hand. Every repeat is recorded as a
+/// [`Warning`](struct.Warning.html), unless it is an error.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum RepeatedSectionPolicy {
+    /// Read the section on top of the earlier one, as the game does: keys
+    /// and combo colours given again replace their earlier values, and
+    /// timing points, hit objects, events and variables are appended.
+    Merge,
+    /// Discard everything read from the earlier occurrences.
+    Replace,
+    /// Fail with a syntax error.
+    Error,
+}
+
+/// Options controlling how beatmaps are read by
+/// [`parse_beatmap_with_options`](fn.parse_beatmap_with_options.html).
+///
+/// The default options keep out of range values and warn about them, use
+/// the last value of repeated keys and merge repeated sections, which
+/// matches the behaviour of [`parse_beatmap`](fn.parse_beatmap.html).
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct ParserOptions {
+    pub range_policy: RangePolicy,
+    /// Applies to the `[General]`, `[Editor]`, `[Metadata]`, `[Difficulty]`
+    /// and `[Colours]` sections.
+    pub duplicate_keys: DuplicateKeyPolicy,
+    pub repeated_sections: RepeatedSectionPolicy,
+    /// Whether to record a [`Warning`](struct.Warning.html) for every hit
+    /// object that can't appear in the beatmap's game mode, such as a hold
+    /// note outside of osu!mania, or a slider or spinner in it. These only
+    /// turn up in corrupted files.
+    pub check_object_types: bool,
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        ParserOptions {
+            range_policy: RangePolicy::Warn,
+            duplicate_keys: DuplicateKeyPolicy::LastWins,
+            repeated_sections: RepeatedSectionPolicy::Merge,
+            check_object_types: false,
+        }
+    }
+}
+
+type Lines<'a> = std::iter::Filter<
+    std::iter::Enumerate<std::str::Lines<'a>>,
+    fn(&(usize, &'a str)) -> bool,
+>;
 
 pub struct ParseState<'a> {
-    lines: Box<dyn Iterator<Item=(usize, &'a str)> + 'a>,
-    // lines: std::iter::Filter<std::str::Lines<'a>, fn(&&str) -> bool>,
+    input: &'a str,
+    lines: Lines<'a>,
     current_line: Option<(usize, &'a str)>,
+    /// The format version of the file, once its header has been read.
+    pub version: FormatVersion,
+    /// Whether to remove trailing `//` comments from lines as they are read.
+    /// The Metadata section turns this off, as values there may contain
+    /// `//`.
+    pub strip_comments: bool,
+    pub options: ParserOptions,
+    pub warnings: Vec<Warning>,
+    /// Name of the section being read.
+    pub section: &'a str,
+    /// Where the hit objects, timing points and key-value pairs kept so far
+    /// were read from.
+    pub hit_object_spans: Vec<Span>,
+    pub timing_point_spans: Vec<Span>,
+    pub key_spans: Vec<(String, &'static str, Span)>,
+    /// Errors recovered from so far, or `None` if parsing stops at the first
+    /// error.
+    pub errors: Option<Vec<Error>>,
 }
 
 impl<'a> ParseState<'a> {
     pub fn new(input: &'a str) -> Self {
+        ParseState::with_options(input, ParserOptions::default())
+    }
+
+    pub fn with_options(input: &'a str, options: ParserOptions) -> Self {
         let mut ps = ParseState {
-            lines: Box::new(input.lines().enumerate()
-                            .filter(|(_, l)| !l.trim().is_empty())),
+            input,
+            lines: input.lines().enumerate().filter(is_content_line),
             current_line: None,
+            version: FormatVersion(0),
+            strip_comments: true,
+            options,
+            warnings: Vec::new(),
+            section: "",
+            hit_object_spans: Vec::new(),
+            timing_point_spans: Vec::new(),
+            key_spans: Vec::new(),
+            errors: None,
         };
 
         ps.read_next_line();
@@ -26,57 +135,226 @@ impl<'a> ParseState<'a> {
     }
 
     pub fn read_next_line(&mut self) -> Option<&'a str> {
-        let next_line = self.lines.next();
+        let strip_comments = self.strip_comments;
+        let next_line = self.lines.next().map(|(i, l)| {
+            if strip_comments {
+                (i, strip_comment(l))
+            } else {
+                (i, l)
+            }
+        });
         self.current_line = next_line;
 
         next_line.map(|(_, l)| l)
     }
 
+    /// Index of the current line, or equivalently the number of lines before
+    /// it.
+    pub fn line_number(&self) -> Option<usize> {
+        self.current_line.map(|(i, _)| i)
+    }
+
+    /// An upper bound on the number of lines left to read, counting the
+    /// current one.
+    pub fn remaining_lines(&self) -> usize {
+        // Lines borrow from the input, so the current line's offset can be
+        // found from its address.
+        let offset = self.current_line
+            .map(|(_, l)| l.as_ptr() as usize - self.input.as_ptr() as usize)
+            .unwrap_or_else(|| self.input.len());
+
+        self.input[offset..].bytes().filter(|&b| b == b'\n').count() + 1
+    }
+
+    /// Where the current line is in the input.
+    pub fn current_span(&self) -> Option<Span> {
+        self.current_line.map(|(i, l)| {
+            let start = l.as_ptr() as usize - self.input.as_ptr() as usize;
+            Span { line: i + 1, start, end: start + l.len() }
+        })
+    }
+
+    /// Where `field`, a slice of the current line, starts within it.
+    pub fn column_of(&self, field: &str) -> usize {
+        self.current_line.map_or(0, |(_, l)| column_of(l, field))
+    }
+
+    /// Record where the key of the current line, in the current section, was
+    /// read from.
+    pub fn record_key_span(&mut self, key: &'static str) {
+        if let Some(span) = self.current_span() {
+            self.key_spans.push((self.section.to_string(), key, span));
+        }
+    }
+
     pub fn syntax_error(&self, reason: &str) -> Error {
-        let line = self.current_line.map(|(i, l)| (i, String::from(l)));
-        Error::Syntax(line, String::from(reason))
+        self.syntax_error_at(reason, None)
+    }
+
+    /// A syntax error on the current line, pointing at the field starting
+    /// `column` bytes into it.
+    pub fn syntax_error_at(&self, reason: &str, column: Option<usize>) -> Error {
+        let section = if self.section.is_empty() { None } else { Some(self.section.to_string()) };
+        let location = self.current_line.map(|(line, text)| Location {
+            line,
+            text: String::from(text),
+            column,
+            section,
+        });
+        Error::Syntax(location, String::from(reason))
+    }
+
+    pub fn warn(&mut self, message: String) {
+        let line = self.current_line.map(|(i, _)| i + 1);
+        self.warnings.push(Warning { line, message });
+    }
+
+    /// Check that a value lies within `min..=max`, handling it according to
+    /// the [`RangePolicy`](enum.RangePolicy.html) if it doesn't.
+    pub fn check_range<T>(&mut self, value: T, min: T, max: T, name: &str) -> Result<T>
+    where
+        T: PartialOrd + Display + Copy,
+    {
+        if value >= min && value <= max {
+            return Ok(value);
+        }
+
+        let message = format!("{} {} is outside of the range {} to {}", name, value, min, max);
+        match self.options.range_policy {
+            RangePolicy::Clamp if value < min => Ok(min),
+            RangePolicy::Clamp => Ok(max),
+            RangePolicy::Warn => {
+                self.warn(message);
+                Ok(value)
+            }
+            RangePolicy::Error => Err(self.syntax_error(&message)),
+        }
+    }
+
+    /// Whether the value of a key that was already read in the current
+    /// section should replace the earlier one, according to the
+    /// [`DuplicateKeyPolicy`](enum.DuplicateKeyPolicy.html).
+    pub fn keep_duplicate(&mut self, key: &str) -> Result<bool> {
+        let message = format!("Duplicate key {}", key);
+        match self.options.duplicate_keys {
+            DuplicateKeyPolicy::LastWins => Ok(true),
+            DuplicateKeyPolicy::FirstWins => Ok(false),
+            DuplicateKeyPolicy::Warn => {
+                self.warn(message);
+                Ok(true)
+            }
+            DuplicateKeyPolicy::Error => {
+                let err = self.syntax_error(&message);
+                self.recover::<()>(Err(err)).map(|_| false)
+            }
+        }
+    }
+
+    /// When recovering from errors, records the error of a failed result
+    /// and returns `None`, so that parsing can carry on past it. Otherwise
+    /// errors are passed on.
+    pub fn recover<T>(&mut self, result: Result<T>) -> Result<Option<T>> {
+        match (result, &mut self.errors) {
+            (Ok(value), _) => Ok(Some(value)),
+            (Err(err), &mut Some(ref mut errors)) => {
+                errors.push(err);
+                Ok(None)
+            }
+            (Err(err), &mut None) => Err(err),
+        }
     }
 
     pub fn wrap_syntax_error<T>(&self, res: Result<T>) -> Result<T> {
         res.map_err(|err| {
             match err {
-                Error::Message(m) => self.syntax_error(&m),
+                Error::Message(m) => self.syntax_error(m),
+                Error::Field(column, err) => match *err {
+                    Error::Message(m) => self.syntax_error_at(m, Some(column)),
+                    err => err,
+                },
                 _ => err,
             }
         })
     }
 }
 
-/// Get the next item of given iterator, and convert it to the correct
-/// type using the given function.
+/// Whether the line has anything to parse. Blank lines and comments are
+/// skipped anywhere in the file.
+fn is_content_line(&(_, line): &(usize, &str)) -> bool {
+    let line = line.trim_start();
+    !line.is_empty() && !line.starts_with("//")
+}
+
+/// Remove a trailing `//` comment from a line, as the game does.
+fn strip_comment(line: &str) -> &str {
+    match line.as_bytes().windows(2).position(|w| w == b"//") {
+        Some(i) if i > 0 => line[..i].trim_end(),
+        _ => line,
+    }
+}
+
+/// Where `field`, a slice of `line`, starts within it.
+pub fn column_of(line: &str, field: &str) -> usize {
+    (field.as_ptr() as usize)
+        .checked_sub(line.as_ptr() as usize)
+        .filter(|&c| c <= line.len())
+        .unwrap_or(0)
+}
+
+/// Get the next item of given iterator over slices of `$line`, and convert
+/// it to the correct type using the given function. Errors record where the
+/// item starts in `$line`.
 macro_rules! read_val {
-    ($iter:ident, $func:expr) => {
-        $iter.next().ok_or(Error::Parse).and_then($func)
+    ($line:expr, $iter:ident, $func:expr) => {
+        $iter.next()
+            .ok_or(Error::Parse)
+            .and_then(|s| $func(s).map_err(|e| e.in_field(column_of($line, s))))
     };
 }
 
-/// Get the next item of given iterator (presumably of type string),
-/// split using given seperator, and convert each split string to the
-/// correct type using given function.
-macro_rules! read_list {
-    ($sep:expr, $iter:ident, $func:expr) => {
-        $iter
-            .next()
-            .ok_or(Error::Parse)
-            .and_then(|s| s.split($sep).map($func).collect())
+/// Like `read_val!`, for fields that may be left out at the end of a line.
+/// Given a separator, the field is read as a list split by it. A missing or
+/// empty field takes the default value, but one that is there and can't be
+/// parsed is still an error.
+macro_rules! read_optional {
+    ($line:expr, $iter:ident, $func:expr) => {
+        match $iter.next() {
+            None | Some("") => Ok(Default::default()),
+            Some(s) => $func(s).map_err(|e| e.in_field(column_of($line, s))),
+        }
+    };
+    ($sep:expr, $line:expr, $iter:ident, $func:expr) => {
+        match $iter.next() {
+            None | Some("") => Ok(Vec::new()),
+            Some(s) => s
+                .split($sep)
+                .map(|s| $func(s).map_err(|e| e.in_field(column_of($line, s))))
+                .collect(),
+        }
     };
 }
 
+/// Split a line using the given separator and parse each item into a field
+/// of the given struct. Fields followed by `=> default` may be missing from
+/// the end of the line, in which case they take the default value.
 macro_rules! parse_into_struct {
-	($sep:expr, $dest:ident, $line:expr; {$($field:ident: $f:expr),*}) => {
+    (@field $line:expr, $iter:ident, $f:expr) => {
+        $iter.next()
+            .ok_or_else(|| Error::Message("Unable to parse line"))
+            .and_then(|s| $f(s).map_err(|e| e.in_field(column_of($line, s))))?
+    };
+    (@field $line:expr, $iter:ident, $f:expr, $default:expr) => {
+        match $iter.next() {
+            Some(s) => $f(s).map_err(|e| e.in_field(column_of($line, s)))?,
+            None => $default,
+        }
+    };
+	($sep:expr, $dest:ident, $line:expr; {$($field:ident: $f:expr $(=> $default:expr)?),*}) => {
 		{
-			let mut iter = $line.split($sep).map(|s| s.trim());
+			let line = $line;
+			let mut iter = line.split($sep).map(|s| s.trim());
 			$dest {
-				$($field: {
-                    iter.next()
-						.ok_or_else(|| Error::Message("Unable to parse line"))
-                        .and_then($f)?
-				}),*
+				$($field: parse_into_struct!(@field line, iter, $f $(, $default)?)),*
 			}
 		}
 	};
@@ -87,37 +365,52 @@ macro_rules! value_parser {
         $fn($v)
     };
     ($v:expr, $fn:expr, $sep:expr) => {
+        // An empty value is an empty list, not a list of one empty item
         $v.split($sep)
-            .map(|s| $fn(s.trim()))
+            .filter(|_| !$v.is_empty())
+            .map(|s| $fn(s.trim()).map_err(|e| e.in_field(column_of($v, s.trim()))))
             .collect::<std::result::Result<Vec<_>, _>>()
     };
 }
 
 /// Parse key-value pair.
-pub fn parse_kv_pair<'a>(state: &'a ParseState) -> Option<(&'a str, &'a str)> {
+pub fn parse_kv_pair<'a>(state: &ParseState<'a>) -> Option<(&'a str, &'a str)> {
     state
         .get_current_line()
         .and_then(|l| {
-            let mut iter = l.splitn(2, ":");
+            let mut iter = l.splitn(2, ':');
             iter.next().and_then(|left| iter.next().map(|right| (left.trim(), right.trim())))
         })
 }
 
+/// Parse a section of key-value pairs into a struct, recording the name of
+/// every key found into the `$keys` [`KeySet`](../struct.KeySet.html).
+/// Unknown keys are skipped, unless a `$unknown` field is given to collect
+/// them into.
 macro_rules! parse_kv_section {
-    (|$s_t:ty, $state:ident| {$($str:expr => $field:ident: $($f:expr),*;)*}) => {
+    (|$start:expr, $state:ident, $keys:ident $(, $unknown:ident)?| {$($str:expr => $field:ident: $($f:expr),*;)*}) => {
         {
-            let mut section: $s_t = Default::default();
+            let mut section = $start;
 
             loop {
                 $state.read_next_line();
                 match parse_kv_pair($state) {
                     $(
                     Some((k, v)) if unicase::eq(k, $str) => {
-                        section.$field = $state
-                            .wrap_syntax_error(value_parser!(v, $($f),*))?
+                        if !$keys.contains($str) || $state.keep_duplicate($str)? {
+                            let value = value_parser!(v, $($f),*).map_err(|e| e.in_field($state.column_of(v)));
+                            let value = $state.wrap_syntax_error(value);
+                            if let Some(value) = $state.recover(value)? {
+                                section.$field = value;
+                            }
+                            $keys.insert($str);
+                            $state.record_key_span($str);
+                        }
                     },
                     )*
-                    Some(_) => {},
+                    Some((_k, _v)) => {
+                        $(section.$unknown.push((_k.to_string(), _v.to_string()));)?
+                    },
                     _ => break,
                 }
             }
@@ -127,11 +420,48 @@ macro_rules! parse_kv_section {
     }
 }
 
+/// Parse a number. Floats may also be `NaN` or infinite, written as Rust
+/// or .NET (`Infinity`, `∞`) write them, or in the forms of the C runtime
+/// (`1.#INF`, `-1.#IND`) found in some old or hand-made files. Values too
+/// large for the type become infinite.
 pub fn parse_num<T: std::str::FromStr>(n: &str) -> Result<T> {
+    let n = match n {
+        "∞" | "1.#INF" => "inf",
+        "-∞" | "-1.#INF" => "-inf",
+        "1.#QNAN" | "-1.#QNAN" | "1.#IND" | "-1.#IND" => "NaN",
+        _ => n,
+    };
+
     n.parse()
         .map_err(|_| Error::Message("Unable to parse number"))
 }
 
+/// Parse an integer that may be written with a fractional part, such as
+/// `192.7`, dropping the fraction as the game does when reading positions.
+pub fn parse_truncated(s: &str) -> Result<i32> {
+    if let Ok(n) = s.parse() {
+        return Ok(n);
+    }
+
+    let value: f64 = s.parse().map_err(|_| Error::Message("Unable to parse number"))?;
+    if value.is_finite() && value >= f64::from(i32::MIN) && value <= f64::from(i32::MAX) {
+        Ok(value.trunc() as i32)
+    } else {
+        Err(Error::Message("Number out of range"))
+    }
+}
+
+/// Parses a time in milliseconds, keeping any fractional part. Times must
+/// be finite and fit in an `i32`, as the game keeps most of them in one.
+pub fn parse_time(s: &str) -> Result<Time> {
+    let value: f64 = parse_num(s)?;
+    if value.is_finite() && value >= f64::from(i32::MIN) && value <= f64::from(i32::MAX) {
+        Ok(Time(value))
+    } else {
+        Err(Error::Message("Time out of range"))
+    }
+}
+
 pub fn parse_string(s: &str) -> Result<String> {
     Ok(String::from(s))
 }
@@ -142,32 +472,92 @@ pub fn parse_bool(s: &str) -> Result<bool> {
         .map_err(|_| Error::Message("Could not parse bool"))
 }
 
+pub fn parse_effects(s: &str) -> Result<Effects> {
+    s.parse::<i32>()
+        .map(Effects::from_bits)
+        .map_err(|_| Error::Message("Could not parse effects"))
+}
+
+/// Parse a game mode, given either as its number or, as found in some older
+/// or hand edited files, its name.
 pub fn parse_mode(s: &str) -> Result<GameMode> {
+    const NAMES: &[(&str, GameMode)] = &[
+        ("osu", GameMode::Osu),
+        ("Standard", GameMode::Osu),
+        ("Taiko", GameMode::Taiko),
+        ("CatchTheBeat", GameMode::CTB),
+        ("Catch", GameMode::CTB),
+        ("CTB", GameMode::CTB),
+        ("Fruits", GameMode::CTB),
+        ("Mania", GameMode::Mania),
+    ];
+
+    let mode = match s.parse::<u8>() {
+        Ok(raw) => GameMode::from_raw(raw),
+        Err(_) => NAMES.iter()
+            .find(|&&(name, _)| unicase::eq(name, s))
+            .map(|&(_, mode)| mode),
+    };
+
+    mode.ok_or(Error::Message("Unable to parse gamemode"))
+}
+
+pub fn parse_countdown(s: &str) -> Result<CountdownSpeed> {
     match s {
-        "0" => Ok(GameMode::Osu),
-        "1" => Ok(GameMode::Taiko),
-        "2" => Ok(GameMode::CTB),
-        "3" => Ok(GameMode::Mania),
-        _ => Err(Error::Message("Unable to parse gamemode")),
+        "0" => Ok(CountdownSpeed::None),
+        "1" => Ok(CountdownSpeed::Normal),
+        "2" => Ok(CountdownSpeed::Half),
+        "3" => Ok(CountdownSpeed::Double),
+        _ => Err(Error::Message("Unable to parse countdown")),
+    }
+}
+
+pub fn parse_overlay_position(s: &str) -> Result<OverlayPosition> {
+    if unicase::eq(s, "NoChange") {
+        Ok(OverlayPosition::NoChange)
+    } else if unicase::eq(s, "Below") {
+        Ok(OverlayPosition::Below)
+    } else if unicase::eq(s, "Above") {
+        Ok(OverlayPosition::Above)
+    } else {
+        Err(Error::Message("Unable to parse overlay position"))
     }
 }
 
 pub fn parse_colour(s: &str) -> Result<Colour> {
-    let mut iter = s.split(",");
+    let mut iter = s.split(',');
     Ok(Colour(
-        read_val!(iter, parse_num)?,
-        read_val!(iter, parse_num)?,
-        read_val!(iter, parse_num)?,
+        read_val!(s, iter, parse_num)?,
+        read_val!(s, iter, parse_num)?,
+        read_val!(s, iter, parse_num)?,
     ))
 }
 
+/// Parse a timing point line. Older maps may leave out the fields after the
+/// beat length, which then take their default values. The volume isn't
+/// checked to be in range.
+pub fn parse_timing_point(s: &str) -> Result<TimingPoint> {
+    Ok(parse_into_struct!(',', TimingPoint, s; {
+        offset: parse_time,
+        ms_per_beat: parse_num,
+        meter: parse_num => 4,
+        sample_set: parse_num => 0,
+        sample_index: parse_num => 0,
+        volume: parse_num => 100,
+        inherited: parse_bool => true,
+        effects: parse_effects => Effects::default()
+    }))
+}
+
+/// Parse hit object extras. Older maps may leave out the fields after the
+/// addition set, which then take their default values.
 pub fn parse_extras(s: &str) -> Result<HitObjectExtras> {
-    Ok(parse_into_struct!(":", HitObjectExtras, s; {
+    Ok(parse_into_struct!(':', HitObjectExtras, s; {
         sample_set: parse_num,
         addition_set: parse_num,
-        custom_index: parse_num,
-        sample_volume: parse_num,
-        filename: parse_string
+        custom_index: parse_num => 0,
+        sample_volume: parse_num => 0,
+        filename: parse_string => String::new()
     }))
 }
 
@@ -182,32 +572,46 @@ pub fn parse_slider_type(s: &str) -> Result<SliderType> {
 }
 
 pub fn parse_coord(s: &str) -> Result<(i32, i32)> {
-    let mut iter = s.split(":");
-    Ok((read_val!(iter, parse_num)?, read_val!(iter, parse_num)?))
+    let mut iter = s.split(':');
+    Ok((read_val!(s, iter, parse_num)?, read_val!(s, iter, parse_num)?))
+}
+
+pub fn parse_edge_sample_set(s: &str) -> Result<EdgeSampleSet> {
+    let mut iter = s.split(':');
+    Ok(EdgeSampleSet {
+        sample_set: read_val!(s, iter, parse_num)?,
+        addition_set: read_val!(s, iter, parse_num)?,
+    })
 }
 
 fn parse_curve_points(s: &str) -> Result<(SliderType, Vec<(i32, i32)>)> {
-    let mut iter = s.split("|");
+    let mut iter = s.split('|');
 
-    let slider_type = read_val!(iter, parse_slider_type)?;
+    let slider_type = read_val!(s, iter, parse_slider_type)?;
 
-    let points = iter.map(parse_coord).collect::<Result<Vec<(i32, i32)>>>()?;
+    let points = iter
+        .map(|p| parse_coord(p).map_err(|e| e.in_field(column_of(s, p))))
+        .collect::<Result<Vec<(i32, i32)>>>()?;
 
     Ok((slider_type, points))
 }
 
+/// Parse a hit object line. Fields after the ones used by the object's type,
+/// such as those appended by third-party tools or a stray trailing comma, are
+/// ignored and not kept. Edge sounds and extras may be left out or empty,
+/// giving their defaults, but are an error if they can't be parsed.
 pub fn parse_hit_object(s: &str) -> Result<HitObject> {
-    let mut iter = s.split(",");
+    let mut iter = s.split(',');
 
-    let x: i32 = read_val!(iter, parse_num)?;
-    let y: i32 = read_val!(iter, parse_num)?;
-    let time: i32 = read_val!(iter, parse_num)?;
-    let obj_type: i32 = read_val!(iter, parse_num)?;
+    let x = read_val!(s, iter, parse_truncated)?;
+    let y = read_val!(s, iter, parse_truncated)?;
+    let time = read_val!(s, iter, parse_time)?;
+    let obj_type: i32 = read_val!(s, iter, parse_num)?;
 
     let new_combo = obj_type & 4 != 0;
     let color_skip = (obj_type >> 4) & 7;
 
-    let hitsound = read_val!(iter, parse_num)?;
+    let hitsound = read_val!(s, iter, parse_num)?;
 
     match obj_type & 139 {
         1 => Ok(HitObject::HitCircle(HitCircle {
@@ -215,32 +619,34 @@ pub fn parse_hit_object(s: &str) -> Result<HitObject> {
             y,
             new_combo,
             color_skip,
+            raw_type: obj_type,
             time,
             hitsound,
 
-            extras: read_val!(iter, parse_extras).unwrap_or(Default::default()),
+            extras: read_optional!(s, iter, parse_extras)?,
         })),
 
         2 => {
-            let (slider_type, curve_points) = read_val!(iter, parse_curve_points)?;
+            let (slider_type, curve_points) = read_val!(s, iter, parse_curve_points)?;
             Ok(HitObject::Slider(Slider {
                 x,
                 y,
                 new_combo,
                 color_skip,
+                raw_type: obj_type,
                 time,
                 hitsound,
                 slider_type,
                 curve_points,
 
-                repeat: read_val!(iter, parse_num)?,
-                pixel_length: read_val!(iter, parse_num)?,
+                repeat: read_val!(s, iter, parse_num)?,
+                pixel_length: read_val!(s, iter, parse_num)?,
 
-                edge_hitsounds: read_list!("|", iter, parse_num).unwrap_or(Vec::new()),
+                edge_hitsounds: read_optional!('|', s, iter, parse_num)?,
 
-                edge_additions: read_list!("|", iter, parse_coord).unwrap_or(Vec::new()),
+                edge_additions: read_optional!('|', s, iter, parse_edge_sample_set)?,
 
-                extras: read_val!(iter, parse_extras).unwrap_or(Default::default()),
+                extras: read_optional!(s, iter, parse_extras)?,
             }))
         }
 
@@ -250,11 +656,12 @@ pub fn parse_hit_object(s: &str) -> Result<HitObject> {
             time,
             new_combo,
             color_skip,
+            raw_type: obj_type,
             hitsound,
 
-            end_time: read_val!(iter, parse_num)?,
+            end_time: read_val!(s, iter, parse_time)?,
 
-            extras: read_val!(iter, parse_extras).unwrap_or(Default::default()),
+            extras: read_optional!(s, iter, parse_extras)?,
         })),
 
         128 => {
@@ -264,28 +671,19 @@ pub fn parse_hit_object(s: &str) -> Result<HitObject> {
                 time,
                 new_combo,
                 color_skip,
+                raw_type: obj_type,
                 hitsound,
 
                 ..Default::default()
             };
 
-            let (end_time, extras) = iter.next()
-                .and_then(|s| {
-                    let mut iter = s.splitn(2, ':');
-                    iter.next().and_then(|et| {
-                        iter.next().map(|ex| (et, ex))
-                    })
-                })
-                .ok_or_else(|| Error::Message("Could not read object extras"))
-                .and_then(|(et, ex)| {
-                    let et: i32 = parse_num(et)?;
-                    let ex = parse_extras(ex)?;
-
-                    return Ok((et, ex))
-                })?;
-
-            obj.end_time = end_time;
-            obj.extras = extras;
+            // The end time shares a field with the extras
+            let mut end_iter = iter.next()
+                .ok_or(Error::Message("Could not read hold note end time"))?
+                .splitn(2, ':');
+
+            obj.end_time = read_val!(s, end_iter, parse_time)?;
+            obj.extras = read_optional!(s, end_iter, parse_extras)?;
 
             Ok(HitObject::HoldNote(obj))
         },
@@ -0,0 +1,334 @@
+//! Performance points (pp) for a score, using the formulas published by the
+//! game for osu!standard, osu!taiko and osu!mania.
+//!
+//! Performance depends on a beatmap's difficulty attributes, such as its aim
+//! and speed strain, which this crate doesn't compute. They are taken as
+//! input, as calculated by a difficulty calculator or returned by the osu!
+//! API, and must already account for the score's mods.
+
+use scoring::Score;
+use Mods;
+
+/// Difficulty attributes of an osu!standard beatmap.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub struct StdAttributes {
+    pub aim_strain: f64,
+    pub speed_strain: f64,
+    pub flashlight_strain: f64,
+    /// Share of the aim difficulty that doesn't come from sliders, from 0 to
+    /// 1.
+    pub slider_factor: f64,
+    /// Approach rate and overall difficulty after mods, including those
+    /// changing the clock rate.
+    pub approach_rate: f64,
+    pub overall_difficulty: f64,
+    pub max_combo: usize,
+    pub circles: usize,
+    pub sliders: usize,
+    pub spinners: usize,
+}
+
+/// Performance of an osu!standard score, split by skill.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub struct StdPerformance {
+    pub aim: f64,
+    pub speed: f64,
+    pub accuracy: f64,
+    pub flashlight: f64,
+    pub total: f64,
+}
+
+/// Difficulty attributes of an osu!taiko beatmap.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub struct TaikoAttributes {
+    pub stars: f64,
+    /// Hit window of a great judgement in milliseconds, after mods.
+    pub great_hit_window: f64,
+    pub max_combo: usize,
+}
+
+/// Difficulty attributes of an osu!mania beatmap.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub struct ManiaAttributes {
+    pub stars: f64,
+}
+
+/// The larger of the actual misses and the misses implied by the combo,
+/// since dropped slider ends break combo without counting as misses.
+fn effective_miss_count(attributes: &StdAttributes, score: &Score) -> f64 {
+    let mut combo_based = 0.0;
+    if attributes.sliders > 0 {
+        let full_combo_threshold = attributes.max_combo as f64 - 0.1 * attributes.sliders as f64;
+        if (score.max_combo as f64) < full_combo_threshold {
+            combo_based = full_combo_threshold / (score.max_combo.max(1) as f64);
+        }
+    }
+    combo_based = combo_based.min((score.n100 + score.n50 + score.misses) as f64);
+
+    combo_based.max(score.misses as f64)
+}
+
+/// Difficulty value of a strain, shared by aim and speed.
+fn strain_value(strain: f64) -> f64 {
+    (5.0 * (strain / 0.0675).max(1.0) - 4.0).powi(3) / 100_000.0
+}
+
+/// Performance of an osu!standard score.
+///
+/// ```
+/// use osuparse::performance::{std_performance, StdAttributes};
+/// use osuparse::scoring::Score;
+/// use osuparse::Mods;
+///
+/// let attributes = StdAttributes {
+///     aim_strain: 2.8,
+///     speed_strain: 2.5,
+///     slider_factor: 1.0,
+///     approach_rate: 9.0,
+///     overall_difficulty: 8.0,
+///     max_combo: 800,
+///     circles: 400,
+///     sliders: 150,
+///     ..Default::default()
+/// };
+/// let fc = Score::from_accuracy(0.99, 550, 0, 800, Mods::NONE);
+/// let choke = Score::from_accuracy(0.99, 550, 3, 300, Mods::NONE);
+///
+/// let pp = std_performance(&attributes, &fc).total;
+/// assert!(pp > 200.0 && pp < 400.0);
+/// assert!(std_performance(&attributes, &choke).total < pp);
+/// ```
+pub fn std_performance(attributes: &StdAttributes, score: &Score) -> StdPerformance {
+    let mods = score.mods;
+    let total_hits = score.total_hits().max(1) as f64;
+    let accuracy = score.accuracy(::GameMode::Osu);
+    let misses = effective_miss_count(attributes, score);
+    let ar = attributes.approach_rate;
+    let od = attributes.overall_difficulty;
+
+    let mut multiplier = 1.14;
+    if mods.contains(Mods::NO_FAIL) {
+        multiplier *= (1.0 - 0.02 * misses).max(0.9);
+    }
+    if mods.contains(Mods::SPUN_OUT) {
+        multiplier *= 1.0 - (attributes.spinners as f64 / total_hits).powf(0.85);
+    }
+
+    let length_bonus = 0.95 + 0.4 * (total_hits / 2000.0).min(1.0)
+        + if total_hits > 2000.0 { (total_hits / 2000.0).log10() * 0.5 } else { 0.0 };
+    let combo_scaling = if attributes.max_combo > 0 {
+        ((score.max_combo as f64).powf(0.8) / (attributes.max_combo as f64).powf(0.8)).min(1.0)
+    } else {
+        1.0
+    };
+    let miss_penalty = |exponent: f64| 0.97 * (1.0 - (misses / total_hits).powf(0.775)).powf(exponent);
+
+    // Aim
+    let mut aim = strain_value(attributes.aim_strain) * length_bonus;
+    if misses > 0.0 {
+        aim *= miss_penalty(misses);
+    }
+    aim *= combo_scaling;
+
+    let mut ar_factor = 0.0;
+    if ar > 10.33 {
+        ar_factor = 0.3 * (ar - 10.33);
+    } else if ar < 8.0 {
+        ar_factor = 0.05 * (8.0 - ar);
+    }
+    aim *= 1.0 + ar_factor * length_bonus;
+    if mods.contains(Mods::HIDDEN) {
+        aim *= 1.0 + 0.04 * (12.0 - ar);
+    }
+
+    let difficult_sliders = attributes.sliders as f64 * 0.15;
+    if difficult_sliders > 0.0 {
+        let dropped_ends = ((score.n100 + score.n50 + score.misses) as f64)
+            .min(attributes.max_combo.saturating_sub(score.max_combo) as f64)
+            .max(0.0)
+            .min(difficult_sliders);
+        let slider_factor = attributes.slider_factor;
+        aim *= (1.0 - slider_factor) * (1.0 - dropped_ends / difficult_sliders).powi(3) + slider_factor;
+    }
+    aim *= accuracy * (0.98 + od * od / 2500.0);
+
+    // Speed
+    let mut speed = strain_value(attributes.speed_strain) * length_bonus;
+    if misses > 0.0 {
+        speed *= miss_penalty(misses.powf(0.875));
+    }
+    speed *= combo_scaling;
+    if ar > 10.33 {
+        speed *= 1.0 + 0.3 * (ar - 10.33) * length_bonus;
+    }
+    if mods.contains(Mods::HIDDEN) {
+        speed *= 1.0 + 0.04 * (12.0 - ar);
+    }
+    speed *= (0.95 + od * od / 750.0) * accuracy.powf((14.5 - od.max(8.0)) / 2.0);
+    let allowed_50s = total_hits / 500.0;
+    if score.n50 as f64 >= allowed_50s {
+        speed *= 0.99f64.powf(score.n50 as f64 - allowed_50s);
+    }
+
+    // Accuracy, which only counts circles
+    let circles = attributes.circles as f64;
+    let mut accuracy_value = 0.0;
+    if circles > 0.0 {
+        let circle_accuracy = (((score.n300 as f64 - (total_hits - circles)) * 6.0
+            + score.n100 as f64 * 2.0
+            + score.n50 as f64)
+            / (circles * 6.0))
+            .max(0.0);
+        accuracy_value = 1.52163f64.powf(od) * circle_accuracy.powi(24) * 2.83;
+        accuracy_value *= (circles / 1000.0).powf(0.3).min(1.15);
+        if mods.contains(Mods::HIDDEN) {
+            accuracy_value *= 1.08;
+        }
+        if mods.contains(Mods::FLASHLIGHT) {
+            accuracy_value *= 1.02;
+        }
+    }
+
+    // Flashlight
+    let mut flashlight = 0.0;
+    if mods.contains(Mods::FLASHLIGHT) {
+        flashlight = attributes.flashlight_strain.powi(2) * 25.0;
+        if misses > 0.0 {
+            flashlight *= miss_penalty(misses.powf(0.875));
+        }
+        flashlight *= combo_scaling;
+        flashlight *= 0.7 + 0.1 * (total_hits / 200.0).min(1.0)
+            + if total_hits > 200.0 { 0.2 * ((total_hits - 200.0) / 200.0).min(1.0) } else { 0.0 };
+        flashlight *= (0.5 + accuracy / 2.0) * (0.98 + od * od / 2500.0);
+    }
+
+    let total = (aim.powf(1.1) + speed.powf(1.1) + accuracy_value.powf(1.1) + flashlight.powf(1.1))
+        .powf(1.0 / 1.1)
+        * multiplier;
+
+    StdPerformance { aim, speed, accuracy: accuracy_value, flashlight, total }
+}
+
+/// Performance of an osu!taiko score.
+pub fn taiko_performance(attributes: &TaikoAttributes, score: &Score) -> f64 {
+    let mods = score.mods;
+    let total_hits = score.total_hits() as f64;
+    let accuracy = score.accuracy(::GameMode::Taiko);
+
+    let mut multiplier = 1.13;
+    if mods.contains(Mods::HIDDEN) {
+        multiplier *= 1.075;
+    }
+    if mods.contains(Mods::EASY) {
+        multiplier *= 0.975;
+    }
+
+    let length_bonus = 1.0 + 0.1 * (total_hits / 1500.0).min(1.0);
+
+    let mut difficulty = (5.0 * (attributes.stars / 0.115).max(1.0) - 4.0).powf(2.25) / 1150.0;
+    difficulty *= length_bonus * 0.986f64.powi(score.misses as i32);
+    if mods.contains(Mods::EASY) {
+        difficulty *= 0.985;
+    }
+    if mods.contains(Mods::HIDDEN) {
+        difficulty *= 1.025;
+    }
+    if mods.contains(Mods::FLASHLIGHT) {
+        difficulty *= 1.05 * length_bonus;
+    }
+    difficulty *= accuracy * accuracy;
+
+    let mut accuracy_value = 0.0;
+    if attributes.great_hit_window > 0.0 {
+        accuracy_value = (60.0 / attributes.great_hit_window).powf(1.1)
+            * accuracy.powi(8)
+            * attributes.stars.powf(0.4)
+            * 27.0;
+        // Not the same as the length bonus of the difficulty value
+        let accuracy_length_bonus = (total_hits / 1500.0).powf(0.3).min(1.15);
+        accuracy_value *= accuracy_length_bonus;
+        if mods.contains(Mods::HIDDEN) && mods.contains(Mods::FLASHLIGHT) {
+            accuracy_value *= (1.075 * accuracy_length_bonus).max(1.05);
+        }
+    }
+
+    (difficulty.powf(1.1) + accuracy_value.powf(1.1)).powf(1.0 / 1.1) * multiplier
+}
+
+/// Performance of an osu!mania score.
+pub fn mania_performance(attributes: &ManiaAttributes, score: &Score) -> f64 {
+    let mods = score.mods;
+    let total_hits = score.mania_total_hits() as f64;
+
+    let mut multiplier = 8.0;
+    if mods.contains(Mods::NO_FAIL) {
+        multiplier *= 0.75;
+    }
+    if mods.contains(Mods::EASY) {
+        multiplier *= 0.5;
+    }
+
+    // MAX judgements are worth slightly more than 300s here
+    let accuracy = if total_hits > 0.0 {
+        (320 * score.n_geki + 300 * score.n300 + 200 * score.n_katu + 100 * score.n100 + 50 * score.n50)
+            as f64
+            / (320.0 * total_hits)
+    } else {
+        0.0
+    };
+
+    let difficulty = (attributes.stars - 0.15).max(0.05).powf(2.2)
+        * (5.0 * accuracy - 4.0).max(0.0)
+        * (1.0 + 0.1 * (total_hits / 1500.0).min(1.0));
+
+    difficulty * multiplier
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_performance() {
+        let taiko = TaikoAttributes { stars: 5.0, great_hit_window: 35.0, max_combo: 1000 };
+        let fc = Score { n300: 1000, max_combo: 1000, ..Default::default() };
+        let misses = Score { n300: 980, misses: 20, max_combo: 400, ..Default::default() };
+        assert!(taiko_performance(&taiko, &fc) > taiko_performance(&taiko, &misses));
+
+        let mania = ManiaAttributes { stars: 4.0 };
+        let ss = Score { n_geki: 1500, ..Default::default() };
+        let pp = mania_performance(&mania, &ss);
+        assert!((pp - 8.0 * 3.85f64.powf(2.2) * 1.1).abs() < 1e-6);
+        let low = Score { n_geki: 1000, n50: 500, ..Default::default() };
+        assert_eq!(mania_performance(&mania, &low), 0.0);
+    }
+
+    #[test]
+    fn test_performance_values() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-6;
+
+        let std = StdAttributes {
+            aim_strain: 2.8,
+            speed_strain: 2.5,
+            slider_factor: 1.0,
+            approach_rate: 9.0,
+            overall_difficulty: 8.0,
+            max_combo: 800,
+            circles: 400,
+            sliders: 150,
+            ..Default::default()
+        };
+        let ss = Score { n300: 550, max_combo: 800, ..Default::default() };
+        let pp = std_performance(&std, &ss);
+        assert!(close(pp.aim, 89.70805138782536), "{}", pp.aim);
+        assert!(close(pp.speed, 65.27608729702446), "{}", pp.speed);
+        assert!(close(pp.accuracy, 61.784129156472844), "{}", pp.accuracy);
+        assert!(close(pp.total, 223.9488765521078), "{}", pp.total);
+
+        // Short enough that the accuracy length bonus is below 1.05
+        let taiko = TaikoAttributes { stars: 5.0, great_hit_window: 35.0, max_combo: 1000 };
+        let hdfl = Score { n300: 1000, max_combo: 1000, mods: Mods::HIDDEN | Mods::FLASHLIGHT, ..Default::default() };
+        let pp = taiko_performance(&taiko, &hdfl);
+        assert!(close(pp, 312.0707796562096), "{}", pp);
+    }
+}
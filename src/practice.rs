@@ -0,0 +1,189 @@
+use std::iter;
+
+use super::*;
+use events::Break;
+
+/// Time the player gets to prepare before the first hit object of an
+/// excerpt.
+const PREPARATION_TIME: Time = Time(2000.0);
+
+impl Beatmap {
+    /// A playable excerpt of the beatmap, holding the hit objects that start
+    /// at or after `start` and before `end`, for practising a section of it.
+    /// The song itself is left as it is, so the excerpt plays at the same
+    /// point in the audio.
+    ///
+    /// * Timing points within the range are kept, along with the ones in
+    ///   effect at `start`, so that the excerpt keeps its beat, slider
+    ///   velocity and hitsounds.
+    /// * Breaks are cut to the span of the remaining hit objects, and
+    ///   bookmarks outside the range are removed.
+    /// * The audio lead-in is set to leave two seconds before the first hit
+    ///   object, and the preview point is moved to it.
+    /// * The beatmap id is cleared, since the excerpt isn't the submitted
+    ///   difficulty. The version name is kept.
+    ///
+    /// [`Events::raw_lines`](events/struct.Events.html#structfield.raw_lines)
+    /// is cleared, so that the excerpt is written from its parsed events.
+    /// Timing points should be sorted by offset, as they are in a valid
+    /// beatmap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use osuparse::Time;
+    ///
+    /// let input = "osu file format v14
+    ///
+    /// [TimingPoints]
+    /// 0,500,4,1,0,100,1,0
+    /// 4000,-50,4,1,0,100,0,0
+    /// 20000,-100,4,1,0,100,0,0
+    ///
+    /// [HitObjects]
+    /// 256,192,1000,1,0
+    /// 256,192,10000,1,0
+    /// 256,192,12000,1,0
+    /// 256,192,30000,1,0
+    /// ";
+    ///
+    /// let map = osuparse::parse_beatmap(input).unwrap();
+    /// let excerpt = map.extract_range(Time(10000.0), Time(20000.0));
+    ///
+    /// assert_eq!(excerpt.hit_objects.len(), 2);
+    /// let offsets: Vec<Time> = excerpt.timing_points.iter().map(|p| p.offset).collect();
+    /// assert_eq!(offsets, vec![Time(0.0), Time(4000.0)]);
+    /// assert_eq!(excerpt.general.preview_time, Time(10000.0));
+    /// ```
+    pub fn extract_range(&self, start: Time, end: Time) -> Beatmap {
+        let mut map = self.clone();
+        let in_range = |time: Time| time >= start && time < end;
+
+        map.hit_objects.retain(|o| in_range(o.time()));
+
+        // The timing points in effect at the start, which may come long
+        // before it
+        let (start_time, end_time) = (start, end);
+        let uninherited = self.timing_points
+            .iter()
+            .rposition(|p| p.is_uninherited() && p.offset <= start_time);
+        let in_effect = self.timing_points.iter().rposition(|p| p.offset <= start_time);
+        map.timing_points = self.timing_points
+            .iter()
+            .enumerate()
+            .filter(|&(i, p)| {
+                (p.offset > start_time && p.offset < end_time)
+                    || Some(i) == uninherited
+                    || Some(i) == in_effect
+            })
+            .map(|(_, p)| p.clone())
+            .collect();
+
+        let first = map.hit_objects.iter().map(HitObject::time).min();
+        let index = map.timing_index();
+        let last = map.hit_objects
+            .iter()
+            .map(|o| Time::from(index.object_end_time(o).round()))
+            .max();
+
+        map.events.raw_lines.clear();
+        map.events.breaks = match (first, last) {
+            (Some(first), Some(last)) => self.events.breaks
+                .iter()
+                .map(|b| Break { start_time: b.start_time.max(first), end_time: b.end_time.min(last) })
+                .filter(|b| b.start_time < b.end_time)
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        map.editor.bookmarks.retain(|&b| in_range(b));
+        map.general.audio_lead_in = first.map_or(Time::ZERO, |first| (PREPARATION_TIME - first).max(Time::ZERO));
+        map.general.preview_time = first.unwrap_or(Time(-1.0));
+        map.metadata.beatmap_id = BeatmapId(0);
+
+        map
+    }
+
+    /// Splits the beatmap at its bookmarks into excerpts made by
+    /// [`extract_range`](#method.extract_range), one for each stretch
+    /// between two bookmarks, plus the stretches before the first and after
+    /// the last. Stretches without hit objects are left out.
+    pub fn split_at_bookmarks(&self) -> Vec<Beatmap> {
+        let mut bookmarks = self.editor.bookmarks.clone();
+        bookmarks.sort_unstable();
+        bookmarks.dedup();
+
+        let bounds: Vec<Time> = iter::once(Time(f64::NEG_INFINITY))
+            .chain(bookmarks)
+            .chain(iter::once(Time(f64::INFINITY)))
+            .collect();
+
+        bounds
+            .windows(2)
+            .map(|range| self.extract_range(range[0], range[1]))
+            .filter(|map| !map.hit_objects.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_at_bookmarks() {
+        let input = "osu file format v14
+
+[General]
+AudioLeadIn: 1500
+PreviewTime: 40000
+
+[Editor]
+Bookmarks: 5000,2500
+
+[Metadata]
+BeatmapID:123
+
+[Events]
+2,1200,4800
+
+[TimingPoints]
+0,500,4,1,0,100,1,0
+2000,-50,4,1,0,100,0,1
+2500,-100,4,1,0,100,0,0
+3000,-200,4,1,0,100,0,0
+
+[HitObjects]
+256,192,1000,1,0
+256,192,3000,1,0
+256,192,4000,1,0
+256,192,6000,1,0
+";
+
+        let map = parse_beatmap(input).unwrap();
+        let parts = map.split_at_bookmarks();
+        assert_eq!(parts.len(), 3);
+
+        let times = |map: &Beatmap| -> Vec<f64> { map.hit_objects.iter().map(|o| o.time().0).collect() };
+        let offsets = |map: &Beatmap| -> Vec<f64> { map.timing_points.iter().map(|p| p.offset.0).collect() };
+
+        assert_eq!(times(&parts[0]), vec![1000.0]);
+        assert_eq!(offsets(&parts[0]), vec![0.0, 2000.0]);
+        assert_eq!(parts[0].general.audio_lead_in, Time(1000.0));
+        assert!(parts[0].events.breaks.is_empty());
+
+        assert_eq!(times(&parts[1]), vec![3000.0, 4000.0]);
+        assert_eq!(offsets(&parts[1]), vec![0.0, 2500.0, 3000.0]);
+        assert_eq!(parts[1].events.breaks, vec![Break { start_time: Time(3000.0), end_time: Time(4000.0) }]);
+        assert_eq!(parts[1].editor.bookmarks, vec![Time(2500.0)]);
+        assert_eq!(parts[1].general.audio_lead_in, Time::ZERO);
+        assert_eq!(parts[1].general.preview_time, Time(3000.0));
+        assert_eq!(parts[1].metadata.beatmap_id, BeatmapId(0));
+
+        assert_eq!(times(&parts[2]), vec![6000.0]);
+        assert_eq!(offsets(&parts[2]), vec![0.0, 3000.0]);
+
+        let reparsed = parse_beatmap(&parts[1].to_osu_string()).unwrap();
+        assert_eq!(reparsed.events.breaks, parts[1].events.breaks);
+    }
+}
@@ -0,0 +1,156 @@
+//! Property tests checking that the parser never panics and that
+//! serialization is lossless.
+
+use proptest::prelude::*;
+
+use super::*;
+use deserialize::ToOsuString;
+
+fn extras() -> impl Strategy<Value = HitObjectExtras> {
+    (0..4, 0..4, 0..100, 0..101, "[a-z]{0,8}(\\.wav)?").prop_map(
+        |(sample_set, addition_set, custom_index, sample_volume, filename)| HitObjectExtras {
+            sample_set,
+            addition_set,
+            custom_index,
+            sample_volume,
+            filename,
+        },
+    )
+}
+
+/// The type bitmap of a hit object, built from the parts the parser derives
+/// from it. Bits above the first byte are kept as they are.
+fn raw_type(base: i32, new_combo: bool, color_skip: i32, high: i32) -> i32 {
+    base | (i32::from(new_combo) << 2) | (color_skip << 4) | (high << 8)
+}
+
+/// A time in whole milliseconds, as most beatmaps write them.
+fn time() -> impl Strategy<Value = Time> + Clone {
+    (0..1_000_000).prop_map(Time::from)
+}
+
+fn hit_object() -> impl Strategy<Value = HitObject> {
+    let common = (-1000..1000, -1000..1000, any::<bool>(), 0..8, 0..0x10000, time(), 0..16);
+
+    prop_oneof![
+        (common.clone(), extras()).prop_map(|((x, y, new_combo, color_skip, high, time, hitsound), extras)| {
+            let raw_type = raw_type(1, new_combo, color_skip, high);
+            HitObject::HitCircle(HitCircle { x, y, new_combo, color_skip, raw_type, time, hitsound, extras })
+        }),
+        (
+            common.clone(),
+            prop_oneof![
+                Just(SliderType::Linear),
+                Just(SliderType::Bezier),
+                Just(SliderType::Perfect),
+                Just(SliderType::Catmull),
+            ],
+            prop::collection::vec((-1000..1000, -1000..1000), 1..6),
+            1..10,
+            0.0f64..10_000.0,
+            extras(),
+        ).prop_map(|((x, y, new_combo, color_skip, high, time, hitsound), slider_type, curve_points, repeat, pixel_length, extras)| {
+            let edges = repeat as usize + 1;
+            let raw_type = raw_type(2, new_combo, color_skip, high);
+            HitObject::Slider(Slider {
+                x,
+                y,
+                new_combo,
+                color_skip,
+                raw_type,
+                time,
+                slider_type,
+                curve_points,
+                repeat,
+                pixel_length,
+                edge_hitsounds: vec![0; edges],
+                edge_additions: vec![EdgeSampleSet::default(); edges],
+                hitsound,
+                extras,
+            })
+        }),
+        (common.clone(), time(), extras()).prop_map(|((x, y, new_combo, color_skip, high, time, hitsound), end_time, extras)| {
+            let raw_type = raw_type(8, new_combo, color_skip, high);
+            HitObject::Spinner(Spinner { x, y, new_combo, color_skip, raw_type, time, hitsound, end_time, extras })
+        }),
+        (common, time(), extras()).prop_map(|((x, y, new_combo, color_skip, high, time, hitsound), end_time, extras)| {
+            let raw_type = raw_type(128, new_combo, color_skip, high);
+            HitObject::HoldNote(HoldNote { x, y, new_combo, color_skip, raw_type, time, hitsound, end_time, extras })
+        }),
+    ]
+}
+
+fn timing_point() -> impl Strategy<Value = TimingPoint> {
+    (
+        -10_000.0f32..1_000_000.0,
+        prop_oneof![1.0f64..5000.0, -1000.0f64..-1.0],
+        1..8,
+        0..4,
+        0..100,
+        0..101,
+        any::<bool>(),
+        0..16,
+    ).prop_map(|(offset, ms_per_beat, meter, sample_set, sample_index, volume, inherited, effects)| {
+        TimingPoint {
+            offset: Time::from(offset),
+            ms_per_beat,
+            meter,
+            sample_set,
+            sample_index,
+            volume,
+            inherited,
+            effects: Effects::from_bits(effects),
+        }
+    })
+}
+
+fn beatmap() -> impl Strategy<Value = Beatmap> {
+    (
+        3..15,
+        "[A-Za-z0-9 ]{0,20}",
+        0.0f32..10.0,
+        prop::collection::vec(timing_point(), 0..20),
+        prop::collection::vec(hit_object(), 0..50),
+        prop::collection::vec((0..256, 0..256, 0..256), 0..8),
+    ).prop_map(|(version, title, approach_rate, timing_points, hit_objects, colours)| {
+        let mut map = Beatmap {
+            version,
+            timing_points,
+            hit_objects,
+            ..Default::default()
+        };
+        map.metadata.title = title.trim().to_string();
+        map.difficulty.approach_rate = approach_rate;
+        map.colours.colours = colours.into_iter().map(|(r, g, b)| Colour(r, g, b)).collect();
+
+        map
+    })
+}
+
+proptest! {
+    #[test]
+    fn parse_serialize_parse(map in beatmap()) {
+        let parsed = parse_beatmap(&map.to_osu_string()).unwrap();
+        let reparsed = parse_beatmap(&parsed.to_osu_string()).unwrap();
+
+        // Every key is written out, so all of them are present once parsed
+        let expected = Beatmap { present_keys: parsed.present_keys.clone(), ..map };
+        prop_assert_eq!(&expected, &parsed);
+        prop_assert_eq!(parsed, reparsed);
+    }
+
+    #[test]
+    fn arbitrary_input_does_not_panic(input in "\\PC*(\n\\PC*){0,20}") {
+        let _ = parse_beatmap(&input);
+        let _ = parse_raw(&input);
+    }
+
+    #[test]
+    fn structured_input_does_not_panic(
+        input in "osu file format v[0-9]{1,3}\n(\\[(General|Editor|Metadata|Difficulty|Events|TimingPoints|HitObjects|Colours)\\]\n([A-Za-z]{0,12}:? ?[-0-9,:|LBPC.]{0,40}\n){0,6}){0,6}"
+    ) {
+        if let Ok(map) = parse_beatmap(&input) {
+            let _ = parse_beatmap(&map.to_osu_string());
+        }
+    }
+}
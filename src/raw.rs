@@ -0,0 +1,154 @@
+//! Untyped access to the contents of a .osu file.
+//!
+//! [`parse_raw`](fn.parse_raw.html) splits a file into its sections without
+//! interpreting any values, which gives access to keys and sections the
+//! typed [`Beatmap`](../struct.Beatmap.html) model doesn't cover yet.
+//!
+//! ```
+//! use osuparse::parse_raw;
+//!
+//! let raw = parse_raw("osu file format v14\n\n[General]\nAudioFilename: a.mp3\n").unwrap();
+//!
+//! assert_eq!(raw.section("General").unwrap().value("AudioFilename"), Some("a.mp3"));
+//! ```
+//!
+//! The parsers for single hit object and timing point lines are available
+//! here too, for tools that read a file line by line or only care about one
+//! section. They expect a line without its line ending or trailing comment,
+//! and don't apply any [`ParserOptions`](../struct.ParserOptions.html).
+//!
+//! ```
+//! use osuparse::Time;
+//! use osuparse::raw::{parse_hit_object, parse_timing_point};
+//!
+//! let object = parse_hit_object("256,192,1000,1,0").unwrap();
+//! assert_eq!(object.time(), Time(1000.0));
+//!
+//! let point = parse_timing_point("764,363.63").unwrap();
+//! assert_eq!((point.meter, point.volume), (4, 100));
+//! ```
+
+use super::*;
+pub use parse::{parse_extras, parse_hit_object, parse_timing_point};
+
+/// A .osu file split into sections. Borrows from the parsed input.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RawBeatmap<'a> {
+    /// The version of the .osu file format.
+    pub version: i32,
+    /// Every section in the file, in the order they appear.
+    pub sections: Vec<RawSection<'a>>,
+}
+
+impl<'a> RawBeatmap<'a> {
+    /// Finds the first section with the given name, ignoring case and
+    /// surrounding spaces. `Colors` finds the `Colours` section and the
+    /// other way around.
+    pub fn section(&self, name: &str) -> Option<&RawSection<'a>> {
+        let name = canonical_section_name(name);
+        self.sections.iter().find(|s| unicase::eq(canonical_section_name(s.name), name))
+    }
+}
+
+/// A single section of a .osu file.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RawSection<'a> {
+    /// The section name, without the surrounding brackets.
+    pub name: &'a str,
+    /// Every non-empty line in the section, in order.
+    pub lines: Vec<&'a str>,
+}
+
+impl<'a> RawSection<'a> {
+    /// The section's lines split into trimmed `(key, value)` pairs at the
+    /// first `:`, in order. Lines without a `:` are left out.
+    ///
+    /// This is only meaningful for key-value sections such as `[General]` or
+    /// `[Metadata]`.
+    pub fn pairs(&self) -> Vec<(&'a str, &'a str)> {
+        self.lines
+            .iter()
+            .filter_map(|l| {
+                let mut iter = l.splitn(2, ':');
+                iter.next()
+                    .and_then(|left| iter.next().map(|right| (left.trim(), right.trim())))
+            })
+            .collect()
+    }
+
+    /// The value of the last pair with the given key, ignoring case.
+    pub fn value(&self, key: &str) -> Option<&'a str> {
+        self.pairs()
+            .into_iter()
+            .rev()
+            .find(|(k, _)| unicase::eq(*k, key))
+            .map(|(_, v)| v)
+    }
+}
+
+/// Splits a .osu file into its sections without interpreting their contents.
+///
+/// Only the version header and the section headers are checked, so this
+/// succeeds on many files [`parse_beatmap`](../fn.parse_beatmap.html) would
+/// reject.
+pub fn parse_raw(input: &str) -> Result<RawBeatmap<'_>> {
+    let mut state = ParseState::new(input);
+
+    let version = parse_version_string(&mut state)?;
+    state.read_next_line();
+
+    let mut sections = Vec::new();
+
+    while let Some(line) = state.get_current_line() {
+        let name = match_header_line(line)
+            .ok_or_else(|| state.syntax_error("Malformed section header"))?;
+
+        state.strip_comments = canonical_section_name(name) != "Metadata";
+
+        let mut lines = Vec::new();
+        loop {
+            match state.read_next_line() {
+                Some(l) if match_header_line(l).is_none() => lines.push(l),
+                _ => break,
+            }
+        }
+
+        sections.push(RawSection { name, lines });
+    }
+
+    Ok(RawBeatmap { version, sections })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_raw() {
+        let raw = parse_raw(
+            "osu file format v14
+
+[General]
+AudioFilename: a.mp3
+FutureKey: 1
+
+[Unknown]
+some line
+
+[HitObjects]
+256,192,1000,1,0,0:0:0:0:
+",
+        ).unwrap();
+
+        assert_eq!(raw.version, 14);
+
+        let names: Vec<&str> = raw.sections.iter().map(|s| s.name).collect();
+        assert_eq!(names, vec!["General", "Unknown", "HitObjects"]);
+
+        let general = raw.section("general").unwrap();
+        assert_eq!(general.pairs(), vec![("AudioFilename", "a.mp3"), ("FutureKey", "1")]);
+        assert_eq!(general.value("futurekey"), Some("1"));
+
+        assert_eq!(raw.sections[2].lines, vec!["256,192,1000,1,0,0:0:0:0:"]);
+    }
+}
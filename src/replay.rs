@@ -0,0 +1,344 @@
+//! Reconstructing judgements from replay input, and generating the input of
+//! a perfect play.
+//!
+//! This crate doesn't read `.osr` files, so the analyzer works on
+//! [`ReplayFrame`](struct.ReplayFrame.html)s, which hold the same data as
+//! the frames of a decompressed replay with their times made absolute.
+
+use super::*;
+use modes::{StdBeatmap, StdObject};
+use scoring::{HitWindows, Judgement, Score};
+
+/// Key bits of the two mouse buttons. Keyboard keys also set the bit of
+/// their mouse button.
+const CLICK_KEYS: u32 = 1 | 2;
+/// The first and second keyboard keys, with their mouse button bits.
+const KEY_1: u32 = 1 | 4;
+const KEY_2: u32 = 2 | 8;
+
+/// Time between frames while following sliders and spinners.
+const FRAME_INTERVAL: Time = Time(16.0);
+/// How long keys are held after hitting a circle or finishing an object.
+const KEY_UP_DELAY: Time = Time(50.0);
+/// Radius of the circle the cursor follows while spinning.
+const SPIN_RADIUS: f32 = 50.0;
+/// Spinning speed in radians per millisecond, about 477 rotations per
+/// minute like the game's Auto mod.
+const SPIN_SPEED: f32 = 0.05;
+/// Centre of the playfield, where spinners are.
+const PLAYFIELD_CENTRE: (f32, f32) = (256.0, 192.0);
+
+/// The cursor position and keys held at a point in time.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub struct ReplayFrame {
+    /// Time from the start of the song.
+    pub time: Time,
+    /// Cursor position in osu!pixels.
+    pub x: f32,
+    pub y: f32,
+    /// Bit flags of the keys held, as stored in replays: `1` and `2` for the
+    /// mouse buttons, `4` and `8` for the keyboard keys.
+    pub keys: u32,
+}
+
+/// How a single hit object was played.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct ObjectJudgement {
+    /// Index of the object in the beatmap's hit objects.
+    pub index: usize,
+    pub judgement: Judgement,
+    /// How late the object was hit, negative if it was hit early. `None`
+    /// for misses.
+    pub error: Option<Time>,
+}
+
+/// The result of walking a replay against a beatmap.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ReplayAnalysis {
+    /// Judgements of the hit circles and sliders, in order. Spinners aren't
+    /// judged.
+    pub judgements: Vec<ObjectJudgement>,
+}
+
+impl ReplayAnalysis {
+    /// The hit error of every object that was hit.
+    pub fn hit_errors(&self) -> Vec<Time> {
+        self.judgements.iter().filter_map(|j| j.error).collect()
+    }
+
+    /// The average hit error, negative when hitting early on average.
+    pub fn mean_error(&self) -> Option<f64> {
+        let errors = self.hit_errors();
+        if errors.is_empty() {
+            return None;
+        }
+        Some(errors.iter().map(|e| e.0).sum::<f64>() / errors.len() as f64)
+    }
+
+    /// Ten times the standard deviation of the hit errors, as shown by the
+    /// game.
+    pub fn unstable_rate(&self) -> Option<f64> {
+        let mean = self.mean_error()?;
+        let errors = self.hit_errors();
+        let variance = errors.iter().map(|e| (e.0 - mean).powi(2)).sum::<f64>() / errors.len() as f64;
+
+        Some(variance.sqrt() * 10.0)
+    }
+
+    /// Counts of each judgement, as a [`Score`](../scoring/struct.Score.html)
+    /// without combo or mods.
+    pub fn counts(&self) -> Score {
+        let mut score = Score::default();
+        for j in &self.judgements {
+            match j.judgement {
+                Judgement::Great => score.n300 += 1,
+                Judgement::Ok => score.n100 += 1,
+                Judgement::Meh => score.n50 += 1,
+                Judgement::Miss => score.misses += 1,
+            }
+        }
+        score
+    }
+}
+
+/// A click in the replay, and whether it has been used to hit an object.
+struct Press {
+    time: Time,
+    x: f32,
+    y: f32,
+    used: bool,
+}
+
+impl<'a> StdBeatmap<'a> {
+    /// Works out how each hit circle and slider head was hit by the given
+    /// replay frames, which must be sorted by time.
+    ///
+    /// An object is hit by the first unused click that lands on it within
+    /// its 50 hit window. This leaves out some of the game's rules, such as
+    /// notelock, slider ticks and ends, and stacking, so judgements may
+    /// differ from the game's on some plays.
+    pub fn analyze_replay(&self, frames: &[ReplayFrame], mods: Mods) -> ReplayAnalysis {
+        let difficulty = &self.beatmap.difficulty;
+        let (mut cs, mut od) = (difficulty.circle_size, difficulty.overall_difficulty);
+        if mods.contains(Mods::HARD_ROCK) {
+            cs = (cs * 1.3).min(10.0);
+            od = (od * 1.4).min(10.0);
+        } else if mods.contains(Mods::EASY) {
+            cs *= 0.5;
+            od *= 0.5;
+        }
+        let windows = HitWindows::from_od(od);
+        let radius = 54.4 - 4.48 * cs;
+        // The playfield is flipped vertically with Hard Rock
+        let flip = |y: i32| if mods.contains(Mods::HARD_ROCK) { 384 - y } else { y };
+
+        let mut presses = Vec::new();
+        let mut held = 0;
+        for frame in frames {
+            let keys = frame.keys & CLICK_KEYS;
+            if keys & !held != 0 {
+                presses.push(Press { time: frame.time, x: frame.x, y: frame.y, used: false });
+            }
+            held = keys;
+        }
+
+        let mut judgements = Vec::new();
+        for (index, obj) in self.objects.iter().enumerate() {
+            let (time, x, y) = match obj {
+                StdObject::Circle(c) => (c.time, c.x, flip(c.y)),
+                StdObject::Slider(s) => (s.time, s.x, flip(s.y)),
+                StdObject::Spinner(_) => continue,
+            };
+
+            let hit = presses.iter_mut().find(|p| {
+                let (dx, dy) = (p.x - x as f32, p.y - y as f32);
+                !p.used
+                    && (p.time - time).abs().0 <= windows.meh
+                    && dx * dx + dy * dy <= radius * radius
+            });
+
+            let judgement = match hit {
+                Some(press) => {
+                    press.used = true;
+                    let error = press.time - time;
+                    ObjectJudgement {
+                        index,
+                        judgement: windows.judge(error.0).unwrap_or(Judgement::Miss),
+                        error: Some(error),
+                    }
+                }
+                None => ObjectJudgement { index, judgement: Judgement::Miss, error: None },
+            };
+            judgements.push(judgement);
+        }
+
+        ReplayAnalysis { judgements }
+    }
+}
+
+impl<'a> StdBeatmap<'a> {
+    /// Generates the input of a perfect play, like the game's Auto mod: the
+    /// cursor moves onto every object and hits it exactly on time, follows
+    /// sliders along their path and spins spinners around the centre of the
+    /// playfield. Keys alternate between objects.
+    ///
+    /// Frames are only generated while hitting objects, so the cursor moves
+    /// in a straight line between them when the frames are interpolated.
+    pub fn autoplay_frames(&self) -> Vec<ReplayFrame> {
+        let index = self.beatmap.timing_index();
+        let mut frames = Vec::new();
+        let frame = |time: Time, (x, y): (f32, f32), keys: u32| ReplayFrame { time, x, y, keys };
+
+        for (i, obj) in self.objects.iter().enumerate() {
+            let keys = if i % 2 == 0 { KEY_1 } else { KEY_2 };
+            let end_time = match obj {
+                StdObject::Circle(c) => {
+                    frames.push(frame(c.time, (c.x as f32, c.y as f32), keys));
+                    c.time
+                }
+                StdObject::Slider(s) => {
+                    let path = s.path();
+                    let duration = index.slider_duration(s);
+                    let spans = s.repeat.max(1) as f32;
+                    let position = |time: Time| {
+                        let progress = if duration > Time::ZERO {
+                            ((time - s.time).0 / duration.0) as f32 * spans
+                        } else {
+                            0.0
+                        };
+                        // Every other span goes back towards the head
+                        let span = progress.floor().min(spans - 1.0);
+                        let within = progress - span;
+                        path.position_at(if span as i32 % 2 == 0 { within } else { 1.0 - within })
+                    };
+
+                    let end_time = s.time + Time::from(duration.round());
+                    let mut time = s.time;
+                    while time < end_time {
+                        frames.push(frame(time, position(time), keys));
+                        time += FRAME_INTERVAL;
+                    }
+                    frames.push(frame(end_time, position(end_time), keys));
+                    end_time
+                }
+                StdObject::Spinner(s) => {
+                    let mut time = s.time;
+                    loop {
+                        let angle = (time - s.time).0 as f32 * SPIN_SPEED;
+                        let position = (
+                            PLAYFIELD_CENTRE.0 + SPIN_RADIUS * angle.cos(),
+                            PLAYFIELD_CENTRE.1 + SPIN_RADIUS * angle.sin(),
+                        );
+                        frames.push(frame(time, position, keys));
+                        if time >= s.end_time {
+                            break;
+                        }
+                        time = (time + FRAME_INTERVAL).min(s.end_time);
+                    }
+                    s.end_time
+                }
+            };
+
+            // Release the key before the next object, if there is time
+            let next_time = self.objects.get(i + 1).map(|o| match o {
+                StdObject::Circle(c) => c.time,
+                StdObject::Slider(s) => s.time,
+                StdObject::Spinner(s) => s.time,
+            });
+            let release_time = match next_time {
+                Some(next) => (end_time + KEY_UP_DELAY).min(next - Time(1.0)),
+                None => end_time + KEY_UP_DELAY,
+            };
+            if release_time > end_time {
+                let last = frames[frames.len() - 1];
+                frames.push(ReplayFrame { time: release_time, keys: 0, ..last });
+            }
+        }
+
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_replay() {
+        let input = "osu file format v14
+
+[Difficulty]
+CircleSize:4
+OverallDifficulty:5
+
+[HitObjects]
+100,100,1000,1,0
+200,100,2000,1,0
+300,100,3000,2,0,L|400:100,1,100
+256,192,4000,8,0,5000
+400,300,6000,1,0
+";
+
+        let map = parse_beatmap(input).unwrap();
+        let std = map.as_std().unwrap();
+        let frame = |time, x, y, keys| ReplayFrame { time: Time(time), x, y, keys };
+        let frames = vec![
+            frame(980.0, 100.0, 100.0, 1),
+            frame(990.0, 100.0, 100.0, 1),
+            frame(1010.0, 100.0, 100.0, 0),
+            frame(2070.0, 205.0, 100.0, 5),
+            frame(2100.0, 205.0, 100.0, 0),
+            frame(3010.0, 300.0, 100.0, 2),
+            frame(6000.0, 100.0, 100.0, 1),
+        ];
+
+        let analysis = std.analyze_replay(&frames, Mods::NONE);
+        let judgements: Vec<Judgement> = analysis.judgements.iter().map(|j| j.judgement).collect();
+        assert_eq!(judgements, vec![Judgement::Great, Judgement::Ok, Judgement::Great, Judgement::Miss]);
+        assert_eq!(analysis.judgements[3].index, 4);
+        assert_eq!(analysis.hit_errors(), vec![Time(-20.0), Time(70.0), Time(10.0)]);
+        assert_eq!(analysis.mean_error(), Some(20.0));
+
+        let ur = analysis.unstable_rate().unwrap();
+        assert!((ur - 374.2).abs() < 0.1);
+        assert_eq!(analysis.counts().n100, 1);
+    }
+
+    #[test]
+    fn test_autoplay() {
+        let input = "osu file format v14
+
+[Difficulty]
+SliderMultiplier:1
+
+[TimingPoints]
+0,500,4,1,0,100,1,0
+
+[HitObjects]
+100,100,1000,1,0
+200,100,1030,2,0,L|300:100,2,100
+256,192,3000,8,0,3100
+";
+
+        let map = parse_beatmap(input).unwrap();
+        let std = map.as_std().unwrap();
+        let frames = std.autoplay_frames();
+
+        assert_eq!(frames[0], ReplayFrame { time: Time(1000.0), x: 100.0, y: 100.0, keys: KEY_1 });
+        assert_eq!(frames[1], ReplayFrame { time: Time(1029.0), x: 100.0, y: 100.0, keys: 0 });
+        assert_eq!((frames[2].time, frames[2].keys), (Time(1030.0), KEY_2));
+
+        // The slider turns around at its end and returns to its head
+        let at = |time| frames.iter().find(|f| f.time == Time(time)).unwrap();
+        assert_eq!((at(1526.0).x, at(1526.0).y), (299.2, 100.0));
+        assert_eq!((at(2030.0).x, at(2030.0).y, at(2030.0).keys), (200.0, 100.0, KEY_2));
+        assert_eq!(at(2080.0).keys, 0);
+
+        assert_eq!(at(3000.0).keys, KEY_1);
+        assert_eq!(frames.last().unwrap().time, Time(3150.0));
+        assert!(frames.windows(2).all(|w| w[0].time < w[1].time));
+
+        let analysis = std.analyze_replay(&frames, Mods::NONE);
+        assert!(analysis.judgements.iter().all(|j| j.judgement == Judgement::Great));
+    }
+}
@@ -0,0 +1,232 @@
+//! Summaries of beatmaps for sharing as a table, such as the mappool sheets
+//! of a tournament.
+//!
+//! # Examples
+//!
+//! ```
+//! use osuparse::{parse_beatmap, Time};
+//! use osuparse::report::{to_csv, MapSummary};
+//!
+//! let map = parse_beatmap("osu file format v14
+//!
+//! [Metadata]
+//! Title:Song
+//! Artist:Artist
+//! Creator:Mapper
+//! Version:Insane
+//!
+//! [Difficulty]
+//! ApproachRate:9
+//!
+//! [TimingPoints]
+//! 0,300,4,1,0,100,1,0
+//!
+//! [HitObjects]
+//! 256,192,1000,1,0
+//! 256,192,91000,1,0
+//! ").unwrap();
+//!
+//! let mut summary = MapSummary::new(&map);
+//! summary.star_rating = Some(5.46);
+//! assert_eq!(summary.bpm, Some(200.0));
+//! assert_eq!(summary.length, Time(90000.0));
+//!
+//! let csv = to_csv(&[summary]);
+//! assert!(csv.lines().nth(1).unwrap().starts_with("Artist,Song,Mapper,Insane,"));
+//! ```
+
+#[cfg(feature = "json")]
+use serde_json;
+
+use super::*;
+
+/// The figures a beatmap is usually judged by at a glance.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MapSummary {
+    pub artist: String,
+    pub title: String,
+    pub creator: String,
+    pub version: String,
+    pub beatmap_id: BeatmapId,
+    pub game_mode: GameMode,
+    pub circle_size: f32,
+    pub approach_rate: f32,
+    pub overall_difficulty: f32,
+    pub hp_drain_rate: f32,
+    /// See [`Beatmap::main_bpm`](../struct.Beatmap.html#method.main_bpm).
+    pub bpm: Option<f64>,
+    pub min_bpm: Option<f64>,
+    pub max_bpm: Option<f64>,
+    /// See [`Beatmap::total_length`](../struct.Beatmap.html#method.total_length).
+    /// Written to CSV in whole milliseconds.
+    pub length: Time,
+    /// See [`Beatmap::drain_time`](../struct.Beatmap.html#method.drain_time).
+    /// Written to CSV in whole milliseconds.
+    pub drain_time: Time,
+    /// Star ratings aren't computed by this crate, so this is `None` unless
+    /// set from elsewhere.
+    pub star_rating: Option<f64>,
+    pub circles: usize,
+    pub sliders: usize,
+    pub spinners: usize,
+    pub hold_notes: usize,
+}
+
+impl MapSummary {
+    pub fn new(map: &Beatmap) -> Self {
+        let count = |f: fn(&HitObject) -> bool| map.hit_objects.iter().filter(|o| f(o)).count();
+        let bpm_range = map.bpm_range();
+
+        MapSummary {
+            artist: map.metadata.artist.clone(),
+            title: map.metadata.title.clone(),
+            creator: map.metadata.creator.clone(),
+            version: map.metadata.version.clone(),
+            beatmap_id: map.metadata.beatmap_id,
+            game_mode: map.general.game_mode,
+            circle_size: map.difficulty.circle_size,
+            approach_rate: map.difficulty.approach_rate,
+            overall_difficulty: map.difficulty.overall_difficulty,
+            hp_drain_rate: map.difficulty.hp_drain_rate,
+            bpm: map.main_bpm(),
+            min_bpm: bpm_range.map(|(min, _)| min),
+            max_bpm: bpm_range.map(|(_, max)| max),
+            length: map.total_length(),
+            drain_time: map.drain_time(),
+            star_rating: None,
+            circles: count(|o| matches!(o, HitObject::HitCircle(_))),
+            sliders: count(|o| matches!(o, HitObject::Slider(_))),
+            spinners: count(|o| matches!(o, HitObject::Spinner(_))),
+            hold_notes: count(|o| matches!(o, HitObject::HoldNote(_))),
+        }
+    }
+
+    /// The values of the summary as written by [`to_csv`](fn.to_csv.html),
+    /// in the order of [`CSV_HEADER`](constant.CSV_HEADER.html).
+    fn csv_fields(&self) -> Vec<String> {
+        let optional = |value: Option<String>| value.unwrap_or_default();
+
+        vec![
+            self.artist.clone(),
+            self.title.clone(),
+            self.creator.clone(),
+            self.version.clone(),
+            self.beatmap_id.to_string(),
+            format!("{:?}", self.game_mode),
+            self.circle_size.to_string(),
+            self.approach_rate.to_string(),
+            self.overall_difficulty.to_string(),
+            self.hp_drain_rate.to_string(),
+            optional(self.bpm.map(|bpm| format!("{:.2}", bpm))),
+            optional(self.min_bpm.map(|bpm| format!("{:.2}", bpm))),
+            optional(self.max_bpm.map(|bpm| format!("{:.2}", bpm))),
+            self.length.round().to_string(),
+            self.drain_time.round().to_string(),
+            optional(self.star_rating.map(|stars| format!("{:.2}", stars))),
+            self.circles.to_string(),
+            self.sliders.to_string(),
+            self.spinners.to_string(),
+            self.hold_notes.to_string(),
+        ]
+    }
+}
+
+/// Column names of the CSV written by [`to_csv`](fn.to_csv.html).
+pub const CSV_HEADER: [&str; 20] = [
+    "artist",
+    "title",
+    "creator",
+    "version",
+    "beatmap_id",
+    "game_mode",
+    "circle_size",
+    "approach_rate",
+    "overall_difficulty",
+    "hp_drain_rate",
+    "bpm",
+    "min_bpm",
+    "max_bpm",
+    "length",
+    "drain_time",
+    "star_rating",
+    "circles",
+    "sliders",
+    "spinners",
+    "hold_notes",
+];
+
+/// Summarizes every beatmap, in order.
+pub fn summarize(maps: &[Beatmap]) -> Vec<MapSummary> {
+    maps.iter().map(MapSummary::new).collect()
+}
+
+/// Writes the summaries as CSV, one row per beatmap under a row with the
+/// column names. BPMs and star ratings are rounded to two decimals, and
+/// missing values are left empty.
+pub fn to_csv(summaries: &[MapSummary]) -> String {
+    let mut output = CSV_HEADER.join(",");
+    output.push('\n');
+
+    for summary in summaries {
+        let fields: Vec<String> = summary.csv_fields().iter().map(|f| escape_csv(f)).collect();
+        output.push_str(&fields.join(","));
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Writes the summaries as a JSON array of objects, with the field names of
+/// [`MapSummary`](struct.MapSummary.html).
+///
+/// Requires the `json` feature.
+#[cfg(feature = "json")]
+pub fn to_json(summaries: &[MapSummary]) -> Result<String> {
+    serde_json::to_string_pretty(summaries).map_err(Error::Json)
+}
+
+/// Quotes a field if it contains a comma, quote or line break, doubling any
+/// quotes inside it.
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_csv() {
+        let map = parse_beatmap("osu file format v14
+
+[General]
+Mode: 3
+
+[Metadata]
+Title:Song, \"Remix\"
+Version:4K
+
+[HitObjects]
+64,192,1000,128,0,1500:0:0:0:0:
+192,192,2000,1,0
+").unwrap();
+
+        let summaries = summarize(&[map]);
+        assert_eq!(summaries[0].hold_notes, 1);
+        assert_eq!(summaries[0].length, Time(1000.0));
+
+        let csv = to_csv(&summaries);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].split(',').count(), CSV_HEADER.len());
+        assert_eq!(
+            lines[1],
+            ",\"Song, \"\"Remix\"\"\",,4K,0,Mania,5,5,5,5,,,,1000,1000,,1,0,0,1"
+        );
+    }
+}
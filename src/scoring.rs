@@ -0,0 +1,302 @@
+//! The results of a play: hit counts, combo and mods, and the score,
+//! accuracy and grade the game gives them.
+
+use super::*;
+
+/// The letter grade of a play, from best to worst.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Copy, Clone)]
+pub enum Grade {
+    /// SS with Hidden or Flashlight.
+    SilverSS,
+    SS,
+    /// S with Hidden or Flashlight.
+    SilverS,
+    S,
+    A,
+    B,
+    C,
+    D,
+}
+
+/// How far from an object's time, in milliseconds either way, a hit still
+/// gets each judgement in osu!standard.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct HitWindows {
+    pub great: f64,
+    pub ok: f64,
+    pub meh: f64,
+}
+
+impl HitWindows {
+    /// The hit windows for an overall difficulty.
+    pub fn from_od(od: f32) -> HitWindows {
+        let od = f64::from(od);
+        HitWindows {
+            great: 80.0 - 6.0 * od,
+            ok: 140.0 - 8.0 * od,
+            meh: 200.0 - 10.0 * od,
+        }
+    }
+
+    /// The judgement of a hit `error` milliseconds away from the object's
+    /// time, or `None` if it is too far off to hit the object at all.
+    pub fn judge(&self, error: f64) -> Option<Judgement> {
+        let error = error.abs();
+        if error <= self.great {
+            Some(Judgement::Great)
+        } else if error <= self.ok {
+            Some(Judgement::Ok)
+        } else if error <= self.meh {
+            Some(Judgement::Meh)
+        } else {
+            None
+        }
+    }
+}
+
+/// The judgement given to a single hit object in osu!standard.
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub enum Judgement {
+    /// A 300.
+    Great,
+    /// A 100.
+    Ok,
+    /// A 50.
+    Meh,
+    Miss,
+}
+
+/// The difficulty multiplier used by score v1, from the beatmap's settings,
+/// its number of hit objects and its drain time in seconds.
+pub fn difficulty_multiplier(hp: f32, cs: f32, od: f32, objects: usize, drain_time: f64) -> f64 {
+    let density = if drain_time > 0.0 {
+        (objects as f64 / drain_time * 8.0).clamp(0.0, 16.0)
+    } else {
+        16.0
+    };
+
+    ((f64::from(hp) + f64::from(cs) + f64::from(od) + density) / 38.0 * 5.0).round()
+}
+
+/// The judgements, combo and mods of a play. Which counts are used depends
+/// on the game mode: osu!taiko only has greats (`n300`), goods (`n100`) and
+/// misses, while osu!mania adds `n_geki` for perfect (MAX) judgements and
+/// `n_katu` for 200s.
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone)]
+pub struct Score {
+    pub mods: Mods,
+    /// Highest combo reached.
+    pub max_combo: usize,
+    pub n_geki: usize,
+    pub n300: usize,
+    pub n_katu: usize,
+    pub n100: usize,
+    pub n50: usize,
+    pub misses: usize,
+}
+
+impl Score {
+    /// A score on an osu!standard or osu!taiko beatmap with `objects`
+    /// judged objects, with 300s and 100s split to reach the given accuracy,
+    /// from 0 to 1, as closely as possible.
+    pub fn from_accuracy(accuracy: f64, objects: usize, misses: usize, max_combo: usize, mods: Mods) -> Score {
+        let misses = misses.min(objects);
+        let hits = (objects - misses) as f64;
+        // Solving 300 * n300 + 100 * n100 = 300 * objects * accuracy
+        let n100 = (1.5 * (hits - objects as f64 * accuracy)).round().max(0.0).min(hits) as usize;
+
+        Score {
+            mods,
+            max_combo,
+            n300: objects - misses - n100,
+            n100,
+            misses,
+            ..Default::default()
+        }
+    }
+
+    /// Number of judged objects in an osu!standard or osu!taiko play.
+    pub fn total_hits(&self) -> usize {
+        self.n300 + self.n100 + self.n50 + self.misses
+    }
+
+    /// Accuracy from 0 to 1, as shown for the given game mode.
+    pub fn accuracy(&self, mode: GameMode) -> f64 {
+        let (points, max) = match mode {
+            GameMode::Osu => (
+                300 * self.n300 + 100 * self.n100 + 50 * self.n50,
+                300 * self.total_hits(),
+            ),
+            GameMode::Taiko => (2 * self.n300 + self.n100, 2 * (self.n300 + self.n100 + self.misses)),
+            GameMode::CTB => {
+                let caught = self.n300 + self.n100 + self.n50;
+                (caught, caught + self.n_katu + self.misses)
+            }
+            GameMode::Mania => (
+                300 * (self.n_geki + self.n300) + 200 * self.n_katu + 100 * self.n100 + 50 * self.n50,
+                300 * self.mania_total_hits(),
+            ),
+        };
+
+        if max == 0 {
+            1.0
+        } else {
+            points as f64 / max as f64
+        }
+    }
+
+    /// Number of judgements in an osu!mania play.
+    pub fn mania_total_hits(&self) -> usize {
+        self.n_geki + self.n_katu + self.total_hits()
+    }
+
+    /// The grade shown for the play in the given game mode.
+    pub fn grade(&self, mode: GameMode) -> Grade {
+        let silver = self.mods.contains(Mods::HIDDEN) || self.mods.contains(Mods::FLASHLIGHT);
+        let accuracy = self.accuracy(mode);
+
+        let grade = match mode {
+            GameMode::Osu | GameMode::Taiko => {
+                let total = self.total_hits().max(1) as f64;
+                let ratio_300 = self.n300 as f64 / total;
+                let ratio_50 = self.n50 as f64 / total;
+
+                if ratio_300 >= 1.0 {
+                    Grade::SS
+                } else if ratio_300 > 0.9 && ratio_50 < 0.01 && self.misses == 0 {
+                    Grade::S
+                } else if (ratio_300 > 0.8 && self.misses == 0) || ratio_300 > 0.9 {
+                    Grade::A
+                } else if (ratio_300 > 0.7 && self.misses == 0) || ratio_300 > 0.8 {
+                    Grade::B
+                } else if ratio_300 > 0.6 {
+                    Grade::C
+                } else {
+                    Grade::D
+                }
+            }
+            GameMode::CTB => grade_by_accuracy(accuracy, [0.98, 0.94, 0.9, 0.85]),
+            GameMode::Mania => grade_by_accuracy(accuracy, [0.95, 0.9, 0.8, 0.7]),
+        };
+
+        match grade {
+            Grade::SS if silver => Grade::SilverSS,
+            Grade::S if silver => Grade::SilverS,
+            grade => grade,
+        }
+    }
+
+    /// Score v1 of an osu!standard play, given the beatmap's
+    /// [`difficulty_multiplier`](fn.difficulty_multiplier.html).
+    ///
+    /// Score v1 depends on the combo at each hit, which can't be known from
+    /// the counts alone, so this assumes hits form combos as long as
+    /// `max_combo`, with the average value of a hit. Slider ticks and
+    /// spinner bonuses aren't counted.
+    pub fn score_v1(&self, difficulty_multiplier: f64) -> u64 {
+        let hits = self.n300 + self.n100 + self.n50;
+        if hits == 0 {
+            return 0;
+        }
+        let average = (300 * self.n300 + 100 * self.n100 + 50 * self.n50) as f64 / hits as f64;
+        let factor = difficulty_multiplier * self.mods.score_multiplier() / 25.0;
+
+        // Within a combo, the nth hit gets a bonus of (n - 1) times the factor
+        let run_value = |length: usize| {
+            let bonuses = length.saturating_sub(2) * length.saturating_sub(1) / 2;
+            average * (length as f64 + factor * bonuses as f64)
+        };
+
+        let combo = self.max_combo.max(1).min(hits);
+        let full_runs = hits / combo;
+        let score = full_runs as f64 * run_value(combo) + run_value(hits % combo);
+
+        score.round() as u64
+    }
+
+    /// Score v2 of an osu!standard play on a beatmap with the given maximum
+    /// combo: 700,000 points for combo and 300,000 for accuracy, scaled by
+    /// the mods. The combo portion is taken from the highest combo, as the
+    /// order of judgements isn't known.
+    pub fn score_v2(&self, map_max_combo: usize) -> u64 {
+        let combo_ratio = if map_max_combo > 0 {
+            (self.max_combo as f64 / map_max_combo as f64).min(1.0)
+        } else {
+            1.0
+        };
+        let accuracy = self.accuracy(GameMode::Osu);
+
+        ((700_000.0 * combo_ratio + 300_000.0 * accuracy.powi(10)) * self.mods.score_multiplier_v2()).round() as u64
+    }
+}
+
+/// The grade for accuracy based modes, given the accuracy needed for an S,
+/// A, B and C.
+fn grade_by_accuracy(accuracy: f64, thresholds: [f64; 4]) -> Grade {
+    if accuracy >= 1.0 {
+        Grade::SS
+    } else if accuracy > thresholds[0] {
+        Grade::S
+    } else if accuracy > thresholds[1] {
+        Grade::A
+    } else if accuracy > thresholds[2] {
+        Grade::B
+    } else if accuracy > thresholds[3] {
+        Grade::C
+    } else {
+        Grade::D
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accuracy() {
+        let score = Score::from_accuracy(0.98, 500, 2, 600, Mods::NONE);
+        assert_eq!((score.n300, score.n100, score.misses), (486, 12, 2));
+        assert!((score.accuracy(GameMode::Osu) - 0.98).abs() < 0.001);
+
+        let score = Score { n300: 90, n100: 10, ..Default::default() };
+        assert_eq!(score.accuracy(GameMode::Taiko), 0.95);
+        assert_eq!(Score::default().accuracy(GameMode::Osu), 1.0);
+    }
+
+    #[test]
+    fn test_hit_windows() {
+        let windows = HitWindows::from_od(5.0);
+        assert_eq!((windows.great, windows.ok, windows.meh), (50.0, 100.0, 150.0));
+        assert_eq!(windows.judge(-50.0), Some(Judgement::Great));
+        assert_eq!(windows.judge(120.0), Some(Judgement::Meh));
+        assert_eq!(windows.judge(151.0), None);
+    }
+
+    #[test]
+    fn test_grade() {
+        let score = |n300, n100, n50, misses, mods| Score { n300, n100, n50, misses, mods, ..Default::default() };
+
+        assert_eq!(score(100, 0, 0, 0, Mods::HIDDEN).grade(GameMode::Osu), Grade::SilverSS);
+        assert_eq!(score(95, 5, 0, 0, Mods::NONE).grade(GameMode::Osu), Grade::S);
+        assert_eq!(score(95, 4, 0, 1, Mods::NONE).grade(GameMode::Osu), Grade::A);
+        assert_eq!(score(85, 14, 0, 1, Mods::NONE).grade(GameMode::Osu), Grade::B);
+        assert_eq!(score(50, 50, 0, 0, Mods::NONE).grade(GameMode::Osu), Grade::D);
+
+        let mania = Score { n_geki: 90, n_katu: 10, ..Default::default() };
+        assert_eq!(mania.grade(GameMode::Mania), Grade::S);
+    }
+
+    #[test]
+    fn test_score() {
+        assert_eq!(difficulty_multiplier(5.0, 4.0, 8.0, 600, 120.0), 4.0);
+
+        // Combo bonuses are 300 * (0 + 0 + 1 + 2) * 4 / 25
+        let score = Score { n300: 4, max_combo: 4, ..Default::default() };
+        assert_eq!(score.score_v1(4.0), 1200 + 144);
+
+        let fc = Score { n300: 500, max_combo: 700, ..Default::default() };
+        assert_eq!(fc.score_v2(700), 1_000_000);
+        let dt = Score { mods: Mods::DOUBLE_TIME, ..fc };
+        assert_eq!(dt.score_v2(700), 1_200_000);
+    }
+}
@@ -0,0 +1,486 @@
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use super::*;
+use deserialize::ToOsuString;
+use lint::{Finding, Severity};
+
+/// The difficulties of a beatmap set, which share a song and are uploaded
+/// together.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct BeatmapSet {
+    pub beatmaps: Vec<Beatmap>,
+}
+
+impl BeatmapSet {
+    pub fn new(beatmaps: Vec<Beatmap>) -> Self {
+        BeatmapSet { beatmaps }
+    }
+
+    /// The metadata of the first difficulty, which the title, artist,
+    /// creator and other fields shared by the set are taken from. Whether
+    /// the other difficulties agree is checked by
+    /// [`check_consistency`](#method.check_consistency).
+    pub fn metadata(&self) -> Option<&MetadataSection> {
+        self.beatmaps.first().map(|map| &map.metadata)
+    }
+
+    /// The set's id on the osu! website, taken from the first difficulty
+    /// that has one.
+    pub fn beatmap_set_id(&self) -> Option<BeatmapSetId> {
+        self.beatmaps
+            .iter()
+            .map(|map| map.metadata.beatmap_set_id)
+            .find(|id| id.is_submitted())
+    }
+
+    /// The difficulty with the given version name, ignoring case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use osuparse::{parse_beatmap, BeatmapId, BeatmapSet};
+    ///
+    /// let map = |version: &str, id: i32| {
+    ///     parse_beatmap(&format!("osu file format v14\n\n[Metadata]\nVersion:{}\nBeatmapID:{}\n", version, id)).unwrap()
+    /// };
+    /// let set = BeatmapSet::new(vec![map("Easy", 100), map("Insane", 101)]);
+    ///
+    /// assert_eq!(set.by_version("insane").unwrap().metadata.beatmap_id, BeatmapId(101));
+    /// assert_eq!(set.by_beatmap_id(BeatmapId(100)).unwrap().metadata.version, "Easy");
+    /// assert!(set.by_version("Hard").is_none());
+    /// ```
+    pub fn by_version(&self, version: &str) -> Option<&Beatmap> {
+        self.beatmaps
+            .iter()
+            .find(|map| unicase::eq(map.metadata.version.as_str(), version))
+    }
+
+    /// The difficulty with the given id on the osu! website.
+    pub fn by_beatmap_id(&self, beatmap_id: BeatmapId) -> Option<&Beatmap> {
+        self.beatmaps
+            .iter()
+            .find(|map| map.metadata.beatmap_id == beatmap_id)
+    }
+
+    /// Sorts the difficulties from easiest to hardest by star rating, as
+    /// song select lists them. Star ratings aren't computed by this crate,
+    /// so `star_rating` gives the rating of each difficulty, e.g. from the
+    /// attributes passed to the [`performance`](performance/index.html)
+    /// functions. It is called once per difficulty.
+    pub fn sort_by_star_rating<F>(&mut self, star_rating: F)
+    where
+        F: FnMut(&Beatmap) -> f64,
+    {
+        let ratings: Vec<f64> = self.beatmaps.iter().map(star_rating).collect();
+        let mut rated: Vec<(f64, Beatmap)> = ratings.into_iter().zip(self.beatmaps.drain(..)).collect();
+        rated.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+        self.beatmaps = rated.into_iter().map(|(_, map)| map).collect();
+    }
+
+    /// Sorts the difficulties by overall difficulty, lowest first.
+    pub fn sort_by_overall_difficulty(&mut self) {
+        self.beatmaps.sort_by(|a, b| {
+            a.difficulty
+                .overall_difficulty
+                .partial_cmp(&b.difficulty.overall_difficulty)
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+
+    /// Sorts the difficulties by version name, ignoring case.
+    pub fn sort_by_version(&mut self) {
+        self.beatmaps.sort_by_cached_key(|map| map.metadata.version.to_lowercase());
+    }
+
+    /// The files referred to by any of the difficulties, sorted and without
+    /// duplicates. See
+    /// [`Beatmap::referenced_files`](struct.Beatmap.html#method.referenced_files).
+    pub fn referenced_files(&self) -> Vec<String> {
+        let files: BTreeSet<String> = self.beatmaps
+            .iter()
+            .flat_map(Beatmap::referenced_files)
+            .collect();
+        files.into_iter().collect()
+    }
+
+    /// Writes every difficulty into `dir`, creating it if needed, under the
+    /// filename osu! would give it (see
+    /// [`MetadataSection::filename`](struct.MetadataSection.html#method.filename)).
+    /// Returns the paths of the files written.
+    ///
+    /// Fails without writing anything if two difficulties would get the same
+    /// filename.
+    pub fn write_to_dir<P: AsRef<Path>>(&self, dir: P) -> Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        let filenames: Vec<String> = self.beatmaps.iter().map(|m| m.metadata.filename()).collect();
+        if filenames.iter().collect::<BTreeSet<_>>().len() != filenames.len() {
+            return Err(Error::Message("Two difficulties have the same filename"));
+        }
+
+        fs::create_dir_all(dir).map_err(Error::Io)?;
+
+        let mut paths = Vec::with_capacity(self.beatmaps.len());
+        for (map, filename) in self.beatmaps.iter().zip(filenames) {
+            let path = dir.join(filename);
+            fs::write(&path, map.to_osu_string()).map_err(Error::Io)?;
+            paths.push(path);
+        }
+
+        Ok(paths)
+    }
+
+    /// Copies the files referred to by the set, such as the audio and
+    /// background, from the directory `from` into the same place under `to`.
+    /// Returns the files that weren't copied because they aren't in `from`,
+    /// or because their path leads outside of it.
+    pub fn copy_resources<P: AsRef<Path>, Q: AsRef<Path>>(&self, from: P, to: Q) -> Result<Vec<String>> {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        let mut missing = Vec::new();
+
+        for file in self.referenced_files() {
+            let relative = match resource_path(&file) {
+                Some(ref path) if from.join(path).is_file() => path.clone(),
+                _ => {
+                    missing.push(file);
+                    continue;
+                }
+            };
+
+            let destination = to.join(&relative);
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent).map_err(Error::Io)?;
+            }
+            fs::copy(from.join(&relative), &destination).map_err(Error::Io)?;
+        }
+
+        Ok(missing)
+    }
+
+    /// Compares the difficulties of the set against the first one, and
+    /// reports what the ranking criteria require to be the same across the
+    /// set but isn't: metadata, uninherited timing points, kiai sections
+    /// and, for osu!standard and osu!catch, combo colours.
+    ///
+    /// Each finding's message names the difficulty that differs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use osuparse::{parse_beatmap, BeatmapSet};
+    ///
+    /// let easy = parse_beatmap("osu file format v14
+    ///
+    /// [Metadata]
+    /// Title:Song
+    /// Version:Easy
+    /// ").unwrap();
+    /// let hard = parse_beatmap("osu file format v14
+    ///
+    /// [Metadata]
+    /// Title:song
+    /// Version:Hard
+    /// ").unwrap();
+    ///
+    /// let findings = BeatmapSet::new(vec![easy, hard]).check_consistency();
+    /// assert_eq!(findings.len(), 1);
+    /// assert_eq!(findings[0].rule, "inconsistent-metadata");
+    /// ```
+    pub fn check_consistency(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let reference = match self.beatmaps.first() {
+            Some(map) => map,
+            None => return findings,
+        };
+
+        for map in &self.beatmaps[1..] {
+            check_metadata(reference, map, &mut findings);
+            check_timing(reference, map, &mut findings);
+            check_kiai(reference, map, &mut findings);
+            check_colours(reference, map, &mut findings);
+        }
+
+        findings.sort_by_key(|f| f.time);
+        findings
+    }
+}
+
+/// The path of a file referred to by a beatmap, relative to the beatmap's
+/// directory. `None` if the path leads outside of the directory.
+pub fn resource_path(file: &str) -> Option<PathBuf> {
+    // Beatmaps made on Windows separate directories with backslashes
+    let path = PathBuf::from(file.replace('\\', "/"));
+    if path.components().all(|c| matches!(c, Component::Normal(_))) {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+fn check_metadata(reference: &Beatmap, map: &Beatmap, findings: &mut Vec<Finding>) {
+    let (a, b) = (&reference.metadata, &map.metadata);
+    let fields = [
+        ("Title", a.title == b.title),
+        ("TitleUnicode", a.title_unicode == b.title_unicode),
+        ("Artist", a.artist == b.artist),
+        ("ArtistUnicode", a.artist_unicode == b.artist_unicode),
+        ("Creator", a.creator == b.creator),
+        ("Source", a.source == b.source),
+        ("Tags", a.tags == b.tags),
+        ("BeatmapSetID", a.beatmap_set_id == b.beatmap_set_id),
+    ];
+
+    for &(name, _) in fields.iter().filter(|f| !f.1) {
+        findings.push(Finding {
+            rule: "inconsistent-metadata",
+            severity: Severity::Problem,
+            message: format!("{} of [{}] differs from [{}]", name, b.version, a.version),
+            time: None,
+        });
+    }
+}
+
+fn check_timing(reference: &Beatmap, map: &Beatmap, findings: &mut Vec<Finding>) {
+    let uninherited = |map: &Beatmap| -> Vec<(Time, f64, i32)> {
+        map.timing_points
+            .iter()
+            .filter(|p| p.is_uninherited())
+            .map(|p| (p.offset, p.ms_per_beat, p.meter))
+            .collect()
+    };
+    let (a, b) = (uninherited(reference), uninherited(map));
+
+    // Report the first point that differs, since everything after it usually
+    // differs as well
+    let differs = a.iter().zip(&b).find(|&(x, y)| x != y).map(|(x, y)| x.0.min(y.0));
+    let time = differs.or_else(|| {
+        if a.len() != b.len() {
+            let i = a.len().min(b.len());
+            Some(a.get(i).or_else(|| b.get(i)).unwrap().0)
+        } else {
+            None
+        }
+    });
+
+    if let Some(time) = time {
+        findings.push(Finding {
+            rule: "inconsistent-timing",
+            severity: Severity::Problem,
+            message: format!(
+                "Uninherited timing points of [{}] differ from [{}]",
+                map.metadata.version,
+                reference.metadata.version
+            ),
+            time: Some(time),
+        });
+    }
+}
+
+/// Start and end times of the kiai sections set by a beatmap's timing
+/// points. The last section ends at infinity if it is never turned off.
+fn kiai_sections(map: &Beatmap) -> Vec<(Time, Time)> {
+    let mut sections = Vec::new();
+    let mut start = None;
+
+    for p in &map.timing_points {
+        match (start, p.effects.kiai()) {
+            (None, true) => start = Some(p.offset),
+            (Some(s), false) => {
+                sections.push((s, p.offset));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        sections.push((s, Time(f64::INFINITY)));
+    }
+
+    sections
+}
+
+fn check_kiai(reference: &Beatmap, map: &Beatmap, findings: &mut Vec<Finding>) {
+    let (a, b) = (kiai_sections(reference), kiai_sections(map));
+
+    for &(start, end) in b.iter().filter(|s| !a.contains(s)) {
+        let message = if end.0.is_finite() {
+            format!(
+                "Kiai from {} to {} in [{}] isn't in [{}]",
+                start,
+                end,
+                map.metadata.version,
+                reference.metadata.version
+            )
+        } else {
+            format!(
+                "Kiai from {} in [{}] isn't in [{}]",
+                start,
+                map.metadata.version,
+                reference.metadata.version
+            )
+        };
+        findings.push(Finding {
+            rule: "inconsistent-kiai",
+            severity: Severity::Warning,
+            message,
+            time: Some(start),
+        });
+    }
+    for &(start, _) in a.iter().filter(|s| !b.contains(s)) {
+        findings.push(Finding {
+            rule: "inconsistent-kiai",
+            severity: Severity::Warning,
+            message: format!(
+                "Kiai at {} in [{}] is missing from [{}]",
+                start,
+                reference.metadata.version,
+                map.metadata.version
+            ),
+            time: Some(start),
+        });
+    }
+}
+
+fn check_colours(reference: &Beatmap, map: &Beatmap, findings: &mut Vec<Finding>) {
+    // osu!mania and osu!taiko don't show combo colours
+    let shows_colours = |map: &Beatmap| {
+        map.general.game_mode == GameMode::Osu || map.general.game_mode == GameMode::CTB
+    };
+
+    if shows_colours(reference) && shows_colours(map) && reference.colours.colours != map.colours.colours {
+        findings.push(Finding {
+            rule: "inconsistent-combo-colours",
+            severity: Severity::Warning,
+            message: format!(
+                "Combo colours of [{}] differ from [{}]",
+                map.metadata.version,
+                reference.metadata.version
+            ),
+            time: None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_consistency() {
+        let reference = "osu file format v14
+
+[Metadata]
+Title:Song
+Artist:Artist
+Version:Normal
+
+[TimingPoints]
+0,500,4,1,0,100,1,0
+1000,-100,4,1,0,100,0,1
+2000,-100,4,1,0,100,0,0
+
+[Colours]
+Combo1 : 255,0,0
+";
+        let other = "osu file format v14
+
+[Metadata]
+Title:Song
+Artist:Someone else
+Version:Hard
+
+[TimingPoints]
+0,500,4,1,0,100,1,0
+1500,400,4,1,0,100,1,1
+2000,-100,4,1,0,100,0,0
+
+[Colours]
+Combo1 : 0,255,0
+";
+
+        let set = BeatmapSet::new(vec![
+            parse_beatmap(reference).unwrap(),
+            parse_beatmap(other).unwrap(),
+        ]);
+        let findings: Vec<(&str, Option<Time>)> = set.check_consistency()
+            .iter()
+            .map(|f| (f.rule, f.time))
+            .collect();
+
+        assert_eq!(findings, vec![
+            ("inconsistent-metadata", None),
+            ("inconsistent-combo-colours", None),
+            ("inconsistent-kiai", Some(Time(1000.0))),
+            ("inconsistent-timing", Some(Time(1500.0))),
+            ("inconsistent-kiai", Some(Time(1500.0))),
+        ]);
+
+        let same = BeatmapSet::new(vec![parse_beatmap(reference).unwrap(); 2]);
+        assert!(same.check_consistency().is_empty());
+    }
+
+    #[test]
+    fn test_sorting() {
+        let map = |version: &str, od: f32| {
+            let mut map = Beatmap::default();
+            map.metadata.version = version.to_string();
+            map.difficulty.overall_difficulty = od;
+            map
+        };
+        let versions = |set: &BeatmapSet| -> Vec<String> {
+            set.beatmaps.iter().map(|m| m.metadata.version.clone()).collect()
+        };
+
+        let mut set = BeatmapSet::new(vec![map("insane", 8.0), map("Easy", 3.0), map("Hard", 7.0)]);
+
+        set.sort_by_version();
+        assert_eq!(versions(&set), vec!["Easy", "Hard", "insane"]);
+
+        set.sort_by_overall_difficulty();
+        assert_eq!(versions(&set), vec!["Easy", "Hard", "insane"]);
+
+        set.sort_by_star_rating(|m| match m.metadata.version.as_str() {
+            "Easy" => 2.5,
+            "Hard" => 5.1,
+            _ => 4.2,
+        });
+        assert_eq!(versions(&set), vec!["Easy", "insane", "Hard"]);
+    }
+
+    #[test]
+    fn test_write_to_dir() {
+        let dir = std::env::temp_dir().join(format!("osuparse-set-{}", std::process::id()));
+        let (source, output) = (dir.join("source"), dir.join("output"));
+        fs::create_dir_all(source.join("sb")).unwrap();
+        fs::write(source.join("audio.mp3"), "audio").unwrap();
+        fs::write(source.join("sb").join("star.png"), "star").unwrap();
+
+        let map = |version: &str| {
+            parse_beatmap(&format!(
+                "osu file format v14\n\n[General]\nAudioFilename: audio.mp3\n\n\
+                 [Metadata]\nArtist:Artist\nTitle:Title\nCreator:Mapper\nVersion:{}\n\n\
+                 [Events]\n0,0,\"bg.jpg\",0,0\nSprite,Foreground,Centre,\"sb\\star.png\",320,240\n F,0,0,1000,1\n",
+                version
+            )).unwrap()
+        };
+
+        let set = BeatmapSet::new(vec![map("Easy"), map("Hard")]);
+        let paths = set.write_to_dir(&output).unwrap();
+        assert_eq!(paths, vec![
+            output.join("Artist - Title (Mapper) [Easy].osu"),
+            output.join("Artist - Title (Mapper) [Hard].osu"),
+        ]);
+        let written = parse_beatmap(&fs::read_to_string(&paths[1]).unwrap()).unwrap();
+        assert_eq!(written.metadata.version, "Hard");
+
+        assert_eq!(set.copy_resources(&source, &output).unwrap(), vec!["bg.jpg"]);
+        assert_eq!(fs::read_to_string(output.join("sb").join("star.png")).unwrap(), "star");
+
+        let duplicate = BeatmapSet::new(vec![map("Easy"), map("Easy")]);
+        assert!(duplicate.write_to_dir(&output).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
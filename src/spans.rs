@@ -0,0 +1,105 @@
+use super::*;
+
+/// Where something was read from in the parsed input.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct Span {
+    /// The line, starting at 1.
+    pub line: usize,
+    /// Byte offset of the start of the line's content.
+    pub start: usize,
+    /// Byte offset of the end of the line's content, leaving out the line
+    /// ending and any trailing comment.
+    pub end: usize,
+}
+
+/// Where the hit objects, timing points and key-value pairs of a beatmap
+/// were read from, as returned by
+/// [`parse_beatmap_with_spans`](fn.parse_beatmap_with_spans.html).
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Spans {
+    hit_objects: Vec<Span>,
+    timing_points: Vec<Span>,
+    /// Section name, key and span of every known key-value pair.
+    keys: Vec<(String, &'static str, Span)>,
+}
+
+impl Spans {
+    /// The span of the hit object at `index` in
+    /// [`Beatmap::hit_objects`](struct.Beatmap.html#structfield.hit_objects).
+    pub fn hit_object(&self, index: usize) -> Option<Span> {
+        self.hit_objects.get(index).cloned()
+    }
+
+    /// The span of the timing point at `index` in
+    /// [`Beatmap::timing_points`](struct.Beatmap.html#structfield.timing_points).
+    pub fn timing_point(&self, index: usize) -> Option<Span> {
+        self.timing_points.get(index).cloned()
+    }
+
+    /// The span of the pair that set `key` in the `[General]`, `[Editor]`,
+    /// `[Metadata]` or `[Difficulty]` section, ignoring case. If the key
+    /// appears more than once, this is the last one, whose value is used.
+    pub fn key(&self, section: &str, key: &str) -> Option<Span> {
+        self.keys
+            .iter()
+            .rev()
+            .find(|(s, k, _)| unicase::eq(s.as_str(), section) && unicase::eq(*k, key))
+            .map(|&(_, _, span)| span)
+    }
+}
+
+/// Same as [`parse_beatmap`](fn.parse_beatmap.html), but also returns where
+/// in `input` each hit object, timing point and key-value pair was read
+/// from, so that tools can point at the lines a problem comes from.
+///
+/// # Examples
+///
+/// ```
+/// use osuparse::parse_beatmap_with_spans;
+///
+/// let input = "osu file format v14\n\n[General]\nMode: 0\n\n[HitObjects]\n256,192,1000,1,0\n";
+/// let (map, spans) = parse_beatmap_with_spans(input).unwrap();
+///
+/// let span = spans.hit_object(0).unwrap();
+/// assert_eq!(span.line, 7);
+/// assert_eq!(&input[span.start..span.end], "256,192,1000,1,0");
+/// assert_eq!(spans.key("General", "Mode").unwrap().line, 4);
+/// # assert_eq!(map.hit_objects.len(), 1);
+/// ```
+pub fn parse_beatmap_with_spans(input: &str) -> Result<(Beatmap, Spans)> {
+    parse_beatmap_with_state(input, &ParserOptions::default(), false, |_| {})
+        .map(|(map, state)| {
+            let spans = Spans {
+                hit_objects: state.hit_object_spans,
+                timing_points: state.timing_point_spans,
+                keys: state.key_spans,
+            };
+            (map, spans)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spans() {
+        let input = "osu file format v14\r\n\r\n[Metadata]\r\nTitle:a//b\r\n\r\n\
+                     [TimingPoints]\r\n0,500,4,1,0,100,1,0 // red line\r\n\r\n\
+                     [HitObjects]\r\n// comment\r\n256,192,1000,1,0\r\n256,192,2000,1,0\r\n";
+
+        let (map, spans) = parse_beatmap_with_spans(input).unwrap();
+        let text = |span: Span| &input[span.start..span.end];
+
+        assert_eq!(map.metadata.title, "a//b");
+        assert_eq!(text(spans.key("metadata", "title").unwrap()), "Title:a//b");
+        assert_eq!(spans.key("General", "Title"), None);
+
+        let point = spans.timing_point(0).unwrap();
+        assert_eq!((point.line, text(point)), (7, "0,500,4,1,0,100,1,0"));
+        assert_eq!(spans.timing_point(1), None);
+
+        assert_eq!(spans.hit_object(0).unwrap().line, 11);
+        assert_eq!(text(spans.hit_object(1).unwrap()), "256,192,2000,1,0");
+    }
+}
@@ -0,0 +1,217 @@
+//! Statistics over a beatmap's hit objects, shaped for plotting.
+
+use super::*;
+use modes::{StdBeatmap, StdObject};
+
+/// Size of the osu! playfield, in osu!pixels.
+const PLAYFIELD_WIDTH: i32 = 512;
+const PLAYFIELD_HEIGHT: i32 = 384;
+
+/// The note density around a point in time.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct NpsSample {
+    pub time: Time,
+    /// Hit objects per second within the window centred on `time`.
+    pub nps: f32,
+}
+
+/// The number of hit objects starting within `start..start + length`.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct DensityBucket {
+    pub start: Time,
+    pub length: Time,
+    pub count: usize,
+}
+
+/// Start times of the hit objects, sorted.
+fn object_times(map: &Beatmap) -> Vec<Time> {
+    let mut times: Vec<Time> = map.hit_objects.iter().map(HitObject::time).collect();
+    times.sort_unstable();
+    times
+}
+
+impl Beatmap {
+    /// Notes per second over a sliding window of `window` milliseconds,
+    /// sampled every `step` milliseconds from the first hit object to the
+    /// last. Returns nothing if the beatmap has no hit objects or either
+    /// length isn't positive.
+    ///
+    /// ```
+    /// use osuparse::Time;
+    ///
+    /// let input = "osu file format v14
+    ///
+    /// [HitObjects]
+    /// 256,192,0,1,0
+    /// 256,192,250,1,0
+    /// 256,192,500,1,0
+    /// 256,192,2000,1,0
+    /// ";
+    ///
+    /// let map = osuparse::parse_beatmap(input).unwrap();
+    /// let curve = map.nps_curve(Time(1000.0), Time(1000.0));
+    ///
+    /// assert_eq!(curve.len(), 3);
+    /// assert_eq!(curve[0].nps, 2.0);
+    /// ```
+    pub fn nps_curve(&self, window: Time, step: Time) -> Vec<NpsSample> {
+        let times = object_times(self);
+        let (first, last) = match (times.first(), times.last()) {
+            (Some(&first), Some(&last)) if window > Time::ZERO && step > Time::ZERO => (first, last),
+            _ => return Vec::new(),
+        };
+
+        let seconds = (window.0 / 1000.0) as f32;
+        let half = window / 2.0;
+
+        (0..)
+            .map(|i| first + step * f64::from(i))
+            .take_while(|&time| time <= last)
+            .map(|time| {
+                let from = times.partition_point(|&t| t < time - half);
+                let to = times.partition_point(|&t| t < time - half + window);
+
+                NpsSample { time, nps: (to - from) as f32 / seconds }
+            })
+            .collect()
+    }
+
+    /// Counts hit objects in consecutive buckets of `length` milliseconds,
+    /// starting from the first hit object. Returns nothing if the beatmap
+    /// has no hit objects or `length` isn't positive.
+    pub fn density_histogram(&self, length: Time) -> Vec<DensityBucket> {
+        let times = object_times(self);
+        let (first, last) = match (times.first(), times.last()) {
+            (Some(&first), Some(&last)) if length > Time::ZERO => (first, last),
+            _ => return Vec::new(),
+        };
+
+        let bucket = |time: Time| ((time - first).0 / length.0) as usize;
+        let mut buckets: Vec<DensityBucket> = (0..=bucket(last))
+            .map(|i| DensityBucket { start: first + length * i as f64, length, count: 0 })
+            .collect();
+        for time in times {
+            buckets[bucket(time)].count += 1;
+        }
+
+        buckets
+    }
+}
+
+/// A grid over the playfield, holding a value per cell.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Heatmap {
+    pub columns: usize,
+    pub rows: usize,
+    /// The value of each cell, row by row from the top left.
+    pub cells: Vec<f32>,
+}
+
+impl Heatmap {
+    fn new(columns: usize, rows: usize) -> Self {
+        Heatmap { columns, rows, cells: vec![0.0; columns * rows] }
+    }
+
+    pub fn get(&self, column: usize, row: usize) -> f32 {
+        self.cells[row * self.columns + column]
+    }
+
+    /// The largest value of any cell, for normalizing colours.
+    pub fn max(&self) -> f32 {
+        self.cells.iter().cloned().fold(0.0, f32::max)
+    }
+
+    /// The cell containing a position. Positions off the playfield count
+    /// towards the nearest cell on its edge.
+    fn cell(&self, (x, y): (i32, i32)) -> usize {
+        let column = (i64::from(x) * self.columns as i64).div_euclid(i64::from(PLAYFIELD_WIDTH));
+        let row = (i64::from(y) * self.rows as i64).div_euclid(i64::from(PLAYFIELD_HEIGHT));
+        let column = column.max(0).min(self.columns as i64 - 1) as usize;
+        let row = row.max(0).min(self.rows as i64 - 1) as usize;
+
+        row * self.columns + column
+    }
+}
+
+impl<'a> StdBeatmap<'a> {
+    /// The number of hit objects in each cell of a `columns` by `rows` grid
+    /// over the playfield. Sliders count at their head.
+    pub fn heatmap(&self, columns: usize, rows: usize) -> Heatmap {
+        self.heatmap_weighted(columns, rows, |_| 1.0)
+    }
+
+    /// Like [`heatmap`](#method.heatmap), but each object adds the value
+    /// given by `weight`, such as its difficulty, to its cell.
+    pub fn heatmap_weighted<F>(&self, columns: usize, rows: usize, mut weight: F) -> Heatmap
+    where
+        F: FnMut(&StdObject<'a>) -> f32,
+    {
+        let mut heatmap = Heatmap::new(columns, rows);
+        if heatmap.cells.is_empty() {
+            return heatmap;
+        }
+
+        for obj in &self.objects {
+            let position = match obj {
+                StdObject::Circle(c) => (c.x, c.y),
+                StdObject::Slider(s) => (s.x, s.y),
+                StdObject::Spinner(s) => (s.x, s.y),
+            };
+            let cell = heatmap.cell(position);
+            heatmap.cells[cell] += weight(obj);
+        }
+
+        heatmap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_density() {
+        let circle = |time: f64| HitObject::from(HitCircle::new(0, 0, Time(time)));
+        let map = Beatmap {
+            hit_objects: vec![circle(1000.0), circle(1100.0), circle(1200.0), circle(2500.0), circle(1150.0)],
+            ..Default::default()
+        };
+
+        let counts: Vec<usize> = map.density_histogram(Time(500.0)).iter().map(|b| b.count).collect();
+        assert_eq!(counts, vec![4, 0, 0, 1]);
+        assert_eq!(map.density_histogram(Time(500.0))[3].start, Time(2500.0));
+
+        let curve: Vec<(f64, f32)> = map.nps_curve(Time(500.0), Time(500.0)).iter().map(|s| (s.time.0, s.nps)).collect();
+        assert_eq!(curve, vec![(1000.0, 8.0), (1500.0, 0.0), (2000.0, 0.0), (2500.0, 2.0)]);
+
+        assert!(Beatmap::default().nps_curve(Time(1000.0), Time(100.0)).is_empty());
+        assert!(map.density_histogram(Time::ZERO).is_empty());
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let input = "osu file format v14
+
+[HitObjects]
+0,0,1000,1,0
+100,50,1100,1,0
+511,383,1200,2,0,L|400:300,1,100
+600,-20,1300,1,0
+256,192,1400,8,0,2000
+";
+
+        let map = parse_beatmap(input).unwrap();
+        let std = map.as_std().unwrap();
+
+        let heatmap = std.heatmap(2, 2);
+        assert_eq!(heatmap.cells, vec![2.0, 1.0, 0.0, 2.0]);
+        assert_eq!(heatmap.max(), 2.0);
+
+        let sliders = std.heatmap_weighted(2, 2, |o| match o {
+            StdObject::Slider(_) => 1.0,
+            _ => 0.0,
+        });
+        assert_eq!(sliders.get(1, 1), 1.0);
+        assert!(std.heatmap(0, 3).cells.is_empty());
+    }
+}
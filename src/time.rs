@@ -0,0 +1,277 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+use std::time::Duration;
+
+use super::*;
+
+/// A point in a beatmap, or a length of time, in milliseconds.
+///
+/// Every time in a beatmap is one: the times of hit objects and events, the
+/// offsets of timing points, the preview point and so on. Most of them are
+/// written as whole milliseconds, but some files have fractional ones, which
+/// are kept as they are.
+///
+/// Times are totally ordered, so that hit objects and events can be sorted
+/// by them. `NaN` sorts after every other time, and `-0.0` equals `0.0`.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use osuparse::Time;
+///
+/// let offset = Time::from(764.5f32);
+/// let time = offset + Time::from_beats(2.0, 500.0);
+///
+/// assert_eq!(time, Time::from_millis(1764.5));
+/// assert_eq!((time - offset).beats(500.0), 2.0);
+/// assert_eq!(time.to_duration(), Some(Duration::from_micros(1_764_500)));
+/// assert_eq!(Time::from(-20).to_duration(), None);
+/// ```
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Time(pub f64);
+
+impl Time {
+    pub const ZERO: Time = Time(0.0);
+
+    pub fn from_millis(millis: f64) -> Self {
+        Time(millis)
+    }
+
+    pub fn from_secs(secs: f64) -> Self {
+        Time(secs * 1000.0)
+    }
+
+    /// The length of `beats` beats at a tempo of `ms_per_beat`, as found in
+    /// [`TimingPoint::ms_per_beat`](struct.TimingPoint.html#structfield.ms_per_beat).
+    pub fn from_beats(beats: f64, ms_per_beat: f64) -> Self {
+        Time(beats * ms_per_beat)
+    }
+
+    pub fn as_millis(self) -> f64 {
+        self.0
+    }
+
+    pub fn as_secs(self) -> f64 {
+        self.0 / 1000.0
+    }
+
+    /// How many beats at a tempo of `ms_per_beat` fit in this length of time.
+    pub fn beats(self, ms_per_beat: f64) -> f64 {
+        self.0 / ms_per_beat
+    }
+
+    pub fn abs(self) -> Self {
+        Time(self.0.abs())
+    }
+
+    /// Rounded to whole milliseconds, as used by hit objects and events.
+    pub fn round(self) -> i32 {
+        self.0.round() as i32
+    }
+
+    /// The value compared and hashed, with negative zero made positive and
+    /// every NaN made the same one.
+    fn key(self) -> f64 {
+        if self.0.is_nan() {
+            f64::NAN
+        } else {
+            self.0 + 0.0
+        }
+    }
+
+    /// Converts to a `Duration`, or `None` if the time is negative or isn't
+    /// finite.
+    pub fn to_duration(self) -> Option<Duration> {
+        if self.0.is_finite() && self.0 >= 0.0 {
+            Some(Duration::from_secs_f64(self.as_secs()))
+        } else {
+            None
+        }
+    }
+}
+
+impl From<i32> for Time {
+    fn from(millis: i32) -> Self {
+        Time(f64::from(millis))
+    }
+}
+
+impl From<f32> for Time {
+    fn from(millis: f32) -> Self {
+        // Go through the shortest decimal form so that offsets like 764.3
+        // don't pick up digits the file never had
+        Time(millis.to_string().parse().unwrap_or_else(|_| f64::from(millis)))
+    }
+}
+
+impl From<f64> for Time {
+    fn from(millis: f64) -> Self {
+        Time(millis)
+    }
+}
+
+impl From<Duration> for Time {
+    fn from(duration: Duration) -> Self {
+        Time::from_secs(duration.as_secs_f64())
+    }
+}
+
+impl From<Time> for f64 {
+    fn from(time: Time) -> Self {
+        time.0
+    }
+}
+
+impl PartialEq for Time {
+    fn eq(&self, other: &Time) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Time {}
+
+impl PartialOrd for Time {
+    fn partial_cmp(&self, other: &Time) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Time {
+    fn cmp(&self, other: &Time) -> Ordering {
+        self.key().total_cmp(&other.key())
+    }
+}
+
+impl Hash for Time {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().to_bits().hash(state);
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}ms", self.0)
+    }
+}
+
+impl Add for Time {
+    type Output = Time;
+
+    fn add(self, other: Time) -> Time {
+        Time(self.0 + other.0)
+    }
+}
+
+impl Sub for Time {
+    type Output = Time;
+
+    fn sub(self, other: Time) -> Time {
+        Time(self.0 - other.0)
+    }
+}
+
+impl AddAssign for Time {
+    fn add_assign(&mut self, other: Time) {
+        self.0 += other.0;
+    }
+}
+
+impl SubAssign for Time {
+    fn sub_assign(&mut self, other: Time) {
+        self.0 -= other.0;
+    }
+}
+
+impl Neg for Time {
+    type Output = Time;
+
+    fn neg(self) -> Time {
+        Time(-self.0)
+    }
+}
+
+impl Sum for Time {
+    fn sum<I: Iterator<Item = Time>>(iter: I) -> Time {
+        iter.fold(Time::ZERO, Add::add)
+    }
+}
+
+impl Mul<f64> for Time {
+    type Output = Time;
+
+    fn mul(self, factor: f64) -> Time {
+        Time(self.0 * factor)
+    }
+}
+
+impl Div<f64> for Time {
+    type Output = Time;
+
+    fn div(self, divisor: f64) -> Time {
+        Time(self.0 / divisor)
+    }
+}
+
+impl TimingPoint {
+    /// The time `beats` beats after this point, at its tempo. Only
+    /// meaningful for uninherited points.
+    pub fn beat_time(&self, beats: f64) -> Time {
+        self.offset + Time::from_beats(beats, self.ms_per_beat)
+    }
+}
+
+impl GeneralSection {
+    /// Same as [`preview_time`](#structfield.preview_time), or `None` if
+    /// no preview point is set.
+    pub fn preview(&self) -> Option<Time> {
+        if self.preview_time < Time::ZERO {
+            None
+        } else {
+            Some(self.preview_time)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_conversions() {
+        let map = parse_beatmap("osu file format v14\n\n[General]\nPreviewTime: -1\n\n\
+                                 [TimingPoints]\n764.3,500,4,1,0,100,1,0\n\n[HitObjects]\n256,192,1764,1,0\n")
+            .unwrap();
+        let point = &map.timing_points[0];
+
+        assert_eq!(point.offset, Time(764.3));
+        assert_eq!(point.beat_time(2.0), Time(1764.3));
+        assert_eq!((map.hit_objects[0].time() - point.offset).beats(500.0).floor(), 1.0);
+        assert_eq!(map.general.preview(), None);
+
+        assert_eq!(Time::from(Duration::from_millis(1500)), Time::from_secs(1.5));
+        assert_eq!((Time(10.4) * 2.0).round(), 21);
+        assert_eq!(Time(f64::NAN).to_duration(), None);
+    }
+
+    #[test]
+    fn test_time_ordering() {
+        let mut times = [Time(f64::NAN), Time(-f64::NAN), Time(500.0), Time(-0.0), Time(f64::NEG_INFINITY), Time(0.0)];
+        times.sort();
+
+        assert_eq!(times[0], Time(f64::NEG_INFINITY));
+        assert_eq!((times[1], times[2]), (Time::ZERO, Time::ZERO));
+        assert_eq!(times[3], Time(500.0));
+        assert!(times[4].0.is_nan() && times[5].0.is_nan());
+        assert_eq!(Time(f64::NAN), Time(f64::NAN));
+
+        let mut set = std::collections::HashSet::new();
+        set.insert(Time(-0.0));
+        assert!(set.contains(&Time::ZERO));
+    }
+}
@@ -0,0 +1,925 @@
+use std::ops::Range;
+
+use super::*;
+
+/// The tempo changes of a beatmap, set by its uninherited timing points.
+///
+/// Some beatmaps have timing points with a beat length of `NaN` or
+/// infinity. A `NaN` beat length makes an inherited point, which the game
+/// gives the normal slider velocity (see
+/// [`TimingPoint::effective_slider_velocity_multiplier`](struct.TimingPoint.html#method.effective_slider_velocity_multiplier)),
+/// while uninherited points whose beat length isn't a positive, finite
+/// number are ignored here, leaving the previous tempo in effect.
+///
+/// ```
+/// use osuparse::{Time, Timing, TimingPoint};
+///
+/// let points = [TimingPoint { offset: Time(100.0), ms_per_beat: 400.0, ..Default::default() }];
+/// let timing = Timing::new(&points);
+///
+/// // 1/4 snaps are 100ms apart
+/// assert_eq!(timing.snap(Time(1030.0), 4), Some((Time(1000.0), Time(30.0))));
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct Timing<'a> {
+    uninherited: Vec<&'a TimingPoint>,
+}
+
+impl<'a> Timing<'a> {
+    /// Takes the uninherited timing points out of `points`, which should be
+    /// sorted by offset. Points with a beat length that isn't positive and
+    /// finite are ignored.
+    pub fn new(points: &'a [TimingPoint]) -> Self {
+        Timing {
+            uninherited: points
+                .iter()
+                .filter(|p| p.is_uninherited() && p.ms_per_beat > 0.0 && p.ms_per_beat.is_finite())
+                .collect(),
+        }
+    }
+
+    /// The uninherited timing points, in order.
+    pub fn points(&self) -> &[&'a TimingPoint] {
+        &self.uninherited
+    }
+
+    /// The uninherited timing point in effect at the given time, or the
+    /// first one if the time is before it.
+    pub fn point_at(&self, time: Time) -> Option<&'a TimingPoint> {
+        let i = self.uninherited.partition_point(|p| p.offset <= time);
+        self.uninherited.get(i.saturating_sub(1)).cloned()
+    }
+
+    /// The duration of a beat at the given time.
+    pub fn beat_length_at(&self, time: Time) -> Option<f64> {
+        self.point_at(time).map(|p| p.ms_per_beat)
+    }
+
+    /// Snaps a time to the nearest `1/divisor` of a beat, counting from the
+    /// timing point in effect. Returns the snapped time and how far the
+    /// original time was from it, positive if it was late. `None` if there
+    /// are no timing points or `divisor` is `0`.
+    pub fn snap(&self, time: Time, divisor: u32) -> Option<(Time, Time)> {
+        let point = self.point_at(time)?;
+        if divisor == 0 {
+            return None;
+        }
+
+        let step = point.ms_per_beat / f64::from(divisor);
+        let snapped = point.offset + Time::from_beats((time - point.offset).beats(step).round(), step);
+        Some((snapped, time - snapped))
+    }
+}
+
+/// Every timing point of a beatmap, sorted once so that the timing at any
+/// time can be found with a binary search. Looking up many times, e.g. the
+/// end of every slider, is faster through an index than through the
+/// methods on [`Beatmap`](struct.Beatmap.html), some of which go through
+/// every timing point on each call.
+///
+/// Timing points at the same offset keep their order from the file, so the
+/// last of them is the one in effect, as in the game.
+///
+/// ```
+/// let input = "osu file format v14
+///
+/// [Difficulty]
+/// SliderMultiplier:1
+///
+/// [TimingPoints]
+/// 1000,-50,4,1,0,100,0,0
+/// 0,500,4,1,0,100,1,0
+///
+/// [HitObjects]
+/// 0,0,2000,2,0,L|100:0,1,100
+/// ";
+///
+/// use osuparse::Time;
+///
+/// let map = osuparse::parse_beatmap(input).unwrap();
+/// let index = map.timing_index();
+///
+/// assert_eq!(index.beat_length_at(Time(1500.0)), Some(500.0));
+/// assert_eq!(index.slider_velocity_at(Time(1500.0)), 2.0);
+/// assert_eq!(index.object_end_time(&map.hit_objects[0]), Time(2250.0));
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct TimingIndex<'a> {
+    points: Vec<&'a TimingPoint>,
+    timing: Timing<'a>,
+    slider_multiplier: f32,
+}
+
+impl<'a> TimingIndex<'a> {
+    pub fn new(map: &'a Beatmap) -> Self {
+        let mut points: Vec<&TimingPoint> = map.timing_points.iter().collect();
+        // Sorting is stable, so points at the same offset keep their order
+        points.sort_by_key(|p| p.offset);
+
+        TimingIndex {
+            timing: Timing {
+                uninherited: points
+                    .iter()
+                    .cloned()
+                    .filter(|p| p.is_uninherited() && p.ms_per_beat > 0.0 && p.ms_per_beat.is_finite())
+                    .collect(),
+            },
+            points,
+            slider_multiplier: map.difficulty.slider_multiplier,
+        }
+    }
+
+    /// The tempo changes, in order.
+    pub fn timing(&self) -> &Timing<'a> {
+        &self.timing
+    }
+
+    /// Same as [`Beatmap::timing_point_at`](struct.Beatmap.html#method.timing_point_at).
+    pub fn point_at(&self, time: Time) -> Option<&'a TimingPoint> {
+        let i = self.points.partition_point(|p| p.offset <= time);
+        self.points.get(i.saturating_sub(1)).cloned()
+    }
+
+    /// Same as [`Beatmap::beat_length_at`](struct.Beatmap.html#method.beat_length_at).
+    pub fn beat_length_at(&self, time: Time) -> Option<f64> {
+        self.timing.beat_length_at(time)
+    }
+
+    /// Same as [`Beatmap::slider_velocity_at`](struct.Beatmap.html#method.slider_velocity_at).
+    pub fn slider_velocity_at(&self, time: Time) -> f32 {
+        self.point_at(time)
+            .map_or(1.0, TimingPoint::effective_slider_velocity_multiplier)
+    }
+
+    /// Everything about the timing in effect at the given time.
+    pub fn context_at(&self, time: Time) -> TimingContext<'a> {
+        let point = self.point_at(time);
+        TimingContext {
+            uninherited: self.timing.point_at(time),
+            point,
+            slider_velocity: point.map_or(1.0, TimingPoint::effective_slider_velocity_multiplier),
+        }
+    }
+
+    /// Same as [`Beatmap::slider_duration`](struct.Beatmap.html#method.slider_duration).
+    pub fn slider_duration(&self, slider: &Slider) -> Time {
+        let time = slider.time;
+        slider_duration(slider, self.slider_multiplier, self.beat_length_at(time), self.slider_velocity_at(time))
+    }
+
+    /// Same as [`Beatmap::object_end_time`](struct.Beatmap.html#method.object_end_time).
+    pub fn object_end_time(&self, object: &HitObject) -> Time {
+        object_end_time(object, |s| self.slider_duration(s))
+    }
+}
+
+/// How long `slider` lasts, given the timing in effect when it starts.
+fn slider_duration(slider: &Slider, slider_multiplier: f32, beat_length: Option<f64>, slider_velocity: f32) -> Time {
+    let velocity = 100.0 * slider_multiplier * slider_velocity;
+    if velocity <= 0.0 {
+        return Time::ZERO;
+    }
+
+    let beats = slider.pixel_length / f64::from(velocity) * f64::from(slider.repeat.max(1));
+    Time::from_beats(beats, beat_length.unwrap_or(0.0))
+}
+
+/// When `object` ends, given how to find the duration of a slider.
+fn object_end_time<F: Fn(&Slider) -> Time>(object: &HitObject, slider_duration: F) -> Time {
+    match object {
+        HitObject::HitCircle(c) => c.time,
+        HitObject::Slider(s) => s.time + slider_duration(s),
+        HitObject::Spinner(s) => s.end_time,
+        HitObject::HoldNote(n) => n.end_time,
+    }
+}
+
+/// The timing in effect when a hit object starts, as given by
+/// [`Beatmap::iter_hit_objects_with_timing`](struct.Beatmap.html#method.iter_hit_objects_with_timing).
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct TimingContext<'a> {
+    /// The uninherited timing point setting the tempo, as found by
+    /// [`Timing::point_at`](struct.Timing.html#method.point_at).
+    pub uninherited: Option<&'a TimingPoint>,
+    /// The timing point setting the sample set, volume and effects, as
+    /// found by
+    /// [`Beatmap::timing_point_at`](struct.Beatmap.html#method.timing_point_at).
+    pub point: Option<&'a TimingPoint>,
+    /// The slider velocity multiplier, as given by
+    /// [`Beatmap::slider_velocity_at`](struct.Beatmap.html#method.slider_velocity_at).
+    pub slider_velocity: f32,
+}
+
+impl<'a> TimingContext<'a> {
+    /// The duration of a beat, or `None` if there is no uninherited timing
+    /// point.
+    pub fn beat_length(&self) -> Option<f64> {
+        self.uninherited.map(|p| p.ms_per_beat)
+    }
+
+    pub fn bpm(&self) -> Option<f64> {
+        self.uninherited.and_then(TimingPoint::bpm)
+    }
+
+    /// The sample set, `0` if there are no timing points.
+    pub fn sample_set(&self) -> i32 {
+        self.point.map_or(0, |p| p.sample_set)
+    }
+
+    /// The volume, `100` if there are no timing points.
+    pub fn volume(&self) -> i32 {
+        self.point.map_or(100, |p| p.volume)
+    }
+
+    pub fn kiai(&self) -> bool {
+        self.point.is_some_and(|p| p.effects.kiai())
+    }
+}
+
+/// Finds the number of points starting at or before `time`, moving on from
+/// the previous result `n` when times only increase.
+fn advance<T, F>(points: &[T], n: &mut usize, time: Time, offset: F)
+where
+    F: Fn(&T) -> Time,
+{
+    if *n > points.len() || (*n > 0 && offset(&points[*n - 1]) > time) {
+        *n = points.partition_point(|p| offset(p) <= time);
+        return;
+    }
+
+    while *n < points.len() && offset(&points[*n]) <= time {
+        *n += 1;
+    }
+}
+
+/// Limits of the slider velocity multiplier the game applies.
+const MIN_SLIDER_VELOCITY: f32 = 0.1;
+const MAX_SLIDER_VELOCITY: f32 = 10.0;
+
+impl TimingPoint {
+    /// The slider velocity multiplier the game actually uses for this point.
+    /// Unlike [`slider_velocity_multiplier`](#method.slider_velocity_multiplier),
+    /// it is kept between `0.1` and `10`, and a beat length of `NaN`, which
+    /// some old beatmaps have, gives the normal velocity.
+    ///
+    /// ```
+    /// use osuparse::TimingPoint;
+    ///
+    /// let point = TimingPoint { ms_per_beat: -5.0, ..Default::default() };
+    /// assert_eq!(point.slider_velocity_multiplier(), 20.0);
+    /// assert_eq!(point.effective_slider_velocity_multiplier(), 10.0);
+    ///
+    /// let point = TimingPoint { ms_per_beat: f64::NAN, ..Default::default() };
+    /// assert_eq!(point.effective_slider_velocity_multiplier(), 1.0);
+    /// ```
+    pub fn effective_slider_velocity_multiplier(&self) -> f32 {
+        if self.ms_per_beat.is_nan() {
+            return 1.0;
+        }
+
+        self.slider_velocity_multiplier()
+            .clamp(MIN_SLIDER_VELOCITY, MAX_SLIDER_VELOCITY)
+    }
+}
+
+impl Beatmap {
+    /// The beatmap's [`Timing`](struct.Timing.html).
+    pub fn timing(&self) -> Timing<'_> {
+        Timing::new(&self.timing_points)
+    }
+
+    /// A [`TimingIndex`](struct.TimingIndex.html) of the beatmap's timing
+    /// points.
+    pub fn timing_index(&self) -> TimingIndex<'_> {
+        TimingIndex::new(self)
+    }
+
+    /// Moves every hit object, and the end of every spinner and hold note,
+    /// to the nearest `1/divisor` of a beat, rounded to the millisecond.
+    /// Returns the number of objects that moved.
+    pub fn resnap_objects(&mut self, divisor: u32) -> usize {
+        let timing = Timing::new(&self.timing_points);
+        let snap = |time: Time| {
+            timing.snap(time, divisor).map_or(time, |(t, _)| Time::from(t.round()))
+        };
+
+        let mut moved = 0;
+        for obj in &mut self.hit_objects {
+            let (time, end_time) = match obj {
+                HitObject::HitCircle(o) => (&mut o.time, None),
+                HitObject::Slider(o) => (&mut o.time, None),
+                HitObject::Spinner(o) => (&mut o.time, Some(&mut o.end_time)),
+                HitObject::HoldNote(o) => (&mut o.time, Some(&mut o.end_time)),
+            };
+
+            let mut changed = false;
+            for t in Some(time).into_iter().chain(end_time) {
+                let snapped = snap(*t);
+                changed |= snapped != *t;
+                *t = snapped;
+            }
+            if changed {
+                moved += 1;
+            }
+        }
+
+        moved
+    }
+
+    /// Sets the slider velocity multiplier from `time` until the next timing
+    /// point, by changing the inherited timing point at `time` or adding
+    /// one. An added point takes its sample settings and effects from the
+    /// point in effect at `time`. The multiplier is kept between `0.1` and
+    /// `10`, the range the game allows.
+    ///
+    /// ```
+    /// use osuparse::Time;
+    ///
+    /// let mut map = osuparse::parse_beatmap("osu file format v14
+    ///
+    /// [TimingPoints]
+    /// 0,500,4,2,0,60,1,0
+    /// 2000,-50,4,2,0,80,0,0
+    /// ").unwrap();
+    ///
+    /// map.set_slider_velocity(Time(1000.0), 1.5);
+    /// map.set_slider_velocity(Time(2000.0), 0.75);
+    /// assert_eq!(map.slider_velocity_at(Time(1500.0)), 1.5);
+    /// assert_eq!(map.timing_points[1].volume, 60);
+    /// assert_eq!(map.slider_velocity_at(Time(2000.0)), 0.75);
+    /// assert_eq!(map.timing_points.len(), 3);
+    /// ```
+    pub fn set_slider_velocity(&mut self, time: Time, multiplier: f32) {
+        let ms_per_beat = -100.0 / f64::from(multiplier.clamp(MIN_SLIDER_VELOCITY, MAX_SLIDER_VELOCITY));
+
+        let end = self.timing_points.partition_point(|p| p.offset <= time);
+        let existing = self.timing_points[..end]
+            .iter_mut()
+            .rev()
+            .take_while(|p| p.offset == time)
+            .find(|p| !p.is_uninherited());
+        if let Some(point) = existing {
+            point.ms_per_beat = ms_per_beat;
+            return;
+        }
+
+        let base = self.timing_point_at(time).cloned().unwrap_or_default();
+        self.timing_points.insert(end, TimingPoint {
+            offset: time,
+            ms_per_beat,
+            inherited: false,
+            ..base
+        });
+    }
+
+    /// Multiplies the slider velocity everywhere in the beatmap by `factor`,
+    /// keeping it between `0.1x` and `10x`. Inherited timing points are
+    /// scaled, and since uninherited points reset the velocity, one is added
+    /// after each that doesn't already have one at the same offset.
+    ///
+    /// ```
+    /// use osuparse::Time;
+    ///
+    /// let mut map = osuparse::parse_beatmap("osu file format v14
+    ///
+    /// [TimingPoints]
+    /// 0,500,4,2,0,60,1,0
+    /// 1000,-50,4,2,0,80,0,0
+    /// ").unwrap();
+    ///
+    /// map.scale_slider_velocities(1.5);
+    /// assert_eq!(map.slider_velocity_at(Time(0.0)), 1.5);
+    /// assert_eq!(map.slider_velocity_at(Time(1000.0)), 3.0);
+    /// ```
+    pub fn scale_slider_velocities(&mut self, factor: f32) {
+        if factor == 1.0 {
+            return;
+        }
+
+        let velocity = |multiplier: f32| {
+            -100.0 / f64::from((multiplier * factor).clamp(MIN_SLIDER_VELOCITY, MAX_SLIDER_VELOCITY))
+        };
+        let points = &self.timing_points;
+
+        let mut scaled = Vec::with_capacity(points.len() * 2);
+        for (i, point) in points.iter().enumerate() {
+            if !point.is_uninherited() {
+                scaled.push(TimingPoint {
+                    ms_per_beat: velocity(point.effective_slider_velocity_multiplier()),
+                    ..point.clone()
+                });
+                continue;
+            }
+
+            scaled.push(point.clone());
+            let has_inherited = points[i + 1..]
+                .iter()
+                .take_while(|p| p.offset == point.offset)
+                .any(|p| !p.is_uninherited());
+            if !has_inherited {
+                scaled.push(TimingPoint {
+                    ms_per_beat: velocity(1.0),
+                    inherited: false,
+                    ..point.clone()
+                });
+            }
+        }
+
+        self.timing_points = scaled;
+    }
+
+    /// Sets the hitsound volume of the given range of time, as in the
+    /// editor's timing setup. Every timing point in the range gets the new
+    /// volume, and inherited points are added at the start and end of the
+    /// range where there is no point yet, so that the volume around it stays
+    /// the same. Uninherited points keep their timing.
+    ///
+    /// ```
+    /// use osuparse::Time;
+    ///
+    /// let mut map = osuparse::parse_beatmap("osu file format v14
+    ///
+    /// [TimingPoints]
+    /// 0,500,4,2,0,60,1,0
+    /// 2000,400,4,2,0,60,1,0
+    /// ").unwrap();
+    ///
+    /// map.set_volume(Time(1000.0)..Time(3000.0), 30);
+    /// let volumes: Vec<(f64, i32)> = map.timing_points.iter().map(|p| (p.offset.0, p.volume)).collect();
+    /// assert_eq!(volumes, vec![(0.0, 60), (1000.0, 30), (2000.0, 30), (3000.0, 60)]);
+    /// assert_eq!(map.timing_points[3].ms_per_beat, -100.0);
+    /// ```
+    pub fn set_volume(&mut self, range: Range<Time>, volume: i32) {
+        self.edit_timing_points(range, |p| p.volume = volume);
+    }
+
+    /// Sets the sample set of the given range of time, like
+    /// [`set_volume`](#method.set_volume) does the volume. See
+    /// [`TimingPoint::sample_set`](struct.TimingPoint.html#structfield.sample_set)
+    /// for the values.
+    pub fn set_sample_set(&mut self, range: Range<Time>, sample_set: i32) {
+        self.edit_timing_points(range, |p| p.sample_set = sample_set);
+    }
+
+    /// Applies `edit` to the timing points in `range`, after adding points
+    /// at its ends as needed.
+    fn edit_timing_points<F>(&mut self, range: Range<Time>, edit: F)
+    where
+        F: Fn(&mut TimingPoint),
+    {
+        if self.timing_points.is_empty() || range.is_empty() {
+            return;
+        }
+
+        self.split_timing_points(range.end);
+        self.split_timing_points(range.start);
+        self.timing_points
+            .iter_mut()
+            .filter(|p| range.contains(&p.offset))
+            .for_each(edit);
+    }
+
+    /// Adds an inherited timing point at `time`, copying the point in effect
+    /// there, unless a point already starts at that time. Nothing is added
+    /// before the first uninherited point, where an inherited one would
+    /// leave the map without a tempo to start from.
+    fn split_timing_points(&mut self, time: Time) {
+        let timed = self.timing_points.iter().any(|p| p.is_uninherited() && p.offset <= time);
+        if !timed || self.timing_points.iter().any(|p| p.offset == time) {
+            return;
+        }
+
+        if let Some(point) = self.timing_point_at(time).cloned() {
+            let end = self.timing_points.partition_point(|p| p.offset <= time);
+            self.timing_points.insert(end, TimingPoint {
+                offset: time,
+                ms_per_beat: -100.0 / f64::from(point.effective_slider_velocity_multiplier()),
+                inherited: false,
+                ..point
+            });
+        }
+    }
+
+    /// Every hit object along with the timing in effect when it starts.
+    /// When the objects are in order, each timing point is only looked at
+    /// once, instead of searching the timing points for every object.
+    ///
+    /// ```
+    /// let input = "osu file format v14
+    ///
+    /// [TimingPoints]
+    /// 0,500,4,2,0,60,1,0
+    /// 1000,-50,4,2,0,80,0,1
+    ///
+    /// [HitObjects]
+    /// 256,192,500,1,0
+    /// 256,192,1500,1,0
+    /// ";
+    ///
+    /// let map = osuparse::parse_beatmap(input).unwrap();
+    /// let contexts: Vec<_> = map.iter_hit_objects_with_timing().map(|(_, t)| t).collect();
+    ///
+    /// assert_eq!(contexts[0].bpm(), Some(120.0));
+    /// assert_eq!((contexts[0].volume(), contexts[0].kiai()), (60, false));
+    /// assert_eq!((contexts[1].slider_velocity, contexts[1].volume(), contexts[1].kiai()), (2.0, 80, true));
+    /// ```
+    pub fn iter_hit_objects_with_timing(&self) -> impl Iterator<Item = (&HitObject, TimingContext<'_>)> {
+        let timing = self.timing();
+        let points = &self.timing_points;
+        let (mut n, mut uninherited_n) = (0, 0);
+
+        self.hit_objects.iter().map(move |object| {
+            let time = object.time();
+            advance(points, &mut n, time, |p| p.offset);
+            advance(&timing.uninherited, &mut uninherited_n, time, |p| p.offset);
+
+            let point = points.get(n.saturating_sub(1));
+            let context = TimingContext {
+                uninherited: timing.uninherited.get(uninherited_n.saturating_sub(1)).cloned(),
+                point,
+                slider_velocity: point.map_or(1.0, TimingPoint::effective_slider_velocity_multiplier),
+            };
+            (object, context)
+        })
+    }
+
+    /// The timing point in effect at the given time: the last one starting
+    /// at or before it. Before the first timing point, the first one applies.
+    /// Inherited timing points are included, so this is the point that sets
+    /// the sample set, volume and effects at that time.
+    ///
+    /// [`timing_points`](struct.Beatmap.html#structfield.timing_points)
+    /// should be sorted by offset, as they are in a valid beatmap.
+    pub fn timing_point_at(&self, time: Time) -> Option<&TimingPoint> {
+        let i = self.timing_points.partition_point(|p| p.offset <= time);
+        self.timing_points.get(i.saturating_sub(1))
+    }
+
+    /// The duration of a beat at the given time, from the last uninherited
+    /// timing point starting at or before it, or the first one before that.
+    /// `None` if the beatmap has no uninherited timing points.
+    pub fn beat_length_at(&self, time: Time) -> Option<f64> {
+        self.timing().beat_length_at(time)
+    }
+
+    /// The beat nearest to the given time, counting beats from the
+    /// uninherited timing point in effect. `None` if there is none.
+    pub fn nearest_beat(&self, time: Time) -> Option<Time> {
+        self.timing().snap(time, 1).map(|(t, _)| t)
+    }
+
+    /// The slider velocity multiplier at the given time. Uninherited timing
+    /// points reset it to `1.0`. Like the game, the multiplier is kept
+    /// between `0.1` and `10`, see
+    /// [`TimingPoint::effective_slider_velocity_multiplier`](struct.TimingPoint.html#method.effective_slider_velocity_multiplier).
+    pub fn slider_velocity_at(&self, time: Time) -> f32 {
+        self.timing_point_at(time)
+            .map_or(1.0, TimingPoint::effective_slider_velocity_multiplier)
+    }
+
+    /// How long a slider lasts in milliseconds, including its repeats.
+    pub fn slider_duration(&self, slider: &Slider) -> Time {
+        let time = slider.time;
+        slider_duration(
+            slider,
+            self.difficulty.slider_multiplier,
+            self.beat_length_at(time),
+            self.slider_velocity_at(time),
+        )
+    }
+
+    /// When a hit object ends, in milliseconds. Hit circles end when they
+    /// start. Use a [`TimingIndex`](struct.TimingIndex.html) when looking up
+    /// the end of many objects.
+    pub fn object_end_time(&self, object: &HitObject) -> Time {
+        object_end_time(object, |s| self.slider_duration(s))
+    }
+
+    /// Time from the start of the first hit object to the end of the last
+    /// one, in milliseconds, as shown as the length of a beatmap on the osu!
+    /// website.
+    ///
+    /// The [audio lead-in](struct.GeneralSection.html#structfield.audio_lead_in)
+    /// isn't counted. It only delays the start of the song, and with it the
+    /// first hit object, so the time between the first and last objects
+    /// stays the same, and the website leaves it out too.
+    pub fn total_length(&self) -> Time {
+        self.object_range().map_or(Time::ZERO, |(start, end)| end - start)
+    }
+
+    /// Like [`total_length`](#method.total_length), but leaving out the
+    /// breaks, during which health doesn't drain. This is the drain time
+    /// shown on the osu! website. As with the total length, the audio
+    /// lead-in isn't counted.
+    ///
+    /// ```
+    /// let input = "osu file format v14
+    ///
+    /// [Events]
+    /// 2,2000,6000
+    ///
+    /// [HitObjects]
+    /// 256,192,1000,1,0
+    /// 256,192,7000,8,0,9000
+    /// ";
+    ///
+    /// use osuparse::Time;
+    ///
+    /// let map = osuparse::parse_beatmap(input).unwrap();
+    /// assert_eq!(map.total_length(), Time(8000.0));
+    /// assert_eq!(map.drain_time(), Time(4000.0));
+    /// ```
+    pub fn drain_time(&self) -> Time {
+        let (start, end) = match self.object_range() {
+            Some(range) => range,
+            None => return Time::ZERO,
+        };
+
+        let breaks: Time = self.events.breaks
+            .iter()
+            .map(|b| (b.end_time.min(end) - b.start_time.max(start)).max(Time::ZERO))
+            .sum();
+
+        (end - start - breaks).max(Time::ZERO)
+    }
+
+    /// The lowest and highest tempo set by the uninherited timing points,
+    /// in beats per minute. `None` if there are none.
+    pub fn bpm_range(&self) -> Option<(f64, f64)> {
+        self.timing()
+            .uninherited
+            .iter()
+            .filter_map(|p| p.bpm())
+            .fold(None, |range, bpm| match range {
+                None => Some((bpm, bpm)),
+                Some((min, max)) => Some((min.min(bpm), max.max(bpm))),
+            })
+    }
+
+    /// The tempo in effect for longest, in beats per minute, which is what
+    /// the osu! website shows as a beatmap's BPM. Each tempo counts from its
+    /// timing point, or the start of the song for the first one, until the
+    /// next uninherited timing point or the end of the last hit object.
+    /// `None` if there are no uninherited timing points, leaving out those
+    /// ignored by [`Timing`](struct.Timing.html).
+    ///
+    /// ```
+    /// let input = "osu file format v14
+    ///
+    /// [TimingPoints]
+    /// 0,500,4,1,0,100,1,0
+    /// 1000,250,4,1,0,100,1,0
+    ///
+    /// [HitObjects]
+    /// 256,192,8000,1,0
+    /// ";
+    ///
+    /// let map = osuparse::parse_beatmap(input).unwrap();
+    /// assert_eq!(map.main_bpm(), Some(240.0));
+    /// assert_eq!(map.bpm_range(), Some((120.0, 240.0)));
+    /// ```
+    pub fn main_bpm(&self) -> Option<f64> {
+        let points = self.timing().uninherited;
+        let end = self.object_range().map_or(Time::ZERO, |(_, end)| end);
+
+        // Total duration of each distinct beat length
+        let mut durations: Vec<(f64, Time)> = Vec::new();
+        for (i, point) in points.iter().enumerate() {
+            let start = if i == 0 { Time::ZERO } else { point.offset };
+            let next = points.get(i + 1).map_or(end, |p| p.offset);
+            let duration = (next.min(end) - start).max(Time::ZERO);
+
+            match durations.iter_mut().find(|(length, _)| *length == point.ms_per_beat) {
+                Some((_, total)) => *total += duration,
+                None => durations.push((point.ms_per_beat, duration)),
+            }
+        }
+
+        // Ties go to the tempo that comes first
+        durations
+            .iter()
+            .fold(None, |longest: Option<(f64, Time)>, &(length, duration)| match longest {
+                Some((_, most)) if most >= duration => longest,
+                _ => Some((length, duration)),
+            })
+            .map(|(length, _)| 60_000.0 / length)
+    }
+
+    /// Start of the first hit object and end of the last one.
+    fn object_range(&self) -> Option<(Time, Time)> {
+        let start = self.hit_objects.iter().map(HitObject::time).min()?;
+        let index = self.timing_index();
+        let end = self.hit_objects.iter().map(|o| index.object_end_time(o)).max()?;
+        Some((start, end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_iter_hit_objects_with_timing() {
+        let input = "osu file format v14\n\n[TimingPoints]\n500,400,4,1,0,50,1,0\n1000,-200,4,2,0,70,0,1\n\
+                     1000,NaN,4,1,0,60,1,0\n3000,300,4,3,0,90,1,0\n\n[HitObjects]\n\
+                     256,192,0,1,0\n256,192,1000,1,0\n256,192,2000,1,0\n256,192,1200,1,0\n256,192,3500,1,0\n";
+        let map = parse_beatmap(input).unwrap();
+
+        let contexts: Vec<TimingContext> = map.iter_hit_objects_with_timing().map(|(_, t)| t).collect();
+        assert_eq!(contexts.len(), 5);
+        for (object, context) in map.hit_objects.iter().zip(&contexts) {
+            let time = object.time();
+            // Compared by address, since NaN beat lengths aren't equal to themselves
+            let address = |p: Option<&TimingPoint>| p.map(|p| p as *const TimingPoint);
+            assert_eq!(address(context.point), address(map.timing_point_at(time)), "{}", time);
+            assert_eq!(context.beat_length(), map.beat_length_at(time), "{}", time);
+            assert_eq!(context.slider_velocity, map.slider_velocity_at(time), "{}", time);
+        }
+
+        assert_eq!(contexts[0].volume(), 50);
+        assert_eq!((contexts[1].volume(), contexts[1].slider_velocity), (60, 1.0));
+        assert_eq!(contexts[4].bpm(), Some(200.0));
+
+        let empty = Beatmap::default();
+        assert_eq!(empty.iter_hit_objects_with_timing().count(), 0);
+    }
+
+    #[test]
+    fn test_timing_point_at() {
+        let point = |offset: f64, volume: i32| TimingPoint { offset: Time(offset), volume, ..Default::default() };
+        let map = Beatmap {
+            timing_points: vec![point(100.0, 10), point(200.0, 20), point(200.0, 30)],
+            ..Default::default()
+        };
+
+        assert_eq!(map.timing_point_at(Time(0.0)).unwrap().volume, 10);
+        assert_eq!(map.timing_point_at(Time(150.0)).unwrap().volume, 10);
+        assert_eq!(map.timing_point_at(Time(200.0)).unwrap().volume, 30);
+        assert!(Beatmap::default().timing_point_at(Time::ZERO).is_none());
+    }
+
+    #[test]
+    fn test_set_sample_set() {
+        let mut map = parse_beatmap("osu file format v14\n\n[TimingPoints]\n\
+                                     0,500,4,1,0,60,1,0\n1000,-50,4,1,0,60,0,0\n3000,-200,4,1,0,60,0,0\n")
+            .unwrap();
+
+        map.set_sample_set(Time(1000.0)..Time(2000.0), 2);
+        map.set_sample_set(Time(500.0)..Time(500.0), 3);
+        let points: Vec<(f64, f64, i32)> = map.timing_points.iter().map(|p| (p.offset.0, p.ms_per_beat, p.sample_set)).collect();
+        assert_eq!(points, vec![
+            (0.0, 500.0, 1),
+            (1000.0, -50.0, 2),
+            (2000.0, -50.0, 1),
+            (3000.0, -200.0, 1),
+        ]);
+    }
+
+    #[test]
+    fn test_set_volume_before_first_point() {
+        let mut map = parse_beatmap("osu file format v14\n\n[TimingPoints]\n\
+                                     1000,500,4,1,0,60,1,0\n3000,-50,4,1,0,60,0,0\n")
+            .unwrap();
+
+        map.set_volume(Time(-500.0)..Time(2000.0), 30);
+        let points: Vec<(f64, f64, i32)> = map.timing_points.iter().map(|p| (p.offset.0, p.ms_per_beat, p.volume)).collect();
+        assert_eq!(points, vec![(1000.0, 500.0, 30), (2000.0, -100.0, 60), (3000.0, -50.0, 60)]);
+        assert!(map.timing_points[0].is_uninherited());
+
+        map.set_volume(Time(0.0)..Time(500.0), 10);
+        assert_eq!(map.timing_points.len(), 3);
+        assert!(map.timing_points.iter().all(|p| p.volume != 10));
+    }
+
+    #[test]
+    fn test_length_ignores_lead_in() {
+        let input = |lead_in: i32| {
+            format!(
+                "osu file format v14\n\n[General]\nAudioLeadIn: {}\n\n[Events]\n2,2000,3000\n\n\
+                 [HitObjects]\n256,192,500,1,0\n256,192,4500,1,0\n",
+                lead_in
+            )
+        };
+
+        for &lead_in in &[0, 2000] {
+            let map = parse_beatmap(&input(lead_in)).unwrap();
+            assert_eq!(map.general.audio_lead_in, Time::from(lead_in));
+            assert_eq!((map.total_length(), map.drain_time()), (Time(4000.0), Time(3000.0)));
+        }
+    }
+
+    #[test]
+    fn test_resnap_objects() {
+        let input = "osu file format v14
+
+[TimingPoints]
+10,300,4,1,0,100,1,0
+1000,-50,4,1,0,100,0,0
+1210,200,4,1,0,100,1,0
+
+[HitObjects]
+0,0,110,1,0
+0,0,165,1,0
+0,0,1200,1,0
+0,0,1300,8,0,1395
+";
+
+        let mut map = parse_beatmap(input).unwrap();
+        let timing = map.timing();
+        assert_eq!(timing.snap(Time(165.0), 2), Some((Time(160.0), Time(5.0))));
+        assert_eq!(timing.snap(Time(1300.0), 4), Some((Time(1310.0), Time(-10.0))));
+        assert_eq!(timing.snap(Time(1300.0), 0), None);
+
+        assert_eq!(map.resnap_objects(4), 4);
+        assert_eq!(map.resnap_objects(4), 0);
+        let times: Vec<Time> = map.hit_objects.iter().map(HitObject::time).collect();
+        assert_eq!(times, vec![Time(85.0), Time(160.0), Time(1210.0), Time(1310.0)]);
+        match map.hit_objects[3] {
+            HitObject::Spinner(ref s) => assert_eq!(s.end_time, Time(1410.0)),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_slider_duration() {
+        let input = "osu file format v14
+
+[Difficulty]
+SliderMultiplier:2
+
+[TimingPoints]
+0,-50,4,1,0,100,0,0
+1000,500,4,1,0,100,1,0
+2000,-50,4,1,0,100,0,0
+2800,NaN,4,1,0,100,0,0
+3000,250,4,1,0,100,1,0
+
+[HitObjects]
+0,0,1500,2,0,L|200:0,1,200
+0,0,2500,2,0,L|200:0,2,200
+";
+
+        let map = parse_beatmap(input).unwrap();
+
+        assert_eq!(map.beat_length_at(Time(0.0)), Some(500.0));
+        assert_eq!(map.beat_length_at(Time(2500.0)), Some(500.0));
+        assert_eq!(map.beat_length_at(Time(3000.0)), Some(250.0));
+        assert_eq!(map.slider_velocity_at(Time(1500.0)), 1.0);
+        assert_eq!(map.slider_velocity_at(Time(2500.0)), 2.0);
+        assert_eq!(map.slider_velocity_at(Time(2900.0)), 1.0);
+
+        let durations: Vec<Time> = map.hit_objects
+            .iter()
+            .map(|o| match o {
+                HitObject::Slider(s) => map.slider_duration(s),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(durations, vec![Time(500.0), Time(500.0)]);
+        assert_eq!(map.total_length(), Time(1500.0));
+    }
+
+    #[test]
+    fn test_non_finite_beat_lengths() {
+        let input = "osu file format v14
+
+[TimingPoints]
+0,500,4,1,0,100,1,0
+1000,1.#INF,4,1,0,100,1,0
+2000,-∞,4,1,0,100,0,0
+3000,NaN,4,1,0,100,0,0
+4000,Infinity,4,1,0,100,1,0
+";
+
+        let map = parse_beatmap(input).unwrap();
+        assert!(map.timing_points[3].ms_per_beat.is_nan());
+
+        assert_eq!(map.beat_length_at(Time(1500.0)), Some(500.0));
+        assert_eq!(map.beat_length_at(Time(4500.0)), Some(500.0));
+        assert_eq!(map.slider_velocity_at(Time(2500.0)), 0.1);
+        assert_eq!(map.slider_velocity_at(Time(3500.0)), 1.0);
+        assert_eq!(map.bpm_range(), Some((120.0, 120.0)));
+    }
+
+    #[test]
+    fn test_drain_time() {
+        let input = "osu file format v14
+
+[Events]
+2,0,500
+2,1000,3000
+2,5500,8000
+
+[HitObjects]
+256,192,400,1,0
+256,192,3000,1,0
+256,192,6000,1,0
+";
+
+        let map = parse_beatmap(input).unwrap();
+        assert_eq!(map.total_length(), Time(5600.0));
+        // Only the parts of the breaks between the first and last objects count
+        assert_eq!(map.drain_time(), Time(5600.0 - 100.0 - 2000.0 - 500.0));
+        assert_eq!(Beatmap::default().drain_time(), Time::ZERO);
+    }
+}
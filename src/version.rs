@@ -0,0 +1,336 @@
+use std::fmt;
+
+use super::*;
+
+/// Keys of key-value sections that were added after the first versions of
+/// the format, with the version whose files the editor first wrote them
+/// to. Keys not listed here are found in files of every version.
+const KEY_VERSIONS: &[(&str, i32)] = &[
+    // General
+    ("Countdown", 5),
+    ("StackLeniency", 5),
+    ("Mode", 6),
+    ("LetterboxInBreaks", 6),
+    ("StoryFireInFront", 8),
+    ("EpilepsyWarning", 9),
+    ("WidescreenStoryboard", 11),
+    ("SpecialStyle", 11),
+    ("UseSkinSprites", 12),
+    ("AlwaysShowPlayfield", 12),
+    ("SkinPreference", 13),
+    ("CountdownOffset", 13),
+    ("OverlayPosition", 13),
+    ("SamplesMatchPlaybackRate", 14),
+    // Editor
+    ("Bookmarks", 5),
+    ("DistanceSpacing", 6),
+    ("BeatDivisor", 6),
+    ("GridSize", 6),
+    ("TimelineZoom", 14),
+    // Metadata
+    ("Source", 6),
+    ("Tags", 6),
+    ("TitleUnicode", 10),
+    ("ArtistUnicode", 10),
+    ("BeatmapID", 10),
+    ("BeatmapSetID", 10),
+    // Difficulty
+    ("ApproachRate", 8),
+];
+
+/// A version of the .osu file format, as written in the first line of a
+/// file, e.g. `osu file format v14`.
+///
+/// Features were added to the format over time, and files written in older
+/// versions leave out the values that came after them. The queries here
+/// tell what a file of a given version can be expected to contain.
+///
+/// The parser only consults [`has_approach_rate`](#method.has_approach_rate),
+/// to fill in the approach rate of files older than v8. Everything else is
+/// read the same way in every version, with missing values left at their
+/// defaults, and times are kept as written, without the
+/// [`time_offset`](#method.time_offset) the game adds to old files. The
+/// other queries are for tools, and are used when writing older versions
+/// with [`WriterOptions::for_version`](deserialize/struct.WriterOptions.html#method.for_version)
+/// and upgrading with [`Beatmap::upgrade_to`](struct.Beatmap.html#method.upgrade_to).
+///
+/// # Examples
+///
+/// ```
+/// use osuparse::{parse_beatmap, FormatVersion};
+///
+/// let map = parse_beatmap("osu file format v7\n").unwrap();
+/// let version = map.format_version();
+///
+/// assert_eq!(version, FormatVersion(7));
+/// assert!(!version.has_approach_rate());
+/// assert!(version < FormatVersion::LATEST);
+/// ```
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FormatVersion(pub i32);
+
+impl FormatVersion {
+    /// The newest version, which files without a version line are assumed
+    /// to be in.
+    pub const LATEST: FormatVersion = FormatVersion(14);
+
+    pub fn get(self) -> i32 {
+        self.0
+    }
+
+    /// Whether the Difficulty section has an `ApproachRate` key. Older files
+    /// use their overall difficulty instead. Added in v8.
+    pub fn has_approach_rate(self) -> bool {
+        self.0 >= 8
+    }
+
+    /// Whether hit objects end with their
+    /// [`HitObjectExtras`](struct.HitObjectExtras.html). Older files leave
+    /// them out, and they take their defaults. Added in v10.
+    pub fn supports_extras(self) -> bool {
+        self.0 >= 10
+    }
+
+    /// Whether the General section has a `WidescreenStoryboard` key. Older
+    /// storyboards are always 4:3. Added in v11.
+    pub fn supports_widescreen(self) -> bool {
+        self.0 >= 11
+    }
+
+    /// Whether files of this version can have `key` in their key-value
+    /// sections. Keys are compared ignoring case, as the parser does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use osuparse::FormatVersion;
+    ///
+    /// assert!(FormatVersion(12).has_key("WidescreenStoryboard"));
+    /// assert!(!FormatVersion(7).has_key("ApproachRate"));
+    /// assert!(FormatVersion(3).has_key("AudioFilename"));
+    /// ```
+    pub fn has_key(self, key: &str) -> bool {
+        KEY_VERSIONS
+            .iter()
+            .find(|&&(k, _)| unicase::eq(k, key))
+            .map_or(true, |&(_, since)| self.0 >= since)
+    }
+
+    /// The sample set used when the General section has no `SampleSet` key.
+    /// This is the same in every version so far.
+    pub fn default_sample_set(self) -> &'static str {
+        "Normal"
+    }
+
+    /// The offset, in milliseconds, the game adds to every time in files of
+    /// this version. Files before v5 were timed against an audio delay that
+    /// was later removed. The parser doesn't apply it, so that times can be
+    /// written back unchanged; [`Beatmap::upgrade_to`](struct.Beatmap.html#method.upgrade_to)
+    /// does.
+    pub fn time_offset(self) -> i32 {
+        if self.0 < 5 {
+            24
+        } else {
+            0
+        }
+    }
+}
+
+impl Default for FormatVersion {
+    fn default() -> Self {
+        FormatVersion::LATEST
+    }
+}
+
+impl From<i32> for FormatVersion {
+    fn from(version: i32) -> Self {
+        FormatVersion(version)
+    }
+}
+
+impl fmt::Display for FormatVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "v{}", self.0)
+    }
+}
+
+impl Beatmap {
+    /// Same as [`version`](#structfield.version), as a
+    /// [`FormatVersion`](struct.FormatVersion.html).
+    pub fn format_version(&self) -> FormatVersion {
+        FormatVersion(self.version)
+    }
+
+    /// Converts a beatmap read from an older format version to how `version`
+    /// represents it, and sets its version, so that it is written out like
+    /// a file saved by a current editor. Nothing is done if the beatmap is
+    /// already in `version` or newer.
+    ///
+    /// - Files older than v8 get an `ApproachRate` equal to their overall
+    ///   difficulty.
+    /// - Sliders of files older than v10 get a hitsound and sample sets for
+    ///   each of their edges. See
+    ///   [`Slider::normalize_edges`](struct.Slider.html#method.normalize_edges).
+    /// - Everything in files older than v5 is moved 24ms later, the offset
+    ///   the game adds to them. See
+    ///   [`shift_time`](#method.shift_time).
+    /// - Event types are written the way the editor writes them, e.g.
+    ///   `Video` rather than `1`, and `2` rather than `Break`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use osuparse::{parse_beatmap, FormatVersion, Time, ToOsuString};
+    ///
+    /// let mut map = parse_beatmap("osu file format v4
+    ///
+    /// [Difficulty]
+    /// OverallDifficulty:6
+    ///
+    /// [Events]
+    /// Break,1000,2000
+    /// ").unwrap();
+    ///
+    /// map.upgrade_to(FormatVersion::LATEST);
+    /// assert_eq!(map.version, 14);
+    /// assert_eq!(map.difficulty.approach_rate, 6.0);
+    /// assert_eq!(map.events.breaks[0].start_time, Time(1024.0));
+    /// assert!(map.to_osu_string().contains("2,1024,2024"));
+    /// ```
+    pub fn upgrade_to(&mut self, version: FormatVersion) {
+        let from = self.format_version();
+        if version <= from {
+            return;
+        }
+
+        if !from.has_approach_rate() && version.has_approach_rate() {
+            if !self.present_keys.difficulty.contains("ApproachRate") {
+                self.difficulty.approach_rate = self.difficulty.overall_difficulty;
+            }
+            self.present_keys.difficulty.insert("ApproachRate");
+        }
+
+        if !from.supports_extras() && version.supports_extras() {
+            for obj in &mut self.hit_objects {
+                if let HitObject::Slider(ref mut slider) = *obj {
+                    slider.normalize_edges();
+                }
+            }
+        }
+
+        for line in &mut self.events.raw_lines {
+            if let Some(modern) = modern_event_line(line) {
+                *line = modern;
+            }
+        }
+
+        let offset = from.time_offset() - version.time_offset();
+        if offset != 0 {
+            self.shift_time(Time::from(offset));
+        }
+
+        self.version = version.0;
+    }
+}
+
+/// The line with its event type written the way the editor writes it, if
+/// it is a top level event line written some other way.
+fn modern_event_line(line: &str) -> Option<String> {
+    if line.starts_with(' ') || line.starts_with('_') {
+        return None;
+    }
+
+    let (code, rest) = line.split_at(line.find(',')?);
+    let modern = events::EventType::from_code(code.trim())?.code();
+    if code == modern {
+        None
+    } else {
+        Some(format!("{}{}", modern, rest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_of_old_versions() {
+        let v7 = "osu file format v7
+
+[General]
+AudioFilename: audio.mp3
+AudioLeadIn: 0
+PreviewTime: 30000
+Countdown: 1
+SampleSet: Normal
+StackLeniency: 0.7
+Mode: 0
+LetterboxInBreaks: 0
+
+[Editor]
+Bookmarks: 1000
+DistanceSpacing: 1.2
+BeatDivisor: 4
+GridSize: 4
+
+[Metadata]
+Title:Title
+Artist:Artist
+Creator:Mapper
+Version:Normal
+Source:
+Tags:tag
+
+[Difficulty]
+HPDrainRate:5
+CircleSize:4
+OverallDifficulty:6
+SliderMultiplier:1.4
+SliderTickRate:1
+";
+        let v9 = v7
+            .replace("v7", "v9")
+            .replace("LetterboxInBreaks: 0\n", "LetterboxInBreaks: 0\nStoryFireInFront: 1\nEpilepsyWarning: 0\n")
+            .replace("OverallDifficulty:6\n", "OverallDifficulty:6\nApproachRate:7\n");
+
+        // Without present keys every key is written, so only those the
+        // version has are left
+        let mut map = parse_beatmap(include_str!("../map.osu")).unwrap();
+        map.present_keys = Default::default();
+        map.editor.bookmarks = vec![Time(1000.0)];
+
+        for &(version, file) in &[(7, v7), (9, v9.as_str())] {
+            let old = parse_beatmap(file).unwrap();
+            let written = map.to_osu_string_with(&WriterOptions::for_version(FormatVersion(version)));
+            let written = parse_beatmap(&written).unwrap();
+
+            assert_eq!(written.version, version);
+            assert_eq!(written.present_keys, old.present_keys, "v{}", version);
+        }
+    }
+
+    #[test]
+    fn test_upgrade_to() {
+        let input = "osu file format v7\n\n[Difficulty]\nOverallDifficulty:7\n\n\
+                     [Events]\n1,500,\"video.mp4\"\nBackground,0,\"bg.jpg\"\n3,100,255,0,0\n\n\
+                     [HitObjects]\n0,0,1000,2,0,L|100:0,1,100\n";
+        let mut map = parse_beatmap(input).unwrap();
+
+        map.upgrade_to(FormatVersion(6));
+        assert_eq!(map.version, 7);
+
+        map.upgrade_to(FormatVersion::LATEST);
+        assert_eq!(map.format_version(), FormatVersion::LATEST);
+        assert!(map.present_keys.difficulty.contains("ApproachRate"));
+        assert_eq!(map.events.raw_lines, vec!["Video,500,\"video.mp4\"", "0,0,\"bg.jpg\"", "3,100,255,0,0"]);
+        match map.hit_objects[0] {
+            HitObject::Slider(ref s) => assert_eq!(s.edge_hitsounds, vec![0, 0]),
+            _ => panic!("expected a slider"),
+        }
+
+        let reparsed = parse_beatmap(&map.to_osu_string()).unwrap();
+        assert_eq!(reparsed.difficulty.approach_rate, 7.0);
+        assert_eq!(reparsed.events.videos[0].start_time, Time(500.0));
+    }
+}